@@ -0,0 +1,6 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    kubewarden_policy_sdk::testing::fuzz::fuzz_decode_request(data);
+});
@@ -0,0 +1,64 @@
+//! Procedural macros used by the [Kubewarden Policy SDK](https://crates.io/crates/kubewarden-policy-sdk).
+//!
+//! These macros are re-exported by the SDK, they should not be used directly.
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Item, Path};
+
+/// Generate the waPC entry point of a policy.
+///
+/// The attribute can be placed either on the type implementing the
+/// `kubewarden_policy_sdk::policy::Policy` trait, or on the `impl Policy`
+/// block itself. It generates the `wapc_init` function that sets up logging,
+/// the panic hook and registers the `validate`, `validate_settings` and
+/// `protocol_version` waPC functions.
+///
+/// The path of the SDK crate defaults to `::kubewarden_policy_sdk`, it can
+/// be changed with `#[policy(crate = path::to::sdk)]`.
+#[proc_macro_attribute]
+pub fn policy(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut sdk: Path = syn::parse_quote!(::kubewarden_policy_sdk);
+    let attr_parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("crate") {
+            sdk = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported policy attribute"))
+        }
+    });
+    parse_macro_input!(attr with attr_parser);
+
+    let item = parse_macro_input!(item as Item);
+    let policy = match &item {
+        Item::Struct(s) => {
+            let ident = &s.ident;
+            quote!(#ident)
+        }
+        Item::Enum(e) => {
+            let ident = &e.ident;
+            quote!(#ident)
+        }
+        Item::Impl(i) => {
+            let self_ty = &i.self_ty;
+            quote!(#self_ty)
+        }
+        _ => {
+            return syn::Error::new_spanned(
+                &item,
+                "#[policy] can be used only on a struct, an enum or an `impl Policy` block",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+
+    quote! {
+        #item
+
+        #[no_mangle]
+        pub extern "C" fn wapc_init() {
+            #sdk::policy::setup::<#policy>();
+        }
+    }
+    .into()
+}
@@ -0,0 +1,162 @@
+//! Compile-once caches for values derived from the settings.
+//!
+//! Policies often build expensive values out of their settings: regular
+//! expressions, glob patterns, label selectors, CEL programs. The settings
+//! don't change during the life of a policy instance, hence these values
+//! can be built on the first request and reused by the following ones.
+//!
+//! The [`lazy_cache!`](crate::lazy_cache) macro declares a cache local to
+//! the call site, keyed by the source string of the value:
+//!
+//! ```
+//! use kubewarden_policy_sdk::lazy_cache;
+//!
+//! struct Glob(String);
+//!
+//! impl Glob {
+//!     fn compile(pattern: &str) -> Result<Glob, String> {
+//!         // expensive work here
+//!         Ok(Glob(pattern.trim_end_matches('*').to_string()))
+//!     }
+//!
+//!     fn matches(&self, value: &str) -> bool {
+//!         value.starts_with(&self.0)
+//!     }
+//! }
+//!
+//! fn allowed(pattern: &str, image: &str) -> Result<bool, String> {
+//!     let glob = lazy_cache!(Glob, pattern, Glob::compile)?;
+//!     Ok(glob.matches(image))
+//! }
+//!
+//! assert!(allowed("ghcr.io/kubewarden/*", "ghcr.io/kubewarden/policy").unwrap());
+//! assert!(!allowed("ghcr.io/kubewarden/*", "docker.io/busybox").unwrap());
+//! ```
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Maximum number of values kept by a [`CompileCache`]. The cache is emptied
+/// when the limit is reached, which protects the policy from unbounded
+/// growth when the keys are not derived from the settings
+pub const MAX_CACHED_VALUES: usize = 256;
+
+/// Cache of values built from a string, see the [module documentation](self)
+#[derive(Debug)]
+pub struct CompileCache<V> {
+    values: RefCell<HashMap<String, Rc<V>>>,
+}
+
+impl<V> Default for CompileCache<V> {
+    fn default() -> Self {
+        CompileCache {
+            values: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<V> CompileCache<V> {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value built from `key`, building it with `init` when it's
+    /// not cached yet. Errors are not cached
+    pub fn get_or_try_insert<E>(
+        &self,
+        key: &str,
+        init: impl FnOnce(&str) -> Result<V, E>,
+    ) -> Result<Rc<V>, E> {
+        if let Some(value) = self.values.borrow().get(key) {
+            return Ok(value.clone());
+        }
+
+        let value = Rc::new(init(key)?);
+        let mut values = self.values.borrow_mut();
+        if values.len() >= MAX_CACHED_VALUES {
+            values.clear();
+        }
+        values.insert(key.to_string(), value.clone());
+        Ok(value)
+    }
+
+    /// Number of cached values
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    /// Returns true when no value is cached
+    pub fn is_empty(&self) -> bool {
+        self.values.borrow().is_empty()
+    }
+
+    /// Drop all the cached values
+    pub fn clear(&self) {
+        self.values.borrow_mut().clear();
+    }
+}
+
+/// Build a value of type `$type` from the string `$key` using the fallible
+/// function `$init`, once: the following invocations of the same call site
+/// with the same key return the cached value, wrapped inside of an `Rc`.
+///
+/// Evaluates to `Result<Rc<$type>, E>`, where `E` is the error of `$init`.
+/// See the [`cache`](crate::cache) module.
+#[macro_export]
+macro_rules! lazy_cache {
+    ($type:ty, $key:expr, $init:expr) => {{
+        ::std::thread_local! {
+            static CACHE: $crate::cache::CompileCache<$type> = $crate::cache::CompileCache::new();
+        }
+        CACHE.with(|cache| cache.get_or_try_insert($key, $init))
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn values_are_built_once() {
+        let builds = Cell::new(0);
+        let build = |key: &str| -> Result<usize, String> {
+            builds.set(builds.get() + 1);
+            if key.is_empty() {
+                Err("empty".to_string())
+            } else {
+                Ok(key.len())
+            }
+        };
+
+        for _ in 0..3 {
+            assert_eq!(*lazy_cache!(usize, "abc", build).unwrap(), 3);
+        }
+        assert_eq!(builds.get(), 1);
+
+        // errors are not cached
+        assert!(lazy_cache!(usize, "", build).is_err());
+        assert!(lazy_cache!(usize, "", build).is_err());
+        assert_eq!(builds.get(), 3);
+    }
+
+    #[test]
+    fn cache_is_bounded() {
+        let cache = CompileCache::new();
+        for i in 0..MAX_CACHED_VALUES {
+            cache
+                .get_or_try_insert::<()>(&i.to_string(), |k| Ok(k.to_string()))
+                .unwrap();
+        }
+        assert_eq!(cache.len(), MAX_CACHED_VALUES);
+
+        cache
+            .get_or_try_insert::<()>("one more", |k| Ok(k.to_string()))
+            .unwrap();
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Support for the validation requests encoded with [CBOR](https://cbor.io).
+//!
+//! Hosts supporting CBOR can send the payloads of the `validate` and
+//! `validate_settings` functions encoded with it, instead of JSON. The
+//! policy replies using the encoding of the request, hence the hosts keep
+//! receiving JSON unless they opt in.
+//!
+//! The encoding is negotiated through the `payload_encodings` waPC function,
+//! which returns the encodings understood by the policy, e.g.
+//! `["json","cbor"]`. Hosts not calling it keep using JSON.
+//!
+//! [`policy::validate`](crate::policy::validate) and
+//! [`policy::validate_settings`](crate::policy::validate_settings) handle the
+//! CBOR payloads, and [`policy::register`](crate::policy::register) registers
+//! the `payload_encodings` function. Policies registering their own functions
+//! can wrap them with [`transcode`]:
+//!
+//! ```
+//! use kubewarden_policy_sdk::{accept_request, cbor};
+//!
+//! fn validate(payload: &[u8]) -> wapc_guest::CallResult {
+//!     accept_request()
+//! }
+//!
+//! fn validate_any_encoding(payload: &[u8]) -> wapc_guest::CallResult {
+//!     cbor::transcode(payload, validate)
+//! }
+//! ```
+//!
+//! The payloads are transcoded to JSON, which is then decoded as usual: the
+//! raw JSON text of the objects is kept by the requests, see
+//! [`LazyValue`](crate::request::LazyValue).
+//!
+//! This module is available when the `cbor` feature is enabled.
+use crate::error::{Result, SdkError};
+
+/// The self-described CBOR tag (55799), which can prefix any CBOR document
+const SELF_DESCRIBED_TAG: [u8; 3] = [0xd9, 0xd9, 0xf7];
+
+/// Returns true when the payload is a CBOR map, the encoding of the
+/// validation requests. JSON documents always start with an ASCII character
+pub fn is_cbor(payload: &[u8]) -> bool {
+    payload.starts_with(&SELF_DESCRIBED_TAG) || payload.first().is_some_and(|byte| byte >> 5 == 5)
+}
+
+/// Transcode a CBOR document into JSON
+pub fn to_json(payload: &[u8]) -> Result<Vec<u8>> {
+    let value: serde_json::Value = ciborium::from_reader(payload)
+        .map_err(|e| SdkError::InvalidInput(format!("invalid CBOR payload: {}", e)))?;
+    serde_json::to_vec(&value).map_err(|e| SdkError::serialization("the CBOR payload", e))
+}
+
+/// Transcode a JSON document into CBOR
+pub fn from_json(payload: &[u8]) -> Result<Vec<u8>> {
+    let value: serde_json::Value = serde_json::from_slice(payload)
+        .map_err(|e| SdkError::deserialization("the JSON payload", e))?;
+    let mut encoded = Vec::new();
+    ciborium::into_writer(&value, &mut encoded)
+        .map_err(|e| SdkError::InvalidInput(format!("cannot encode the payload: {}", e)))?;
+    Ok(encoded)
+}
+
+/// Invoke the waPC function `f`, which handles JSON payloads. CBOR payloads
+/// are transcoded into JSON, and the response of `f` is transcoded back into
+/// CBOR. JSON payloads are passed to `f` as they are
+pub fn transcode<F>(payload: &[u8], f: F) -> wapc_guest::CallResult
+where
+    F: FnOnce(&[u8]) -> wapc_guest::CallResult,
+{
+    if !is_cbor(payload) {
+        return f(payload);
+    }
+    let response = f(&to_json(payload)?)?;
+    Ok(from_json(&response)?)
+}
+
+/// waPC guest function returning the encodings of the payloads understood
+/// by the policy
+pub fn payload_encodings_guest(_payload: &[u8]) -> wapc_guest::CallResult {
+    Ok(serde_json::to_vec(&["json", "cbor"])?)
+}
+
+/// Register the `payload_encodings` waPC function, see [`payload_encodings_guest`]
+pub fn register_payload_encodings() {
+    wapc_guest::register_function("payload_encodings", payload_encodings_guest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn cbor(value: &serde_json::Value) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        ciborium::into_writer(value, &mut encoded).unwrap();
+        encoded
+    }
+
+    #[test]
+    fn detect_cbor_payloads() {
+        assert!(is_cbor(&cbor(&json!({"request": {}}))));
+        assert!(is_cbor(&[0xd9, 0xd9, 0xf7, 0xa0]));
+        assert!(!is_cbor(br#"{"request": {}}"#));
+        assert!(!is_cbor(b" {}"));
+        assert!(!is_cbor(b""));
+    }
+
+    #[test]
+    fn reply_with_the_encoding_of_the_request() {
+        let echo = |payload: &[u8]| -> wapc_guest::CallResult {
+            let value: serde_json::Value = serde_json::from_slice(payload)?;
+            Ok(serde_json::to_vec(&json!({"received": value}))?)
+        };
+        let request = json!({"request": {"uid": "1", "dryRun": true, "replicas": 3}});
+
+        let response = transcode(&cbor(&request), echo).unwrap();
+        let response: serde_json::Value = ciborium::from_reader(response.as_slice()).unwrap();
+        assert_eq!(response, json!({"received": request}));
+
+        let response = transcode(request.to_string().as_bytes(), echo).unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response, json!({"received": request}));
+
+        assert!(transcode(&[0xa1, 0x61], echo).is_err());
+    }
+}
@@ -0,0 +1,517 @@
+use super::parser::{BinaryOp, Expr};
+use crate::error::{Result, SdkError};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The variables visible while evaluating an expression. Variables introduced
+/// by macros (like `all` and `exists`) shadow the ones defined by the parent scope
+pub(crate) struct Scope<'a> {
+    variables: &'a HashMap<String, Value>,
+    local: Option<(&'a str, &'a Value)>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    pub(crate) fn new(variables: &'a HashMap<String, Value>) -> Self {
+        Scope {
+            variables,
+            local: None,
+            parent: None,
+        }
+    }
+
+    fn child(&'a self, name: &'a str, value: &'a Value) -> Scope<'a> {
+        Scope {
+            variables: self.variables,
+            local: Some((name, value)),
+            parent: Some(self),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        match self.local {
+            Some((local, value)) if local == name => Some(value),
+            _ => match self.parent {
+                Some(parent) => parent.lookup(name),
+                None => self.variables.get(name),
+            },
+        }
+    }
+}
+
+pub(crate) fn eval(expr: &Expr, scope: &Scope) -> Result<Value> {
+    match expr {
+        Expr::Literal(value) => Ok(value.clone()),
+        Expr::Ident(name) => scope
+            .lookup(name)
+            .cloned()
+            .ok_or_else(|| SdkError::InvalidInput(format!("undeclared reference to '{}'", name))),
+        Expr::Select(operand, field) => match eval(operand, scope)? {
+            Value::Object(map) => map
+                .get(field)
+                .cloned()
+                .ok_or_else(|| SdkError::InvalidInput(format!("no such key: {}", field))),
+            other => Err(SdkError::InvalidInput(format!(
+                "cannot select field '{}' from {}",
+                field,
+                type_name(&other)
+            ))),
+        },
+        Expr::Index(operand, index) => {
+            let operand = eval(operand, scope)?;
+            let index = eval(index, scope)?;
+            match (&operand, &index) {
+                (Value::Array(items), Value::Number(n)) => n
+                    .as_i64()
+                    .and_then(|i| usize::try_from(i).ok())
+                    .and_then(|i| items.get(i))
+                    .cloned()
+                    .ok_or_else(|| SdkError::InvalidInput(format!("index out of range: {}", n))),
+                (Value::Object(map), Value::String(key)) => map
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| SdkError::InvalidInput(format!("no such key: {}", key))),
+                _ => Err(SdkError::InvalidInput(format!(
+                    "cannot index {} with {}",
+                    type_name(&operand),
+                    type_name(&index)
+                ))),
+            }
+        }
+        Expr::List(items) => Ok(Value::Array(
+            items
+                .iter()
+                .map(|item| eval(item, scope))
+                .collect::<Result<_>>()?,
+        )),
+        Expr::Map(entries) => {
+            let mut map = Map::new();
+            for (key, value) in entries {
+                let key = match eval(key, scope)? {
+                    Value::String(key) => key,
+                    other => {
+                        return Err(SdkError::InvalidInput(format!(
+                            "unsupported map key type: {}",
+                            type_name(&other)
+                        )))
+                    }
+                };
+                map.insert(key, eval(value, scope)?);
+            }
+            Ok(Value::Object(map))
+        }
+        Expr::Not(operand) => match eval(operand, scope)? {
+            Value::Bool(b) => Ok(Value::Bool(!b)),
+            other => Err(SdkError::InvalidInput(format!(
+                "no such overload: !{}",
+                type_name(&other)
+            ))),
+        },
+        Expr::Neg(operand) => match eval(operand, scope)? {
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => i
+                    .checked_neg()
+                    .map(Value::from)
+                    .ok_or_else(|| SdkError::InvalidInput("integer overflow".to_string())),
+                None => Ok(Value::from(-n.as_f64().unwrap_or_default())),
+            },
+            other => Err(SdkError::InvalidInput(format!(
+                "no such overload: -{}",
+                type_name(&other)
+            ))),
+        },
+        Expr::Conditional(condition, if_true, if_false) => match eval(condition, scope)? {
+            Value::Bool(true) => eval(if_true, scope),
+            Value::Bool(false) => eval(if_false, scope),
+            other => Err(SdkError::InvalidInput(format!(
+                "conditional requires a bool, found {}",
+                type_name(&other)
+            ))),
+        },
+        Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), left, right) => {
+            // logical operators are commutative: an error of one operand is
+            // ignored when the other one decides the result, `true` for `||`
+            // and `false` for `&&`
+            let decisive = *op == BinaryOp::Or;
+            let left = eval(left, scope).and_then(as_bool);
+            if matches!(left, Ok(b) if b == decisive) {
+                return Ok(Value::Bool(decisive));
+            }
+            let right = eval(right, scope).and_then(as_bool);
+            if matches!(right, Ok(b) if b == decisive) {
+                return Ok(Value::Bool(decisive));
+            }
+            left?;
+            right?;
+            Ok(Value::Bool(!decisive))
+        }
+        Expr::Binary(op, left, right) => binary(*op, eval(left, scope)?, eval(right, scope)?),
+        Expr::Call {
+            target,
+            function,
+            args,
+        } => call(target.as_deref(), function, args, scope),
+    }
+}
+
+fn binary(op: BinaryOp, left: Value, right: Value) -> Result<Value> {
+    match op {
+        BinaryOp::Eq => Ok(Value::Bool(equals(&left, &right))),
+        BinaryOp::Ne => Ok(Value::Bool(!equals(&left, &right))),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = compare(&left, &right)?;
+            Ok(Value::Bool(match op {
+                BinaryOp::Lt => ordering == Ordering::Less,
+                BinaryOp::Le => ordering != Ordering::Greater,
+                BinaryOp::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        BinaryOp::In => match &right {
+            Value::Array(items) => Ok(Value::Bool(items.iter().any(|i| equals(i, &left)))),
+            Value::Object(map) => match &left {
+                Value::String(key) => Ok(Value::Bool(map.contains_key(key))),
+                _ => Ok(Value::Bool(false)),
+            },
+            other => Err(SdkError::InvalidInput(format!(
+                "no such overload: in {}",
+                type_name(other)
+            ))),
+        },
+        BinaryOp::Add => match (left, right) {
+            (Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+            (Value::Array(mut l), Value::Array(r)) => {
+                l.extend(r);
+                Ok(Value::Array(l))
+            }
+            (Value::Number(l), Value::Number(r)) => arithmetic(op, &l, &r),
+            (l, r) => Err(no_overload("+", &l, &r)),
+        },
+        BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => match (left, right) {
+            (Value::Number(l), Value::Number(r)) => arithmetic(op, &l, &r),
+            (l, r) => Err(no_overload(arithmetic_symbol(op), &l, &r)),
+        },
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators are short-circuited"),
+    }
+}
+
+fn arithmetic(op: BinaryOp, left: &Number, right: &Number) -> Result<Value> {
+    // there are no implicit conversions between int and double
+    if left.is_f64() != right.is_f64() {
+        return Err(no_overload(
+            arithmetic_symbol(op),
+            &Value::Number(left.clone()),
+            &Value::Number(right.clone()),
+        ));
+    }
+    if let (Some(l), Some(r)) = (left.as_i64(), right.as_i64()) {
+        let result = match op {
+            BinaryOp::Add => l.checked_add(r),
+            BinaryOp::Sub => l.checked_sub(r),
+            BinaryOp::Mul => l.checked_mul(r),
+            BinaryOp::Div if r == 0 => {
+                return Err(SdkError::InvalidInput("division by zero".to_string()))
+            }
+            BinaryOp::Div => l.checked_div(r),
+            BinaryOp::Rem if r == 0 => {
+                return Err(SdkError::InvalidInput("modulus by zero".to_string()))
+            }
+            _ => l.checked_rem(r),
+        };
+        return result
+            .map(Value::from)
+            .ok_or_else(|| SdkError::InvalidInput("integer overflow".to_string()));
+    }
+
+    let (l, r) = (
+        left.as_f64().unwrap_or_default(),
+        right.as_f64().unwrap_or_default(),
+    );
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div => l / r,
+        _ => {
+            return Err(SdkError::InvalidInput(
+                "no such overload: double % double".to_string(),
+            ))
+        }
+    };
+    Ok(Value::from(result))
+}
+
+fn call(target: Option<&Expr>, function: &str, args: &[Expr], scope: &Scope) -> Result<Value> {
+    // macros receive their arguments unevaluated
+    match (target, function, args) {
+        (None, "has", [Expr::Select(operand, field)]) => {
+            return match eval(operand, scope)? {
+                Value::Object(map) => Ok(Value::Bool(map.contains_key(field))),
+                other => Err(SdkError::InvalidInput(format!(
+                    "invalid argument to has(): {}",
+                    type_name(&other)
+                ))),
+            };
+        }
+        (None, "has", _) => {
+            return Err(SdkError::InvalidInput(
+                "invalid argument to has() macro".to_string(),
+            ))
+        }
+        (
+            Some(target),
+            "all" | "exists" | "exists_one" | "map" | "filter",
+            [Expr::Ident(var), body],
+        ) => {
+            return comprehension(function, eval(target, scope)?, var, body, scope);
+        }
+        _ => {}
+    }
+
+    let target = target.map(|t| eval(t, scope)).transpose()?;
+    let args: Vec<Value> = args
+        .iter()
+        .map(|arg| eval(arg, scope))
+        .collect::<Result<_>>()?;
+
+    // global functions can be invoked using the receiver style too
+    let mut all_args: Vec<&Value> = target.iter().collect();
+    all_args.extend(args.iter());
+
+    match (function, all_args.as_slice()) {
+        ("size", [value]) => size(value),
+        ("int", [value]) => to_int(value),
+        ("double", [value]) => to_double(value),
+        ("string", [value]) => to_string(value),
+        ("startsWith", [Value::String(s), Value::String(prefix)]) => {
+            Ok(Value::Bool(s.starts_with(prefix.as_str())))
+        }
+        ("endsWith", [Value::String(s), Value::String(suffix)]) => {
+            Ok(Value::Bool(s.ends_with(suffix.as_str())))
+        }
+        ("contains", [Value::String(s), Value::String(substring)]) => {
+            Ok(Value::Bool(s.contains(substring.as_str())))
+        }
+        ("lowerAscii", [Value::String(s)]) => Ok(Value::String(s.to_ascii_lowercase())),
+        ("upperAscii", [Value::String(s)]) => Ok(Value::String(s.to_ascii_uppercase())),
+        ("split", [Value::String(s), Value::String(separator)]) => Ok(Value::Array(
+            s.split(separator.as_str())
+                .map(|part| Value::String(part.to_string()))
+                .collect(),
+        )),
+        _ => Err(SdkError::InvalidInput(format!(
+            "found no matching overload for '{}' applied to ({})",
+            function,
+            all_args
+                .iter()
+                .map(|a| type_name(a))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))),
+    }
+}
+
+fn comprehension(
+    macro_name: &str,
+    range: Value,
+    var: &str,
+    body: &Expr,
+    scope: &Scope,
+) -> Result<Value> {
+    let items: Vec<Value> = match range {
+        Value::Array(items) => items,
+        Value::Object(map) => map.into_iter().map(|(k, _)| Value::String(k)).collect(),
+        other => {
+            return Err(SdkError::InvalidInput(format!(
+                "cannot iterate over {} using {}()",
+                type_name(&other),
+                macro_name
+            )))
+        }
+    };
+
+    let eval_item = |item: &Value| eval(body, &scope.child(var, item));
+
+    match macro_name {
+        "all" => {
+            for item in &items {
+                if !as_bool(eval_item(item)?)? {
+                    return Ok(Value::Bool(false));
+                }
+            }
+            Ok(Value::Bool(true))
+        }
+        "exists" => {
+            for item in &items {
+                if as_bool(eval_item(item)?)? {
+                    return Ok(Value::Bool(true));
+                }
+            }
+            Ok(Value::Bool(false))
+        }
+        "exists_one" => {
+            let mut count = 0;
+            for item in &items {
+                if as_bool(eval_item(item)?)? {
+                    count += 1;
+                }
+            }
+            Ok(Value::Bool(count == 1))
+        }
+        "map" => Ok(Value::Array(
+            items.iter().map(eval_item).collect::<Result<_>>()?,
+        )),
+        _ => {
+            let mut filtered = Vec::new();
+            for item in items {
+                if as_bool(eval_item(&item)?)? {
+                    filtered.push(item);
+                }
+            }
+            Ok(Value::Array(filtered))
+        }
+    }
+}
+
+fn size(value: &Value) -> Result<Value> {
+    let size = match value {
+        Value::String(s) => s.chars().count(),
+        Value::Array(items) => items.len(),
+        Value::Object(map) => map.len(),
+        other => {
+            return Err(SdkError::InvalidInput(format!(
+                "no such overload: size({})",
+                type_name(other)
+            )))
+        }
+    };
+    Ok(Value::from(size as i64))
+}
+
+fn to_int(value: &Value) -> Result<Value> {
+    match value {
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => Ok(Value::from(i)),
+            None => {
+                let f = n.as_f64().unwrap_or_default().trunc();
+                if f.is_finite() && f >= i64::MIN as f64 && f <= i64::MAX as f64 {
+                    Ok(Value::from(f as i64))
+                } else {
+                    Err(SdkError::InvalidInput("integer overflow".to_string()))
+                }
+            }
+        },
+        Value::String(s) => s
+            .parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| SdkError::InvalidInput(format!("cannot convert '{}' to int", s))),
+        other => Err(SdkError::InvalidInput(format!(
+            "no such overload: int({})",
+            type_name(other)
+        ))),
+    }
+}
+
+fn to_double(value: &Value) -> Result<Value> {
+    match value {
+        Value::Number(n) => Ok(Value::from(n.as_f64().unwrap_or_default())),
+        Value::String(s) => s
+            .parse::<f64>()
+            .map(Value::from)
+            .map_err(|_| SdkError::InvalidInput(format!("cannot convert '{}' to double", s))),
+        other => Err(SdkError::InvalidInput(format!(
+            "no such overload: double({})",
+            type_name(other)
+        ))),
+    }
+}
+
+fn to_string(value: &Value) -> Result<Value> {
+    match value {
+        Value::String(s) => Ok(Value::String(s.clone())),
+        Value::Number(n) => Ok(Value::String(n.to_string())),
+        Value::Bool(b) => Ok(Value::String(b.to_string())),
+        other => Err(SdkError::InvalidInput(format!(
+            "no such overload: string({})",
+            type_name(other)
+        ))),
+    }
+}
+
+fn as_bool(value: Value) -> Result<bool> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        other => Err(SdkError::InvalidInput(format!(
+            "expected a bool, found {}",
+            type_name(&other)
+        ))),
+    }
+}
+
+/// Equality with numeric values compared by their value, regardless of their type
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => compare_numbers(l, r) == Some(Ordering::Equal),
+        (Value::Array(l), Value::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equals(l, r))
+        }
+        (Value::Object(l), Value::Object(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .all(|(k, v)| r.get(k).is_some_and(|other| equals(v, other)))
+        }
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Result<Ordering> {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => compare_numbers(l, r)
+            .ok_or_else(|| SdkError::InvalidInput("cannot compare NaN values".to_string())),
+        (Value::String(l), Value::String(r)) => Ok(l.cmp(r)),
+        (Value::Bool(l), Value::Bool(r)) => Ok(l.cmp(r)),
+        _ => Err(no_overload("comparison", left, right)),
+    }
+}
+
+fn compare_numbers(left: &Number, right: &Number) -> Option<Ordering> {
+    match (left.as_i64(), right.as_i64()) {
+        (Some(l), Some(r)) => Some(l.cmp(&r)),
+        _ => left
+            .as_f64()
+            .unwrap_or_default()
+            .partial_cmp(&right.as_f64().unwrap_or_default()),
+    }
+}
+
+fn arithmetic_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        _ => "%",
+    }
+}
+
+fn no_overload(operator: &str, left: &Value, right: &Value) -> SdkError {
+    SdkError::InvalidInput(format!(
+        "no such overload: {} {} {}",
+        type_name(left),
+        operator,
+        type_name(right)
+    ))
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(n) if n.is_f64() => "double",
+        Value::Number(_) => "int",
+        Value::String(_) => "string",
+        Value::Array(_) => "list",
+        Value::Object(_) => "map",
+    }
+}
@@ -0,0 +1,446 @@
+use crate::error::{Result, SdkError};
+use serde_json::Value;
+
+/// Abstract syntax tree of a CEL expression
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Expr {
+    Literal(Value),
+    Ident(String),
+    Select(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call {
+        target: Option<Box<Expr>>,
+        function: String,
+        args: Vec<Expr>,
+    },
+    List(Vec<Expr>),
+    Map(Vec<(Expr, Expr)>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    In,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Double(f64),
+    Str(String),
+    Ident(String),
+    Punct(&'static str),
+}
+
+const PUNCTUATION: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "!", "?", ":", ".", ",",
+    "(", ")", "[", "]", "{", "}",
+];
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let start = pos;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let is_double =
+                pos + 1 < chars.len() && chars[pos] == '.' && chars[pos + 1].is_ascii_digit();
+            if is_double {
+                pos += 1;
+                while pos < chars.len() && chars[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+                let text: String = chars[start..pos].iter().collect();
+                let value = text.parse().map_err(|_| {
+                    SdkError::InvalidInput(format!("invalid double literal: {}", text))
+                })?;
+                tokens.push(Token::Double(value));
+            } else {
+                let text: String = chars[start..pos].iter().collect();
+                let value = text.parse().map_err(|_| {
+                    SdkError::InvalidInput(format!("integer literal out of range: {}", text))
+                })?;
+                // unsigned literals are handled as regular integers
+                if pos < chars.len() && (chars[pos] == 'u' || chars[pos] == 'U') {
+                    pos += 1;
+                }
+                tokens.push(Token::Int(value));
+            }
+        } else if c == '"' || c == '\'' {
+            let quote = c;
+            pos += 1;
+            let mut text = String::new();
+            loop {
+                let c = *chars.get(pos).ok_or_else(|| {
+                    SdkError::InvalidInput("unterminated string literal".to_string())
+                })?;
+                pos += 1;
+                match c {
+                    '\\' => {
+                        let escaped = *chars.get(pos).ok_or_else(|| {
+                            SdkError::InvalidInput("unterminated string literal".to_string())
+                        })?;
+                        pos += 1;
+                        text.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            '\\' | '"' | '\'' => escaped,
+                            _ => {
+                                return Err(SdkError::InvalidInput(format!(
+                                    "invalid escape sequence: \\{}",
+                                    escaped
+                                )))
+                            }
+                        });
+                    }
+                    c if c == quote => break,
+                    c => text.push(c),
+                }
+            }
+            tokens.push(Token::Str(text));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Ident(chars[start..pos].iter().collect()));
+        } else {
+            let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| SdkError::InvalidInput(format!("unexpected character '{}'", c)))?;
+            pos += punct.chars().count();
+            tokens.push(Token::Punct(punct));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a CEL expression
+pub(crate) fn parse(input: &str) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let expr = parser.expr()?;
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(SdkError::InvalidInput(format!(
+            "unexpected token {:?}",
+            token
+        ))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Punct(p)) if *p == punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(SdkError::InvalidInput(format!(
+                "expected '{}', found {:?}",
+                punct,
+                self.peek()
+            )))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let condition = self.or()?;
+        if self.eat("?") {
+            let if_true = self.or()?;
+            self.expect(":")?;
+            let if_false = self.expr()?;
+            return Ok(Expr::Conditional(
+                Box::new(condition),
+                Box::new(if_true),
+                Box::new(if_false),
+            ));
+        }
+        Ok(condition)
+    }
+
+    fn binary<F>(&mut self, ops: &[(&str, BinaryOp)], mut operand: F) -> Result<Expr>
+    where
+        F: FnMut(&mut Self) -> Result<Expr>,
+    {
+        let mut left = operand(self)?;
+        'outer: loop {
+            for (punct, op) in ops {
+                let matched = match *punct {
+                    "in" => {
+                        if self.peek() == Some(&Token::Ident("in".to_string())) {
+                            self.pos += 1;
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    _ => self.eat(punct),
+                };
+                if matched {
+                    let right = operand(self)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        self.binary(&[("&&", BinaryOp::And)], Self::relation)
+    }
+
+    fn relation(&mut self) -> Result<Expr> {
+        self.binary(
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+                ("in", BinaryOp::In),
+            ],
+            Self::addition,
+        )
+    }
+
+    fn addition(&mut self) -> Result<Expr> {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Self::multiplication,
+        )
+    }
+
+    fn multiplication(&mut self) -> Result<Expr> {
+        self.binary(
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("-") {
+            return Ok(match self.unary()? {
+                Expr::Literal(Value::Number(n)) if n.is_i64() => {
+                    Expr::Literal(Value::from(-n.as_i64().unwrap_or_default()))
+                }
+                Expr::Literal(Value::Number(n)) if n.is_f64() => {
+                    Expr::Literal(Value::from(-n.as_f64().unwrap_or_default()))
+                }
+                operand => Expr::Neg(Box::new(operand)),
+            });
+        }
+        self.member()
+    }
+
+    fn member(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                let field = match self.next() {
+                    Some(Token::Ident(field)) => field,
+                    token => {
+                        return Err(SdkError::InvalidInput(format!(
+                            "expected field name, found {:?}",
+                            token
+                        )))
+                    }
+                };
+                if self.eat("(") {
+                    let args = self.args(")")?;
+                    expr = Expr::Call {
+                        target: Some(Box::new(expr)),
+                        function: field,
+                        args,
+                    };
+                } else {
+                    expr = Expr::Select(Box::new(expr), field);
+                }
+            } else if self.eat("[") {
+                let index = self.expr()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn args(&mut self, close: &str) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.eat(close) {
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expr()?);
+            if self.eat(close) {
+                return Ok(args);
+            }
+            self.expect(",")?;
+            // trailing commas are allowed
+            if self.eat(close) {
+                return Ok(args);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Int(i)) => Ok(Expr::Literal(Value::from(i))),
+            Some(Token::Double(d)) => Ok(Expr::Literal(Value::from(d))),
+            Some(Token::Str(s)) => Ok(Expr::Literal(Value::String(s))),
+            Some(Token::Ident(ident)) => match ident.as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ => {
+                    if self.eat("(") {
+                        let args = self.args(")")?;
+                        Ok(Expr::Call {
+                            target: None,
+                            function: ident,
+                            args,
+                        })
+                    } else {
+                        Ok(Expr::Ident(ident))
+                    }
+                }
+            },
+            Some(Token::Punct("(")) => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Some(Token::Punct("[")) => Ok(Expr::List(self.args("]")?)),
+            Some(Token::Punct("{")) => {
+                let mut entries = Vec::new();
+                if self.eat("}") {
+                    return Ok(Expr::Map(entries));
+                }
+                loop {
+                    let key = self.expr()?;
+                    self.expect(":")?;
+                    let value = self.expr()?;
+                    entries.push((key, value));
+                    if self.eat("}") {
+                        return Ok(Expr::Map(entries));
+                    }
+                    self.expect(",")?;
+                    if self.eat("}") {
+                        return Ok(Expr::Map(entries));
+                    }
+                }
+            }
+            token => Err(SdkError::InvalidInput(format!(
+                "unexpected token {:?}",
+                token
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_precedence() {
+        let expr = parse("1 + 2 * 3 == 7 && !false").unwrap();
+        match expr {
+            Expr::Binary(BinaryOp::And, left, right) => {
+                assert!(matches!(*left, Expr::Binary(BinaryOp::Eq, _, _)));
+                assert!(matches!(*right, Expr::Not(_)));
+            }
+            _ => panic!("unexpected expression {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn parse_member_access_and_calls() {
+        let expr = parse("object.spec.containers.all(c, c.image.startsWith('ghcr.io/'))").unwrap();
+        match expr {
+            Expr::Call {
+                target: Some(_),
+                function,
+                args,
+            } => {
+                assert_eq!(function, "all");
+                assert_eq!(args.len(), 2);
+            }
+            _ => panic!("unexpected expression {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("1 +").is_err());
+        assert!(parse("'unterminated").is_err());
+        assert!(parse("a.b)").is_err());
+        assert!(parse("#").is_err());
+    }
+}
@@ -0,0 +1,15 @@
+//! Access to the monotonic clock.
+//!
+//! Policies built for `wasm32-wasip1` read the clock through WASI, while
+//! the `wasm32-unknown-unknown` target has no clock at all: the standard
+//! library panics when it's read. The features depending on the clock,
+//! like timers and deadlines, are turned off on that target.
+use std::time::Instant;
+
+/// True when the target provides a clock
+pub(crate) const AVAILABLE: bool = !cfg!(all(target_arch = "wasm32", target_os = "unknown"));
+
+/// The current instant, `None` when the target doesn't provide a clock
+pub(crate) fn now() -> Option<Instant> {
+    AVAILABLE.then(Instant::now)
+}
@@ -0,0 +1,204 @@
+//! Guest bindings based on the WebAssembly [component model](https://component-model.bytecodealliance.org),
+//! an alternative to the waPC ones.
+//!
+//! The policy is exported as a component implementing the `policy` world
+//! published in `wit/policy.wit`. The world mirrors the waPC contract: the
+//! `validate`, `validate-settings` and `protocol-version` functions exchange
+//! the same JSON documents, and the host capabilities are invoked through
+//! the `host` interface imported by the policy. Hence the same [`Policy`]
+//! can be built for both the hosts.
+//!
+//! The [`export_component!`](crate::export_component) macro exports the
+//! functions of the world for the given policy, like the
+//! [`setup!`](crate::setup) macro does for waPC:
+//!
+//! ```ignore
+//! use kubewarden_policy_sdk::{accept_request, policy::Policy, request::ValidationRequest};
+//!
+//! struct MyPolicy;
+//!
+//! impl Policy for MyPolicy {
+//!     type Settings = ();
+//!
+//!     fn validate(_request: &ValidationRequest<()>) -> wapc_guest::CallResult {
+//!         accept_request()
+//!     }
+//! }
+//!
+//! kubewarden_policy_sdk::export_component!(MyPolicy);
+//! ```
+//!
+//! When the functions of the world are invoked, the host capabilities and
+//! the log events go through the `host` interface, see
+//! [`ComponentHostClient`].
+//!
+//! # Building the component
+//!
+//! Components are built for the `wasm32-wasip2` target. The waPC bindings
+//! are linked too, and they import the functions of the `wapc` module,
+//! which are not part of the world: the `wit/wapc-adapter.wasm` adapter,
+//! built from `wit/wapc-adapter.wat`, provides them. They are never invoked
+//! by the component. The adapter is passed to the linker, e.g. through the
+//! `.cargo/config.toml` file of the policy:
+//!
+//! ```toml
+//! [target.wasm32-wasip2]
+//! rustflags = ["-C", "link-arg=--adapt=wapc=wapc-adapter.wasm"]
+//! ```
+//!
+//! This module is available when the `component-model` feature is enabled.
+use crate::host_capabilities::client::{set_host_client, HostClient};
+use crate::policy::Policy;
+use std::{cell::Cell, rc::Rc};
+
+/// The bindings of the `policy` world, generated by wit-bindgen
+#[allow(missing_docs)]
+pub mod bindings {
+    wit_bindgen::generate!({
+        path: "wit/policy.wit",
+        world: "policy",
+        pub_export_macro: true,
+        export_macro_name: "export_policy",
+    });
+}
+
+thread_local! {
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Generate the exports of the `policy` world for the given [`Policy`],
+/// which invoke the functions of the [`component`](crate::component) module.
+#[macro_export]
+macro_rules! export_component {
+    ($policy:ty) => {
+        const _: () = {
+            struct KubewardenComponent;
+
+            impl $crate::component::bindings::Guest for KubewardenComponent {
+                fn validate(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+                    $crate::component::validate::<$policy>(&payload)
+                }
+
+                fn validate_settings(payload: Vec<u8>) -> Result<Vec<u8>, String> {
+                    $crate::component::validate_settings::<$policy>(&payload)
+                }
+
+                fn protocol_version() -> Result<Vec<u8>, String> {
+                    $crate::component::protocol_version()
+                }
+            }
+
+            $crate::component::bindings::export_policy!(
+                KubewardenComponent with_types_in $crate::component::bindings
+            );
+        };
+    };
+}
+
+/// The [`HostClient`] used by policies running as components, which invokes
+/// the `call` function of the `host` interface
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ComponentHostClient;
+
+impl HostClient for ComponentHostClient {
+    #[cfg(target_arch = "wasm32")]
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        Ok(bindings::kubewarden::policy::host::call(
+            namespace, operation, payload,
+        )?)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        crate::host_capabilities::client::WapcHostClient.call(namespace, operation, payload)
+    }
+}
+
+/// Returns true when the policy is running as a component, which is known
+/// once the host invoked one of the functions of the world
+pub fn is_running() -> bool {
+    RUNNING.with(|running| running.get())
+}
+
+/// Send a log event to the host through the `host` interface
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn log(event: &[u8]) {
+    bindings::kubewarden::policy::host::log(event);
+}
+
+/// Prepare the policy the first time the host invokes it, like
+/// [`policy::setup`](crate::policy::setup) does for waPC, then run `f`
+fn run(f: impl FnOnce() -> wapc_guest::CallResult) -> Result<Vec<u8>, String> {
+    if !is_running() {
+        RUNNING.with(|running| running.set(true));
+        set_host_client(Rc::new(ComponentHostClient));
+        crate::logging::init();
+        crate::policy::install_panic_hook();
+    }
+    f().map_err(|e| e.to_string())
+}
+
+/// The `validate` function of the world, see [`policy::validate`](crate::policy::validate)
+pub fn validate<P: Policy>(payload: &[u8]) -> Result<Vec<u8>, String> {
+    run(|| crate::policy::validate::<P>(payload))
+}
+
+/// The `validate-settings` function of the world, see
+/// [`policy::validate_settings`](crate::policy::validate_settings)
+pub fn validate_settings<P: Policy>(payload: &[u8]) -> Result<Vec<u8>, String> {
+    run(|| crate::policy::validate_settings::<P>(payload))
+}
+
+/// The `protocol-version` function of the world, see
+/// [`protocol_version_guest`](crate::protocol_version_guest)
+pub fn protocol_version() -> Result<Vec<u8>, String> {
+    run(|| crate::protocol_version_guest(&[]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accept_request, request::ValidationRequest, response::ValidationResponse};
+
+    struct AcceptAll;
+
+    impl Policy for AcceptAll {
+        type Settings = ();
+
+        fn validate(_request: &ValidationRequest<()>) -> wapc_guest::CallResult {
+            accept_request()
+        }
+    }
+
+    crate::export_component!(AcceptAll);
+
+    #[test]
+    fn validate_requests() {
+        let payload = serde_json::to_vec(&serde_json::json!({
+            "request": {"uid": "1", "operation": "CREATE"},
+            "settings": null
+        }))
+        .unwrap();
+
+        let response = validate::<AcceptAll>(&payload).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response).unwrap();
+        assert!(response.accepted);
+        assert!(is_running());
+    }
+
+    #[test]
+    fn report_errors_as_strings() {
+        let error = validate::<AcceptAll>(b"not json").unwrap_err();
+        assert!(error.contains("deserializing"), "{}", error);
+    }
+
+    #[test]
+    fn validate_settings_and_protocol_version() {
+        let response = validate_settings::<AcceptAll>(b"null").unwrap();
+        let response: serde_json::Value = serde_json::from_slice(&response).unwrap();
+        assert_eq!(response["valid"], true);
+
+        let version = protocol_version().unwrap();
+        assert_eq!(version, crate::protocol_version_guest(&[]).unwrap());
+    }
+}
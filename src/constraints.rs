@@ -0,0 +1,409 @@
+//! Ready-made checks covering the most common policy requirements.
+//!
+//! Each constraint is configured by a serde-friendly type, which can be
+//! embedded inside of the policy settings, and implements the
+//! [`Validator`] trait over the Kubernetes object being evaluated.
+//!
+//! The pod related constraints work with Pods and with all the high level
+//! objects defining a pod template: Deployment, ReplicaSet, StatefulSet,
+//! DaemonSet, ReplicationController, Job and CronJob.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::constraints::Constraints;
+//! use kubewarden_policy_sdk::validator::Validator;
+//! use serde_json::json;
+//!
+//! let constraints: Constraints = serde_json::from_value(json!({
+//!     "requiredLabels": {"labels": ["owner"]},
+//!     "allowedRegistries": {"registries": ["registry.example.com"]},
+//!     "runAsNonRoot": true
+//! }))
+//! .unwrap();
+//!
+//! let pod = json!({
+//!     "kind": "Pod",
+//!     "metadata": {"name": "nginx", "labels": {"owner": "team-a"}},
+//!     "spec": {
+//!         "securityContext": {"runAsNonRoot": true},
+//!         "containers": [{"name": "nginx", "image": "docker.io/nginx"}]
+//!     }
+//! });
+//!
+//! let result = constraints.validate(&pod);
+//! assert_eq!(
+//!     result.message(),
+//!     "/spec/containers/0/image: registry docker.io is not allowed"
+//! );
+//! ```
+use crate::diff::escape_pointer_token;
+use crate::validator::{ValidationResult, Validator, Violation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Require the object to have all the given labels
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RequiredLabels {
+    /// Keys of the labels that must be defined
+    pub labels: Vec<String>,
+}
+
+impl Validator<Value> for RequiredLabels {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        self.labels
+            .iter()
+            .filter(|label| object["metadata"]["labels"].get(label.as_str()).is_none())
+            .map(|label| {
+                Violation::new("/metadata/labels", &format!("label {} is required", label))
+            })
+            .collect()
+    }
+}
+
+/// Reject objects that have any of the given annotations
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForbiddenAnnotations {
+    /// Keys of the annotations that must not be defined
+    pub annotations: Vec<String>,
+}
+
+impl Validator<Value> for ForbiddenAnnotations {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        self.annotations
+            .iter()
+            .filter(|annotation| {
+                object["metadata"]["annotations"]
+                    .get(annotation.as_str())
+                    .is_some()
+            })
+            .map(|annotation| {
+                Violation::new(
+                    &format!("/metadata/annotations/{}", escape_pointer_token(annotation)),
+                    &format!("annotation {} is not allowed", annotation),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Allow only container images coming from the given registries
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AllowedRegistries {
+    /// The registries allowed. Images without an explicit registry,
+    /// like `nginx:latest`, come from `docker.io`
+    pub registries: Vec<String>,
+}
+
+impl Validator<Value> for AllowedRegistries {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        containers(object)
+            .filter_map(|(path, container)| {
+                let image = container["image"].as_str()?;
+                let registry = image_registry(image);
+                if self.registries.iter().any(|r| r == registry) {
+                    None
+                } else {
+                    Some(Violation::new(
+                        &format!("{}/image", path),
+                        &format!("registry {} is not allowed", registry),
+                    ))
+                }
+            })
+            .collect()
+    }
+}
+
+/// Require all the containers to define resource limits
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ResourceLimitsRequired {
+    /// Require the cpu limit to be set
+    pub cpu: bool,
+    /// Require the memory limit to be set
+    pub memory: bool,
+}
+
+impl Default for ResourceLimitsRequired {
+    fn default() -> Self {
+        ResourceLimitsRequired {
+            cpu: true,
+            memory: true,
+        }
+    }
+}
+
+impl Validator<Value> for ResourceLimitsRequired {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        let required: Vec<&str> = [("cpu", self.cpu), ("memory", self.memory)]
+            .into_iter()
+            .filter_map(|(resource, required)| required.then_some(resource))
+            .collect();
+
+        containers(object)
+            .flat_map(|(path, container)| {
+                required
+                    .iter()
+                    .filter(|resource| container["resources"]["limits"].get(resource).is_none())
+                    .map(|resource| {
+                        Violation::new(
+                            &format!("{}/resources/limits", path),
+                            &format!("{} limit is required", resource),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// Require all the containers to run as a non root user, either via the
+/// security context of the pod or via the one of the container
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunAsNonRoot;
+
+impl Validator<Value> for RunAsNonRoot {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        let pod_context = pod_spec(object)
+            .map(|(_, spec)| &spec["securityContext"])
+            .unwrap_or(&Value::Null);
+
+        containers(object)
+            .filter(|(_, container)| {
+                let container_context = &container["securityContext"];
+                let non_root = container_context["runAsNonRoot"]
+                    .as_bool()
+                    .or_else(|| pod_context["runAsNonRoot"].as_bool())
+                    .unwrap_or_default();
+                let run_as_user = container_context["runAsUser"]
+                    .as_i64()
+                    .or_else(|| pod_context["runAsUser"].as_i64());
+                !non_root || run_as_user == Some(0)
+            })
+            .map(|(path, _)| {
+                Violation::new(
+                    &format!("{}/securityContext/runAsNonRoot", path),
+                    "container must run as non root",
+                )
+            })
+            .collect()
+    }
+}
+
+/// All the constraints, which can be embedded inside of the policy settings.
+/// Only the constraints that are set are evaluated
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Constraints {
+    /// See [`RequiredLabels`]
+    pub required_labels: Option<RequiredLabels>,
+    /// See [`ForbiddenAnnotations`]
+    pub forbidden_annotations: Option<ForbiddenAnnotations>,
+    /// See [`AllowedRegistries`]
+    pub allowed_registries: Option<AllowedRegistries>,
+    /// See [`ResourceLimitsRequired`]
+    pub resource_limits: Option<ResourceLimitsRequired>,
+    /// See [`RunAsNonRoot`]
+    pub run_as_non_root: bool,
+}
+
+impl Validator<Value> for Constraints {
+    fn validate(&self, object: &Value) -> ValidationResult {
+        let mut result = ValidationResult::valid();
+        if let Some(c) = &self.required_labels {
+            result = result.merge(c.validate(object));
+        }
+        if let Some(c) = &self.forbidden_annotations {
+            result = result.merge(c.validate(object));
+        }
+        if let Some(c) = &self.allowed_registries {
+            result = result.merge(c.validate(object));
+        }
+        if let Some(c) = &self.resource_limits {
+            result = result.merge(c.validate(object));
+        }
+        if self.run_as_non_root {
+            result = result.merge(RunAsNonRoot.validate(object));
+        }
+        result
+    }
+}
+
+/// Returns the JSON Pointer and the value of the pod spec defined by the object
+fn pod_spec(object: &Value) -> Option<(&'static str, &Value)> {
+    let path = match object["kind"].as_str()? {
+        "Pod" => "/spec",
+        "CronJob" => "/spec/jobTemplate/spec/template/spec",
+        "Deployment"
+        | "ReplicaSet"
+        | "StatefulSet"
+        | "DaemonSet"
+        | "ReplicationController"
+        | "Job" => "/spec/template/spec",
+        _ => return None,
+    };
+    object.pointer(path).map(|spec| (path, spec))
+}
+
+/// Iterate over all the containers, init containers and ephemeral containers
+/// of the object, together with their JSON Pointer
+fn containers(object: &Value) -> impl Iterator<Item = (String, &Value)> {
+    pod_spec(object).into_iter().flat_map(|(path, spec)| {
+        ["containers", "initContainers", "ephemeralContainers"]
+            .into_iter()
+            .flat_map(move |field| {
+                spec[field]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                    .map(move |(i, container)| (format!("{}/{}/{}", path, field, i), container))
+            })
+    })
+}
+
+/// Returns the registry of an image reference, following the same rules of
+/// the container runtimes
+pub(crate) fn image_registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((registry, _))
+            if registry.contains('.') || registry.contains(':') || registry == "localhost" =>
+        {
+            registry
+        }
+        _ => "docker.io",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn deployment(containers: Value) -> Value {
+        json!({
+            "kind": "Deployment",
+            "metadata": {
+                "name": "app",
+                "labels": {"owner": "team-a"},
+                "annotations": {"example.com/skip": "true"}
+            },
+            "spec": {"template": {"spec": {"containers": containers}}}
+        })
+    }
+
+    #[test]
+    fn labels_and_annotations() {
+        let object = deployment(json!([]));
+
+        let labels = RequiredLabels {
+            labels: vec!["owner".to_string(), "cost-center".to_string()],
+        };
+        assert_eq!(
+            labels.validate(&object).message(),
+            "/metadata/labels: label cost-center is required"
+        );
+
+        let annotations = ForbiddenAnnotations {
+            annotations: vec!["example.com/skip".to_string()],
+        };
+        assert_eq!(
+            annotations.validate(&object).violations()[0].path,
+            "/metadata/annotations/example.com~1skip"
+        );
+    }
+
+    #[test]
+    fn registries() {
+        let object = deployment(json!([
+            {"name": "a", "image": "nginx"},
+            {"name": "b", "image": "registry.example.com/app:1.0"},
+            {"name": "c", "image": "localhost:5000/app"}
+        ]));
+        let constraint = AllowedRegistries {
+            registries: vec![
+                "registry.example.com".to_string(),
+                "localhost:5000".to_string(),
+            ],
+        };
+
+        let result = constraint.validate(&object);
+        assert_eq!(
+            result.violations(),
+            &[Violation::new(
+                "/spec/template/spec/containers/0/image",
+                "registry docker.io is not allowed"
+            )]
+        );
+    }
+
+    #[test]
+    fn resource_limits() {
+        let object = json!({
+            "kind": "CronJob",
+            "spec": {"jobTemplate": {"spec": {"template": {"spec": {
+                "containers": [{"name": "a", "resources": {"limits": {"cpu": "1"}}}],
+                "initContainers": [{"name": "b", "resources": {"limits": {"cpu": "1", "memory": "1Gi"}}}]
+            }}}}}
+        });
+
+        let result = ResourceLimitsRequired::default().validate(&object);
+        assert_eq!(result.violations().len(), 1);
+        assert_eq!(
+            result.violations()[0].to_string(),
+            "/spec/jobTemplate/spec/template/spec/containers/0/resources/limits: memory limit is required"
+        );
+
+        let cpu_only = ResourceLimitsRequired {
+            cpu: true,
+            memory: false,
+        };
+        assert!(cpu_only.validate(&object).is_valid());
+    }
+
+    #[test]
+    fn run_as_non_root() {
+        let pod = json!({
+            "kind": "Pod",
+            "spec": {
+                "securityContext": {"runAsNonRoot": true},
+                "containers": [
+                    {"name": "a"},
+                    {"name": "b", "securityContext": {"runAsNonRoot": false}},
+                    {"name": "c", "securityContext": {"runAsUser": 0}}
+                ]
+            }
+        });
+
+        let result = RunAsNonRoot.validate(&pod);
+        let paths: Vec<&str> = result
+            .violations()
+            .iter()
+            .map(|v| v.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/spec/containers/1/securityContext/runAsNonRoot",
+                "/spec/containers/2/securityContext/runAsNonRoot"
+            ]
+        );
+    }
+
+    #[test]
+    fn constraints_from_settings() {
+        let constraints: Constraints = serde_json::from_value(json!({
+            "requiredLabels": {"labels": ["owner"]},
+            "resourceLimits": {}
+        }))
+        .unwrap();
+        assert!(!constraints.run_as_non_root);
+
+        let object = deployment(json!([{"name": "a", "image": "nginx"}]));
+        assert_eq!(constraints.validate(&object).violations().len(), 2);
+        assert!(Constraints::default().validate(&object).is_valid());
+    }
+}
@@ -0,0 +1,284 @@
+//! Conversion of Custom Resources between versions.
+//!
+//! Custom Resource Definitions serving more than one version can delegate
+//! the conversion of their objects to a [conversion webhook](https://kubernetes.io/docs/tasks/extend-kubernetes/custom-resources/custom-resource-definition-versioning/#webhook-conversion).
+//! This module allows to write the conversion logic as a wasm module: the
+//! [`Converter`] trait converts a single object, while [`setup`] registers
+//! the `convert` waPC function handling the `ConversionReview` objects.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::conversion::Converter;
+//! use serde_json::{json, Value};
+//!
+//! struct CronTabConverter;
+//!
+//! impl Converter for CronTabConverter {
+//!     fn convert(mut object: Value, desired_api_version: &str) -> Result<Value, String> {
+//!         match desired_api_version {
+//!             "stable.example.com/v2" => {
+//!                 let spec = object["spec"].take();
+//!                 object["spec"] = json!({"schedule": {"cron": spec["cronSpec"]}});
+//!             }
+//!             other => return Err(format!("cannot convert to {}", other)),
+//!         }
+//!         object["apiVersion"] = json!(desired_api_version);
+//!         Ok(object)
+//!     }
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn wapc_init() {
+//!     kubewarden_policy_sdk::conversion::setup::<CronTabConverter>();
+//! }
+//! ```
+use crate::logging;
+use crate::policy::{catch_panic, install_panic_hook};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// apiVersion of the ConversionReview objects
+pub const API_VERSION: &str = "apiextensions.k8s.io/v1";
+
+/// Trait implemented by the conversion webhooks
+pub trait Converter {
+    /// Convert `object` to `desired_api_version`. The converted object must
+    /// have the desired `apiVersion`, and its metadata must not change,
+    /// labels and annotations excluded
+    fn convert(object: Value, desired_api_version: &str) -> Result<Value, String>;
+}
+
+/// The request of a ConversionReview
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConversionRequest {
+    /// Identifier of the conversion, copied into the response
+    pub uid: String,
+    /// The version the objects must be converted to
+    #[serde(rename = "desiredAPIVersion")]
+    pub desired_api_version: String,
+    /// The objects to be converted, possibly of different versions
+    pub objects: Vec<Value>,
+}
+
+/// Outcome of the conversion
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversionResult {
+    /// Either `Success` or `Failure`
+    pub status: String,
+    /// Description of the failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// The response of a ConversionReview
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ConversionResponse {
+    /// Identifier of the conversion, copied from the request
+    pub uid: String,
+    /// The converted objects, in the same order of the request
+    pub converted_objects: Vec<Value>,
+    /// Outcome of the conversion
+    pub result: ConversionResult,
+}
+
+/// A ConversionReview object
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionReview {
+    pub api_version: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<ConversionRequest>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<ConversionResponse>,
+}
+
+impl ConversionRequest {
+    /// Convert all the objects of the request using `C`. Objects already
+    /// at the desired version are not converted
+    pub fn convert<C: Converter>(self) -> ConversionResponse {
+        let desired = self.desired_api_version;
+        let converted: Result<Vec<Value>, String> = self
+            .objects
+            .into_iter()
+            .map(|object| {
+                if object["apiVersion"] == desired.as_str() {
+                    return Ok(object);
+                }
+                let name = object["metadata"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                let converted = C::convert(object, &desired)
+                    .map_err(|e| format!("cannot convert {}: {}", name, e))?;
+                if converted["apiVersion"] != desired.as_str() {
+                    return Err(format!("{} has not been converted to {}", name, desired));
+                }
+                Ok(converted)
+            })
+            .collect();
+
+        match converted {
+            Ok(converted_objects) => ConversionResponse {
+                uid: self.uid,
+                converted_objects,
+                result: ConversionResult {
+                    status: "Success".to_string(),
+                    message: None,
+                },
+            },
+            Err(message) => ConversionResponse::failure(self.uid, message),
+        }
+    }
+}
+
+impl ConversionResponse {
+    fn failure(uid: String, message: String) -> Self {
+        ConversionResponse {
+            uid,
+            converted_objects: Vec::new(),
+            result: ConversionResult {
+                status: "Failure".to_string(),
+                message: Some(message),
+            },
+        }
+    }
+}
+
+/// Prepare the conversion webhook for execution: initialize the global
+/// logger, install the panic hook and register the `convert` and
+/// `protocol_version` waPC functions.
+///
+/// This must be invoked from the `wapc_init` function.
+pub fn setup<C: Converter>() {
+    logging::init();
+    install_panic_hook();
+    wapc_guest::register_function("convert", convert::<C>);
+    crate::register_protocol_version();
+}
+
+/// waPC guest function that converts the objects of a `ConversionReview`,
+/// returning the `ConversionReview` holding the response.
+///
+/// On native targets, a panic of the converter results in a failed
+/// conversion. On the wasm targets the instance traps after the panic hook
+/// logs the panic, like for [`policy::validate`](crate::policy::validate).
+pub fn convert<C: Converter>(payload: &[u8]) -> wapc_guest::CallResult {
+    let review: ConversionReview = serde_json::from_slice(payload)?;
+    let request = review.request.unwrap_or_default();
+    let uid = request.uid.clone();
+
+    catch_panic(
+        || {
+            Ok(serde_json::to_vec(&ConversionReview {
+                api_version: API_VERSION.to_string(),
+                kind: "ConversionReview".to_string(),
+                request: None,
+                response: Some(request.convert::<C>()),
+            })?)
+        },
+        |message| {
+            Ok(serde_json::to_vec(&ConversionReview {
+                api_version: API_VERSION.to_string(),
+                kind: "ConversionReview".to_string(),
+                request: None,
+                response: Some(ConversionResponse::failure(uid, message)),
+            })?)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Converter;
+
+    impl super::Converter for Converter {
+        fn convert(mut object: Value, desired_api_version: &str) -> Result<Value, String> {
+            match object["spec"]["size"].as_str() {
+                Some("panic") => panic!("unexpected size"),
+                Some("forget") => {}
+                Some(size) => {
+                    object["spec"] = json!({"replicas": size.len()});
+                    object["apiVersion"] = json!(desired_api_version);
+                }
+                None => return Err("missing size".to_string()),
+            }
+            Ok(object)
+        }
+    }
+
+    fn review(objects: Vec<Value>) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "apiVersion": API_VERSION,
+            "kind": "ConversionReview",
+            "request": {
+                "uid": "705ab4f5",
+                "desiredAPIVersion": "example.com/v2",
+                "objects": objects
+            }
+        }))
+        .unwrap()
+    }
+
+    fn object(api_version: &str, size: &str) -> Value {
+        json!({"apiVersion": api_version, "metadata": {"name": size}, "spec": {"size": size}})
+    }
+
+    fn run(objects: Vec<Value>) -> ConversionResponse {
+        let response = convert::<Converter>(&review(objects)).unwrap();
+        let review: ConversionReview = serde_json::from_slice(&response).unwrap();
+        assert_eq!(review.kind, "ConversionReview");
+        review.response.unwrap()
+    }
+
+    #[test]
+    fn convert_objects() {
+        let response = run(vec![
+            object("example.com/v1", "xxx"),
+            object("example.com/v2", "yy"),
+        ]);
+        assert_eq!(response.uid, "705ab4f5");
+        assert_eq!(response.result.status, "Success");
+        assert_eq!(
+            response.converted_objects,
+            vec![
+                json!({"apiVersion": "example.com/v2", "metadata": {"name": "xxx"}, "spec": {"replicas": 3}}),
+                object("example.com/v2", "yy")
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_conversions() {
+        for (size, message) in [
+            ("forget", "forget has not been converted to example.com/v2"),
+            (
+                "panic",
+                "internal policy error: policy panicked: unexpected size",
+            ),
+        ] {
+            let response = run(vec![
+                object("example.com/v1", "xxx"),
+                object("example.com/v1", size),
+            ]);
+            assert_eq!(response.uid, "705ab4f5");
+            assert_eq!(response.result.status, "Failure");
+            assert_eq!(response.result.message.as_deref(), Some(message));
+            assert!(response.converted_objects.is_empty());
+        }
+
+        let mut missing = object("example.com/v1", "missing");
+        missing["spec"] = json!({});
+        let response = run(vec![missing]);
+        assert_eq!(
+            response.result.message.as_deref(),
+            Some("cannot convert missing: missing size")
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Settings types understanding the artifacts used with cosign.
+//!
+//! Signature verification policies are usually configured with the same
+//! keys and identities already used by the signing pipelines. The types of
+//! this module accept them in the forms they are found there, so they can
+//! be pasted into the policy settings as they are:
+//!
+//! * [`PublicKeys`]: a list of PEM keys, or the content of one or more
+//!   `cosign.pub` files concatenated into a single string
+//! * [`Identity`]: an `issuer`/`subject` pair, the same pair using the
+//!   names of the cosign flags, or the flags themselves, e.g.
+//!   `--certificate-identity=... --certificate-oidc-issuer=...`
+//! * [`Identities`]: a list of identities, or an identities file with an
+//!   `identities` key, like the ones of the sigstore policy-controller
+//!
+//! ```
+//! use kubewarden_policy_sdk::cosign::{Identities, PublicKeys};
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! #[serde(rename_all = "camelCase")]
+//! struct Settings {
+//!     pub_keys: PublicKeys,
+//!     identities: Identities,
+//! }
+//!
+//! let settings: Settings = serde_yaml::from_str(r#"
+//! pubKeys: |
+//!   -----BEGIN PUBLIC KEY-----
+//!   MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE...
+//!   -----END PUBLIC KEY-----
+//!   -----BEGIN PUBLIC KEY-----
+//!   MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAF...
+//!   -----END PUBLIC KEY-----
+//! identities:
+//!   - --certificate-identity=release@example.com --certificate-oidc-issuer=https://accounts.google.com
+//!   - certificate-identity: https://github.com/org/repo/.github/workflows/release.yml@refs/heads/main
+//!     certificate-oidc-issuer: https://token.actions.githubusercontent.com
+//! "#).unwrap();
+//!
+//! assert_eq!(settings.pub_keys.len(), 2);
+//! # #[cfg(feature = "verification")]
+//! assert_eq!(settings.identities.keyless_infos()[0].subject, "release@example.com");
+//! ```
+#[cfg(feature = "verification")]
+use crate::host_capabilities::verification::KeylessInfo;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// PEM encoded public keys
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PublicKeys(pub Vec<String>);
+
+impl Deref for PublicKeys {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.0
+    }
+}
+
+/// Split a string into its PEM blocks, returns `None` when it contains
+/// anything else
+fn pem_blocks(pem: &str) -> Option<Vec<String>> {
+    let mut blocks = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find("-----BEGIN ") {
+        let end_marker = rest[start..].find("-----END ")? + start;
+        let end = rest[end_marker + "-----END ".len()..].find("-----")?
+            + end_marker
+            + "-----END ".len()
+            + "-----".len();
+        let block: Vec<&str> = rest[start..end].lines().map(str::trim).collect();
+        blocks.push(block.join("\n") + "\n");
+        rest = &rest[end..];
+    }
+    if blocks.is_empty() || !rest.trim().is_empty() {
+        return None;
+    }
+    Some(blocks)
+}
+
+impl<'de> Deserialize<'de> for PublicKeys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        let pems = match Repr::deserialize(deserializer)? {
+            Repr::One(pem) => vec![pem],
+            Repr::Many(pems) => pems,
+        };
+        let mut keys = Vec::new();
+        for pem in pems {
+            keys.extend(pem_blocks(&pem).ok_or_else(|| {
+                serde::de::Error::custom(format!("{} is not a list of PEM blocks", pem.trim()))
+            })?);
+        }
+        Ok(PublicKeys(keys))
+    }
+}
+
+/// The identity of a keyless signer: the OIDC issuer and the subject of
+/// the signing certificate
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identity {
+    /// The OIDC issuer, `--certificate-oidc-issuer`
+    pub issuer: String,
+    /// The subject, `--certificate-identity`
+    pub subject: String,
+}
+
+impl FromStr for Identity {
+    type Err = String;
+
+    /// Parse the `--certificate-identity` and `--certificate-oidc-issuer`
+    /// flags of cosign, in both the `--flag=value` and `--flag value` forms
+    fn from_str(flags: &str) -> Result<Self, Self::Err> {
+        let (mut issuer, mut subject) = (None, None);
+        let mut words = flags.split_whitespace();
+        while let Some(word) = words.next() {
+            let (flag, value) = match word.split_once('=') {
+                Some((flag, value)) => (flag, Some(value)),
+                None => (word, None),
+            };
+            let target = match flag {
+                "--certificate-identity" => &mut subject,
+                "--certificate-oidc-issuer" => &mut issuer,
+                "--certificate-identity-regexp" | "--certificate-oidc-issuer-regexp" => {
+                    return Err(format!("{} is not supported", flag))
+                }
+                _ => return Err(format!("unknown flag {}", flag)),
+            };
+            let value = value
+                .or_else(|| words.next())
+                .ok_or_else(|| format!("{} requires a value", flag))?;
+            *target = Some(value.trim_matches(|c| c == '"' || c == '\'').to_string());
+        }
+
+        match (issuer, subject) {
+            (Some(issuer), Some(subject)) => Ok(Identity { issuer, subject }),
+            _ => Err(
+                "both --certificate-identity and --certificate-oidc-issuer are required"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Identity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            #[serde(alias = "certificate-oidc-issuer", alias = "certificateOidcIssuer")]
+            issuer: String,
+            #[serde(alias = "certificate-identity", alias = "certificateIdentity")]
+            subject: String,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Flags(String),
+            Fields(Fields),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Flags(flags) => flags.parse().map_err(serde::de::Error::custom),
+            Repr::Fields(Fields { issuer, subject }) => Ok(Identity { issuer, subject }),
+        }
+    }
+}
+
+#[cfg(feature = "verification")]
+impl From<Identity> for KeylessInfo {
+    fn from(identity: Identity) -> Self {
+        KeylessInfo {
+            issuer: identity.issuer,
+            subject: identity.subject,
+        }
+    }
+}
+
+/// A list of keyless signer identities
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identities(pub Vec<Identity>);
+
+impl Identities {
+    /// The identities, in the form used by the verification functions, see
+    /// [`verify_keyless_exact_match`](crate::host_capabilities::verification::verify_keyless_exact_match)
+    #[cfg(feature = "verification")]
+    pub fn keyless_infos(&self) -> Vec<KeylessInfo> {
+        self.0.iter().cloned().map(KeylessInfo::from).collect()
+    }
+}
+
+impl Deref for Identities {
+    type Target = [Identity];
+
+    fn deref(&self) -> &[Identity] {
+        &self.0
+    }
+}
+
+impl<'de> Deserialize<'de> for Identities {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            List(Vec<Identity>),
+            File { identities: Vec<Identity> },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::List(identities) | Repr::File { identities } => Identities(identities),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY_A: &str = "-----BEGIN PUBLIC KEY-----\nAAAA\n-----END PUBLIC KEY-----\n";
+    const KEY_B: &str = "-----BEGIN PUBLIC KEY-----\nBBBB\n-----END PUBLIC KEY-----\n";
+
+    #[test]
+    fn public_keys() {
+        let keys: PublicKeys =
+            serde_json::from_value(json!(format!("{}\n{}", KEY_A, KEY_B))).unwrap();
+        assert_eq!(keys.0, vec![KEY_A, KEY_B]);
+
+        let keys: PublicKeys = serde_json::from_value(json!([KEY_A, KEY_B])).unwrap();
+        assert_eq!(keys.len(), 2);
+
+        // indented, like in YAML block scalars
+        let keys: PublicKeys = serde_json::from_value(json!(KEY_A.replace('\n', "\n  "))).unwrap();
+        assert_eq!(keys.0, vec![KEY_A]);
+
+        assert!(serde_json::from_value::<PublicKeys>(json!("AAAA")).is_err());
+        assert!(serde_json::from_value::<PublicKeys>(json!([KEY_A, "garbage"])).is_err());
+        assert!(serde_json::from_value::<PublicKeys>(json!(format!("{} garbage", KEY_A))).is_err());
+    }
+
+    #[test]
+    fn identities() {
+        let expected = Identity {
+            issuer: "https://accounts.google.com".to_string(),
+            subject: "alice@example.com".to_string(),
+        };
+
+        for identity in [
+            json!({"issuer": "https://accounts.google.com", "subject": "alice@example.com"}),
+            json!({
+                "certificate-oidc-issuer": "https://accounts.google.com",
+                "certificate-identity": "alice@example.com"
+            }),
+            json!("--certificate-identity=alice@example.com --certificate-oidc-issuer=https://accounts.google.com"),
+            json!("--certificate-oidc-issuer 'https://accounts.google.com' --certificate-identity alice@example.com"),
+        ] {
+            let identities: Identities = serde_json::from_value(json!([identity])).unwrap();
+            assert_eq!(identities.0, vec![expected.clone()]);
+        }
+
+        let identities: Identities = serde_json::from_value(json!({
+            "identities": [{"issuer": "https://accounts.google.com", "subject": "alice@example.com"}]
+        }))
+        .unwrap();
+        assert_eq!(identities[0].subject, "alice@example.com");
+        #[cfg(feature = "verification")]
+        assert_eq!(identities.keyless_infos()[0].subject, "alice@example.com");
+
+        for flags in [
+            "--certificate-identity=alice@example.com",
+            "--certificate-identity-regexp=.* --certificate-oidc-issuer=x",
+            "--certificate-identity",
+            "--key cosign.pub",
+        ] {
+            assert!(flags.parse::<Identity>().is_err(), "{}", flags);
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The time budget of the evaluation.
+//!
+//! The host interrupts the policies that take too long to evaluate a
+//! request, which results in an opaque error. Policies performing many
+//! host capability calls, like the context aware ones, can instead check
+//! the [`Deadline`] of the evaluation and stop early, rejecting the request
+//! with a clear message.
+//!
+//! The budget is provided by the host through the `timeoutSeconds` key of
+//! the validation payload. Operators can reduce it with the reserved
+//! `evaluationTimeoutSeconds` key of the policy settings; the smaller of the
+//! two values is used. There's no deadline when none of them is set, or
+//! when the policy is built for a target without a clock, like
+//! `wasm32-unknown-unknown`.
+//! Like `logLevel`, settings types rejecting unknown fields must declare
+//! the `evaluationTimeoutSeconds` field to support it.
+//!
+//! ```
+//! use kubewarden_policy_sdk::deadline;
+//! use kubewarden_policy_sdk::request::ValidationRequest;
+//!
+//! let payload = br#"{"settings": {}, "request": {}, "timeoutSeconds": 2}"#;
+//! let _request = ValidationRequest::<serde_json::Value>::new(payload).unwrap();
+//!
+//! for image in ["busybox", "nginx"] {
+//!     if let Err(e) = deadline::check() {
+//!         // the images left are not verified
+//!         let _ = e.fail_closed();
+//!         break;
+//!     }
+//!     // verify the image
+//! }
+//! assert!(deadline::current().unwrap().remaining().as_secs() <= 2);
+//! ```
+use crate::error::{Result, SdkError};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// A time budget, started when the deadline is created
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    started: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Create a deadline expiring after `budget`, starting from now.
+    ///
+    /// Panics on targets without a clock, like `wasm32-unknown-unknown`
+    pub fn new(budget: Duration) -> Self {
+        Deadline {
+            started: Instant::now(),
+            budget,
+        }
+    }
+
+    /// The whole time budget
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// The time spent since the deadline has been created
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The time left before the deadline expires, zero when it's expired
+    pub fn remaining(&self) -> Duration {
+        self.budget.saturating_sub(self.elapsed())
+    }
+
+    /// Returns true when the whole budget has been spent
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Returns [`SdkError::DeadlineExceeded`] when the deadline is expired
+    pub fn check(&self) -> Result<()> {
+        if self.is_expired() {
+            return Err(SdkError::DeadlineExceeded {
+                budget: self.budget,
+            });
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static DEADLINE: Cell<Option<Deadline>> = const { Cell::new(None) };
+}
+
+/// The deadline of the request being evaluated, if any
+pub fn current() -> Option<Deadline> {
+    DEADLINE.with(|deadline| deadline.get())
+}
+
+/// Returns [`SdkError::DeadlineExceeded`] when the deadline of the request
+/// being evaluated is expired. Always succeeds when there's no deadline
+pub fn check() -> Result<()> {
+    current().map_or(Ok(()), |deadline| deadline.check())
+}
+
+/// Start the deadline of a new evaluation. Invalid budgets, like negative
+/// ones, are ignored
+pub(crate) fn start(host_budget: Option<f64>, settings_budget: Option<f64>) {
+    if !crate::clock::AVAILABLE {
+        return;
+    }
+    let budget = [host_budget, settings_budget]
+        .into_iter()
+        .flatten()
+        .filter_map(|secs| Duration::try_from_secs_f64(secs).ok())
+        .min();
+    DEADLINE.with(|deadline| deadline.set(budget.map(Deadline::new)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::ValidationRequest;
+
+    #[test]
+    fn check_the_deadline() {
+        let deadline = Deadline::new(Duration::from_secs(3600));
+        assert!(deadline.check().is_ok());
+        assert!(deadline.remaining() <= deadline.budget());
+
+        let expired = Deadline::new(Duration::ZERO);
+        assert!(expired.is_expired());
+        assert_eq!(
+            expired.check().unwrap_err().to_string(),
+            "the evaluation deadline of 0ns has been exceeded"
+        );
+    }
+
+    #[test]
+    fn deadline_of_the_evaluation() {
+        let payload = br#"{"settings": {"evaluationTimeoutSeconds": 0.5}, "request": {}, "timeoutSeconds": 10}"#;
+        ValidationRequest::<serde_json::Value>::new(payload).unwrap();
+        assert_eq!(current().unwrap().budget(), Duration::from_millis(500));
+        assert!(check().is_ok());
+
+        let payload = br#"{"settings": {"evaluationTimeoutSeconds": -1}, "request": {}, "timeoutSeconds": 10}"#;
+        ValidationRequest::<serde_json::Value>::new(payload).unwrap();
+        assert_eq!(current().unwrap().budget(), Duration::from_secs(10));
+
+        ValidationRequest::<serde_json::Value>::new(br#"{"settings": {}, "request": {}}"#).unwrap();
+        assert!(current().is_none());
+        assert!(check().is_ok());
+    }
+}
@@ -0,0 +1,471 @@
+//! Utilities to compare JSON objects, like the `object` and `oldObject`
+//! fields of an UPDATE request.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::diff::changed_paths;
+//! use serde_json::json;
+//!
+//! let old = json!({"spec": {"replicas": 1, "storageClassName": "fast"}});
+//! let new = json!({"spec": {"replicas": 3, "storageClassName": "fast"}});
+//!
+//! assert_eq!(changed_paths(&old, &new), vec!["/spec/replicas"]);
+//! ```
+use crate::error::{Result, SdkError};
+use serde_json::{json, Value};
+
+/// Compare two JSON values and return the JSON Pointers of the leaves that
+/// have been added, removed or modified. Arrays are compared element by
+/// element.
+pub fn changed_paths(old: &Value, new: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_changes(old, new, &mut String::new(), &mut paths);
+    paths
+}
+
+/// Ensure the values referenced by the given JSON Pointers are the same
+/// inside of the `old` and `new` objects. A value missing in both the objects
+/// is considered unchanged.
+///
+/// Returns an error listing all the changed paths.
+pub fn assert_unchanged(old: &Value, new: &Value, paths: &[&str]) -> Result<()> {
+    let changed: Vec<&str> = paths
+        .iter()
+        .filter(|path| old.pointer(path) != new.pointer(path))
+        .copied()
+        .collect();
+
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(SdkError::InvalidInput(format!(
+            "the following fields are immutable: {}",
+            changed.join(", ")
+        )))
+    }
+}
+
+/// Compute the [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch
+/// turning `old` into `new`.
+///
+/// The patch is made only of `add`, `remove` and `replace` operations and is
+/// deterministic: the same pair of values always produces the same patch.
+/// Elements removed from the tail of an array are removed starting from the
+/// last one, so the patch can be applied in order.
+///
+/// ```
+/// use kubewarden_policy_sdk::diff::json_patch;
+/// use serde_json::json;
+///
+/// let old = json!({"metadata": {"labels": {"app": "nginx"}}});
+/// let new = json!({"metadata": {"labels": {"app": "nginx", "tier": "web"}}});
+///
+/// assert_eq!(
+///     json_patch(&old, &new),
+///     json!([{"op": "add", "path": "/metadata/labels/tier", "value": "web"}])
+/// );
+/// ```
+pub fn json_patch(old: &Value, new: &Value) -> Value {
+    let mut operations = Vec::new();
+    collect_patch(old, new, &mut String::new(), &mut operations);
+    Value::Array(operations)
+}
+
+/// Apply an [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch to
+/// `document`, returning the patched document.
+///
+/// All the operations are supported: `add`, `remove`, `replace`, `move`,
+/// `copy` and `test`. The patch is applied atomically: an error is returned,
+/// and `document` is left untouched, when any of the operations fails.
+///
+/// ```
+/// use kubewarden_policy_sdk::diff::apply_patch;
+/// use serde_json::json;
+///
+/// let pod = json!({"metadata": {"name": "nginx"}});
+/// let patch = json!([{"op": "add", "path": "/metadata/labels", "value": {"app": "nginx"}}]);
+///
+/// assert_eq!(
+///     apply_patch(&pod, &patch).unwrap(),
+///     json!({"metadata": {"name": "nginx", "labels": {"app": "nginx"}}})
+/// );
+/// ```
+pub fn apply_patch(document: &Value, patch: &Value) -> Result<Value> {
+    let operations = patch.as_array().ok_or_else(|| {
+        SdkError::InvalidInput("a JSON Patch must be an array of operations".to_string())
+    })?;
+
+    let mut document = document.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut document, operation).map_err(|e| {
+            SdkError::InvalidInput(format!(
+                "cannot apply JSON Patch operation #{}: {}",
+                index, e
+            ))
+        })?;
+    }
+    Ok(document)
+}
+
+/// Escape a key to be used inside of a JSON Pointer, as described by RFC 6901
+pub(crate) fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn collect_changes(old: &Value, new: &Value, path: &mut String, paths: &mut Vec<String>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&escape_pointer_token(key));
+                match new_map.get(key) {
+                    Some(new_value) => collect_changes(old_value, new_value, path, paths),
+                    None => paths.push(path.clone()),
+                }
+                path.truncate(len);
+            }
+            for key in new_map.keys().filter(|key| !old_map.contains_key(*key)) {
+                paths.push(format!("{}/{}", path, escape_pointer_token(key)));
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for index in 0..old_items.len().max(new_items.len()) {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&index.to_string());
+                match (old_items.get(index), new_items.get(index)) {
+                    (Some(old_item), Some(new_item)) => {
+                        collect_changes(old_item, new_item, path, paths)
+                    }
+                    _ => paths.push(path.clone()),
+                }
+                path.truncate(len);
+            }
+        }
+        _ => {
+            if old != new {
+                paths.push(path.clone());
+            }
+        }
+    }
+}
+
+fn patch_operation(op: &str, path: &str, value: Option<&Value>) -> Value {
+    let mut operation = json!({"op": op, "path": path});
+    if let Some(value) = value {
+        operation["value"] = value.clone();
+    }
+    operation
+}
+
+fn collect_patch(old: &Value, new: &Value, path: &mut String, operations: &mut Vec<Value>) {
+    match (old, new) {
+        (Value::Object(old_map), Value::Object(new_map)) => {
+            for (key, old_value) in old_map {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&escape_pointer_token(key));
+                match new_map.get(key) {
+                    Some(new_value) => collect_patch(old_value, new_value, path, operations),
+                    None => operations.push(patch_operation("remove", path, None)),
+                }
+                path.truncate(len);
+            }
+            for (key, new_value) in new_map
+                .iter()
+                .filter(|(key, _)| !old_map.contains_key(*key))
+            {
+                let path = format!("{}/{}", path, escape_pointer_token(key));
+                operations.push(patch_operation("add", &path, Some(new_value)));
+            }
+        }
+        (Value::Array(old_items), Value::Array(new_items)) => {
+            for (index, (old_item, new_item)) in old_items.iter().zip(new_items).enumerate() {
+                let len = path.len();
+                path.push('/');
+                path.push_str(&index.to_string());
+                collect_patch(old_item, new_item, path, operations);
+                path.truncate(len);
+            }
+            for (index, new_item) in new_items.iter().enumerate().skip(old_items.len()) {
+                let path = format!("{}/{}", path, index);
+                operations.push(patch_operation("add", &path, Some(new_item)));
+            }
+            for index in (new_items.len()..old_items.len()).rev() {
+                let path = format!("{}/{}", path, index);
+                operations.push(patch_operation("remove", &path, None));
+            }
+        }
+        _ => {
+            if old != new {
+                operations.push(patch_operation("replace", path, Some(new)));
+            }
+        }
+    }
+}
+
+fn apply_operation(document: &mut Value, operation: &Value) -> Result<()> {
+    let field = |name: &str| {
+        operation
+            .get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| SdkError::InvalidInput(format!("missing `{}` field", name)))
+    };
+    let value = || {
+        operation
+            .get("value")
+            .cloned()
+            .ok_or_else(|| SdkError::InvalidInput("missing `value` field".to_string()))
+    };
+    let path = field("path")?;
+
+    match field("op")? {
+        "add" => add_value(document, path, value()?),
+        "remove" => remove_value(document, path).map(|_| ()),
+        "replace" => {
+            let target = document
+                .pointer_mut(path)
+                .ok_or_else(|| SdkError::InvalidInput(format!("path {} does not exist", path)))?;
+            *target = value()?;
+            Ok(())
+        }
+        "move" => {
+            let from = field("from")?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(SdkError::InvalidInput(format!(
+                    "cannot move {} inside of one of its children",
+                    from
+                )));
+            }
+            let moved = remove_value(document, from)?;
+            add_value(document, path, moved)
+        }
+        "copy" => {
+            let from = field("from")?;
+            let copied = document
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| SdkError::InvalidInput(format!("path {} does not exist", from)))?;
+            add_value(document, path, copied)
+        }
+        "test" => match document.pointer(path) {
+            Some(current) if *current == value()? => Ok(()),
+            _ => Err(SdkError::InvalidInput(format!(
+                "test of path {} failed",
+                path
+            ))),
+        },
+        op => Err(SdkError::InvalidInput(format!(
+            "unknown operation `{}`",
+            op
+        ))),
+    }
+}
+
+/// Split a JSON Pointer into the pointer of the parent and the unescaped
+/// last token
+fn split_pointer(path: &str) -> Result<(&str, String)> {
+    let (parent, token) = path
+        .rsplit_once('/')
+        .ok_or_else(|| SdkError::InvalidInput(format!("invalid JSON Pointer `{}`", path)))?;
+    Ok((parent, token.replace("~1", "/").replace("~0", "~")))
+}
+
+fn array_index(token: &str, len: usize) -> Result<usize> {
+    match token.parse::<usize>() {
+        Ok(index) if index <= len && (token == "0" || !token.starts_with('0')) => Ok(index),
+        _ => Err(SdkError::InvalidInput(format!(
+            "invalid array index `{}`",
+            token
+        ))),
+    }
+}
+
+fn add_value(document: &mut Value, path: &str, value: Value) -> Result<()> {
+    if path.is_empty() {
+        *document = value;
+        return Ok(());
+    }
+
+    let (parent, token) = split_pointer(path)?;
+    match document.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = if token == "-" {
+                items.len()
+            } else {
+                array_index(&token, items.len())?
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err(SdkError::InvalidInput(format!(
+            "path {} is neither an object nor an array",
+            parent
+        ))),
+        None => Err(SdkError::InvalidInput(format!(
+            "path {} does not exist",
+            parent
+        ))),
+    }
+}
+
+fn remove_value(document: &mut Value, path: &str) -> Result<Value> {
+    let (parent, token) = split_pointer(path)?;
+    let removed = match document.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token),
+        Some(Value::Array(items)) => match array_index(&token, items.len()) {
+            Ok(index) if index < items.len() => Some(items.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| SdkError::InvalidInput(format!("path {} does not exist", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_changes() {
+        let value = json!({"spec": {"containers": [{"image": "nginx"}]}});
+        assert!(changed_paths(&value, &value).is_empty());
+    }
+
+    #[test]
+    fn detect_added_removed_and_modified_fields() {
+        let old = json!({
+            "metadata": {"labels": {"app": "a", "app.kubernetes.io/name": "a"}},
+            "spec": {"containers": [{"image": "nginx"}, {"image": "busybox"}]}
+        });
+        let new = json!({
+            "metadata": {"labels": {"app": "b"}, "annotations": {"a~b": "c"}},
+            "spec": {"containers": [{"image": "nginx:1.25"}]}
+        });
+
+        let mut paths = changed_paths(&old, &new);
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                "/metadata/annotations",
+                "/metadata/labels/app",
+                "/metadata/labels/app.kubernetes.io~1name",
+                "/spec/containers/0/image",
+                "/spec/containers/1",
+            ]
+        );
+    }
+
+    #[test]
+    fn type_change_is_reported_once() {
+        let old = json!({"spec": {"ports": [80]}});
+        let new = json!({"spec": {"ports": "80"}});
+        assert_eq!(changed_paths(&old, &new), vec!["/spec/ports"]);
+    }
+
+    #[test]
+    fn unchanged_fields() {
+        let old = json!({"spec": {"storageClassName": "fast", "size": "1Gi"}});
+        let new = json!({"spec": {"storageClassName": "fast", "size": "2Gi"}});
+
+        assert!(assert_unchanged(&old, &new, &["/spec/storageClassName", "/spec/missing"]).is_ok());
+        let err = assert_unchanged(&old, &new, &["/spec/storageClassName", "/spec/size"])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "the following fields are immutable: /spec/size");
+    }
+
+    #[test]
+    fn patch_operations() {
+        let old = json!({
+            "metadata": {"labels": {"app": "a", "app.kubernetes.io/name": "a"}},
+            "spec": {"containers": [{"image": "nginx"}, {"image": "busybox"}, {"image": "redis"}]}
+        });
+        let new = json!({
+            "metadata": {"labels": {"app": "b"}, "annotations": {"a~b": "c"}},
+            "spec": {"containers": [{"image": "nginx:1.25"}]}
+        });
+
+        assert_eq!(
+            json_patch(&old, &new),
+            json!([
+                {"op": "replace", "path": "/metadata/labels/app", "value": "b"},
+                {"op": "remove", "path": "/metadata/labels/app.kubernetes.io~1name"},
+                {"op": "add", "path": "/metadata/annotations", "value": {"a~b": "c"}},
+                {"op": "replace", "path": "/spec/containers/0/image", "value": "nginx:1.25"},
+                {"op": "remove", "path": "/spec/containers/2"},
+                {"op": "remove", "path": "/spec/containers/1"},
+            ])
+        );
+        assert_eq!(json_patch(&old, &old), json!([]));
+    }
+
+    #[test]
+    fn apply_generated_patch() {
+        let old = json!({
+            "metadata": {"labels": {"app": "a", "a/b": "a"}},
+            "spec": {"containers": [{"image": "nginx"}, {"image": "busybox"}, {"image": "redis"}]}
+        });
+        let new = json!({
+            "metadata": {"labels": {"app": "b"}, "annotations": {"a~b": "c"}},
+            "spec": {"containers": [{"image": "nginx:1.25"}], "hostNetwork": false}
+        });
+
+        assert_eq!(apply_patch(&old, &json_patch(&old, &new)).unwrap(), new);
+        assert_eq!(apply_patch(&new, &json_patch(&new, &old)).unwrap(), old);
+    }
+
+    #[test]
+    fn apply_all_operations() {
+        let document = json!({"spec": {"containers": [{"name": "a"}], "volumes": []}});
+        let patch = json!([
+            {"op": "test", "path": "/spec/containers/0/name", "value": "a"},
+            {"op": "add", "path": "/spec/containers/-", "value": {"name": "b"}},
+            {"op": "copy", "from": "/spec/containers/0", "path": "/spec/initContainers"},
+            {"op": "move", "from": "/spec/volumes", "path": "/spec/ephemeralVolumes"},
+            {"op": "replace", "path": "/spec/containers/1/name", "value": "c"},
+            {"op": "remove", "path": "/spec/containers/0"},
+        ]);
+
+        assert_eq!(
+            apply_patch(&document, &patch).unwrap(),
+            json!({"spec": {
+                "containers": [{"name": "c"}],
+                "initContainers": {"name": "a"},
+                "ephemeralVolumes": []
+            }})
+        );
+    }
+
+    #[test]
+    fn apply_invalid_patch() {
+        let document = json!({"spec": {"containers": []}});
+        for (patch, error) in [
+            (
+                json!([{"op": "remove", "path": "/spec/volumes"}]),
+                "cannot apply JSON Patch operation #0: path /spec/volumes does not exist",
+            ),
+            (
+                json!([{"op": "add", "path": "/spec/containers/1", "value": {}}]),
+                "cannot apply JSON Patch operation #0: invalid array index `1`",
+            ),
+            (
+                json!([{"op": "test", "path": "/spec", "value": {}}]),
+                "cannot apply JSON Patch operation #0: test of path /spec failed",
+            ),
+            (json!({}), "a JSON Patch must be an array of operations"),
+        ] {
+            assert_eq!(
+                apply_patch(&document, &patch).unwrap_err().to_string(),
+                error
+            );
+        }
+    }
+}
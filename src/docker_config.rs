@@ -0,0 +1,431 @@
+//! Parse the registry credentials stored in `~/.docker/config.json` files,
+//! and inside of the `kubernetes.io/dockerconfigjson` Secrets used as image
+//! pull secrets.
+//!
+//! The credentials of a registry can be stored inline, under `auths`, or be
+//! provided by a credential helper configured via `credHelpers` or
+//! `credsStore`. Helpers are external programs, which cannot run inside of
+//! the cluster: [`DockerConfig::lookup`] reports them, so that policies can
+//! reject pull secrets that would not work.
+//!
+//! ```
+//! use kubewarden_policy_sdk::docker_config::{Credential, DockerConfig, Lookup};
+//!
+//! let config = DockerConfig::parse(br#"{
+//!     "auths": {"https://index.docker.io/v1/": {"auth": "YWxpY2U6c2VjcmV0"}},
+//!     "credHelpers": {"123456789012.dkr.ecr.eu-west-1.amazonaws.com": "ecr-login"}
+//! }"#)
+//! .unwrap();
+//!
+//! match config.lookup("nginx:latest").unwrap() {
+//!     Some(Lookup::Credential(Credential::Basic { username, .. })) => assert_eq!(username, "alice"),
+//!     _ => unreachable!(),
+//! }
+//! assert_eq!(
+//!     config.lookup("123456789012.dkr.ecr.eu-west-1.amazonaws.com/app").unwrap(),
+//!     Some(Lookup::Helper("ecr-login".to_string()))
+//! );
+//! ```
+use crate::constraints::image_registry;
+use crate::error::{Result, SdkError};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Type of the Secrets holding a `config.json` file
+pub const DOCKER_CONFIG_JSON_SECRET_TYPE: &str = "kubernetes.io/dockerconfigjson";
+
+/// Type of the Secrets holding a legacy `~/.dockercfg` file
+pub const DOCKER_CFG_SECRET_TYPE: &str = "kubernetes.io/dockercfg";
+
+/// The registry host used for the images without one
+const DOCKER_HUB: &str = "docker.io";
+
+/// The contents of a `config.json` file. Only the fields related to the
+/// registry credentials are decoded
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DockerConfig {
+    /// The inline credentials, keyed by registry
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub auths: BTreeMap<String, AuthEntry>,
+    /// The credential helper of each registry, without the
+    /// `docker-credential-` prefix
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cred_helpers: BTreeMap<String, String>,
+    /// The credential helper used by all the registries without an entry
+    /// inside of `credHelpers`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creds_store: Option<String>,
+}
+
+/// The credentials of a registry stored inside of `auths`
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthEntry {
+    /// base64 encoding of `username:password`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// OAuth refresh token, exchanged for a registry token
+    #[serde(rename = "identitytoken", skip_serializing_if = "Option::is_none")]
+    pub identity_token: Option<String>,
+    /// Bearer token sent as it is to the registry
+    #[serde(rename = "registrytoken", skip_serializing_if = "Option::is_none")]
+    pub registry_token: Option<String>,
+}
+
+impl fmt::Debug for AuthEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redact = |value: &Option<String>| value.as_ref().map(|_| crate::logging::REDACTED);
+        f.debug_struct("AuthEntry")
+            .field("auth", &redact(&self.auth))
+            .field("username", &self.username)
+            .field("password", &redact(&self.password))
+            .field("identity_token", &redact(&self.identity_token))
+            .field("registry_token", &redact(&self.registry_token))
+            .finish()
+    }
+}
+
+impl AuthEntry {
+    /// The credential stored by the entry. The `auth` field takes precedence
+    /// over `username` and `password`, like the Docker CLI does. Returns
+    /// `None` for entries without credentials
+    pub fn credential(&self) -> Result<Option<Credential>> {
+        if let Some(token) = &self.identity_token {
+            return Ok(Some(Credential::IdentityToken(token.clone())));
+        }
+        if let Some(token) = &self.registry_token {
+            return Ok(Some(Credential::RegistryToken(token.clone())));
+        }
+        if let Some(auth) = self.auth.as_deref().filter(|auth| !auth.is_empty()) {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(auth)
+                .map_err(|e| SdkError::InvalidInput(format!("invalid auth field: {}", e)))?;
+            let decoded = String::from_utf8(decoded).map_err(|_| {
+                SdkError::InvalidInput("invalid auth field: not valid UTF-8".to_string())
+            })?;
+            let (username, password) = decoded.split_once(':').ok_or_else(|| {
+                SdkError::InvalidInput(
+                    "invalid auth field: username and password must be separated by ':'"
+                        .to_string(),
+                )
+            })?;
+            return Ok(Some(Credential::Basic {
+                username: username.to_string(),
+                password: password.to_string(),
+            }));
+        }
+        match (&self.username, &self.password) {
+            (Some(username), Some(password)) => Ok(Some(Credential::Basic {
+                username: username.clone(),
+                password: password.clone(),
+            })),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// A registry credential. The secrets are redacted by the `Debug` output
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credential {
+    /// Username and password
+    Basic { username: String, password: String },
+    /// OAuth refresh token
+    IdentityToken(String),
+    /// Bearer token
+    RegistryToken(String),
+}
+
+impl fmt::Debug for Credential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credential::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &crate::logging::REDACTED)
+                .finish(),
+            Credential::IdentityToken(_) => f
+                .debug_tuple("IdentityToken")
+                .field(&crate::logging::REDACTED)
+                .finish(),
+            Credential::RegistryToken(_) => f
+                .debug_tuple("RegistryToken")
+                .field(&crate::logging::REDACTED)
+                .finish(),
+        }
+    }
+}
+
+/// Where the credentials of a registry come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// The credential is stored inside of the configuration
+    Credential(Credential),
+    /// The credential is provided by the given helper, without the
+    /// `docker-credential-` prefix
+    Helper(String),
+}
+
+impl DockerConfig {
+    /// Parse the contents of a `config.json` file
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        serde_json::from_slice(contents)
+            .map_err(|e| SdkError::deserialization("the docker config", e))
+    }
+
+    /// Decode the configuration stored inside of an image pull Secret, either
+    /// of type `kubernetes.io/dockerconfigjson` or of the legacy
+    /// `kubernetes.io/dockercfg` one
+    pub fn from_secret(secret: &serde_json::Value) -> Result<Self> {
+        let secret_type = secret["type"].as_str().unwrap_or_default();
+        let key = match secret_type {
+            DOCKER_CONFIG_JSON_SECRET_TYPE => ".dockerconfigjson",
+            DOCKER_CFG_SECRET_TYPE => ".dockercfg",
+            _ => {
+                return Err(SdkError::InvalidInput(format!(
+                    "Secret of type '{}' is not an image pull secret",
+                    secret_type
+                )))
+            }
+        };
+        let data = secret["data"][key]
+            .as_str()
+            .ok_or_else(|| SdkError::InvalidInput(format!("the Secret has no {} key", key)))?;
+        let contents = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| SdkError::InvalidInput(format!("invalid Secret data: {}", e)))?;
+
+        if secret_type == DOCKER_CFG_SECRET_TYPE {
+            // the legacy format holds only the `auths` map
+            let auths = serde_json::from_slice(&contents)
+                .map_err(|e| SdkError::deserialization("the dockercfg file", e))?;
+            return Ok(DockerConfig {
+                auths,
+                ..Default::default()
+            });
+        }
+        Self::parse(&contents)
+    }
+
+    /// The registries with a credential, either inline or provided by a
+    /// helper. The hosts are normalized, `docker.io` is used for Docker Hub
+    pub fn registries(&self) -> Vec<String> {
+        let mut registries: Vec<String> = self
+            .auths
+            .keys()
+            .chain(self.cred_helpers.keys())
+            .map(|key| normalize_registry(key).to_string())
+            .collect();
+        registries.sort();
+        registries.dedup();
+        registries
+    }
+
+    /// Whether a credential helper is configured, for any registry
+    pub fn uses_helpers(&self) -> bool {
+        self.creds_store.is_some() || !self.cred_helpers.is_empty()
+    }
+
+    /// Find the credential of the registry of `image`, which can also be a
+    /// bare registry host. The lookup follows the Docker CLI rules:
+    /// `credHelpers` first, then `auths`, finally `credsStore`
+    pub fn lookup(&self, image: &str) -> Result<Option<Lookup>> {
+        let registry = registry_of(image);
+
+        if let Some(helper) = self
+            .cred_helpers
+            .iter()
+            .find(|(key, _)| normalize_registry(key) == registry)
+            .map(|(_, helper)| helper)
+        {
+            return Ok(Some(Lookup::Helper(helper.clone())));
+        }
+        if let Some(entry) = self
+            .auths
+            .iter()
+            .find(|(key, _)| normalize_registry(key) == registry)
+            .map(|(_, entry)| entry)
+        {
+            if let Some(credential) = entry.credential()? {
+                return Ok(Some(Lookup::Credential(credential)));
+            }
+        }
+        Ok(self.creds_store.clone().map(Lookup::Helper))
+    }
+}
+
+/// The registry of an image reference, a bare host is already a registry.
+/// Names without a `/` are hosts only when they look like one, `nginx:1.25`
+/// is a Docker Hub image
+fn registry_of(image: &str) -> &str {
+    let name = image.split(':').next().unwrap_or_default();
+    if image.contains('/') {
+        normalize_registry(image_registry(image))
+    } else if name.contains('.') || name == "localhost" {
+        normalize_registry(image)
+    } else {
+        DOCKER_HUB
+    }
+}
+
+/// Reduce a key of `auths`, which can be a URL like
+/// `https://index.docker.io/v1/`, to the registry host
+pub(crate) fn normalize_registry(key: &str) -> &str {
+    let host = key
+        .strip_prefix("https://")
+        .or_else(|| key.strip_prefix("http://"))
+        .unwrap_or(key);
+    let host = host.split('/').next().unwrap_or_default();
+    match host {
+        "index.docker.io" | "registry-1.docker.io" | "registry.hub.docker.com" => DOCKER_HUB,
+        host => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encode(data: &str) -> String {
+        base64::engine::general_purpose::STANDARD.encode(data)
+    }
+
+    #[test]
+    fn parse_credentials() {
+        let config = DockerConfig::parse(
+            json!({
+                "auths": {
+                    "https://index.docker.io/v1/": {"auth": encode("alice:pa:ss")},
+                    "registry.example.com": {"username": "bob", "password": "secret"},
+                    "ghcr.io": {"identitytoken": "refresh"},
+                    "quay.io": {}
+                },
+                "credsStore": "desktop",
+                "HttpHeaders": {"User-Agent": "docker"}
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.lookup("busybox").unwrap(),
+            Some(Lookup::Credential(Credential::Basic {
+                username: "alice".to_string(),
+                password: "pa:ss".to_string(),
+            }))
+        );
+        assert_eq!(
+            config.lookup("registry.example.com/team/app:1.0").unwrap(),
+            Some(Lookup::Credential(Credential::Basic {
+                username: "bob".to_string(),
+                password: "secret".to_string(),
+            }))
+        );
+        assert_eq!(
+            config.lookup("ghcr.io").unwrap(),
+            Some(Lookup::Credential(Credential::IdentityToken(
+                "refresh".to_string()
+            )))
+        );
+        // entries without credentials fall back to the global helper
+        assert_eq!(
+            config.lookup("quay.io/org/app").unwrap(),
+            Some(Lookup::Helper("desktop".to_string()))
+        );
+        assert_eq!(
+            config.registries(),
+            ["docker.io", "ghcr.io", "quay.io", "registry.example.com"]
+        );
+        assert!(config.uses_helpers());
+        assert!(DockerConfig::parse(br#"{"auths": []}"#).is_err());
+    }
+
+    #[test]
+    fn credential_helpers_take_precedence() {
+        let config: DockerConfig = serde_json::from_value(json!({
+            "auths": {"gcr.io": {"auth": encode("_json_key:{}")}},
+            "credHelpers": {"gcr.io": "gcloud"}
+        }))
+        .unwrap();
+        assert_eq!(
+            config.lookup("gcr.io/project/app").unwrap(),
+            Some(Lookup::Helper("gcloud".to_string()))
+        );
+        assert_eq!(config.lookup("localhost:5000/app").unwrap(), None);
+        assert_eq!(config.lookup("localhost:5000").unwrap(), None);
+        assert_eq!(config.lookup("nginx:1.25").unwrap(), None);
+        assert!(!DockerConfig::default().uses_helpers());
+    }
+
+    #[test]
+    fn invalid_auth_fields() {
+        let entry = AuthEntry {
+            auth: Some(encode("no-separator")),
+            ..Default::default()
+        };
+        assert!(entry.credential().is_err());
+
+        let entry = AuthEntry {
+            auth: Some("not base64!".to_string()),
+            ..Default::default()
+        };
+        assert!(entry.credential().is_err());
+        assert_eq!(AuthEntry::default().credential().unwrap(), None);
+    }
+
+    #[test]
+    fn decode_pull_secrets() {
+        let config = json!({"auths": {"ghcr.io": {"auth": encode("user:token")}}});
+        let secret = json!({
+            "type": "kubernetes.io/dockerconfigjson",
+            "data": {".dockerconfigjson": encode(&config.to_string())}
+        });
+        let config = DockerConfig::from_secret(&secret).unwrap();
+        assert_eq!(config.registries(), ["ghcr.io"]);
+
+        let secret = json!({
+            "type": "kubernetes.io/dockercfg",
+            "data": {".dockercfg": encode(r#"{"https://quay.io": {"auth": "dTpw"}}"#)}
+        });
+        let config = DockerConfig::from_secret(&secret).unwrap();
+        assert_eq!(
+            config.lookup("quay.io/app").unwrap(),
+            Some(Lookup::Credential(Credential::Basic {
+                username: "u".to_string(),
+                password: "p".to_string(),
+            }))
+        );
+
+        assert!(DockerConfig::from_secret(&json!({"type": "Opaque", "data": {}})).is_err());
+        assert!(DockerConfig::from_secret(&json!({
+            "type": "kubernetes.io/dockerconfigjson",
+            "data": {}
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn redact_secrets() {
+        let credential = Credential::Basic {
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+        };
+        let debug = format!("{:?}", credential);
+        assert!(debug.contains("alice"));
+        assert!(!debug.contains("secret"));
+
+        let entry = AuthEntry {
+            auth: Some(encode("alice:secret")),
+            ..Default::default()
+        };
+        assert!(!format!("{:?}", entry).contains(&encode("alice:secret")));
+    }
+}
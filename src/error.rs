@@ -0,0 +1,504 @@
+//! The error type returned by the SDK.
+//!
+//! Host capabilities and request parsing functions return [`SdkError`], which
+//! allows policies to match on the cause of a failure. `SdkError` implements
+//! [`std::error::Error`], hence it can be converted into an `anyhow::Error` or
+//! returned from a waPC function via the `?` operator.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::error::SdkError;
+//! use kubewarden_policy_sdk::request::GroupVersionKind;
+//!
+//! match "Pod".parse::<GroupVersionKind>() {
+//!     Err(SdkError::InvalidInput(message)) => assert!(message.contains("Pod")),
+//!     _ => unreachable!(),
+//! }
+//! ```
+//!
+//! # Breadcrumbs
+//!
+//! The [`ErrorContext`] trait attaches a description of the operation that
+//! was being performed to an error, building a trail of breadcrumbs that is
+//! shown by the error message:
+//!
+//! ```
+//! use kubewarden_policy_sdk::error::{ErrorContext, Result, SdkError};
+//!
+//! fn fetch_manifest(image: &str) -> Result<String> {
+//!     Err(SdkError::InvalidInput(format!("{} not found", image)))
+//! }
+//!
+//! fn verify(image: &str) -> Result<String> {
+//!     fetch_manifest(image)
+//!         .breadcrumb("while fetching manifest")
+//!         .with_breadcrumb(|| format!("while verifying image {}", image))
+//! }
+//!
+//! let error = verify("busybox").unwrap_err();
+//! assert_eq!(
+//!     error.to_string(),
+//!     "while verifying image busybox: while fetching manifest: busybox not found"
+//! );
+//! assert_eq!(error.breadcrumbs(), vec!["while verifying image busybox", "while fetching manifest"]);
+//! ```
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use thiserror::Error;
+
+/// Result type returned by the SDK functions
+pub type Result<T, E = SdkError> = std::result::Result<T, E>;
+
+/// Errors returned by the SDK
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum SdkError {
+    /// A value could not be serialized, usually the payload of a host capability
+    #[error("error serializing {what}: {source}")]
+    Serialization {
+        /// Description of the value being serialized
+        what: String,
+        /// The serialization error
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A value could not be deserialized, usually the incoming request or the
+    /// response of a host capability
+    #[error("error deserializing {what}: {source}")]
+    Deserialization {
+        /// Description of the value being deserialized
+        what: String,
+        /// The deserialization error
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The host returned an error while running a host capability
+    #[error("error invoking host capability {namespace}.{operation}: {source}")]
+    HostCallback {
+        /// The namespace of the capability (e.g. `oci`)
+        namespace: String,
+        /// The operation of the capability (e.g. `v2/verify`)
+        operation: String,
+        /// The error reported by the host
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    /// The host capability failed, reporting a structured [`HostError`]
+    #[error("error invoking host capability {namespace}.{operation}: {error}")]
+    HostCapability {
+        /// The namespace of the capability (e.g. `oci`)
+        namespace: String,
+        /// The operation of the capability (e.g. `v2/verify`)
+        operation: String,
+        /// The error reported by the host
+        #[source]
+        error: HostError,
+    },
+
+    /// The operation is not supported by the host capability
+    #[error("{capability} capability: {message}")]
+    UnsupportedCapability {
+        /// The capability (e.g. `oci.v1/verify`)
+        capability: String,
+        /// Description of the problem
+        message: String,
+    },
+
+    /// The object kind is not supported by the operation
+    #[error("unsupported kind {kind}, expected one of: {}", expected.join(", "))]
+    UnsupportedKind {
+        /// The kind found
+        kind: String,
+        /// The kinds supported by the operation
+        expected: Vec<String>,
+    },
+
+    /// A state-changing side effect was attempted while evaluating a dry-run request
+    #[error("{action} is not allowed during dry-run evaluations")]
+    DryRun {
+        /// Description of the side effect
+        action: String,
+    },
+
+    /// The time budget of the evaluation has been spent, see [`deadline`](crate::deadline)
+    #[error("the evaluation deadline of {budget:?} has been exceeded")]
+    DeadlineExceeded {
+        /// The whole time budget of the evaluation
+        budget: std::time::Duration,
+    },
+
+    /// The value provided is not valid
+    #[error("{0}")]
+    InvalidInput(String),
+
+    /// An error that happened while performing the described operation. See
+    /// [`ErrorContext`]
+    #[error("{context}: {source}")]
+    Context {
+        /// Description of the operation, e.g. `while verifying image busybox`
+        context: String,
+        /// The error
+        #[source]
+        source: Box<SdkError>,
+    },
+}
+
+impl SdkError {
+    pub(crate) fn serialization(what: &str, source: serde_json::Error) -> Self {
+        SdkError::Serialization {
+            what: what.to_string(),
+            source,
+        }
+    }
+
+    pub(crate) fn deserialization(what: &str, source: serde_json::Error) -> Self {
+        SdkError::Deserialization {
+            what: what.to_string(),
+            source,
+        }
+    }
+
+    /// The error of a host capability. Errors using the [`HostError`] wire
+    /// format become [`SdkError::HostCapability`], the other ones
+    /// [`SdkError::HostCallback`]
+    pub(crate) fn host_callback(
+        namespace: &str,
+        operation: &str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    ) -> Self {
+        match HostError::parse(&source.to_string()) {
+            Some(error) => SdkError::HostCapability {
+                namespace: namespace.to_string(),
+                operation: operation.to_string(),
+                error,
+            },
+            None => SdkError::HostCallback {
+                namespace: namespace.to_string(),
+                operation: operation.to_string(),
+                source,
+            },
+        }
+    }
+
+    /// The structured error reported by the host, if any
+    pub fn host_error(&self) -> Option<&HostError> {
+        match self.root_cause() {
+            SdkError::HostCapability { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Whether the host, or the SDK, doesn't support the capability. Besides
+    /// [`SdkError::UnsupportedCapability`] and the [`HostErrorCode::Unimplemented`]
+    /// host errors, this recognizes the messages of the hosts predating the
+    /// structured errors, like `unknown operation: v2/verify`
+    pub fn is_unsupported_capability(&self) -> bool {
+        match self.root_cause() {
+            SdkError::UnsupportedCapability { .. } => true,
+            SdkError::HostCapability { error, .. } => error.code == HostErrorCode::Unimplemented,
+            SdkError::HostCallback { source, .. } => {
+                let message = source.to_string().to_lowercase();
+                LEGACY_UNSUPPORTED_MESSAGES
+                    .iter()
+                    .any(|legacy| message.contains(legacy))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the operation can succeed when attempted again, e.g. because
+    /// the registry was temporarily unreachable. Only the host can tell, all
+    /// the other errors are not retryable
+    pub fn is_retryable(&self) -> bool {
+        self.host_error().is_some_and(|error| error.retryable)
+    }
+
+    /// The descriptions of the operations attached through [`ErrorContext`],
+    /// the outermost first
+    pub fn breadcrumbs(&self) -> Vec<&str> {
+        let mut breadcrumbs = Vec::new();
+        let mut error = self;
+        while let SdkError::Context { context, source } = error {
+            breadcrumbs.push(context.as_str());
+            error = source;
+        }
+        breadcrumbs
+    }
+
+    /// The error without the breadcrumbs attached through [`ErrorContext`]
+    pub fn root_cause(&self) -> &SdkError {
+        match self {
+            SdkError::Context { source, .. } => source.root_cause(),
+            error => error,
+        }
+    }
+
+    /// Reject the request because of this error, failing closed. The error
+    /// is logged together with its breadcrumbs, and is shown to the user by
+    /// the rejection message
+    pub fn fail_closed(&self) -> wapc_guest::CallResult {
+        slog::error!(
+            crate::logging::logger(),
+            "rejecting the request because of an error";
+            "error" => self.root_cause().to_string(),
+            "breadcrumbs" => slog::Serde(self.breadcrumbs().iter().map(|b| b.to_string()).collect::<Vec<_>>())
+        );
+        crate::response::ValidationResponse::reject(self.to_string()).build()
+    }
+
+    pub(crate) fn unsupported_kind(kind: &str, expected: &[&str]) -> Self {
+        SdkError::UnsupportedKind {
+            kind: kind.to_string(),
+            expected: expected.iter().map(|k| k.to_string()).collect(),
+        }
+    }
+}
+
+/// Messages of the hosts predating [`HostError`], reporting unknown
+/// capabilities
+const LEGACY_UNSUPPORTED_MESSAGES: &[&str] = &[
+    "unknown operation",
+    "unknown namespace",
+    "unknown binding",
+    "not implemented",
+];
+
+/// The category of a [`HostError`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum HostErrorCode {
+    /// The request sent by the policy is not valid
+    InvalidArgument,
+    /// The resource does not exist, e.g. the image or the Kubernetes object
+    NotFound,
+    /// The host is not allowed to access the resource
+    PermissionDenied,
+    /// The host could not authenticate against the remote service
+    Unauthenticated,
+    /// The remote service cannot be reached
+    Unavailable,
+    /// The remote service did not answer in time
+    DeadlineExceeded,
+    /// A quota or a rate limit has been exhausted
+    ResourceExhausted,
+    /// An unexpected error of the host
+    Internal,
+    /// The capability is not implemented by the host
+    Unimplemented,
+    /// A code unknown to this version of the SDK
+    #[serde(other)]
+    Unknown,
+}
+
+impl fmt::Display for HostErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = serde_json::to_value(self).expect("codes are always serializable");
+        f.write_str(code.as_str().unwrap_or_default())
+    }
+}
+
+/// The structured error of a host capability.
+///
+/// Hosts report it as the waPC error message, using the JSON wire format
+/// `{"code": "unavailable", "message": "...", "retryable": true}`. The
+/// `retryable` field is optional, and defaults to `false`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HostError {
+    /// The category of the error
+    pub code: HostErrorCode,
+    /// Description of the error
+    pub message: String,
+    /// Whether the operation can succeed when attempted again
+    #[serde(default)]
+    pub retryable: bool,
+}
+
+impl HostError {
+    /// Decode the error message reported by the host. Returns `None` when
+    /// the message doesn't use the wire format
+    pub fn parse(message: &str) -> Option<Self> {
+        if !message.trim_start().starts_with('{') {
+            return None;
+        }
+        serde_json::from_str(message).ok()
+    }
+}
+
+impl fmt::Display for HostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.message, self.code)?;
+        if self.retryable {
+            write!(f, ", retryable")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for HostError {}
+
+/// Extension trait attaching breadcrumbs to the errors of the SDK, to
+/// describe the operation that was being performed when they happened
+pub trait ErrorContext<T> {
+    /// Attach the given breadcrumb to the error
+    fn breadcrumb<C: std::fmt::Display>(self, context: C) -> Result<T>;
+
+    /// Attach the breadcrumb returned by `f` to the error. `f` is invoked
+    /// only when there is an error
+    fn with_breadcrumb<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T> ErrorContext<T> for Result<T> {
+    fn breadcrumb<C: std::fmt::Display>(self, context: C) -> Result<T> {
+        self.with_breadcrumb(|| context)
+    }
+
+    fn with_breadcrumb<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|source| SdkError::Context {
+            context: f().to_string(),
+            source: Box::new(source),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_messages() {
+        let source = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = SdkError::deserialization("the validation request", source);
+        assert!(error
+            .to_string()
+            .starts_with("error deserializing the validation request: "));
+        assert!(std::error::Error::source(&error).is_some());
+
+        let error = SdkError::host_callback("oci", "v2/verify", "boom".into());
+        assert_eq!(
+            error.to_string(),
+            "error invoking host capability oci.v2/verify: boom"
+        );
+
+        let error = SdkError::unsupported_kind("Service", &["Pod", "Deployment"]);
+        assert_eq!(
+            error.to_string(),
+            "unsupported kind Service, expected one of: Pod, Deployment"
+        );
+    }
+
+    #[test]
+    fn breadcrumbs() {
+        let result: Result<()> = Err(SdkError::host_callback(
+            "oci",
+            "v1/manifest_digest",
+            "not found".into(),
+        ));
+        let error = result
+            .breadcrumb("while fetching manifest")
+            .breadcrumb("while verifying image busybox")
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "while verifying image busybox: while fetching manifest: error invoking host capability oci.v1/manifest_digest: not found"
+        );
+        assert_eq!(
+            error.breadcrumbs(),
+            vec!["while verifying image busybox", "while fetching manifest"]
+        );
+        assert!(matches!(error.root_cause(), SdkError::HostCallback { .. }));
+
+        let response: crate::response::ValidationResponse =
+            serde_json::from_slice(&error.fail_closed().unwrap()).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.unwrap(), error.to_string());
+
+        let ok: Result<u8> = Ok(1);
+        assert_eq!(
+            ok.with_breadcrumb(|| -> String { unreachable!() }).unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn structured_host_errors() {
+        let error = SdkError::host_callback(
+            "oci",
+            "v1/manifest_digest",
+            r#"{"code": "unavailable", "message": "registry unreachable", "retryable": true}"#
+                .into(),
+        );
+        assert_eq!(
+            error.to_string(),
+            "error invoking host capability oci.v1/manifest_digest: registry unreachable (unavailable, retryable)"
+        );
+        assert!(error.is_retryable());
+        assert_eq!(error.host_error().unwrap().code, HostErrorCode::Unavailable);
+
+        let error: Result<()> = Err(SdkError::host_callback(
+            "oci",
+            "v1/manifest_digest",
+            r#"{"code": "not_found", "message": "no such image"}"#.into(),
+        ));
+        let error = error
+            .breadcrumb("while verifying image busybox")
+            .unwrap_err();
+        assert!(!error.is_retryable());
+        assert_eq!(error.host_error().unwrap().code, HostErrorCode::NotFound);
+
+        let unknown = HostError::parse(r#"{"code": "teapot", "message": "short and stout"}"#);
+        assert_eq!(unknown.unwrap().code, HostErrorCode::Unknown);
+
+        // plain messages, and JSON not following the wire format
+        for message in ["boom", r#"{"error": "boom"}"#] {
+            let error = SdkError::host_callback("net", "v1/dns_lookup_host", message.into());
+            assert!(matches!(error, SdkError::HostCallback { .. }));
+            assert!(error.host_error().is_none());
+            assert!(!error.is_retryable());
+        }
+    }
+
+    #[test]
+    fn unsupported_capabilities() {
+        let unsupported = [
+            SdkError::UnsupportedCapability {
+                capability: "oci.v1/verify".to_string(),
+                message: "verification mode not supported".to_string(),
+            },
+            SdkError::host_callback(
+                "oci",
+                "v3/verify",
+                r#"{"code": "unimplemented", "message": "no such capability"}"#.into(),
+            ),
+            SdkError::host_callback("oci", "v3/verify", "Unknown operation: v3/verify".into()),
+        ];
+        for error in unsupported {
+            assert!(error.is_unsupported_capability(), "{}", error);
+        }
+
+        let error = SdkError::host_callback("oci", "v2/verify", "image not signed".into());
+        assert!(!error.is_unsupported_capability());
+        assert!(
+            !SdkError::InvalidInput("unknown operation".to_string()).is_unsupported_capability()
+        );
+    }
+
+    #[test]
+    fn convert_into_anyhow() {
+        fn fallible() -> anyhow::Result<()> {
+            Err(SdkError::InvalidInput("bad value".to_string()))?;
+            Ok(())
+        }
+
+        let error = fallible().unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SdkError>(),
+            Some(SdkError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Adapters for the input and output of OPA Gatekeeper constraints.
+//!
+//! Gatekeeper constraint templates receive the admission request as
+//! `input.review` and the parameters of the constraint as
+//! `input.parameters`, and produce a list of violations. These adapters
+//! convert between that shape and the one of the SDK, easing the migration
+//! of constraint logic and of its test data:
+//!
+//! ```
+//! use kubewarden_policy_sdk::gatekeeper::{GatekeeperInput, Violation};
+//! use serde::Deserialize;
+//! use serde_json::json;
+//!
+//! #[derive(Deserialize, Default)]
+//! struct Parameters {
+//!     labels: Vec<String>,
+//! }
+//!
+//! let input: GatekeeperInput<Parameters> = serde_json::from_value(json!({
+//!     "review": {
+//!         "operation": "CREATE",
+//!         "kind": {"group": "", "version": "v1", "kind": "Namespace"},
+//!         "object": {"metadata": {"name": "team-a", "labels": {}}}
+//!     },
+//!     "parameters": {"labels": ["owner"]}
+//! }))
+//! .unwrap();
+//! let request = input.into_validation_request();
+//!
+//! let violations: Vec<Violation> = request
+//!     .settings
+//!     .labels
+//!     .iter()
+//!     .filter(|label| request.request.object["metadata"]["labels"].get(label.as_str()).is_none())
+//!     .map(|label| Violation::new(format!("missing label {}", label)))
+//!     .collect();
+//!
+//! let response = Violation::into_response(violations);
+//! assert!(!response.accepted);
+//! assert_eq!(response.message.unwrap(), "missing label owner");
+//! ```
+use crate::request::{KubernetesAdmissionRequest, ValidationRequest};
+use crate::response::ValidationResponse;
+use serde::{Deserialize, Serialize};
+
+/// The `input` document of a Gatekeeper constraint template
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GatekeeperInput<P: Default> {
+    /// The admission request being reviewed
+    #[serde(default)]
+    pub review: KubernetesAdmissionRequest,
+
+    /// The parameters of the constraint
+    #[serde(default)]
+    pub parameters: P,
+}
+
+impl<P: Default> GatekeeperInput<P> {
+    /// Convert into the request evaluated by a policy, using the parameters
+    /// of the constraint as settings
+    pub fn into_validation_request(self) -> ValidationRequest<P> {
+        ValidationRequest::from_parts(self.parameters, self.review)
+    }
+}
+
+impl<P: Default> From<ValidationRequest<P>> for GatekeeperInput<P> {
+    fn from(request: ValidationRequest<P>) -> Self {
+        GatekeeperInput {
+            review: request.request,
+            parameters: request.settings,
+        }
+    }
+}
+
+/// A violation reported by a Gatekeeper constraint template
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// Message describing the violation
+    pub msg: String,
+
+    /// Additional details about the violation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl Violation {
+    /// Create a violation with the given message
+    pub fn new(msg: impl Into<String>) -> Self {
+        Violation {
+            msg: msg.into(),
+            details: None,
+        }
+    }
+
+    /// Attach details to the violation
+    pub fn details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Convert the violations into a response: the request is accepted
+    /// when there are none, otherwise it's rejected with all the messages.
+    /// The details are not part of the response
+    pub fn into_response(violations: Vec<Violation>) -> ValidationResponse {
+        if violations.is_empty() {
+            return ValidationResponse::accept().response();
+        }
+        let message = violations
+            .into_iter()
+            .map(|v| v.msg)
+            .collect::<Vec<_>>()
+            .join("; ");
+        ValidationResponse::reject(message).response()
+    }
+
+    /// Convert a response into violations, the opposite of [`Violation::into_response`]
+    pub fn from_response(response: &ValidationResponse) -> Vec<Violation> {
+        if response.accepted {
+            return Vec::new();
+        }
+        vec![Violation::new(response.message.clone().unwrap_or_default())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn gatekeeper_round_trip() {
+        let input: GatekeeperInput<serde_json::Value> = serde_json::from_value(json!({
+            "review": {
+                "uid": "1234",
+                "operation": "UPDATE",
+                "userInfo": {"username": "alice"},
+                "object": {"metadata": {"name": "nginx"}},
+                "oldObject": {"metadata": {"name": "nginx"}}
+            },
+            "parameters": {"maxReplicas": 3}
+        }))
+        .unwrap();
+
+        let request = input.into_validation_request();
+        assert_eq!(request.settings["maxReplicas"], 3);
+        assert_eq!(request.request.user_info.username, "alice");
+        assert_eq!(request.request.old_object["metadata"]["name"], "nginx");
+
+        let input = GatekeeperInput::from(request);
+        assert_eq!(input.review.uid, "1234");
+        assert_eq!(input.parameters["maxReplicas"], 3);
+    }
+
+    #[test]
+    fn violations_and_responses() {
+        assert!(Violation::into_response(Vec::new()).accepted);
+
+        let response = Violation::into_response(vec![
+            Violation::new("too many replicas").details(json!({"max": 3})),
+            Violation::new("missing label owner"),
+        ]);
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.as_deref(),
+            Some("too many replicas; missing label owner")
+        );
+        assert_eq!(
+            Violation::from_response(&response),
+            vec![Violation::new("too many replicas; missing label owner")]
+        );
+    }
+}
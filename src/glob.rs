@@ -0,0 +1,209 @@
+//! Glob matching for the values found in policy settings: hostnames,
+//! namespaces, label values, image repositories.
+//!
+//! Patterns support `*`, matching any sequence of characters, and `?`,
+//! matching a single character. [`matches()`] treats the value as a plain
+//! string, while [`matches_path`] treats it as a list of segments, like a
+//! path or a hostname:
+//!
+//! * `*` and `?` never match the separator
+//! * `**` matches any sequence of characters, separators included. A
+//!   `**` followed by the separator can also match nothing, so `a/**/b`
+//!   matches `a/b`
+//!
+//! The matching doesn't allocate, patterns are used as they are without
+//! being compiled first.
+//!
+//! ```
+//! use kubewarden_policy_sdk::glob;
+//!
+//! assert!(glob::matches("team-*", "team-a"));
+//! assert!(glob::matches("v1.?", "v1.2"));
+//!
+//! assert!(glob::matches_path("*.example.com", "www.example.com", '.'));
+//! assert!(!glob::matches_path("*.example.com", "a.b.example.com", '.'));
+//! assert!(glob::matches_path("**.example.com", "a.b.example.com", '.'));
+//!
+//! assert!(glob::matches_path("org/*/app", "org/team/app", '/'));
+//! assert!(glob::matches_path("org/**/app", "org/app", '/'));
+//! assert!(glob::matches_path("org/**", "org/team/app", '/'));
+//! ```
+
+/// Whether `text` matches `pattern`, where `*` matches any sequence of
+/// characters and `?` any single character
+pub fn matches(pattern: &str, text: &str) -> bool {
+    wild(pattern, text, None, true) == Outcome::Match
+}
+
+/// Whether `text`, made of segments divided by `separator`, matches
+/// `pattern`. `*` and `?` don't match the separator, `**` does
+pub fn matches_path(pattern: &str, text: &str, separator: char) -> bool {
+    wild(pattern, text, Some(separator), true) == Outcome::Match
+}
+
+/// Whether `value` contains the glob metacharacters
+pub fn is_pattern(value: &str) -> bool {
+    value.contains(['*', '?'])
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Match,
+    NoMatch,
+    /// No shorter suffix of the text can match, stop all the backtracking
+    AbortAll,
+    /// A `*` hit a separator: only an enclosing `**` can backtrack further
+    AbortToDoubleStar,
+}
+
+/// The matching algorithm of git's `wildmatch`: a star tries all the
+/// possible lengths, the abort outcomes keep the backtracking polynomial
+fn wild(pattern: &str, text: &str, separator: Option<char>, segment_start: bool) -> Outcome {
+    let mut p = pattern;
+    let mut t = text;
+    let mut segment_start = segment_start;
+    loop {
+        let Some(pc) = p.chars().next() else {
+            return if t.is_empty() {
+                Outcome::Match
+            } else {
+                Outcome::NoMatch
+            };
+        };
+
+        if pc == '*' {
+            let double = separator.is_some() && p.starts_with("**");
+            let crosses = separator.is_none() || double;
+            let rest = p.trim_start_matches('*');
+
+            if double && segment_start {
+                // `**/` also matches zero segments
+                if let Some(after) = separator.and_then(|s| rest.strip_prefix(s)) {
+                    if wild(after, t, separator, true) == Outcome::Match {
+                        return Outcome::Match;
+                    }
+                }
+            }
+            if rest.is_empty() {
+                return if crosses || separator.is_none_or(|s| !t.contains(s)) {
+                    Outcome::Match
+                } else {
+                    Outcome::NoMatch
+                };
+            }
+
+            let mut candidate = t;
+            loop {
+                match wild(rest, candidate, separator, false) {
+                    Outcome::NoMatch => {}
+                    Outcome::AbortToDoubleStar if crosses => {}
+                    outcome => return outcome,
+                }
+                let Some(c) = candidate.chars().next() else {
+                    return Outcome::AbortAll;
+                };
+                if !crosses && Some(c) == separator {
+                    return Outcome::AbortToDoubleStar;
+                }
+                candidate = &candidate[c.len_utf8()..];
+            }
+        }
+
+        let Some(tc) = t.chars().next() else {
+            return Outcome::AbortAll;
+        };
+        let matched = if pc == '?' {
+            Some(tc) != separator
+        } else {
+            pc == tc
+        };
+        if !matched {
+            return Outcome::NoMatch;
+        }
+        segment_start = Some(tc) == separator;
+        p = &p[pc.len_utf8()..];
+        t = &t[tc.len_utf8()..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_strings() {
+        for (pattern, text) in [
+            ("", ""),
+            ("*", ""),
+            ("*", "anything/at/all"),
+            ("team-*", "team-"),
+            ("team-*", "team-a"),
+            ("*-prod", "api-prod"),
+            ("a*b*c", "abbbc"),
+            ("a*b*c", "axxbyyc"),
+            ("?", "é"),
+            ("v1.?", "v1.2"),
+            ("**", "a/b"),
+        ] {
+            assert!(matches(pattern, text), "{} {}", pattern, text);
+        }
+        for (pattern, text) in [
+            ("", "a"),
+            ("?", ""),
+            ("team-*", "team"),
+            ("team-*", "other-a"),
+            ("a*b*c", "abcb"),
+            ("v1.?", "v1.23"),
+        ] {
+            assert!(!matches(pattern, text), "{} {}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn paths() {
+        for (pattern, text) in [
+            ("org/*", "org/app"),
+            ("org/*/app", "org/team/app"),
+            ("org/**", "org/team/app"),
+            ("org/**/app", "org/app"),
+            ("org/**/app", "org/a/b/app"),
+            ("**/app", "app"),
+            ("**/app", "org/team/app"),
+            ("org/*-prod", "org/api-prod"),
+            ("org/a?c", "org/abc"),
+        ] {
+            assert!(matches_path(pattern, text, '/'), "{} {}", pattern, text);
+        }
+        for (pattern, text) in [
+            ("org/*", "org/team/app"),
+            ("org/*/app", "org/app"),
+            ("org/**/app", "org/apps"),
+            ("org/x**/app", "org/app"),
+            ("org?app", "org/app"),
+            ("*", "org/app"),
+        ] {
+            assert!(!matches_path(pattern, text, '/'), "{} {}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn hostnames() {
+        assert!(matches_path("*.example.com", "www.example.com", '.'));
+        assert!(!matches_path("*.example.com", "example.com", '.'));
+        assert!(!matches_path("*.example.com", "a.b.example.com", '.'));
+        assert!(matches_path("**.example.com", "a.b.example.com", '.'));
+        assert!(matches_path(
+            "registry-*.example.com",
+            "registry-eu.example.com",
+            '.'
+        ));
+    }
+
+    #[test]
+    fn backtracking_is_bounded() {
+        let text = "a".repeat(64);
+        let pattern = format!("{}b", "*a".repeat(32));
+        assert!(!matches(&pattern, &text));
+        assert!(!matches_path(&pattern.replace('*', "**"), &text, '/'));
+    }
+}
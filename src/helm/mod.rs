@@ -0,0 +1,490 @@
+//! Types and checks for Helm charts, meant to be used by raw policies
+//! gating the charts before they are installed, e.g. inside of a CI pipeline.
+//!
+//! [`Chart`] is the content of a `Chart.yaml` file. [`ChartRules`] can be
+//! part of the policy settings, and enforces the organization rules about the
+//! chart metadata: the repositories of the dependencies, the allowed versions,
+//! and the mandatory fields. The images referenced by a `values.yaml` file can
+//! be found with [`values_images`].
+//!
+//! ```
+//! use kubewarden_policy_sdk::helm::{Chart, ChartRules};
+//!
+//! let chart = Chart::parse(r#"
+//! apiVersion: v2
+//! name: web
+//! version: 1.4.0
+//! dependencies:
+//!   - name: redis
+//!     version: ~18.1.0
+//!     repository: https://charts.bitnami.com/bitnami
+//! "#)
+//! .unwrap();
+//!
+//! let rules: ChartRules = serde_json::from_value(serde_json::json!({
+//!     "allowedRepositories": ["https://charts.example.com"],
+//!     "version": ">=1.0.0"
+//! }))
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     rules.check(&chart),
+//!     vec!["dependency redis comes from https://charts.bitnami.com/bitnami, which is not an allowed repository"]
+//! );
+//! ```
+use crate::response::ValidationResponse;
+use crate::settings::Validatable;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub use crate::semver::{Version, VersionConstraint};
+
+/// The content of a `Chart.yaml` file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Chart {
+    /// `v2` for Helm 3 charts, `v1` for the legacy ones
+    pub api_version: String,
+    pub name: String,
+    /// The SemVer 2 version of the chart
+    pub version: String,
+    /// Constraint on the supported Kubernetes versions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kube_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// `application` or `library`
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub chart_type: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub home: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<Dependency>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub maintainers: Vec<Maintainer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// The version of the packaged application, not necessarily SemVer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    pub deprecated: bool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// A chart the chart depends on
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Dependency {
+    pub name: String,
+    /// A version constraint, see [`VersionConstraint`]
+    pub version: String,
+    /// The repository URL, or an alias like `@stable`. Empty for the charts
+    /// stored inside of the `charts` directory
+    pub repository: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+/// A maintainer of the chart
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Maintainer {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Chart {
+    /// Parse the content of a `Chart.yaml` file. The chart is not validated,
+    /// see [`Chart::validate`]
+    pub fn parse(yaml: &str) -> crate::error::Result<Self> {
+        crate::yaml::decode_single(yaml)
+    }
+
+    /// The version of the chart
+    pub fn version(&self) -> crate::error::Result<Version> {
+        self.version.parse()
+    }
+
+    /// Ensure the chart is well-formed, performing the same checks of
+    /// `helm lint`: name and version are set, the version is SemVer and
+    /// the dependency versions are valid constraints
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.api_version.as_str(), "v1" | "v2") {
+            return Err(format!("invalid apiVersion '{}'", self.api_version));
+        }
+        if self.name.is_empty() {
+            return Err("the chart name is missing".to_string());
+        }
+        self.version().map_err(|e| e.to_string())?;
+        if let Some(chart_type) = &self.chart_type {
+            if !matches!(chart_type.as_str(), "application" | "library") {
+                return Err(format!("invalid chart type '{}'", chart_type));
+            }
+        }
+        for dependency in &self.dependencies {
+            if dependency.name.is_empty() {
+                return Err("a dependency has no name".to_string());
+            }
+            dependency
+                .version
+                .parse::<VersionConstraint>()
+                .map_err(|e| format!("dependency {}: {}", dependency.name, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Organization rules about the chart metadata, usually part of the policy
+/// settings
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChartRules {
+    /// The repositories the dependencies can come from. An entry matches the
+    /// repository with the same URL, and the ones nested inside of it, e.g.
+    /// `oci://ghcr.io/org` allows `oci://ghcr.io/org/charts`. The charts
+    /// stored inside of the `charts` directory, or referenced with `file://`,
+    /// are always allowed. All the repositories are allowed when the list
+    /// is empty
+    pub allowed_repositories: Vec<String>,
+    /// The allowed versions of the chart
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<VersionConstraint>,
+    /// Reject the charts marked as deprecated
+    pub deny_deprecated: bool,
+    /// Reject the charts without maintainers
+    pub require_maintainers: bool,
+}
+
+impl Validatable for ChartRules {
+    fn validate(&self) -> Result<(), String> {
+        if self
+            .allowed_repositories
+            .iter()
+            .any(|r| r.trim().is_empty())
+        {
+            return Err("allowedRepositories cannot contain empty entries".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl ChartRules {
+    /// Check the chart against the rules. Returns all the violations, an
+    /// empty list means the chart is allowed
+    pub fn check(&self, chart: &Chart) -> Vec<String> {
+        let mut violations = Vec::new();
+        if let Err(e) = chart.validate() {
+            violations.push(e);
+        }
+
+        if let Some(constraint) = &self.version {
+            if let Ok(version) = chart.version() {
+                if !constraint.matches(&version) {
+                    violations.push(format!(
+                        "chart version {} doesn't satisfy '{}'",
+                        version, constraint
+                    ));
+                }
+            }
+        }
+        if self.deny_deprecated && chart.deprecated {
+            violations.push(format!("chart {} is deprecated", chart.name));
+        }
+        if self.require_maintainers && chart.maintainers.is_empty() {
+            violations.push(format!("chart {} has no maintainers", chart.name));
+        }
+        for dependency in &chart.dependencies {
+            if !self.is_repository_allowed(&dependency.repository) {
+                violations.push(format!(
+                    "dependency {} comes from {}, which is not an allowed repository",
+                    dependency.name, dependency.repository
+                ));
+            }
+        }
+        violations
+    }
+
+    /// Accept the chart when there are no violations, otherwise reject it
+    /// with a message listing all of them
+    pub fn evaluate(&self, chart: &Chart) -> ValidationResponse {
+        let violations = self.check(chart);
+        if violations.is_empty() {
+            ValidationResponse::accept().response()
+        } else {
+            ValidationResponse::reject(violations.join("; ")).response()
+        }
+    }
+
+    /// Whether the dependencies can come from `repository`
+    pub fn is_repository_allowed(&self, repository: &str) -> bool {
+        if self.allowed_repositories.is_empty()
+            || repository.is_empty()
+            || repository.starts_with("file://")
+        {
+            return true;
+        }
+        let repository = repository.trim_end_matches('/');
+        self.allowed_repositories.iter().any(|allowed| {
+            let allowed = allowed.trim_end_matches('/');
+            repository == allowed
+                || repository
+                    .strip_prefix(allowed)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// Parse the content of a `values.yaml` file. An empty file is an empty
+/// object
+pub fn parse_values(yaml: &str) -> crate::error::Result<Value> {
+    let mut documents = crate::yaml::documents(yaml)?;
+    match documents.len() {
+        0 => Ok(Value::Object(Default::default())),
+        1 => Ok(documents.remove(0)),
+        count => Err(crate::error::SdkError::InvalidInput(format!(
+            "expected a single YAML document, found {}",
+            count
+        ))),
+    }
+}
+
+/// Find the images referenced by the chart values, following the common
+/// chart conventions. The values named `image` are either the image
+/// reference, or an object with the `repository` and the optional
+/// `registry`, `tag` and `digest` fields.
+///
+/// Returns the JSON pointer of each value, together with the image, sorted
+/// by pointer
+pub fn values_images(values: &Value) -> Vec<(String, String)> {
+    let mut images = Vec::new();
+    collect_images(values, String::new(), &mut images);
+    images.sort();
+    images
+}
+
+fn collect_images(value: &Value, path: String, images: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = format!("{}/{}", path, crate::diff::escape_pointer_token(key));
+                if key == "image" {
+                    if let Some(image) = image_reference(value) {
+                        images.push((path, image));
+                        continue;
+                    }
+                }
+                collect_images(value, path, images);
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                collect_images(item, format!("{}/{}", path, i), images);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn image_reference(value: &Value) -> Option<String> {
+    match value {
+        Value::String(image) if !image.is_empty() => Some(image.clone()),
+        Value::Object(image) => {
+            let repository = image.get("repository")?.as_str()?;
+            let mut reference = match image.get("registry").and_then(Value::as_str) {
+                Some(registry) if !registry.is_empty() => format!("{}/{}", registry, repository),
+                _ => repository.to_string(),
+            };
+            match image.get("tag") {
+                Some(Value::String(tag)) if !tag.is_empty() => {
+                    reference.push(':');
+                    reference.push_str(tag);
+                }
+                // unquoted tags like `1.25` are numbers
+                Some(Value::Number(tag)) => {
+                    reference.push(':');
+                    reference.push_str(&tag.to_string());
+                }
+                _ => {}
+            }
+            if let Some(digest) = image.get("digest").and_then(Value::as_str) {
+                if !digest.is_empty() {
+                    reference.push('@');
+                    reference.push_str(digest);
+                }
+            }
+            Some(reference)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const CHART: &str = r#"
+apiVersion: v2
+name: web
+description: The web frontend
+type: application
+version: 1.4.0-rc.1
+appVersion: "2.1"
+maintainers:
+  - name: team-web
+    email: web@example.com
+dependencies:
+  - name: redis
+    version: ~18.1.0
+    repository: oci://registry.example.com/charts/
+  - name: common
+    version: 2.x
+    repository: file://../common
+  - name: postgresql
+    version: ">=12.0.0"
+    repository: https://charts.bitnami.com/bitnami
+"#;
+
+    fn rules(settings: Value) -> ChartRules {
+        serde_json::from_value(settings).unwrap()
+    }
+
+    #[test]
+    fn parse_charts() {
+        let chart = Chart::parse(CHART).unwrap();
+        assert_eq!(chart.name, "web");
+        assert_eq!(chart.app_version.as_deref(), Some("2.1"));
+        assert_eq!(chart.chart_type.as_deref(), Some("application"));
+        assert_eq!(chart.dependencies[0].version, "~18.1.0");
+        assert_eq!(chart.version().unwrap().pre, ["rc", "1"]);
+        assert!(chart.validate().is_ok());
+
+        let invalid = [
+            (
+                "apiVersion: v3\nname: a\nversion: 1.0.0",
+                "invalid apiVersion 'v3'",
+            ),
+            (
+                "apiVersion: v2\nversion: 1.0.0",
+                "the chart name is missing",
+            ),
+            (
+                "apiVersion: v2\nname: a\nversion: latest",
+                "invalid version 'latest': 'latest' is not a number",
+            ),
+            (
+                "apiVersion: v2\nname: a\nversion: 1.0.0\ntype: plugin",
+                "invalid chart type 'plugin'",
+            ),
+        ];
+        for (yaml, message) in invalid {
+            assert_eq!(Chart::parse(yaml).unwrap().validate().unwrap_err(), message);
+        }
+    }
+
+    #[test]
+    fn enforce_rules() {
+        let chart = Chart::parse(CHART).unwrap();
+
+        let allowed = rules(json!({
+            "allowedRepositories": ["oci://registry.example.com/charts", "https://charts.bitnami.com/bitnami"],
+            "version": ">=1.4.0-0",
+            "requireMaintainers": true,
+            "denyDeprecated": true
+        }));
+        assert!(allowed.validate().is_ok());
+        assert!(allowed.check(&chart).is_empty());
+        assert!(allowed.evaluate(&chart).accepted);
+
+        let strict = rules(json!({
+            "allowedRepositories": ["oci://registry.example.com"],
+            "version": "^1.0.0"
+        }));
+        assert_eq!(
+            strict.check(&chart),
+            vec![
+                "chart version 1.4.0-rc.1 doesn't satisfy '^1.0.0'",
+                "dependency postgresql comes from https://charts.bitnami.com/bitnami, which is not an allowed repository",
+            ]
+        );
+        assert!(!strict.evaluate(&chart).accepted);
+
+        let mut deprecated = chart.clone();
+        deprecated.deprecated = true;
+        deprecated.maintainers.clear();
+        let violations =
+            rules(json!({"requireMaintainers": true, "denyDeprecated": true})).check(&deprecated);
+        assert_eq!(
+            violations,
+            vec!["chart web is deprecated", "chart web has no maintainers"]
+        );
+    }
+
+    #[test]
+    fn repository_matching() {
+        let allowed = rules(json!({"allowedRepositories": ["https://charts.example.com/"]}));
+        assert!(allowed.is_repository_allowed("https://charts.example.com"));
+        assert!(allowed.is_repository_allowed("https://charts.example.com/stable"));
+        assert!(!allowed.is_repository_allowed("https://charts.example.com.evil.io"));
+        assert!(!allowed.is_repository_allowed("@stable"));
+        assert!(allowed.is_repository_allowed(""));
+        assert!(ChartRules::default().is_repository_allowed("@stable"));
+
+        let invalid = rules(json!({"allowedRepositories": [" "]}));
+        assert!(invalid.validate().is_err());
+        assert!(serde_json::from_value::<ChartRules>(json!({"version": ">>1"})).is_err());
+    }
+
+    #[test]
+    fn find_values_images() {
+        let values = parse_values(
+            r#"
+image:
+  registry: docker.io
+  repository: bitnami/nginx
+  tag: 1.25
+sidecars:
+  - name: proxy
+    image: envoyproxy/envoy:v1.29
+metrics:
+  image:
+    repository: bitnami/exporter
+    digest: sha256:0123
+  enabled: false
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            values_images(&values),
+            vec![
+                (
+                    "/image".to_string(),
+                    "docker.io/bitnami/nginx:1.25".to_string()
+                ),
+                (
+                    "/metrics/image".to_string(),
+                    "bitnami/exporter@sha256:0123".to_string()
+                ),
+                (
+                    "/sidecars/0/image".to_string(),
+                    "envoyproxy/envoy:v1.29".to_string()
+                ),
+            ]
+        );
+        assert_eq!(parse_values("").unwrap(), json!({}));
+        assert!(parse_values("a: 1\n---\nb: 2").is_err());
+    }
+}
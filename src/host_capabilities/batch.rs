@@ -0,0 +1,283 @@
+//! Invoke several host capabilities with a single waPC call.
+//!
+//! Policies that perform many lookups, like checking the digests of all the
+//! images of a Pod, can collect the requests inside of a [`Batch`] and send
+//! them at once. The host answers with the list of the responses, which are
+//! then retrieved using the [`BatchHandle`] returned when adding the requests.
+//!
+//! ```
+//! # #[cfg(feature = "net")]
+//! # {
+//! use kubewarden_policy_sdk::host_capabilities::batch::batch;
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "batch",
+//!     "v1/call",
+//!     &json!([{"ok": {"ips": ["10.0.0.1"]}}, {"error": "no such host"}]),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let mut batch = batch();
+//!     let first = batch.lookup_host("example.com").unwrap();
+//!     let second = batch.lookup_host("unknown.lan").unwrap();
+//!
+//!     let responses = batch.send().unwrap();
+//!     assert_eq!(responses.get(&first).unwrap().ips, vec!["10.0.0.1"]);
+//!     assert!(responses.get(&second).is_err());
+//! });
+//! # }
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::marker::PhantomData;
+
+/// A single request of the batch, as sent to the host
+#[derive(Serialize, Debug)]
+struct BatchCall {
+    namespace: String,
+    operation: String,
+    payload: Box<RawValue>,
+}
+
+/// The outcome of a single request of the batch, as returned by the host
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+enum BatchOutcome {
+    Ok(Box<RawValue>),
+    Error(String),
+}
+
+/// Requests waiting to be sent to the host, see [`batch`]
+#[derive(Debug, Default)]
+pub struct Batch {
+    calls: Vec<BatchCall>,
+}
+
+/// Handle used to retrieve the response of a request from [`BatchResponses`]
+#[derive(Debug)]
+pub struct BatchHandle<T> {
+    index: usize,
+    response: PhantomData<T>,
+}
+
+/// Start a new batch of host capability requests
+pub fn batch() -> Batch {
+    Batch::default()
+}
+
+impl Batch {
+    /// Add a request for the capability identified by `namespace` and
+    /// `operation`. The handle returned is used to get the response, decoded
+    /// as `T`
+    pub fn add<R, T>(
+        &mut self,
+        namespace: &str,
+        operation: &str,
+        request: &R,
+    ) -> Result<BatchHandle<T>>
+    where
+        R: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        let payload = serde_json::value::to_raw_value(request).map_err(|e| {
+            SdkError::serialization(&format!("the {}.{} request", namespace, operation), e)
+        })?;
+        self.calls.push(BatchCall {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload,
+        });
+        Ok(BatchHandle {
+            index: self.calls.len() - 1,
+            response: PhantomData,
+        })
+    }
+
+    /// Add a DNS lookup of the given hostname,
+    /// see [`lookup_host`](crate::host_capabilities::net::lookup_host)
+    #[cfg(feature = "net")]
+    pub fn lookup_host(
+        &mut self,
+        host: &str,
+    ) -> Result<BatchHandle<crate::host_capabilities::net::LookupResponse>> {
+        self.add("net", "v1/dns_lookup_host", host)
+    }
+
+    /// Add the computation of the digest of the OCI object referenced by
+    /// `image`, see [`get_manifest_digest`](crate::host_capabilities::oci::get_manifest_digest)
+    #[cfg(feature = "oci")]
+    pub fn manifest_digest(
+        &mut self,
+        image: &str,
+    ) -> Result<BatchHandle<crate::host_capabilities::oci::ManifestDigestResponse>> {
+        self.add("oci", "v1/manifest_digest", image)
+    }
+
+    /// Add the retrieval of a Kubernetes resource,
+    /// see [`get_resource`](crate::host_capabilities::kubernetes::get_resource)
+    #[cfg(feature = "cluster-context")]
+    pub fn get_resource<T>(
+        &mut self,
+        req: &crate::host_capabilities::kubernetes::GetResourceRequest,
+    ) -> Result<BatchHandle<T>>
+    where
+        T: k8s_openapi::Resource + DeserializeOwned,
+    {
+        self.add("kubernetes", "get_resource", req)
+    }
+
+    /// Number of requests inside of the batch
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Returns true when the batch has no request
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Send all the requests to the host with a single call. An empty batch
+    /// doesn't invoke the host.
+    ///
+    /// The errors of the single requests are reported by [`BatchResponses::get`],
+    /// an error is returned only when the whole batch fails.
+    pub fn send(self) -> Result<BatchResponses> {
+        if self.calls.is_empty() {
+            return Ok(BatchResponses {
+                outcomes: Vec::new(),
+                calls: Vec::new(),
+            });
+        }
+
+        let msg = serde_json::to_vec(&self.calls)
+            .map_err(|e| SdkError::serialization("the batch request", e))?;
+        let response_raw = host_call("batch", "v1/call", &msg)?;
+        let outcomes: Vec<BatchOutcome> = serde_json::from_slice(&response_raw)
+            .map_err(|e| SdkError::deserialization("the batch response", e))?;
+        if outcomes.len() != self.calls.len() {
+            return Err(SdkError::InvalidInput(format!(
+                "the batch response has {} results, {} were expected",
+                outcomes.len(),
+                self.calls.len()
+            )));
+        }
+
+        Ok(BatchResponses {
+            outcomes,
+            calls: self
+                .calls
+                .into_iter()
+                .map(|call| (call.namespace, call.operation))
+                .collect(),
+        })
+    }
+}
+
+/// The responses to the requests of a [`Batch`]
+#[derive(Debug)]
+pub struct BatchResponses {
+    outcomes: Vec<BatchOutcome>,
+    calls: Vec<(String, String)>,
+}
+
+impl BatchResponses {
+    /// Get the response of the request identified by `handle`
+    pub fn get<T: DeserializeOwned>(&self, handle: &BatchHandle<T>) -> Result<T> {
+        let (namespace, operation) = self.calls.get(handle.index).ok_or_else(|| {
+            SdkError::InvalidInput("the handle belongs to another batch".to_string())
+        })?;
+        match &self.outcomes[handle.index] {
+            BatchOutcome::Ok(response) => serde_json::from_str(response.get()).map_err(|e| {
+                SdkError::deserialization(&format!("the {}.{} response", namespace, operation), e)
+            }),
+            BatchOutcome::Error(message) => Err(SdkError::host_callback(
+                namespace,
+                operation,
+                message.clone().into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    #[cfg(feature = "net")]
+    use serde_json::json;
+    use std::rc::Rc;
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn send_requests_with_a_single_call() {
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond(
+                    "batch",
+                    "v1/call",
+                    &json!([
+                        {"ok": {"ips": ["10.0.0.1"]}},
+                        {"error": "not found"},
+                        {"ok": [1, 2]}
+                    ]),
+                )
+                .expect("batch", "v1/call", 1),
+        );
+
+        with_host_client(client.clone(), || {
+            let mut batch = batch();
+            let lookup = batch.lookup_host("example.com").unwrap();
+            let missing: BatchHandle<serde_json::Value> =
+                batch.add("oci", "v1/manifest_digest", "busybox").unwrap();
+            let custom: BatchHandle<Vec<u8>> =
+                batch.add("custom", "v1/op", &json!({"a": 1})).unwrap();
+            assert_eq!(batch.len(), 3);
+
+            let responses = batch.send().unwrap();
+            assert_eq!(responses.get(&lookup).unwrap().ips, vec!["10.0.0.1"]);
+            assert_eq!(
+                responses.get(&missing).unwrap_err().to_string(),
+                "error invoking host capability oci.v1/manifest_digest: not found"
+            );
+            assert_eq!(responses.get(&custom).unwrap(), vec![1, 2]);
+        });
+
+        client.assert_called_with(
+            "batch",
+            "v1/call",
+            &json!([
+                {"namespace": "net", "operation": "v1/dns_lookup_host", "payload": "example.com"},
+                {"namespace": "oci", "operation": "v1/manifest_digest", "payload": "busybox"},
+                {"namespace": "custom", "operation": "v1/op", "payload": {"a": 1}}
+            ]),
+            1,
+        );
+    }
+
+    #[test]
+    fn empty_batch_does_not_call_the_host() {
+        let client = Rc::new(MockHostClient::new());
+        with_host_client(client.clone(), || {
+            let batch = batch();
+            assert!(batch.is_empty());
+            batch.send().unwrap();
+        });
+        assert!(client.calls().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn mismatching_number_of_results() {
+        let client = Rc::new(MockHostClient::new().respond("batch", "v1/call", &json!([])));
+        with_host_client(client, || {
+            let mut batch = batch();
+            batch.lookup_host("example.com").unwrap();
+            assert!(batch.send().is_err());
+        });
+    }
+}
@@ -0,0 +1,713 @@
+//! All the host capabilities are invoked through a [`HostClient`].
+//!
+//! Policies running inside of a Kubewarden host use [`WapcHostClient`], which
+//! is the default one. Unit tests can replace it with a [`MockHostClient`],
+//! or any other implementation of the trait, to exercise the code paths
+//! relying on host capabilities without a wasm host.
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "net")]
+//! # {
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::net::{lookup_host, LookupResponse};
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "net",
+//!     "v1/dns_lookup_host",
+//!     &LookupResponse {
+//!         ips: vec!["10.0.0.1".to_string()],
+//!     },
+//! ));
+//!
+//! let response = with_host_client(client.clone(), || lookup_host("example.com")).unwrap();
+//! assert_eq!(response.ips, vec!["10.0.0.1"]);
+//! assert_eq!(client.calls()[0].payload_json().unwrap(), "example.com");
+//! # }
+//! ```
+use crate::error::{HostError, SdkError};
+use serde::Serialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+
+/// Client used to invoke the capabilities exposed by the host
+pub trait HostClient {
+    /// Invoke the given capability, identified by its namespace (e.g. `oci`)
+    /// and operation (e.g. `v1/manifest_digest`), and return the raw response
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult;
+}
+
+/// The [`HostClient`] used by policies running inside of a Kubewarden host,
+/// which relies on the waPC protocol. Outside of wasm, like in unit tests,
+/// there is no host: all the capabilities are reported as not implemented
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WapcHostClient;
+
+impl HostClient for WapcHostClient {
+    #[cfg(target_arch = "wasm32")]
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        wapc_guest::host_call("kubewarden", namespace, operation, payload)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn call(&self, namespace: &str, operation: &str, _payload: &[u8]) -> wapc_guest::CallResult {
+        Err(format!(
+            "{}.{} not implemented: no wasm host is running the policy",
+            namespace, operation
+        )
+        .into())
+    }
+}
+
+thread_local! {
+    static HOST_CLIENT: RefCell<Rc<dyn HostClient>> = RefCell::new(Rc::new(WapcHostClient));
+    static DEBUG_PAYLOADS: Cell<Option<usize>> = const { Cell::new(None) };
+    static MEMOIZED: RefCell<Option<HashMap<MemoKey, Vec<u8>>>> = const { RefCell::new(None) };
+}
+
+/// Capabilities identified by namespace, operation and request payload
+type MemoKey = (String, String, Vec<u8>);
+
+/// Capabilities reading data without side effects, the only ones whose
+/// responses are memoized. `None` stands for all the operations of the
+/// namespace
+const MEMOIZED_CAPABILITIES: &[(&str, Option<&str>)] = &[
+    ("crypto", None),
+    ("host", Some("v1/now")),
+    ("kubernetes", Some("get_resource")),
+    ("kubernetes", Some("list_resources_all")),
+    ("kubernetes", Some("list_resources_by_namespace")),
+    ("net", None),
+    ("oci", None),
+];
+
+/// Default number of bytes of the payloads logged by [`set_debug_payloads`]
+pub const DEFAULT_DEBUG_PAYLOADS_LIMIT: usize = 4096;
+
+/// Replace the client used to invoke the host capabilities
+pub fn set_host_client(client: Rc<dyn HostClient>) {
+    HOST_CLIENT.with(|c| *c.borrow_mut() = client);
+    clear_memoized_responses();
+    super::encoding::reset_negotiation();
+}
+
+/// Returns the client currently used to invoke the host capabilities
+pub fn host_client() -> Rc<dyn HostClient> {
+    HOST_CLIENT.with(|c| c.borrow().clone())
+}
+
+/// Run `f` using the given client to invoke the host capabilities. The previous
+/// client is restored afterwards, even when `f` panics
+pub fn with_host_client<R>(client: Rc<dyn HostClient>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<Rc<dyn HostClient>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(previous) = self.0.take() {
+                set_host_client(previous);
+            }
+        }
+    }
+
+    let _restore = Restore(Some(host_client()));
+    set_host_client(client);
+    f()
+}
+
+/// Start memoizing the responses of the host capabilities, dropping the
+/// ones of the previous evaluation. This is done when the validation request
+/// is decoded: during an evaluation, identical requests (e.g. the digest of
+/// the same image used by several containers) are sent to the host only once.
+pub(crate) fn start_memoization() {
+    MEMOIZED.with(|m| *m.borrow_mut() = Some(HashMap::new()));
+}
+
+/// Stop memoizing the responses of the host capabilities, dropping the
+/// ones of the evaluation. This is done by [`policy::validate`](crate::policy::validate)
+/// once both the validation and the mutation of the request are over. The
+/// policies providing their own `validate` function drop the responses when
+/// the next request is decoded.
+pub(crate) fn end_memoization() {
+    MEMOIZED.with(|m| *m.borrow_mut() = None);
+}
+
+/// Forget the responses memoized during the current evaluation, forcing
+/// the next requests to reach the host
+pub fn clear_memoized_responses() {
+    MEMOIZED.with(|m| {
+        if let Some(memoized) = m.borrow_mut().as_mut() {
+            memoized.clear();
+        }
+    });
+}
+
+fn memoized_response(key: &MemoKey) -> Option<Vec<u8>> {
+    MEMOIZED.with(|m| m.borrow().as_ref()?.get(key).cloned())
+}
+
+fn memoize_response(key: MemoKey, response: &[u8]) {
+    let memoized = MEMOIZED_CAPABILITIES.iter().any(|(namespace, operation)| {
+        *namespace == key.0
+            && operation
+                .is_none_or(|operation| operation == super::encoding::base_operation(&key.1))
+    });
+    if !memoized {
+        return;
+    }
+    MEMOIZED.with(|m| {
+        if let Some(memoized) = m.borrow_mut().as_mut() {
+            memoized.insert(key, response.to_vec());
+        }
+    });
+}
+
+/// Log the payloads exchanged with the host capabilities, at the debug
+/// level, to troubleshoot mismatches between the guest and the host.
+///
+/// Payloads longer than `limit` bytes are truncated. Use `None` to stop
+/// logging them, which is the default.
+pub fn set_debug_payloads(limit: Option<usize>) {
+    DEBUG_PAYLOADS.with(|l| l.set(limit));
+}
+
+/// Truncate a payload to at most `limit` bytes, for logging purposes.
+/// The credentials found inside of JSON payloads are redacted, see
+/// [`redact_value`](crate::logging::redact_value)
+pub(crate) fn truncate_payload(payload: &[u8], limit: usize) -> String {
+    let redacted = serde_json::from_slice::<serde_json::Value>(payload)
+        .ok()
+        .and_then(|mut value| {
+            let original = value.clone();
+            crate::logging::redact_value(&mut value);
+            if value == original {
+                None
+            } else {
+                serde_json::to_vec(&value).ok()
+            }
+        });
+    let payload = redacted.as_deref().unwrap_or(payload);
+    if payload.len() <= limit {
+        return String::from_utf8_lossy(payload).into_owned();
+    }
+    format!(
+        "{}... ({} bytes)",
+        String::from_utf8_lossy(&payload[..limit]).trim_end_matches('\u{FFFD}'),
+        payload.len()
+    )
+}
+
+fn debug_payloads(
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+    response: &wapc_guest::CallResult,
+) {
+    let Some(limit) = DEBUG_PAYLOADS.with(|l| l.get()) else {
+        return;
+    };
+    let capability = format!("{}.{}", namespace, operation);
+    let request = truncate_payload(payload, limit);
+    match response {
+        Ok(response) => slog::debug!(
+            crate::logging::logger(),
+            "host capability payloads";
+            "capability" => capability,
+            "request" => request,
+            "response" => truncate_payload(response, limit)
+        ),
+        Err(e) => slog::debug!(
+            crate::logging::logger(),
+            "host capability payloads";
+            "capability" => capability,
+            "request" => request,
+            "error" => e.to_string()
+        ),
+    }
+}
+
+/// Invoke a host capability using the current client.
+///
+/// Successful responses are memoized for the duration of the evaluation,
+/// see [`clear_memoized_responses`]
+pub(crate) fn host_call(
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+) -> Result<Vec<u8>, SdkError> {
+    let key = (
+        namespace.to_string(),
+        operation.to_string(),
+        payload.to_vec(),
+    );
+    if let Some(response) = memoized_response(&key) {
+        return Ok(response);
+    }
+
+    #[cfg(feature = "host-call-spans")]
+    let start = crate::clock::now();
+
+    let response = host_client().call(namespace, operation, payload);
+    debug_payloads(namespace, operation, payload, &response);
+
+    #[cfg(feature = "host-call-spans")]
+    super::spans::record(
+        namespace,
+        operation,
+        payload,
+        start.map(|s| s.elapsed()).unwrap_or_default(),
+        response.is_ok(),
+    );
+
+    let response = response.map_err(|e| SdkError::host_callback(namespace, operation, e))?;
+    memoize_response(key, &response);
+    Ok(response)
+}
+
+/// A call received by a [`MockHostClient`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostCall {
+    /// Namespace of the capability
+    pub namespace: String,
+    /// Operation of the capability
+    pub operation: String,
+    /// The raw payload sent to the host
+    pub payload: Vec<u8>,
+}
+
+impl HostCall {
+    /// Decode the payload as JSON
+    pub fn payload_json(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::from_slice(&self.payload)
+    }
+}
+
+type MockResponse = Result<Vec<u8>, String>;
+
+/// The number of calls a [`MockHostClient`] expects to receive by a
+/// capability, optionally restricted to a given payload
+#[derive(Debug, Clone)]
+struct Expectation {
+    namespace: String,
+    operation: String,
+    payload: Option<serde_json::Value>,
+    times: usize,
+}
+
+impl Expectation {
+    fn matches(&self, call: &HostCall) -> bool {
+        call.namespace == self.namespace
+            && call.operation == self.operation
+            && self
+                .payload
+                .as_ref()
+                .is_none_or(|payload| call.payload_json().is_ok_and(|p| &p == payload))
+    }
+}
+
+impl std::fmt::Display for Expectation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.namespace, self.operation)?;
+        if let Some(payload) = &self.payload {
+            write!(f, " with payload {}", payload)?;
+        }
+        write!(f, " expected to be called {} time(s)", self.times)
+    }
+}
+
+/// An in-memory [`HostClient`], which returns pre-configured responses and
+/// records all the calls it receives.
+///
+/// Calls to capabilities without a configured response fail.
+///
+/// The number of calls expected for a capability can be declared with
+/// [`MockHostClient::expect`] and [`MockHostClient::expect_with`]: the
+/// expectations are checked by [`MockHostClient::verify`], and when the
+/// client is dropped.
+#[derive(Debug, Default)]
+pub struct MockHostClient {
+    responses: HashMap<(String, String), MockResponse>,
+    expectations: Vec<Expectation>,
+    calls: Mutex<Vec<HostCall>>,
+}
+
+impl MockHostClient {
+    /// Create a client without any configured response
+    pub fn new() -> Self {
+        MockHostClient::default()
+    }
+
+    /// Reply to the given capability with the JSON serialization of `response`
+    pub fn respond<T: Serialize + ?Sized>(
+        self,
+        namespace: &str,
+        operation: &str,
+        response: &T,
+    ) -> Self {
+        let response = serde_json::to_vec(response).expect("cannot serialize mock response");
+        self.respond_raw(namespace, operation, response)
+    }
+
+    /// Reply to the given capability with the given raw payload
+    pub fn respond_raw(mut self, namespace: &str, operation: &str, response: Vec<u8>) -> Self {
+        self.responses
+            .insert((namespace.to_string(), operation.to_string()), Ok(response));
+        self
+    }
+
+    /// Make the given capability fail with the given error message
+    pub fn fail(mut self, namespace: &str, operation: &str, message: &str) -> Self {
+        self.responses.insert(
+            (namespace.to_string(), operation.to_string()),
+            Err(message.to_string()),
+        );
+        self
+    }
+
+    /// Make the given capability fail with a structured error, using the
+    /// wire format of [`HostError`]
+    pub fn fail_with(self, namespace: &str, operation: &str, error: &HostError) -> Self {
+        let message = serde_json::to_string(error).expect("cannot serialize host error");
+        self.fail(namespace, operation, &message)
+    }
+
+    /// Expect the given capability to be called exactly `times` times
+    pub fn expect(mut self, namespace: &str, operation: &str, times: usize) -> Self {
+        self.expectations.push(Expectation {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload: None,
+            times,
+        });
+        self
+    }
+
+    /// Expect the given capability to be called exactly `times` times with
+    /// a payload matching the JSON serialization of `payload`
+    pub fn expect_with<T: Serialize + ?Sized>(
+        mut self,
+        namespace: &str,
+        operation: &str,
+        payload: &T,
+        times: usize,
+    ) -> Self {
+        let payload = serde_json::to_value(payload).expect("cannot serialize expected payload");
+        self.expectations.push(Expectation {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload: Some(payload),
+            times,
+        });
+        self
+    }
+
+    /// Check all the expectations declared on the client
+    ///
+    /// # Panics
+    ///
+    /// Panics when a capability has not been called the expected number of
+    /// times
+    pub fn verify(&self) {
+        let failures: Vec<String> = self
+            .expectations
+            .iter()
+            .filter_map(|expectation| {
+                let calls = self.count_calls(expectation);
+                (calls != expectation.times)
+                    .then(|| format!("{}, called {} time(s)", expectation, calls))
+            })
+            .collect();
+        if !failures.is_empty() {
+            panic!(
+                "unmet host capability expectations:\n{}",
+                failures.join("\n")
+            );
+        }
+    }
+
+    /// Ensure the given capability has been called exactly `times` times
+    ///
+    /// # Panics
+    ///
+    /// Panics when the number of calls is different
+    pub fn assert_called_times(&self, namespace: &str, operation: &str, times: usize) {
+        let calls = self.calls_to(namespace, operation).len();
+        assert_eq!(
+            calls, times,
+            "{}.{} has been called {} time(s), expected {}",
+            namespace, operation, calls, times
+        );
+    }
+
+    /// Ensure the given capability has been called exactly `times` times
+    /// with a payload matching the JSON serialization of `payload`
+    ///
+    /// # Panics
+    ///
+    /// Panics when the number of calls is different
+    pub fn assert_called_with<T: Serialize + ?Sized>(
+        &self,
+        namespace: &str,
+        operation: &str,
+        payload: &T,
+        times: usize,
+    ) {
+        let expectation = Expectation {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload: Some(serde_json::to_value(payload).expect("cannot serialize payload")),
+            times,
+        };
+        let calls = self.count_calls(&expectation);
+        assert_eq!(calls, times, "{}, called {} time(s)", expectation, calls);
+    }
+
+    fn count_calls(&self, expectation: &Expectation) -> usize {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| expectation.matches(call))
+            .count()
+    }
+
+    /// All the calls received so far
+    pub fn calls(&self) -> Vec<HostCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The calls received so far by the given capability
+    pub fn calls_to(&self, namespace: &str, operation: &str) -> Vec<HostCall> {
+        self.calls()
+            .into_iter()
+            .filter(|c| c.namespace == namespace && c.operation == operation)
+            .collect()
+    }
+}
+
+impl Drop for MockHostClient {
+    fn drop(&mut self) {
+        // do not hide the panic that is unwinding the test
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+impl HostClient for MockHostClient {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        self.calls.lock().unwrap().push(HostCall {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            payload: payload.to_vec(),
+        });
+
+        match self
+            .responses
+            .get(&(namespace.to_string(), operation.to_string()))
+        {
+            Some(Ok(response)) => Ok(response.clone()),
+            Some(Err(message)) => Err(message.clone().into()),
+            // like the hosts not implementing the capability
+            None => Err(format!(
+                "unknown operation {}.{}: no response configured",
+                namespace, operation
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_client_records_calls() {
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond(
+                    "oci",
+                    "v1/manifest_digest",
+                    &serde_json::json!({"digest": "sha256:1"}),
+                )
+                .fail("net", "v1/dns_lookup_host", "boom"),
+        );
+
+        with_host_client(client.clone(), || {
+            assert_eq!(
+                host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap(),
+                br#"{"digest":"sha256:1"}"#
+            );
+            let error = host_call("net", "v1/dns_lookup_host", b"").unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "error invoking host capability net.v1/dns_lookup_host: boom"
+            );
+            assert!(host_call("crypto", "v1/is_certificate_trusted", b"").is_err());
+        });
+
+        assert_eq!(client.calls().len(), 3);
+        assert_eq!(
+            client.calls_to("oci", "v1/manifest_digest")[0]
+                .payload_json()
+                .unwrap(),
+            "busybox"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn structured_errors_reach_the_wrappers() {
+        let error = HostError {
+            code: crate::error::HostErrorCode::Unavailable,
+            message: "DNS server unreachable".to_string(),
+            retryable: true,
+        };
+        let client = Rc::new(MockHostClient::new().fail_with("net", "v1/dns_lookup_host", &error));
+
+        let result = with_host_client(client, || {
+            crate::host_capabilities::net::lookup_host("example.com")
+        });
+        let failure = result.unwrap_err();
+        assert!(failure.is_retryable());
+        assert_eq!(failure.host_error(), Some(&error));
+    }
+
+    #[test]
+    fn mock_client_expectations() {
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond("oci", "v1/manifest_digest", &"sha256:1")
+                .expect("oci", "v1/manifest_digest", 2)
+                .expect_with("oci", "v1/manifest_digest", "busybox", 1)
+                .expect("net", "v1/dns_lookup_host", 0),
+        );
+
+        with_host_client(client.clone(), || {
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+            let result = std::panic::catch_unwind(|| client.verify());
+            assert!(result.is_err());
+            host_call("oci", "v1/manifest_digest", b"\"nginx\"").unwrap();
+        });
+
+        client.verify();
+        client.assert_called_times("oci", "v1/manifest_digest", 2);
+        client.assert_called_with("oci", "v1/manifest_digest", "nginx", 1);
+        let result = std::panic::catch_unwind(|| {
+            client.assert_called_with("oci", "v1/manifest_digest", "alpine", 1)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn responses_are_memoized_during_evaluations() {
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond("oci", "v1/manifest_digest", &"sha256:1")
+                .respond("events", "v1/emit", &())
+                .respond("webhook", "v1/post", &())
+                .respond("kubernetes", "get_resource", &())
+                .expect_with("oci", "v1/manifest_digest", "busybox", 5)
+                .expect_with("oci", "v1/manifest_digest", "nginx", 1)
+                .expect("net", "v1/dns_lookup_host", 2)
+                .expect("events", "v1/emit", 2)
+                .expect("webhook", "v1/post", 2)
+                .expect("kubernetes", "get_resource", 1),
+        );
+
+        with_host_client(client.clone(), || {
+            // not evaluating a request yet
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+
+            start_memoization();
+            for _ in 0..3 {
+                host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+                host_call("oci", "v1/manifest_digest", b"\"nginx\"").unwrap();
+            }
+            // failures and side effects are not memoized
+            assert!(host_call("net", "v1/dns_lookup_host", b"\"x\"").is_err());
+            assert!(host_call("net", "v1/dns_lookup_host", b"\"x\"").is_err());
+            host_call("events", "v1/emit", b"{}").unwrap();
+            host_call("events", "v1/emit", b"{}").unwrap();
+            // only the capabilities reading data are memoized
+            host_call("webhook", "v1/post", b"{}").unwrap();
+            host_call("webhook", "v1/post", b"{}").unwrap();
+            host_call("kubernetes", "get_resource", b"{}").unwrap();
+            host_call("kubernetes", "get_resource", b"{}").unwrap();
+
+            // the end of the evaluation
+            end_memoization();
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+
+            // the next evaluation
+            start_memoization();
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+        });
+        client.verify();
+
+        // replacing the client drops the memoized responses
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond("oci", "v1/manifest_digest", &"sha256:2")
+                .expect("oci", "v1/manifest_digest", 1),
+        );
+        with_host_client(client, || {
+            assert_eq!(
+                host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap(),
+                b"\"sha256:2\""
+            );
+            host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap();
+        });
+        MEMOIZED.with(|m| *m.borrow_mut() = None);
+    }
+
+    #[test]
+    fn mock_client_verifies_on_drop() {
+        let result = std::panic::catch_unwind(|| {
+            drop(MockHostClient::new().expect("oci", "v1/manifest_digest", 1));
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn debug_payloads_are_truncated() {
+        assert_eq!(truncate_payload(b"\"busybox\"", 16), "\"busybox\"");
+        assert_eq!(truncate_payload(b"\"busybox\"", 4), "\"bus... (9 bytes)");
+        // multi-byte characters are not split
+        assert_eq!(truncate_payload("\"ü\"".as_bytes(), 2), "\"... (4 bytes)");
+        assert_eq!(
+            truncate_payload(br#"{"password": "admin"}"#, 64),
+            r#"{"password":"[REDACTED]"}"#
+        );
+
+        let client = Rc::new(MockHostClient::new().respond("host", "echo", &"pong"));
+        set_debug_payloads(Some(DEFAULT_DEBUG_PAYLOADS_LIMIT));
+        with_host_client(client, || {
+            assert_eq!(host_call("host", "echo", b"\"ping\"").unwrap(), b"\"pong\"");
+            assert!(host_call("host", "missing", b"").is_err());
+        });
+        set_debug_payloads(None);
+    }
+
+    #[test]
+    fn previous_client_is_restored() {
+        let outer = Rc::new(MockHostClient::new().respond("host", "outer", &true));
+        let inner = Rc::new(MockHostClient::new());
+
+        with_host_client(outer.clone(), || {
+            let result = std::panic::catch_unwind(|| {
+                with_host_client(inner.clone(), || panic!("boom"));
+            });
+            assert!(result.is_err());
+            assert!(host_call("host", "outer", b"").is_ok());
+        });
+
+        assert_eq!(inner.calls().len(), 0);
+        assert_eq!(outer.calls().len(), 1);
+    }
+}
@@ -1,7 +1,8 @@
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
 use crate::host_capabilities::crypto_v1::{
     CertificateVerificationRequest, CertificateVerificationResponse,
 };
-use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
 /// A x509 certificate
@@ -69,17 +70,12 @@ pub fn verify_cert(
         cert_chain,
         not_after,
     };
-    let msg = serde_json::to_vec(&req).map_err(|e| {
-        anyhow!(
-            "error serializing the certificate verification request: {}",
-            e
-        )
-    })?;
-    let response_raw =
-        wapc_guest::host_call("kubewarden", "crypto", "v1/is_certificate_trusted", &msg)
-            .map_err(|e| anyhow!("{}", e))?;
+    let msg = serde_json::to_vec(&req)
+        .map_err(|e| SdkError::serialization("the certificate verification request", e))?;
+    let response_raw = host_call("crypto", "v1/is_certificate_trusted", &msg)?;
 
-    let response: CertificateVerificationResponse = serde_json::from_slice(&response_raw)?;
+    let response: CertificateVerificationResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the certificate verification response", e))?;
     match response.trusted {
         true => Ok(BoolWithReason::True),
         false => Ok(BoolWithReason::False(format!(
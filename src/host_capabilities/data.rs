@@ -0,0 +1,110 @@
+//! Query the external data sources configured on the host.
+//!
+//! Allowlists, denylists and other data that change more often than the
+//! policy settings can be managed centrally and served by the host. A data
+//! source is either a key/value store or a single JSON document, identified
+//! by the name given to it by the host configuration.
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::data;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "data",
+//!     "v1/get",
+//!     &json!({"value": ["ghcr.io", "registry.k8s.io"]}),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let registries: Vec<String> = data::get("registries", "allowed").unwrap().unwrap();
+//!     assert!(registries.contains(&"ghcr.io".to_string()));
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Request sent to the host by the data functions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DataRequest {
+    /// Name of the data source, as configured on the host
+    pub source: String,
+    /// The key to look up, `None` to get the whole document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+/// Response of the host, the value is `null` when the key doesn't exist
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DataResponse<T> {
+    /// The value found
+    pub value: Option<T>,
+}
+
+fn fetch<T: DeserializeOwned>(req: &DataRequest) -> Result<Option<T>> {
+    let msg =
+        serde_json::to_vec(req).map_err(|e| SdkError::serialization("the data request", e))?;
+    let response_raw = host_call("data", "v1/get", &msg)?;
+
+    let response: DataResponse<T> = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the data response", e))?;
+    Ok(response.value)
+}
+
+/// Get the value stored under `key` inside of a key/value data source.
+/// Returns `None` when the key doesn't exist
+pub fn get<T: DeserializeOwned>(source: &str, key: &str) -> Result<Option<T>> {
+    fetch(&DataRequest {
+        source: source.to_string(),
+        key: Some(key.to_string()),
+    })
+}
+
+/// Get the whole document served by a data source
+pub fn document<T: DeserializeOwned>(source: &str) -> Result<T> {
+    fetch(&DataRequest {
+        source: source.to_string(),
+        key: None,
+    })?
+    .ok_or_else(|| SdkError::InvalidInput(format!("data source {} is empty", source)))
+}
+
+/// Returns true when `key` exists inside of a key/value data source
+pub fn contains_key(source: &str, key: &str) -> Result<bool> {
+    get::<serde_json::Value>(source, key).map(|value| value.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    #[test]
+    fn query_data_sources() {
+        let client =
+            Rc::new(MockHostClient::new().respond("data", "v1/get", &json!({"value": null})));
+        with_host_client(client.clone(), || {
+            assert!(!contains_key("denied-images", "busybox").unwrap());
+            assert!(document::<serde_json::Value>("empty").is_err());
+        });
+        client.assert_called_with(
+            "data",
+            "v1/get",
+            &json!({"source": "denied-images", "key": "busybox"}),
+            1,
+        );
+        client.assert_called_with("data", "v1/get", &json!({"source": "empty"}), 1);
+
+        let client =
+            Rc::new(MockHostClient::new().respond("data", "v1/get", &json!({"value": ["a", "b"]})));
+        with_host_client(client, || {
+            let namespaces: HashSet<String> = document("namespaces").unwrap();
+            assert!(namespaces.contains("a"));
+        });
+    }
+}
@@ -0,0 +1,291 @@
+//! Encodings of the payloads exchanged with the host capabilities.
+//!
+//! The payloads are JSON documents. Hosts can implement more compact
+//! encodings of a capability as additional operations, named after the
+//! operation and the encoding: for example `list_resources_all+msgpack`
+//! receives the request and returns the response encoded with the
+//! encoding. The SDK uses the additional encodings when the corresponding
+//! feature is enabled:
+//!
+//! * `msgpack`, operations ending with `+msgpack`: [MessagePack](https://msgpack.org),
+//!   using maps for the structs
+//! * `gzip`, operations ending with `+gzip`: JSON compressed with gzip
+//!
+//! They are used by the functions listing Kubernetes resources, whose
+//! responses can hold thousands of objects.
+//!
+//! The encodings are negotiated with the host: the first time a capability
+//! is invoked the encoded operations are attempted, in the order above, and
+//! when the host reports one as unsupported the SDK remembers it for the
+//! lifetime of the policy instance. JSON is used when none is supported.
+//! Other errors are returned as they are.
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{cell::RefCell, collections::HashSet};
+
+thread_local! {
+    // the `namespace.operation+encoding` capabilities not supported by the host
+    static UNSUPPORTED: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+/// An encoding of the payloads, alternative to JSON
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    MessagePack,
+    Gzip,
+}
+
+/// The encodings enabled by the features, from the preferred one
+const ENCODINGS: &[Encoding] = &[
+    #[cfg(feature = "msgpack")]
+    Encoding::MessagePack,
+    #[cfg(feature = "gzip")]
+    Encoding::Gzip,
+];
+
+impl Encoding {
+    /// Suffix of the operations exchanging payloads with this encoding
+    fn suffix(self) -> &'static str {
+        match self {
+            Encoding::MessagePack => "+msgpack",
+            Encoding::Gzip => "+gzip",
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "msgpack", feature = "gzip")),
+        allow(unused_variables)
+    )]
+    fn encode<T: Serialize + ?Sized>(self, value: &T, what: &str) -> Result<Vec<u8>> {
+        match self {
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)
+                .map_err(|e| SdkError::serialization(what, serde::ser::Error::custom(e))),
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => {
+                use std::io::Write;
+
+                let json =
+                    serde_json::to_vec(value).map_err(|e| SdkError::serialization(what, e))?;
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+                encoder
+                    .write_all(&json)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| SdkError::serialization(what, serde::ser::Error::custom(e)))
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not enabled", self),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "msgpack", feature = "gzip")),
+        allow(unused_variables)
+    )]
+    fn decode<T: DeserializeOwned>(self, payload: &[u8], what: &str) -> Result<T> {
+        match self {
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => rmp_serde::from_slice(payload)
+                .map_err(|e| SdkError::deserialization(what, serde::de::Error::custom(e))),
+            #[cfg(feature = "gzip")]
+            Encoding::Gzip => serde_json::from_reader(flate2::read::GzDecoder::new(payload))
+                .map_err(|e| SdkError::deserialization(what, e)),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!("{:?} is not enabled", self),
+        }
+    }
+}
+
+/// The operation without the encoding suffix, e.g. `list_resources_all` for
+/// `list_resources_all+msgpack`
+pub(crate) fn base_operation(operation: &str) -> &str {
+    [Encoding::MessagePack, Encoding::Gzip]
+        .iter()
+        .find_map(|encoding| operation.strip_suffix(encoding.suffix()))
+        .unwrap_or(operation)
+}
+
+/// Forget the outcome of the negotiations, done when the host client is
+/// replaced
+pub(crate) fn reset_negotiation() {
+    UNSUPPORTED.with(|unsupported| unsupported.borrow_mut().clear());
+}
+
+/// Invoke the given capability using the most compact encoding supported
+/// by the host. `request` and `response` describe the payloads in the errors
+#[cfg_attr(not(feature = "cluster-context"), allow(dead_code))]
+pub(crate) fn call<Req, Resp>(
+    namespace: &str,
+    operation: &str,
+    payload: &Req,
+    request: &str,
+    response: &str,
+) -> Result<Resp>
+where
+    Req: Serialize + ?Sized,
+    Resp: DeserializeOwned,
+{
+    for encoding in ENCODINGS {
+        let encoded_operation = format!("{}{}", operation, encoding.suffix());
+        let capability = format!("{}.{}", namespace, encoded_operation);
+        if UNSUPPORTED.with(|unsupported| unsupported.borrow().contains(&capability)) {
+            continue;
+        }
+
+        let msg = encoding.encode(payload, request)?;
+        match host_call(namespace, &encoded_operation, &msg) {
+            Err(e) if e.is_unsupported_capability() => {
+                UNSUPPORTED.with(|unsupported| unsupported.borrow_mut().insert(capability));
+            }
+            result => return encoding.decode(&result?, response),
+        }
+    }
+
+    let msg = serde_json::to_vec(payload).map_err(|e| SdkError::serialization(request, e))?;
+    let response_raw = host_call(namespace, operation, &msg)?;
+    serde_json::from_slice(&response_raw).map_err(|e| SdkError::deserialization(response, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde::Deserialize;
+    use std::rc::Rc;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Request {
+        kind: String,
+    }
+
+    fn request() -> Request {
+        Request {
+            kind: "Pod".to_string(),
+        }
+    }
+
+    fn list() -> Result<Vec<String>> {
+        super::call(
+            "kubernetes",
+            "list",
+            &request(),
+            "the request",
+            "the response",
+        )
+    }
+
+    #[test]
+    fn strip_the_encoding_of_the_operations() {
+        assert_eq!(
+            base_operation("list_resources_all+msgpack"),
+            "list_resources_all"
+        );
+        assert_eq!(
+            base_operation("list_resources_all+gzip"),
+            "list_resources_all"
+        );
+        assert_eq!(base_operation("list_resources_all"), "list_resources_all");
+    }
+
+    #[test]
+    fn fall_back_to_json_on_old_hosts() {
+        // the capabilities without a response are reported as unknown
+        let mut client = MockHostClient::new()
+            .respond("kubernetes", "list", &["a"])
+            .expect("kubernetes", "list", 2);
+        for encoding in ENCODINGS {
+            client = client.expect("kubernetes", &format!("list{}", encoding.suffix()), 1);
+        }
+        let client = Rc::new(client);
+
+        with_host_client(client.clone(), || {
+            assert_eq!(list().unwrap(), ["a"]);
+            assert_eq!(list().unwrap(), ["a"]);
+        });
+        client.verify();
+    }
+
+    #[test]
+    #[cfg(any(feature = "msgpack", feature = "gzip"))]
+    fn transient_errors_are_returned() {
+        let operation = format!("list{}", ENCODINGS[0].suffix());
+        let client = Rc::new(
+            MockHostClient::new()
+                .fail("kubernetes", &operation, "connection refused")
+                .respond("kubernetes", "list", &["a"])
+                .expect("kubernetes", "list", 0),
+        );
+
+        assert!(with_host_client(client.clone(), list).is_err());
+        client.verify();
+    }
+
+    #[test]
+    #[cfg(feature = "msgpack")]
+    fn use_msgpack_when_supported() {
+        let response = rmp_serde::to_vec_named(&["a", "b"]).unwrap();
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond_raw("kubernetes", "list+msgpack", response)
+                .expect("kubernetes", "list", 0),
+        );
+
+        assert_eq!(with_host_client(client.clone(), list).unwrap(), ["a", "b"]);
+
+        let calls = client.calls_to("kubernetes", "list+msgpack");
+        let request: Request = rmp_serde::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(request, self::request());
+        client.verify();
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn use_gzip_when_supported() {
+        let response = Encoding::Gzip.encode(&["a", "b"], "the response").unwrap();
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond_raw("kubernetes", "list+gzip", response)
+                .expect("kubernetes", "list", 0),
+        );
+
+        assert_eq!(with_host_client(client.clone(), list).unwrap(), ["a", "b"]);
+
+        let calls = client.calls_to("kubernetes", "list+gzip");
+        let request: Request = Encoding::Gzip
+            .decode(&calls[0].payload, "the request")
+            .unwrap();
+        assert_eq!(request, self::request());
+        client.verify();
+    }
+
+    #[test]
+    #[cfg(all(feature = "msgpack", feature = "cluster-context"))]
+    fn decode_kubernetes_lists() {
+        let list = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "PodList",
+            "metadata": {"resourceVersion": "1"},
+            "items": [{"metadata": {"name": "nginx", "labels": {"app": "web"}}}]
+        });
+        let client = Rc::new(MockHostClient::new().respond_raw(
+            "kubernetes",
+            "list+msgpack",
+            rmp_serde::to_vec_named(&list).unwrap(),
+        ));
+
+        let list: k8s_openapi::List<k8s_openapi::api::core::v1::Pod> =
+            with_host_client(client, || {
+                super::call(
+                    "kubernetes",
+                    "list",
+                    &request(),
+                    "the request",
+                    "the response",
+                )
+            })
+            .unwrap();
+        assert_eq!(list.items[0].metadata.name.as_deref(), Some("nginx"));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use crate::request::{ensure_side_effects_allowed, KubernetesAdmissionRequest};
+use serde::{Deserialize, Serialize};
+
+/// The type of an [`AuditEvent`], like the one of the Kubernetes Events
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventType {
+    /// A noteworthy decision, like an exemption
+    #[default]
+    Normal,
+    /// A decision the operators should look into
+    Warning,
+}
+
+/// Reference to the object an [`AuditEvent`] is about
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EventObjectReference {
+    /// API version of the object, e.g. `apps/v1`
+    pub api_version: String,
+    /// Kind of the object
+    pub kind: String,
+    /// Namespace of the object, empty for cluster-wide objects
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub namespace: String,
+    /// Name of the object
+    pub name: String,
+    /// UID of the object, when known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+}
+
+/// An event describing a decision taken by the policy, recorded by the host
+/// as a Kubernetes Event, e.g. visible with `kubectl get events`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    /// Type of the event
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    /// Short, machine understandable reason, in UpperCamelCase, e.g. `Exempted`
+    pub reason: String,
+    /// Human readable description of the decision
+    pub message: String,
+    /// The object the event is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regarding: Option<EventObjectReference>,
+}
+
+impl AuditEvent {
+    /// Create a `Normal` event with the given reason and message
+    pub fn new(reason: &str, message: &str) -> Self {
+        AuditEvent {
+            event_type: EventType::Normal,
+            reason: reason.to_string(),
+            message: message.to_string(),
+            regarding: None,
+        }
+    }
+
+    /// Create a `Warning` event with the given reason and message
+    pub fn warning(reason: &str, message: &str) -> Self {
+        AuditEvent {
+            event_type: EventType::Warning,
+            ..AuditEvent::new(reason, message)
+        }
+    }
+
+    /// Make the event about the object of the given admission request
+    pub fn regarding_request(mut self, request: &KubernetesAdmissionRequest) -> Self {
+        let gvk = &request.kind;
+        let object = if request.object.is_null() {
+            &request.old_object
+        } else {
+            &request.object
+        };
+        self.regarding = Some(EventObjectReference {
+            api_version: gvk.api_version(),
+            kind: gvk.kind.clone(),
+            namespace: request.namespace.clone(),
+            name: request.name.clone(),
+            uid: object["metadata"]["uid"].as_str().map(String::from),
+        });
+        self
+    }
+}
+
+/// Ask the host to record the given event.
+///
+/// Events are side effects: an [`SdkError::DryRun`] error is returned while
+/// evaluating a dry-run request, see [`ensure_side_effects_allowed`].
+pub fn emit_event(event: &AuditEvent) -> Result<()> {
+    if event.reason.is_empty() {
+        return Err(SdkError::InvalidInput(
+            "the reason of an event cannot be empty".to_string(),
+        ));
+    }
+    ensure_side_effects_allowed("emitting events")?;
+
+    let msg =
+        serde_json::to_vec(event).map_err(|e| SdkError::serialization("the audit event", e))?;
+    host_call("events", "v1/emit", &msg)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use crate::request::ValidationRequest;
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn request(dry_run: bool) -> KubernetesAdmissionRequest {
+        let payload = json!({
+            "settings": {},
+            "request": {
+                "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "namespace": "default",
+                "name": "nginx",
+                "dryRun": dry_run,
+                "object": {"metadata": {"name": "nginx", "uid": "1234"}},
+            }
+        });
+        ValidationRequest::<serde_json::Value>::new(&serde_json::to_vec(&payload).unwrap())
+            .unwrap()
+            .request
+    }
+
+    #[test]
+    fn emit_events() {
+        let client = Rc::new(MockHostClient::new().respond("events", "v1/emit", &()));
+        let request = request(false);
+        let event = AuditEvent::new("Exempted", "pod exempted due to annotation")
+            .regarding_request(&request);
+
+        with_host_client(client.clone(), || emit_event(&event)).unwrap();
+
+        assert_eq!(
+            client.calls_to("events", "v1/emit")[0]
+                .payload_json()
+                .unwrap(),
+            json!({
+                "type": "Normal",
+                "reason": "Exempted",
+                "message": "pod exempted due to annotation",
+                "regarding": {
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "namespace": "default",
+                    "name": "nginx",
+                    "uid": "1234",
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn no_events_during_dry_run() {
+        let client = Rc::new(MockHostClient::new().respond("events", "v1/emit", &()));
+        let event = AuditEvent::warning("Exempted", "dry-run").regarding_request(&request(true));
+
+        let result = with_host_client(client.clone(), || emit_event(&event));
+        assert!(matches!(result, Err(SdkError::DryRun { .. })));
+        assert!(client.calls().is_empty());
+
+        // leave the dry-run mode
+        request(false);
+        let result = with_host_client(client, || emit_event(&AuditEvent::new("", "no reason")));
+        assert!(matches!(result, Err(SdkError::InvalidInput(_))));
+    }
+}
@@ -0,0 +1,162 @@
+//! Degrade gracefully when the host doesn't support a capability.
+//!
+//! Policies using a recent capability can be deployed on policy-servers
+//! that predate it. [`capability_or`] lets the policy author decide what
+//! happens then: either fail the evaluation, as any other host error, or
+//! carry on with a default value and warn the user that the check has been
+//! skipped. Only the errors reporting an unsupported capability (see
+//! [`SdkError::is_unsupported_capability`](crate::error::SdkError::is_unsupported_capability))
+//! are handled, all the other ones are returned unchanged.
+//!
+//! ```
+//! # #[cfg(feature = "net")]
+//! # {
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::{capability_or, net, FailMode};
+//! use kubewarden_policy_sdk::response::ValidationResponse;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().fail(
+//!     "net",
+//!     "v1/dns_lookup_host",
+//!     "unknown operation: v1/dns_lookup_host",
+//! ));
+//!
+//! let lookup = with_host_client(client, || {
+//!     capability_or(None, FailMode::AcceptWithWarning, || {
+//!         net::lookup_host("example.com").map(Some)
+//!     })
+//! })
+//! .unwrap();
+//! assert!(lookup.value.is_none());
+//!
+//! let response = lookup.warn(ValidationResponse::accept()).response();
+//! assert_eq!(response.warnings.unwrap().len(), 1);
+//! # }
+//! ```
+use crate::error::Result;
+use crate::response::ResponseBuilder;
+use serde::{Deserialize, Serialize};
+
+/// What to do when the host doesn't support a capability. This can be
+/// exposed in the settings of the policy, as `reject` or
+/// `acceptWithWarning`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FailMode {
+    /// Return the error, the request is rejected
+    #[default]
+    Reject,
+    /// Use the default value and warn the user
+    AcceptWithWarning,
+}
+
+/// The value returned by [`capability_or`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fallback<T> {
+    /// The value returned by the capability, or the default one
+    pub value: T,
+    /// Set when the default value has been used
+    pub warning: Option<String>,
+}
+
+impl<T> Fallback<T> {
+    /// Whether the default value has been used
+    pub fn is_degraded(&self) -> bool {
+        self.warning.is_some()
+    }
+
+    /// Add the warning, if any, to the response
+    pub fn warn(&self, response: ResponseBuilder) -> ResponseBuilder {
+        match &self.warning {
+            Some(warning) => response.warning(warning.clone()),
+            None => response,
+        }
+    }
+}
+
+/// Invoke `operation`, falling back to `default` according to `fail_mode`
+/// when the host doesn't support the capability
+pub fn capability_or<T, F>(default: T, fail_mode: FailMode, operation: F) -> Result<Fallback<T>>
+where
+    F: FnOnce() -> Result<T>,
+{
+    match operation() {
+        Ok(value) => Ok(Fallback {
+            value,
+            warning: None,
+        }),
+        Err(error)
+            if fail_mode == FailMode::AcceptWithWarning && error.is_unsupported_capability() =>
+        {
+            slog::warn!(
+                crate::logging::logger(),
+                "host capability not supported, using the default value";
+                "error" => error.to_string()
+            );
+            Ok(Fallback {
+                value: default,
+                warning: Some(format!("check skipped: {}", error)),
+            })
+        }
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::SdkError;
+    use crate::response::ValidationResponse;
+
+    fn unsupported() -> SdkError {
+        SdkError::host_callback("oci", "v3/verify", "unknown operation: v3/verify".into())
+    }
+
+    #[test]
+    fn use_the_value_of_the_capability() {
+        let result = capability_or(0, FailMode::AcceptWithWarning, || Ok(42)).unwrap();
+        assert_eq!(result.value, 42);
+        assert!(!result.is_degraded());
+
+        let response = result.warn(ValidationResponse::accept()).response();
+        assert!(response.warnings.is_none());
+    }
+
+    #[test]
+    fn accept_with_warning() {
+        let result = capability_or(0, FailMode::AcceptWithWarning, || Err(unsupported())).unwrap();
+        assert_eq!(result.value, 0);
+        assert!(result.is_degraded());
+
+        let response = result.warn(ValidationResponse::accept()).response();
+        let warnings = response.warnings.unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("oci.v3/verify"), "{}", warnings[0]);
+    }
+
+    #[test]
+    fn reject() {
+        let result = capability_or(0, FailMode::Reject, || Err(unsupported()));
+        assert!(result.unwrap_err().is_unsupported_capability());
+    }
+
+    #[test]
+    fn return_other_errors() {
+        let result = capability_or(0, FailMode::AcceptWithWarning, || {
+            Err(SdkError::host_callback(
+                "oci",
+                "v2/verify",
+                "image not signed".into(),
+            ))
+        });
+        assert!(!result.unwrap_err().is_unsupported_capability());
+    }
+
+    #[test]
+    fn fail_mode_from_settings() {
+        let mode: FailMode = serde_json::from_str(r#""acceptWithWarning""#).unwrap();
+        assert_eq!(mode, FailMode::AcceptWithWarning);
+        assert_eq!(FailMode::default(), FailMode::Reject);
+    }
+}
@@ -0,0 +1,145 @@
+//! Resolve the groups of a user through the identity providers configured
+//! on the host.
+//!
+//! The groups found inside of the admission request are the ones known by
+//! the authenticator of the API server, which often doesn't know about the
+//! groups of the corporate directory. The host can query the directory, via
+//! an LDAP or OIDC connector, so exemption rules can be written in terms of
+//! directory groups without copying their members into the settings:
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::identity;
+//! use kubewarden_policy_sdk::request::UserInfo;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "identity",
+//!     "v1/groups",
+//!     &json!({"groups": ["cn=sre,ou=groups,dc=example,dc=com"]}),
+//! ));
+//!
+//! let user = UserInfo {
+//!     username: "alice".to_string(),
+//!     ..Default::default()
+//! };
+//! with_host_client(client, || {
+//!     assert!(identity::is_member(&user, "cn=sre,ou=groups,dc=example,dc=com").unwrap());
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use crate::request::UserInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Request sent to the host by the identity functions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GroupsRequest {
+    /// The name of the user, as found inside of the admission request
+    pub username: String,
+    /// The connector to query, the default one of the host when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connector: Option<String>,
+}
+
+/// Response of the host, the groups are empty when the user is unknown
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GroupsResponse {
+    /// The groups of the user
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// Resolve the groups of `username` using the given connector, or the
+/// default connector of the host when `None`
+pub fn groups(username: &str, connector: Option<&str>) -> Result<Vec<String>> {
+    let req = GroupsRequest {
+        username: username.to_string(),
+        connector: connector.map(str::to_string),
+    };
+    let msg =
+        serde_json::to_vec(&req).map_err(|e| SdkError::serialization("the groups request", e))?;
+    let response_raw = host_call("identity", "v1/groups", &msg)?;
+
+    let response: GroupsResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the groups response", e))?;
+    Ok(response.groups)
+}
+
+/// All the groups of the user making the request: the ones provided by
+/// the authenticator of the API server, plus the ones resolved by the
+/// default connector of the host
+pub fn all_groups(user: &UserInfo) -> Result<HashSet<String>> {
+    let mut all = user.groups.clone();
+    all.extend(groups(&user.username, None)?);
+    Ok(all)
+}
+
+/// Returns true when the user belongs to `group`. The identity provider is
+/// queried only when the group is not found inside of the admission request
+pub fn is_member(user: &UserInfo, group: &str) -> Result<bool> {
+    if user.groups.contains(group) {
+        return Ok(true);
+    }
+    Ok(groups(&user.username, None)?.iter().any(|g| g == group))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn user() -> UserInfo {
+        UserInfo {
+            username: "alice".to_string(),
+            groups: HashSet::from(["system:authenticated".to_string()]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resolve_groups() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "identity",
+            "v1/groups",
+            &json!({"groups": ["sre", "oncall"]}),
+        ));
+
+        with_host_client(client.clone(), || {
+            assert_eq!(
+                groups("alice", Some("ldap")).unwrap(),
+                vec!["sre", "oncall"]
+            );
+            assert_eq!(all_groups(&user()).unwrap().len(), 3);
+            assert!(is_member(&user(), "oncall").unwrap());
+            assert!(!is_member(&user(), "admins").unwrap());
+        });
+
+        let calls = client.calls_to("identity", "v1/groups");
+        let request: GroupsRequest = serde_json::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(request.connector.as_deref(), Some("ldap"));
+        let request: GroupsRequest = serde_json::from_slice(&calls[1].payload).unwrap();
+        assert_eq!(
+            request,
+            GroupsRequest {
+                username: "alice".to_string(),
+                connector: None
+            }
+        );
+    }
+
+    #[test]
+    fn request_groups_do_not_need_the_host() {
+        let client = Rc::new(MockHostClient::new().fail("identity", "v1/groups", "unreachable"));
+
+        with_host_client(client.clone(), || {
+            assert!(is_member(&user(), "system:authenticated").unwrap());
+            assert!(is_member(&user(), "sre").is_err());
+        });
+        assert_eq!(client.calls().len(), 1);
+    }
+}
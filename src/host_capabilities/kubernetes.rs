@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::{client::host_call, encoding};
 use serde::{Deserialize, Serialize};
 
 /// Describe the set of parameters used by the `list_resources_by_namespace`
@@ -28,26 +29,13 @@ pub fn list_resources_by_namespace<T>(
 where
     T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
 {
-    let msg = serde_json::to_vec(req).map_err(|e| {
-        anyhow!(
-            "error serializing the list resources by namespace request: {}",
-            e
-        )
-    })?;
-    let response_raw = wapc_guest::host_call(
-        "kubewarden",
+    encoding::call(
         "kubernetes",
         "list_resources_by_namespace",
-        &msg,
+        req,
+        "the list resources by namespace request",
+        "list resources by namespace response into Kubernetes resource",
     )
-    .map_err(|e| anyhow!("{}", e))?;
-
-    serde_json::from_slice(&response_raw).map_err(|e| {
-        anyhow!(
-            "error deserializing list resources by namespace response into Kubernetes resource: {:?}",
-            e
-        )
-    })
 }
 
 /// Describe the set of parameters used by the `list_all_resources` function.
@@ -71,18 +59,13 @@ pub fn list_all_resources<T>(req: &ListAllResourcesRequest) -> Result<k8s_openap
 where
     T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
 {
-    let msg = serde_json::to_vec(req)
-        .map_err(|e| anyhow!("error serializing the list all resources request: {}", e))?;
-    let response_raw =
-        wapc_guest::host_call("kubewarden", "kubernetes", "list_resources_all", &msg)
-            .map_err(|e| anyhow!("{}", e))?;
-
-    serde_json::from_slice(&response_raw).map_err(|e| {
-        anyhow!(
-            "error deserializing list all resources response into Kubernetes resource: {:?}",
-            e
-        )
-    })
+    encoding::call(
+        "kubernetes",
+        "list_resources_all",
+        req,
+        "the list all resources request",
+        "list all resources response into Kubernetes resource",
+    )
 }
 
 /// Describe the set of parameters used by the `get_resource` function.
@@ -111,14 +94,226 @@ where
     T: serde::de::DeserializeOwned + Clone,
 {
     let msg = serde_json::to_vec(req)
-        .map_err(|e| anyhow!("error serializing the get resource request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "kubernetes", "get_resource", &msg)
-        .map_err(|e| anyhow!("{}", e))?;
-
-    serde_json::from_slice(&response_raw).map_err(|e| {
-        anyhow!(
-            "error deserializing get resource response into Kubernetes resource: {:?}",
-            e
-        )
-    })
+        .map_err(|e| SdkError::serialization("the get resource request", e))?;
+    let response_raw = host_call("kubernetes", "get_resource", &msg)?;
+
+    serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("get resource response into Kubernetes resource", e))
+}
+
+/// Parameters of the chunked list functions: the list request, plus the
+/// Kubernetes pagination fields
+#[derive(Serialize, Debug)]
+struct ListChunkRequest<'a, R> {
+    #[serde(flatten)]
+    request: &'a R,
+    limit: u32,
+    #[serde(rename = "continue", skip_serializing_if = "Option::is_none")]
+    continue_token: Option<String>,
+}
+
+/// Iterator over the resources returned by the chunked list functions, see
+/// [`list_resources_by_namespace_chunked`] and [`list_all_resources_chunked`].
+///
+/// The resources are requested to the host in chunks, the next chunk is
+/// requested only once the resources of the previous one have been consumed.
+/// After an error is returned, the iteration stops.
+pub struct ListChunks<'a, R, T> {
+    operation: &'static str,
+    request: &'a R,
+    limit: u32,
+    // `None` once the last chunk has been received
+    next: Option<Option<String>>,
+    items: std::vec::IntoIter<T>,
+}
+
+impl<R, T> ListChunks<'_, R, T>
+where
+    R: Serialize,
+    T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
+{
+    fn fetch(&mut self, continue_token: Option<String>) -> Result<()> {
+        let list: k8s_openapi::List<T> = encoding::call(
+            "kubernetes",
+            self.operation,
+            &ListChunkRequest {
+                request: self.request,
+                limit: self.limit,
+                continue_token,
+            },
+            "the chunked list request",
+            "chunked list response into Kubernetes resource",
+        )?;
+
+        // hosts not supporting chunks return the whole list, without continue token
+        self.next = list.metadata.continue_.filter(|c| !c.is_empty()).map(Some);
+        self.items = list.items.into_iter();
+        Ok(())
+    }
+}
+
+impl<R, T> Iterator for ListChunks<'_, R, T>
+where
+    R: Serialize,
+    T: k8s_openapi::ListableResource + serde::de::DeserializeOwned + Clone,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.items.next() {
+                return Some(Ok(item));
+            }
+            let continue_token = self.next.take()?;
+            if let Err(e) = self.fetch(continue_token) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// Like [`list_resources_by_namespace`], but the resources are transferred
+/// from the host in chunks of at most `chunk_size` resources, which avoids
+/// allocating the whole list at once
+pub fn list_resources_by_namespace_chunked<T>(
+    req: &ListResourcesByNamespaceRequest,
+    chunk_size: u32,
+) -> ListChunks<'_, ListResourcesByNamespaceRequest, T> {
+    ListChunks {
+        operation: "list_resources_by_namespace",
+        request: req,
+        limit: chunk_size,
+        next: Some(None),
+        items: Vec::new().into_iter(),
+    }
+}
+
+/// Like [`list_all_resources`], but the resources are transferred from the
+/// host in chunks of at most `chunk_size` resources, which avoids allocating
+/// the whole list at once
+pub fn list_all_resources_chunked<T>(
+    req: &ListAllResourcesRequest,
+    chunk_size: u32,
+) -> ListChunks<'_, ListAllResourcesRequest, T> {
+    ListChunks {
+        operation: "list_resources_all",
+        request: req,
+        limit: chunk_size,
+        next: Some(None),
+        items: Vec::new().into_iter(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, HostClient, MockHostClient};
+    use k8s_openapi::api::core::v1::Pod;
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Host returning the pods named `pod-0`..`pod-<total>` in chunks
+    struct ChunkingHost {
+        total: usize,
+        requests: RefCell<Vec<serde_json::Value>>,
+    }
+
+    impl HostClient for ChunkingHost {
+        fn call(
+            &self,
+            _namespace: &str,
+            operation: &str,
+            payload: &[u8],
+        ) -> wapc_guest::CallResult {
+            if operation != "list_resources_by_namespace" {
+                return Err(format!("unknown operation {}", operation).into());
+            }
+            let request: serde_json::Value = serde_json::from_slice(payload)?;
+            self.requests.borrow_mut().push(request.clone());
+            let start: usize = request["continue"].as_str().unwrap_or("0").parse()?;
+            let end = (start + request["limit"].as_u64().unwrap() as usize).min(self.total);
+            let items: Vec<_> = (start..end)
+                .map(|i| json!({"metadata": {"name": format!("pod-{}", i)}}))
+                .collect();
+            let continue_token = (end < self.total).then(|| end.to_string());
+            Ok(serde_json::to_vec(&json!({
+                "apiVersion": "v1",
+                "kind": "PodList",
+                "metadata": {"continue": continue_token},
+                "items": items
+            }))?)
+        }
+    }
+
+    fn request() -> ListResourcesByNamespaceRequest {
+        ListResourcesByNamespaceRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            namespace: "default".to_string(),
+            label_selector: None,
+            field_selector: None,
+        }
+    }
+
+    #[test]
+    fn resources_are_fetched_in_chunks() {
+        let host = Rc::new(ChunkingHost {
+            total: 5,
+            requests: RefCell::new(Vec::new()),
+        });
+        let request = request();
+
+        let names: Vec<String> = with_host_client(host.clone(), || {
+            list_resources_by_namespace_chunked::<Pod>(&request, 2)
+                .map(|pod| pod.unwrap().metadata.name.unwrap())
+                .collect()
+        });
+
+        assert_eq!(names, ["pod-0", "pod-1", "pod-2", "pod-3", "pod-4"]);
+        let requests = host.requests.borrow();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0]["namespace"], "default");
+        assert!(requests[0].get("continue").is_none());
+        assert_eq!(requests[2]["continue"], "4");
+    }
+
+    #[test]
+    fn hosts_without_chunks_return_everything_at_once() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "kubernetes",
+            "list_resources_all",
+            &json!({
+                "apiVersion": "v1",
+                "kind": "PodList",
+                "metadata": {},
+                "items": [{"metadata": {"name": "a"}}, {"metadata": {"name": "b"}}]
+            }),
+        ));
+        let request = ListAllResourcesRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            label_selector: None,
+            field_selector: None,
+        };
+
+        with_host_client(client.clone(), || {
+            let pods: Vec<Pod> = list_all_resources_chunked(&request, 1)
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(pods.len(), 2);
+        });
+        client.assert_called_times("kubernetes", "list_resources_all", 1);
+    }
+
+    #[test]
+    fn errors_stop_the_iteration() {
+        let client = Rc::new(MockHostClient::new());
+        let request = request();
+        with_host_client(client, || {
+            let mut pods = list_resources_by_namespace_chunked::<Pod>(&request, 10);
+            assert!(pods.next().unwrap().is_err());
+            assert!(pods.next().is_none());
+        });
+    }
 }
@@ -0,0 +1,81 @@
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The kind of a metric
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum MetricKind {
+    /// A monotonic counter, the value is added to the current one
+    Counter,
+    /// A value that can go up and down, the value replaces the current one
+    Gauge,
+    /// A distribution of values, like latencies
+    Histogram,
+}
+
+/// A measurement of a metric, sent to the host
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    /// Name of the metric, e.g. `images_rejected_total`. It must match the
+    /// `[a-zA-Z_:][a-zA-Z0-9_:]*` regular expression
+    pub name: String,
+    /// Kind of the metric
+    pub kind: MetricKind,
+    /// The measured value
+    pub value: f64,
+    /// Labels of the sample. Label names must match the
+    /// `[a-zA-Z_][a-zA-Z0-9_]*` regular expression
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Request sent to the host by [`record`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MetricsRequest {
+    /// The samples to record
+    pub samples: Vec<MetricSample>,
+}
+
+fn valid_name(name: &str, allow_colon: bool) -> bool {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || c == '_' || (allow_colon && c == ':');
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit()) && name.chars().all(allowed)
+}
+
+impl MetricSample {
+    /// Ensure the names of the metric and of its labels are valid
+    pub fn validate(&self) -> Result<()> {
+        if !valid_name(&self.name, true) {
+            return Err(SdkError::InvalidInput(format!(
+                "invalid metric name `{}`",
+                self.name
+            )));
+        }
+        if let Some(label) = self.labels.keys().find(|l| !valid_name(l, false)) {
+            return Err(SdkError::InvalidInput(format!(
+                "invalid label name `{}` of metric `{}`",
+                label, self.name
+            )));
+        }
+        if !self.value.is_finite() {
+            return Err(SdkError::InvalidInput(format!(
+                "invalid value {} of metric `{}`",
+                self.value, self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Send the given samples to the host, which exposes them together with the
+/// metrics of the policy server
+pub fn record(samples: Vec<MetricSample>) -> Result<()> {
+    for sample in &samples {
+        sample.validate()?;
+    }
+    let msg = serde_json::to_vec(&MetricsRequest { samples })
+        .map_err(|e| SdkError::serialization("the metrics request", e))?;
+    host_call("metrics", "v1/record", &msg)?;
+
+    Ok(())
+}
@@ -1,16 +1,53 @@
-use crate::host_capabilities::verification::{KeylessInfo, KeylessPrefixInfo};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+cfg_if::cfg_if! {
+    if #[cfg(feature = "verification")] {
+        use crate::error::SdkError;
+        use crate::host_capabilities::verification::{KeylessInfo, KeylessPrefixInfo};
+        use serde::{Deserialize, Serialize};
+        use std::collections::HashMap;
+        use std::convert::TryFrom;
+    }
+}
 
+pub mod batch;
+pub mod client;
+#[cfg(feature = "crypto")]
 pub mod crypto;
+pub mod data;
+pub mod encoding;
+pub mod events;
+pub mod fallback;
+pub mod identity;
 #[cfg(feature = "cluster-context")]
 pub mod kubernetes;
+pub mod metrics;
+#[cfg(feature = "net")]
 pub mod net;
+#[cfg(feature = "oci")]
 pub mod oci;
+pub mod policy_context;
+pub mod protocol_version;
+#[cfg(feature = "oci")]
+pub mod registry;
+pub mod replay;
+pub mod retry;
+pub mod scan;
+#[cfg(feature = "host-call-spans")]
+pub mod spans;
+pub mod time;
+#[cfg(feature = "verification")]
 pub mod verification;
+pub mod webhook;
+
+pub use fallback::{capability_or, FailMode, Fallback};
+pub use retry::{with_retry, RetryPolicy};
 
 /// SigstoreVerificationInputV1 is used for the v1/verify callback
+///
+/// New variants can be added without a major release of the SDK, hence the
+/// enum is `non_exhaustive`. Use the constructor functions to create new values.
+#[cfg(feature = "verification")]
 #[derive(Serialize, Deserialize, Debug)]
+#[non_exhaustive]
 pub enum SigstoreVerificationInputV1 {
     /// Require the verification of the manifest digest of an OCI object (be
     /// it an image or anything else that can be stored into an OCI registry)
@@ -38,8 +75,14 @@ pub enum SigstoreVerificationInputV1 {
 
 /// SigstoreVerificationInputV2 is used for the v2/verify callback
 /// From now on we use serde internally tagged.
+///
+/// New verification modes can be added without a major release of the SDK,
+/// hence the enum is `non_exhaustive`. Use the constructor functions to
+/// create new values.
+#[cfg(feature = "verification")]
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "type")]
+#[non_exhaustive]
 pub enum SigstoreVerificationInputV2 {
     /// Require the verification of the manifest digest of an OCI object (be
     /// it an image or anything else that can be stored into an OCI registry)
@@ -112,6 +155,146 @@ pub enum SigstoreVerificationInputV2 {
     },
 }
 
+#[cfg(feature = "verification")]
+impl SigstoreVerificationInputV1 {
+    /// Verification using public keys
+    pub fn pub_key(
+        image: impl Into<String>,
+        pub_keys: Vec<String>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV1::SigstorePubKeyVerify {
+            image: image.into(),
+            pub_keys,
+            annotations,
+        }
+    }
+
+    /// Verification using keyless signatures, with exact match of issuer and subject
+    pub fn keyless(
+        image: impl Into<String>,
+        keyless: Vec<KeylessInfo>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV1::SigstoreKeylessVerify {
+            image: image.into(),
+            keyless,
+            annotations,
+        }
+    }
+}
+
+#[cfg(feature = "verification")]
+impl SigstoreVerificationInputV2 {
+    /// Verification using public keys
+    pub fn pub_key(
+        image: impl Into<String>,
+        pub_keys: Vec<String>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV2::SigstorePubKeyVerify {
+            image: image.into(),
+            pub_keys,
+            annotations,
+        }
+    }
+
+    /// Verification using keyless signatures, with exact match of issuer and subject
+    pub fn keyless(
+        image: impl Into<String>,
+        keyless: Vec<KeylessInfo>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV2::SigstoreKeylessVerify {
+            image: image.into(),
+            keyless,
+            annotations,
+        }
+    }
+
+    /// Verification using keyless signatures, where the subject is a URL prefix
+    pub fn keyless_prefix(
+        image: impl Into<String>,
+        keyless_prefix: Vec<KeylessPrefixInfo>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV2::SigstoreKeylessPrefixVerify {
+            image: image.into(),
+            keyless_prefix,
+            annotations,
+        }
+    }
+
+    /// Verification of keyless signatures produced by GitHub Actions
+    pub fn github_actions(
+        image: impl Into<String>,
+        owner: impl Into<String>,
+        repo: Option<String>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV2::SigstoreGithubActionsVerify {
+            image: image.into(),
+            owner: owner.into(),
+            repo,
+            annotations,
+        }
+    }
+
+    /// Verification using a user provided certificate. See
+    /// [`verification::verify_certificate`] for the meaning of the arguments
+    pub fn certificate(
+        image: impl Into<String>,
+        certificate: Vec<u8>,
+        certificate_chain: Option<Vec<Vec<u8>>>,
+        require_rekor_bundle: bool,
+        annotations: Option<HashMap<String, String>>,
+    ) -> Self {
+        SigstoreVerificationInputV2::SigstoreCertificateVerify {
+            image: image.into(),
+            certificate,
+            certificate_chain,
+            require_rekor_bundle,
+            annotations,
+        }
+    }
+}
+
+#[cfg(feature = "verification")]
+impl TryFrom<SigstoreVerificationInputV2> for SigstoreVerificationInputV1 {
+    type Error = SdkError;
+
+    /// Convert a v2 verification request into a v1 one. This is used to
+    /// interact with hosts that do not implement the `v2/verify` capability.
+    /// Only the verification modes known by v1 can be converted.
+    fn try_from(input: SigstoreVerificationInputV2) -> Result<Self, Self::Error> {
+        match input {
+            SigstoreVerificationInputV2::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            } => Ok(SigstoreVerificationInputV1::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            }),
+            SigstoreVerificationInputV2::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            } => Ok(SigstoreVerificationInputV1::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            }),
+            _ => Err(SdkError::UnsupportedCapability {
+                capability: "oci.v1/verify".to_string(),
+                message: "verification mode not supported".to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "crypto")]
 pub mod crypto_v1 {
     use crate::host_capabilities::crypto::Certificate;
     use serde::{Deserialize, Serialize};
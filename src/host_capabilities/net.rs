@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
@@ -13,11 +14,11 @@ pub struct LookupResponse {
 pub fn lookup_host(host: &str) -> Result<LookupResponse> {
     let req = json!(host);
     let msg = serde_json::to_vec(&req)
-        .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "net", "v1/dns_lookup_host", &msg)
-        .map_err(|e| anyhow!("error invoking wapc net.dns_lookup_host : {:?}", e))?;
+        .map_err(|e| SdkError::serialization("the DNS lookup request", e))?;
+    let response_raw = host_call("net", "v1/dns_lookup_host", &msg)?;
 
-    let response: LookupResponse = serde_json::from_slice(&response_raw)?;
+    let response: LookupResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the DNS lookup response", e))?;
 
     Ok(response)
 }
@@ -1,9 +1,8 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
 use oci_spec::image::{ImageConfiguration, ImageIndex, ImageManifest};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-#[cfg(test)]
-use tests::mock_wapc as wapc_guest;
 
 /// Response to manifest digest request
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -14,6 +13,7 @@ pub struct ManifestDigestResponse {
 /// An image, or image index, OCI manifest
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(untagged)]
+#[non_exhaustive]
 pub enum OciManifestResponse {
     //Using  box here to make linter happy. It complains about the different sizes between the two
     //enum elements. See more here:
@@ -36,11 +36,11 @@ pub struct OciManifestAndConfigResponse {
 pub fn get_manifest_digest(image: &str) -> Result<ManifestDigestResponse> {
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
-        .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v1/manifest_digest", &msg)
-        .map_err(|e| anyhow!("error invoking wapc oci.manifest_digest: {:?}", e))?;
+        .map_err(|e| SdkError::serialization("the manifest digest request", e))?;
+    let response_raw = host_call("oci", "v1/manifest_digest", &msg)?;
 
-    let response: ManifestDigestResponse = serde_json::from_slice(&response_raw)?;
+    let response: ManifestDigestResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the manifest digest response", e))?;
 
     Ok(response)
 }
@@ -49,10 +49,10 @@ pub fn get_manifest_digest(image: &str) -> Result<ManifestDigestResponse> {
 pub fn get_manifest(image: &str) -> Result<OciManifestResponse> {
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
-        .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v1/oci_manifest", &msg)
-        .map_err(|e| anyhow!("error invoking wapc oci.manifest_digest: {:?}", e))?;
-    let response: OciManifestResponse = serde_json::from_slice(&response_raw)?;
+        .map_err(|e| SdkError::serialization("the OCI manifest request", e))?;
+    let response_raw = host_call("oci", "v1/oci_manifest", &msg)?;
+    let response: OciManifestResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the OCI manifest response", e))?;
     Ok(response)
 }
 
@@ -60,12 +60,11 @@ pub fn get_manifest(image: &str) -> Result<OciManifestResponse> {
 pub fn get_manifest_and_config(image: &str) -> Result<OciManifestAndConfigResponse> {
     let req = json!(image);
     let msg = serde_json::to_vec(&req)
-        .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw =
-        wapc_guest::host_call("kubewarden", "oci", "v1/oci_manifest_config", &msg)
-            .map_err(|e| anyhow!("error invoking wapc oci.manifest_and_config: {:?}", e))?;
+        .map_err(|e| SdkError::serialization("the OCI manifest and config request", e))?;
+    let response_raw = host_call("oci", "v1/oci_manifest_config", &msg)?;
 
-    let response: OciManifestAndConfigResponse = serde_json::from_slice(&response_raw)?;
+    let response: OciManifestAndConfigResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the OCI manifest and config response", e))?;
 
     Ok(response)
 }
@@ -73,24 +72,15 @@ pub fn get_manifest_and_config(image: &str) -> Result<OciManifestAndConfigRespon
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockall::automock;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
     use oci_spec::image::{
         Arch, ConfigBuilder, Descriptor, DescriptorBuilder, History, HistoryBuilder,
         ImageConfigurationBuilder, ImageIndexBuilder, ImageManifestBuilder, MediaType, Os,
         PlatformBuilder, RootFsBuilder, SCHEMA_VERSION,
     };
-    use serial_test::serial;
-
-    #[automock()]
-    pub mod wapc {
-        use wapc_guest::CallResult;
+    use std::rc::Rc;
 
-        // needed for creating mocks
-        #[allow(dead_code)]
-        pub fn host_call(_binding: &str, _ns: &str, _op: &str, _msg: &[u8]) -> CallResult {
-            Ok(vec![u8::from(true)])
-        }
-    }
+    const IMAGE: &str = "ghcr.io/kubewarden/policy-server:latest";
     fn create_oci_index_image_manifest() -> ImageIndex {
         let manifests: Vec<Descriptor> = [
             (
@@ -172,12 +162,12 @@ mod tests {
         ]
         .iter()
         .map(|l| {
-            return DescriptorBuilder::default()
+            DescriptorBuilder::default()
                 .media_type(MediaType::ImageLayerGzip)
                 .size(l.0)
                 .digest(l.1.to_owned())
                 .build()
-                .expect("build manifest");
+                .expect("build manifest")
         })
         .collect();
 
@@ -259,22 +249,14 @@ mod tests {
             .expect("build image configuration")
     }
 
-    // these tests need to run sequentially because mockall creates a global context to create the mocks
-    #[serial]
     #[test]
     fn verify_oci_image_manifest() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .once()
-            .withf(|binding: &str, ns: &str, op: &str, msg: &[u8]| {
-                binding == "kubewarden"
-                    && ns == "oci"
-                    && op == "v1/oci_manifest"
-                    && std::str::from_utf8(msg).unwrap()
-                        == "\"ghcr.io/kubewarden/policy-server:latest\""
-            })
-            .returning(|_, _, _, _| Ok(serde_json::to_vec(&create_oci_image_manifest()).unwrap()));
-        let response = get_manifest("ghcr.io/kubewarden/policy-server:latest")
+        let client = Rc::new(MockHostClient::new().respond(
+            "oci",
+            "v1/oci_manifest",
+            &create_oci_image_manifest(),
+        ));
+        let response = with_host_client(client.clone(), || get_manifest(IMAGE))
             .expect("failed to get oci manifest reponse");
         match response {
             OciManifestResponse::Image(image) => {
@@ -282,26 +264,20 @@ mod tests {
             }
             OciManifestResponse::ImageIndex(_) => panic!("Invalid oci manifest type returned"),
         }
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].payload_json().unwrap(), IMAGE);
     }
 
-    // these tests need to run sequentially because mockall creates a global context to create the mocks
-    #[serial]
     #[test]
     fn verify_oci_index_image_manifest() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .once()
-            .withf(|binding: &str, ns: &str, op: &str, msg: &[u8]| {
-                binding == "kubewarden"
-                    && ns == "oci"
-                    && op == "v1/oci_manifest"
-                    && std::str::from_utf8(msg).unwrap()
-                        == "\"ghcr.io/kubewarden/policy-server:latest\""
-            })
-            .returning(|_, _, _, _| {
-                Ok(serde_json::to_vec(&create_oci_index_image_manifest()).unwrap())
-            });
-        let response = get_manifest("ghcr.io/kubewarden/policy-server:latest")
+        let client = Rc::new(MockHostClient::new().respond(
+            "oci",
+            "v1/oci_manifest",
+            &create_oci_index_image_manifest(),
+        ));
+        let response = with_host_client(client.clone(), || get_manifest(IMAGE))
             .expect("failed to get oci manifest reponse");
         match response {
             OciManifestResponse::Image(_) => panic!("Invalid oci manifest type returned"),
@@ -309,35 +285,31 @@ mod tests {
                 assert_eq!(*image, create_oci_index_image_manifest());
             }
         }
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].payload_json().unwrap(), IMAGE);
     }
 
-    // these tests need to run sequentially because mockall creates a global context to create the mocks
-    #[serial]
     #[test]
     fn verify_oci_image_manifest_and_config() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .once()
-            .withf(|binding: &str, ns: &str, op: &str, msg: &[u8]| {
-                binding == "kubewarden"
-                    && ns == "oci"
-                    && op == "v1/oci_manifest_config"
-                    && std::str::from_utf8(msg).unwrap()
-                        == "\"ghcr.io/kubewarden/policy-server:latest\""
-            })
-            .returning(|_, _, _, _| {
-                let response_raw = serde_json::to_vec(&OciManifestAndConfigResponse {
-                    manifest: create_oci_image_manifest(),
-                    digest: "sha256:983".to_owned(),
-                    config: create_oci_image_configuration(),
-                })
-                .expect("serialize response");
-                Ok(response_raw)
-            });
-        let response = get_manifest_and_config("ghcr.io/kubewarden/policy-server:latest")
+        let client = Rc::new(MockHostClient::new().respond(
+            "oci",
+            "v1/oci_manifest_config",
+            &OciManifestAndConfigResponse {
+                manifest: create_oci_image_manifest(),
+                digest: "sha256:983".to_owned(),
+                config: create_oci_image_configuration(),
+            },
+        ));
+        let response = with_host_client(client.clone(), || get_manifest_and_config(IMAGE))
             .expect("failed to get oci manifest reponse");
         assert_eq!(response.config, create_oci_image_configuration());
         assert_eq!(response.manifest, create_oci_image_manifest());
         assert_eq!(response.digest, "sha256:983");
+
+        let calls = client.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].payload_json().unwrap(), IMAGE);
     }
 }
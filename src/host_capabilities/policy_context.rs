@@ -0,0 +1,125 @@
+//! Obtain the identity of the policy being evaluated.
+//!
+//! The same policy module can be deployed many times, both as a namespaced
+//! `AdmissionPolicy` and as a `ClusterAdmissionPolicy`. Shared policy code
+//! can adjust its behavior to the deployment it's running in:
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::policy_context;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "host",
+//!     "v1/policy_context",
+//!     &json!({"name": "psa", "kind": "ClusterAdmissionPolicy"}),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let context = policy_context::fetch().unwrap();
+//!     // the kube-system namespace is skipped only by the cluster-wide policies
+//!     let skip_kube_system = context.is_cluster_scoped();
+//!     assert!(skip_kube_system);
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+static POLICY_CONTEXT: OnceLock<PolicyContext> = OnceLock::new();
+
+/// The kind of the resource deploying the policy
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PolicyKind {
+    /// A namespaced policy, evaluating only the requests of its namespace
+    AdmissionPolicy,
+    /// A cluster-wide policy
+    ClusterAdmissionPolicy,
+}
+
+/// The identity of the policy being evaluated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PolicyContext {
+    /// Name of the policy resource
+    pub name: String,
+    /// Kind of the policy resource
+    pub kind: PolicyKind,
+    /// The namespace of the policy, set only for `AdmissionPolicy`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+impl PolicyContext {
+    /// Returns true when the policy is a `ClusterAdmissionPolicy`
+    pub fn is_cluster_scoped(&self) -> bool {
+        self.kind == PolicyKind::ClusterAdmissionPolicy
+    }
+}
+
+/// Returns the identity of the policy being evaluated.
+///
+/// The value is obtained from the host the first time this function
+/// succeeds, and then cached for the lifetime of the policy instance.
+pub fn policy_context() -> Result<PolicyContext> {
+    if let Some(context) = POLICY_CONTEXT.get() {
+        return Ok(context.clone());
+    }
+    let context = fetch()?;
+    Ok(POLICY_CONTEXT.get_or_init(|| context).clone())
+}
+
+/// Query the host for the identity of the policy, bypassing the cache used
+/// by [`policy_context`]
+pub fn fetch() -> Result<PolicyContext> {
+    let response_raw = host_call("host", "v1/policy_context", &[])?;
+    let context: PolicyContext = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the policy context", e))?;
+    if context.kind == PolicyKind::AdmissionPolicy && context.namespace.is_none() {
+        return Err(SdkError::InvalidInput(format!(
+            "the AdmissionPolicy {} has no namespace",
+            context.name
+        )));
+    }
+    Ok(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    #[test]
+    fn fetch_namespaced_policy() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "host",
+            "v1/policy_context",
+            &json!({"name": "no-latest", "kind": "AdmissionPolicy", "namespace": "team-a"}),
+        ));
+
+        let context = with_host_client(client, fetch).unwrap();
+
+        assert!(!context.is_cluster_scoped());
+        assert_eq!(context.name, "no-latest");
+        assert_eq!(context.namespace.as_deref(), Some("team-a"));
+    }
+
+    #[test]
+    fn invalid_contexts() {
+        for response in [
+            json!({"name": "no-latest", "kind": "AdmissionPolicy"}),
+            json!({"name": "no-latest", "kind": "Policy"}),
+        ] {
+            let client =
+                Rc::new(MockHostClient::new().respond("host", "v1/policy_context", &response));
+            assert!(with_host_client(client, fetch).is_err(), "{}", response);
+        }
+
+        let client = Rc::new(MockHostClient::new());
+        assert!(with_host_client(client, fetch).is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use crate::metadata::ProtocolVersion;
+use std::cell::Cell;
+
+thread_local! {
+    static HOST_PROTOCOL_VERSION: Cell<Option<ProtocolVersion>> = const { Cell::new(None) };
+}
+
+/// Returns the protocol version implemented by the host running the policy,
+/// as learned from the host capabilities invoked so far.
+///
+/// Hosts don't advertise their version, hence `None` is returned until a
+/// capability answers in a way that tells it. For example,
+/// [`verify_with_fallback`](crate::host_capabilities::verification::verify_with_fallback)
+/// finds out the host implements [`ProtocolVersion::V2`] when the `v2/verify`
+/// capability succeeds, and [`ProtocolVersion::V1`] when the host reports it
+/// as unsupported. Transient failures are not recorded.
+pub fn host_protocol_version() -> Option<ProtocolVersion> {
+    HOST_PROTOCOL_VERSION.with(|version| version.get())
+}
+
+/// Record the protocol version implemented by the host, for the lifetime of
+/// the policy instance
+#[cfg_attr(not(feature = "verification"), allow(dead_code))]
+pub(crate) fn set_host_protocol_version(version: ProtocolVersion) {
+    HOST_PROTOCOL_VERSION.with(|cached| cached.set(Some(version)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_is_unknown_until_recorded() {
+        assert_eq!(host_protocol_version(), None);
+
+        set_host_protocol_version(ProtocolVersion::V1);
+        assert_eq!(host_protocol_version(), Some(ProtocolVersion::V1));
+    }
+}
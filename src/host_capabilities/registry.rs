@@ -0,0 +1,191 @@
+//! Obtain short-lived pull tokens for the registries of the cloud providers.
+//!
+//! The host exchanges the identity of the node, or of the workload running
+//! the policy server, for a registry token: no long-lived credential has to
+//! be stored inside of the policy settings. The supported registries are:
+//!
+//! * Amazon ECR, `<account>.dkr.ecr.<region>.amazonaws.com`
+//! * Google Container Registry and Artifact Registry, `gcr.io` and
+//!   `<region>-docker.pkg.dev`
+//! * Azure Container Registry, `<name>.azurecr.io`
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::registry::{self, Provider};
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let image = "123456789012.dkr.ecr.eu-west-1.amazonaws.com/app:1.0";
+//! assert_eq!(Provider::detect(image), Some(Provider::Ecr));
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "registry",
+//!     "v1/token",
+//!     &json!({"username": "AWS", "password": "c2VjcmV0", "expiresAt": "2030-01-01T00:00:00Z"}),
+//! ));
+//! with_host_client(client, || {
+//!     let token = registry::token(image).unwrap();
+//!     assert_eq!(token.username, "AWS");
+//! });
+//! ```
+use crate::constraints::image_registry;
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The cloud provider owning a registry
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum Provider {
+    /// Amazon Elastic Container Registry
+    Ecr,
+    /// Google Container Registry and Artifact Registry
+    Gcr,
+    /// Azure Container Registry
+    Acr,
+}
+
+impl Provider {
+    /// The provider of the registry of an image reference, or of a registry
+    /// host. Returns `None` for the registries not owned by a cloud provider
+    pub fn detect(image: &str) -> Option<Provider> {
+        let host = registry_host(image).split(':').next().unwrap_or_default();
+
+        if host.contains(".dkr.ecr.") && host.ends_with(".amazonaws.com") {
+            Some(Provider::Ecr)
+        } else if host == "gcr.io" || host.ends_with(".gcr.io") || host.ends_with("-docker.pkg.dev")
+        {
+            Some(Provider::Gcr)
+        } else if host.ends_with(".azurecr.io") {
+            Some(Provider::Acr)
+        } else {
+            None
+        }
+    }
+}
+
+/// The registry of an image reference, a bare host like `gcr.io` is
+/// already a registry
+fn registry_host(image: &str) -> &str {
+    if image.contains('/') {
+        image_registry(image)
+    } else {
+        image
+    }
+}
+
+/// Request sent to the host by [`token`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TokenRequest {
+    /// The registry host, e.g. `myregistry.azurecr.io`
+    pub registry: String,
+    /// The provider of the registry
+    pub provider: Provider,
+}
+
+/// A registry token, to be used as the password of the basic
+/// authentication. The password is not part of the `Debug` output
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryToken {
+    /// The user name, fixed by some providers, e.g. `AWS` for ECR
+    pub username: String,
+    /// The token
+    pub password: String,
+    /// Expiration of the token, RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl fmt::Debug for RegistryToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RegistryToken")
+            .field("username", &self.username)
+            .field("password", &crate::logging::REDACTED)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Obtain a pull token for the registry of `image`, which can also be a bare
+/// registry host. Fails when the registry doesn't belong to a supported
+/// cloud provider
+pub fn token(image: &str) -> Result<RegistryToken> {
+    let provider = Provider::detect(image).ok_or_else(|| {
+        SdkError::InvalidInput(format!(
+            "{} is not hosted by a supported cloud registry",
+            image
+        ))
+    })?;
+    let req = TokenRequest {
+        registry: registry_host(image).to_string(),
+        provider,
+    };
+    let msg = serde_json::to_vec(&req)
+        .map_err(|e| SdkError::serialization("the registry token request", e))?;
+    let response_raw = host_call("registry", "v1/token", &msg)?;
+
+    let response: RegistryToken = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the registry token response", e))?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    #[test]
+    fn detect_providers() {
+        for (image, provider) in [
+            (
+                "123456789012.dkr.ecr.us-east-1.amazonaws.com/app",
+                Some(Provider::Ecr),
+            ),
+            ("gcr.io/project/app:1.0", Some(Provider::Gcr)),
+            ("eu.gcr.io", Some(Provider::Gcr)),
+            (
+                "europe-west1-docker.pkg.dev/project/repo/app",
+                Some(Provider::Gcr),
+            ),
+            ("myregistry.azurecr.io/app@sha256:abcd", Some(Provider::Acr)),
+            ("myregistry.azurecr.io:443/app", Some(Provider::Acr)),
+            ("nginx:latest", None),
+            ("ghcr.io/kubewarden/policy-server", None),
+            ("amazonaws.com/app", None),
+        ] {
+            assert_eq!(Provider::detect(image), provider, "{}", image);
+        }
+    }
+
+    #[test]
+    fn request_tokens() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "registry",
+            "v1/token",
+            &json!({"username": "00000000-0000-0000-0000-000000000000", "password": "secret"}),
+        ));
+
+        let acr =
+            with_host_client(client.clone(), || token("myregistry.azurecr.io/app:1.0")).unwrap();
+        assert_eq!(acr.password, "secret");
+        assert!(!format!("{:?}", acr).contains("secret"));
+
+        let calls = client.calls_to("registry", "v1/token");
+        let request: TokenRequest = serde_json::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(
+            request,
+            TokenRequest {
+                registry: "myregistry.azurecr.io".to_string(),
+                provider: Provider::Acr,
+            }
+        );
+
+        assert!(with_host_client(client.clone(), || token("docker.io/nginx")).is_err());
+        assert_eq!(client.calls().len(), 1);
+    }
+}
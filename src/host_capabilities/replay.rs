@@ -0,0 +1,229 @@
+//! Record the host capability calls made by a policy and replay them later.
+//!
+//! A [`RecordingHostClient`] wraps another [`HostClient`] and keeps track of
+//! all the requests and responses going through it. Once saved to a file, the
+//! recording can be loaded by a [`ReplayHostClient`] to test context-aware
+//! policies against the captured cluster state, without a Kubewarden host.
+//!
+//! Capability payloads are stored as JSON, which keeps the recordings easy
+//! to review and to edit by hand.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, WapcHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::replay::{RecordingHostClient, ReplayHostClient};
+//! use std::rc::Rc;
+//!
+//! // while running against a real host
+//! let recorder = Rc::new(RecordingHostClient::new(WapcHostClient));
+//! with_host_client(recorder.clone(), || {
+//!     // evaluate the policy
+//! });
+//! recorder.save("test_data/recording.json").unwrap();
+//!
+//! // inside of the unit tests
+//! let replay = ReplayHostClient::from_file("test_data/recording.json").unwrap();
+//! with_host_client(Rc::new(replay), || {
+//!     // evaluate the policy
+//! });
+//! ```
+use super::client::HostClient;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A host capability call, together with its outcome
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    /// Namespace of the capability
+    pub namespace: String,
+    /// Operation of the capability
+    pub operation: String,
+    /// The payload sent to the host
+    pub request: Value,
+    /// The response of the host, when the call succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    /// True when the response is not JSON and has been stored as a string
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub raw_response: bool,
+    /// The error returned by the host, when the call failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn decode_payload(payload: &[u8]) -> (Value, bool) {
+    match serde_json::from_slice(payload) {
+        Ok(value) => (value, false),
+        Err(_) => (
+            Value::String(String::from_utf8_lossy(payload).into_owned()),
+            true,
+        ),
+    }
+}
+
+/// A [`HostClient`] that forwards all the calls to another client and
+/// records them
+#[derive(Debug)]
+pub struct RecordingHostClient<C: HostClient> {
+    inner: C,
+    recordings: Mutex<Vec<Recording>>,
+}
+
+impl<C: HostClient> RecordingHostClient<C> {
+    /// Record all the calls handled by `inner`
+    pub fn new(inner: C) -> Self {
+        RecordingHostClient {
+            inner,
+            recordings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The calls recorded so far
+    pub fn recordings(&self) -> Vec<Recording> {
+        self.recordings.lock().unwrap().clone()
+    }
+
+    /// Write the calls recorded so far to the given file, as JSON
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let contents = serde_json::to_string_pretty(&self.recordings())?;
+        std::fs::write(path, contents)
+            .with_context(|| format!("cannot write recording to {}", path.display()))
+    }
+}
+
+impl<C: HostClient> HostClient for RecordingHostClient<C> {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        let result = self.inner.call(namespace, operation, payload);
+
+        let (request, _) = decode_payload(payload);
+        let mut recording = Recording {
+            namespace: namespace.to_string(),
+            operation: operation.to_string(),
+            request,
+            response: None,
+            raw_response: false,
+            error: None,
+        };
+        match &result {
+            Ok(response) => {
+                let (response, raw) = decode_payload(response);
+                recording.response = Some(response);
+                recording.raw_response = raw;
+            }
+            Err(e) => recording.error = Some(e.to_string()),
+        }
+        self.recordings.lock().unwrap().push(recording);
+
+        result
+    }
+}
+
+/// A [`HostClient`] answering with previously recorded responses.
+///
+/// Calls are matched by namespace, operation and request payload, the latter
+/// being compared as JSON. Calls without a matching recording fail.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayHostClient {
+    recordings: Vec<Recording>,
+}
+
+impl ReplayHostClient {
+    /// Replay the given recordings
+    pub fn new(recordings: Vec<Recording>) -> Self {
+        ReplayHostClient { recordings }
+    }
+
+    /// Replay the recordings stored inside of the given file, as written by
+    /// [`RecordingHostClient::save`]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read recording {}", path.display()))?;
+        let recordings = serde_json::from_str(&contents)
+            .with_context(|| format!("cannot parse recording {}", path.display()))?;
+        Ok(ReplayHostClient::new(recordings))
+    }
+}
+
+impl HostClient for ReplayHostClient {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        let (request, _) = decode_payload(payload);
+        let recording = self
+            .recordings
+            .iter()
+            .find(|r| r.namespace == namespace && r.operation == operation && r.request == request)
+            .ok_or_else(|| {
+                format!(
+                    "no recording found for capability {}.{} with request {}",
+                    namespace, operation, request
+                )
+            })?;
+
+        match (&recording.response, &recording.error) {
+            (_, Some(error)) => Err(error.clone().into()),
+            (Some(Value::String(response)), None) if recording.raw_response => {
+                Ok(response.as_bytes().to_vec())
+            }
+            (Some(response), None) => Ok(serde_json::to_vec(response)?),
+            (None, None) => Err(format!(
+                "the recording of capability {}.{} has neither a response nor an error",
+                namespace, operation
+            )
+            .into()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{host_call, with_host_client, MockHostClient};
+    use crate::host_capabilities::net::{lookup_host, LookupResponse};
+    use std::rc::Rc;
+
+    #[test]
+    fn record_and_replay() {
+        let mock = MockHostClient::new()
+            .respond(
+                "net",
+                "v1/dns_lookup_host",
+                &LookupResponse {
+                    ips: vec!["10.0.0.1".to_string()],
+                },
+            )
+            .respond_raw("oci", "v1/raw", b"not json".to_vec())
+            .fail("oci", "v1/manifest_digest", "registry unreachable");
+        let recorder = Rc::new(RecordingHostClient::new(mock));
+
+        with_host_client(recorder.clone(), || {
+            lookup_host("example.com").unwrap();
+            assert!(host_call("oci", "v1/manifest_digest", b"\"busybox\"").is_err());
+            host_call("oci", "v1/raw", b"{}").unwrap();
+        });
+
+        let recordings = recorder.recordings();
+        assert_eq!(recordings.len(), 3);
+        assert_eq!(recordings[0].request, "example.com");
+        assert_eq!(recordings[1].error.as_deref(), Some("registry unreachable"));
+        assert!(recordings[2].raw_response);
+
+        let path = std::env::temp_dir().join(format!("recording-{}.json", std::process::id()));
+        recorder.save(&path).unwrap();
+        let replay = Rc::new(ReplayHostClient::from_file(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        with_host_client(replay, || {
+            assert_eq!(lookup_host("example.com").unwrap().ips, vec!["10.0.0.1"]);
+            assert!(lookup_host("kubewarden.io").is_err());
+            let error = host_call("oci", "v1/manifest_digest", b"\"busybox\"").unwrap_err();
+            assert!(error.to_string().ends_with("registry unreachable"));
+            assert_eq!(host_call("oci", "v1/raw", b"{}").unwrap(), b"not json");
+        });
+    }
+}
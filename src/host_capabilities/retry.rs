@@ -0,0 +1,320 @@
+//! Retry the host capabilities failing with a transient error.
+//!
+//! A registry or a DNS server that is briefly unreachable should not turn
+//! into a rejected workload. [`with_retry`] invokes the capability again when
+//! the host reports a retryable error (see [`SdkError::is_retryable`](crate::error::SdkError::is_retryable)),
+//! waiting an exponentially growing delay between the attempts. The delays
+//! are randomized ("full jitter"), so that many policies do not retry at the
+//! same time.
+//!
+//! Retries never outlive the [deadline](crate::deadline) of the evaluation:
+//! the last error is returned when the next attempt would happen after it.
+//! On targets without a clock, like `wasm32-unknown-unknown`, the attempts
+//! are performed without waiting.
+//!
+//! ```
+//! # #[cfg(feature = "net")]
+//! # {
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::{net, with_retry, RetryPolicy};
+//! use std::rc::Rc;
+//! use std::time::Duration;
+//!
+//! let client = Rc::new(MockHostClient::new().fail(
+//!     "net",
+//!     "v1/dns_lookup_host",
+//!     r#"{"code": "unavailable", "message": "DNS server unreachable", "retryable": true}"#,
+//! ));
+//!
+//! let policy = RetryPolicy::new(3).initial_delay(Duration::from_millis(1));
+//! let result = with_host_client(client.clone(), || {
+//!     with_retry(&policy, || net::lookup_host("example.com"))
+//! });
+//! assert!(result.unwrap_err().is_retryable());
+//! client.assert_called_times("net", "v1/dns_lookup_host", 3);
+//! # }
+//! ```
+use crate::error::Result;
+use std::cell::Cell;
+use std::time::Duration;
+
+/// How many times, and how often, an operation is attempted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, the first one included
+    pub max_attempts: u32,
+    /// The delay before the first retry
+    pub initial_delay: Duration,
+    /// The upper bound of the delays
+    pub max_delay: Duration,
+    /// The factor applied to the delay after each retry
+    pub multiplier: f64,
+    /// Randomize the delays between zero and their computed value
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, waiting up to 100ms and then up to 200ms
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The default policy, performing at most `max_attempts` attempts
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy {
+            max_attempts,
+            ..Default::default()
+        }
+    }
+
+    /// Set the delay before the first retry
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Set the upper bound of the delays
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set the factor applied to the delay after each retry
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Enable or disable the randomization of the delays
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The delay before the given retry, starting from 1, without jitter
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(retry.saturating_sub(1) as i32);
+        self.initial_delay
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_delay)
+    }
+}
+
+/// Invoke `operation` until it succeeds, it fails with an error that is not
+/// retryable, or the attempts allowed by `policy` are exhausted. The error of
+/// the last attempt is returned
+pub fn with_retry<T, F>(policy: &RetryPolicy, mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match operation() {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        if !error.is_retryable() || attempt >= policy.max_attempts {
+            return Err(error);
+        }
+
+        let mut delay = policy.backoff(attempt);
+        if policy.jitter {
+            delay = jittered(delay, next_random());
+        }
+        if !fits_deadline(delay) {
+            return Err(error);
+        }
+        slog::debug!(
+            crate::logging::logger(),
+            "retrying host capability";
+            "attempt" => attempt + 1,
+            "delay_ms" => delay.as_millis() as u64,
+            "error" => error.to_string()
+        );
+        sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Whether waiting `delay` leaves time for another attempt before the
+/// deadline of the evaluation
+fn fits_deadline(delay: Duration) -> bool {
+    crate::deadline::current().is_none_or(|deadline| deadline.remaining() > delay)
+}
+
+fn sleep(delay: Duration) {
+    if crate::clock::AVAILABLE && !delay.is_zero() {
+        std::thread::sleep(delay);
+    }
+}
+
+/// A random delay between zero and `delay`, `random` being in `[0, 1)`
+fn jittered(delay: Duration, random: f64) -> Duration {
+    delay.mul_f64(random.clamp(0.0, 1.0))
+}
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let nanos = if crate::clock::AVAILABLE {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    } else {
+        0
+    };
+    // xorshift requires a state different from zero
+    nanos | 1
+}
+
+/// A pseudo-random number in `[0, 1)`, xorshift is good enough to spread
+/// the retries
+fn next_random() -> f64 {
+    RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{HostError, HostErrorCode, SdkError};
+
+    fn host_error(retryable: bool) -> SdkError {
+        let error = HostError {
+            code: HostErrorCode::Unavailable,
+            message: "registry unreachable".to_string(),
+            retryable,
+        };
+        SdkError::host_callback(
+            "oci",
+            "v1/manifest_digest",
+            serde_json::to_string(&error).unwrap().into(),
+        )
+    }
+
+    fn no_delay(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy::new(max_attempts).initial_delay(Duration::ZERO)
+    }
+
+    #[test]
+    fn retry_transient_errors() {
+        let mut attempts = 0;
+        let result = with_retry(&no_delay(5), || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(host_error(true))
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut attempts = 0;
+        let result: Result<()> = with_retry(&no_delay(2), || {
+            attempts += 1;
+            Err(host_error(true))
+        });
+        assert!(result.unwrap_err().is_retryable());
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn do_not_retry_permanent_errors() {
+        let mut attempts = 0;
+        let result: Result<()> = with_retry(&no_delay(5), || {
+            attempts += 1;
+            Err(host_error(false))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result: Result<()> = with_retry(&no_delay(5), || {
+            attempts += 1;
+            Err(SdkError::InvalidInput("bad image".to_string()))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn exponential_backoff() {
+        let policy = RetryPolicy::new(10)
+            .initial_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(500));
+        let delays: Vec<u128> = (1..=5).map(|r| policy.backoff(r).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+        assert_eq!(
+            policy.multiplier(0.5).backoff(3),
+            Duration::from_millis(100)
+        );
+
+        assert_eq!(jittered(Duration::from_millis(100), 0.0), Duration::ZERO);
+        assert_eq!(
+            jittered(Duration::from_millis(100), 0.5),
+            Duration::from_millis(50)
+        );
+        for _ in 0..100 {
+            let random = next_random();
+            assert!((0.0..1.0).contains(&random));
+        }
+    }
+
+    #[test]
+    fn retries_respect_the_deadline() {
+        crate::deadline::start(Some(1.0), None);
+        let policy = RetryPolicy::new(5)
+            .initial_delay(Duration::from_secs(5))
+            .max_delay(Duration::from_secs(5))
+            .jitter(false);
+
+        let mut attempts = 0;
+        let result: Result<()> = with_retry(&policy, || {
+            attempts += 1;
+            Err(host_error(true))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+        crate::deadline::start(None, None);
+    }
+
+    #[test]
+    #[cfg(feature = "net")]
+    fn retry_host_capabilities() {
+        use crate::host_capabilities::client::{with_host_client, MockHostClient};
+        use std::rc::Rc;
+
+        let client = Rc::new(MockHostClient::new().fail(
+            "net",
+            "v1/dns_lookup_host",
+            r#"{"code": "deadline_exceeded", "message": "timeout", "retryable": true}"#,
+        ));
+        let result = with_host_client(client.clone(), || {
+            with_retry(&no_delay(4), || {
+                crate::host_capabilities::net::lookup_host("example.com")
+            })
+        });
+        assert!(result.is_err());
+        client.assert_called_times("net", "v1/dns_lookup_host", 4);
+    }
+}
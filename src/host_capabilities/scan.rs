@@ -0,0 +1,164 @@
+//! Look up the vulnerability scan results of the images.
+//!
+//! The host queries the scanner backend it's configured with, e.g. a Trivy
+//! server, for the latest scan of an image. The lookup is done by digest,
+//! so the result refers to the exact content that is going to run: tags
+//! can be resolved with [`get_manifest_digest`](crate::host_capabilities::oci::get_manifest_digest).
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::scan;
+//! use kubewarden_policy_sdk::report::Severity;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "scan",
+//!     "v1/result",
+//!     &json!({
+//!         "scanner": "trivy",
+//!         "scannedAt": "2024-05-01T10:00:00Z",
+//!         "counts": {"critical": 1, "high": 4}
+//!     }),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let result = scan::scan_result("ghcr.io/org/app@sha256:0123456789abcdef")
+//!         .unwrap()
+//!         .expect("the image has not been scanned yet");
+//!     assert_eq!(result.counts.at_least(Severity::High), 5);
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use crate::report::Severity;
+use serde::{Deserialize, Serialize};
+
+/// Request sent to the host by [`scan_result`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ScanRequest {
+    /// The image reference, including the digest
+    pub image: String,
+}
+
+/// The number of vulnerabilities found, by severity
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct SeverityCounts {
+    pub critical: u32,
+    pub high: u32,
+    pub medium: u32,
+    pub low: u32,
+    /// Vulnerabilities without a severity assigned
+    pub unknown: u32,
+}
+
+impl SeverityCounts {
+    /// The number of vulnerabilities of the given severity, or of a higher
+    /// one. [`Severity::Info`] counts all of them, the unknown ones included
+    pub fn at_least(&self, severity: Severity) -> u32 {
+        [
+            (Severity::Critical, self.critical),
+            (Severity::High, self.high),
+            (Severity::Medium, self.medium),
+            (Severity::Low, self.low),
+            (Severity::Info, self.unknown),
+        ]
+        .into_iter()
+        .filter(|(s, _)| *s >= severity)
+        .map(|(_, count)| count)
+        .sum()
+    }
+}
+
+/// The latest scan of an image
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanResult {
+    /// The scanner that produced the result
+    #[serde(default)]
+    pub scanner: String,
+    /// When the scan was done, RFC 3339
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scanned_at: Option<String>,
+    /// The vulnerabilities found
+    #[serde(default)]
+    pub counts: SeverityCounts,
+}
+
+/// Fetch the latest scan result of `image`, which must be pinned by digest,
+/// e.g. `ghcr.io/org/app@sha256:...`. Returns `None` when the scanner has
+/// not scanned the image yet, the host answers `null` in that case
+pub fn scan_result(image: &str) -> Result<Option<ScanResult>> {
+    if !image.contains("@sha256:") {
+        return Err(SdkError::InvalidInput(format!(
+            "{} is not pinned by digest",
+            image
+        )));
+    }
+    let req = ScanRequest {
+        image: image.to_string(),
+    };
+    let msg =
+        serde_json::to_vec(&req).map_err(|e| SdkError::serialization("the scan request", e))?;
+    let response_raw = host_call("scan", "v1/result", &msg)?;
+
+    let response: Option<ScanResult> = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the scan response", e))?;
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    const IMAGE: &str = "registry.local/app@sha256:0123456789abcdef";
+
+    #[test]
+    fn count_vulnerabilities() {
+        let counts = SeverityCounts {
+            critical: 1,
+            high: 2,
+            medium: 3,
+            low: 4,
+            unknown: 5,
+        };
+        assert_eq!(counts.at_least(Severity::Critical), 1);
+        assert_eq!(counts.at_least(Severity::Medium), 6);
+        assert_eq!(counts.at_least(Severity::Info), 15);
+    }
+
+    #[test]
+    fn fetch_scan_results() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "scan",
+            "v1/result",
+            &json!({"scanner": "trivy", "counts": {"low": 2}}),
+        ));
+        let result = with_host_client(client.clone(), || scan_result(IMAGE))
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.scanner, "trivy");
+        assert_eq!(result.counts.at_least(Severity::Critical), 0);
+        assert_eq!(result.counts.low, 2);
+
+        let calls = client.calls_to("scan", "v1/result");
+        let request: ScanRequest = serde_json::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(request.image, IMAGE);
+
+        let client = Rc::new(MockHostClient::new().respond("scan", "v1/result", &json!(null)));
+        assert!(with_host_client(client, || scan_result(IMAGE))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn require_digests() {
+        let client = Rc::new(MockHostClient::new());
+        assert!(with_host_client(client.clone(), || scan_result("nginx:latest")).is_err());
+        assert!(client.calls().is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Timing of the host capability calls, enabled by the `host-call-spans`
+//! feature.
+//!
+//! Every host capability call produces a [`Span`], recording the capability,
+//! a summary of its arguments, the duration of the call and its outcome. The
+//! spans are logged at the debug level, and the most recent ones are kept in
+//! memory so tests and benchmarks can find which calls dominate the
+//! evaluation time.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::spans;
+//!
+//! // evaluate a request...
+//!
+//! let slowest = spans::take_spans()
+//!     .into_iter()
+//!     .max_by_key(|s| s.duration);
+//! ```
+use super::client::truncate_payload;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Number of spans kept in memory
+pub const MAX_SPANS: usize = 1024;
+
+/// Number of bytes of the payload included in the summary
+const SUMMARY_LEN: usize = 120;
+
+thread_local! {
+    static SPANS: RefCell<VecDeque<Span>> = const { RefCell::new(VecDeque::new()) };
+}
+
+/// A host capability call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// The capability, e.g. `oci.v1/manifest_digest`
+    pub capability: String,
+    /// The beginning of the payload sent to the host
+    pub summary: String,
+    /// The time spent waiting for the host
+    pub duration: Duration,
+    /// Whether the call succeeded
+    pub success: bool,
+}
+
+/// The spans recorded so far, oldest first
+pub fn spans() -> Vec<Span> {
+    SPANS.with(|s| s.borrow().iter().cloned().collect())
+}
+
+/// Returns the spans recorded so far, oldest first, and forget them
+pub fn take_spans() -> Vec<Span> {
+    SPANS.with(|s| s.borrow_mut().drain(..).collect())
+}
+
+/// Record a host capability call
+pub(crate) fn record(
+    namespace: &str,
+    operation: &str,
+    payload: &[u8],
+    duration: Duration,
+    success: bool,
+) {
+    let span = Span {
+        capability: format!("{}.{}", namespace, operation),
+        summary: truncate_payload(payload, SUMMARY_LEN),
+        duration,
+        success,
+    };
+    slog::debug!(
+        crate::logging::logger(),
+        "host capability call";
+        "capability" => &span.capability,
+        "arguments" => &span.summary,
+        "duration_ms" => duration.as_secs_f64() * 1000.0,
+        "success" => success
+    );
+
+    SPANS.with(|s| {
+        let mut spans = s.borrow_mut();
+        if spans.len() == MAX_SPANS {
+            spans.pop_front();
+        }
+        spans.push_back(span);
+    });
+}
+
+#[cfg(all(test, feature = "net"))]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{host_call, with_host_client, MockHostClient};
+    use crate::host_capabilities::net::lookup_host;
+    use std::rc::Rc;
+
+    #[test]
+    fn capability_calls_are_timed() {
+        take_spans();
+        let client = Rc::new(MockHostClient::new().respond(
+            "net",
+            "v1/dns_lookup_host",
+            &serde_json::json!({"ips": []}),
+        ));
+        with_host_client(client, || {
+            lookup_host("example.com").unwrap();
+            lookup_host(&"a".repeat(200)).unwrap();
+            assert!(host_call("oci", "v1/manifest_digest", b"\"busybox\"").is_err());
+        });
+
+        let spans = take_spans();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0].capability, "net.v1/dns_lookup_host");
+        assert_eq!(spans[0].summary, "\"example.com\"");
+        assert!(spans[0].success);
+        assert!(spans[1].summary.ends_with("... (202 bytes)"));
+        assert_eq!(spans[2].capability, "oci.v1/manifest_digest");
+        assert!(!spans[2].success);
+        assert!(super::spans().is_empty());
+    }
+}
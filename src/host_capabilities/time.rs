@@ -0,0 +1,102 @@
+//! Read the current time from the host running the policy.
+//!
+//! The clock of the wasm runtime can't be trusted by policies: the
+//! `wasm32-unknown-unknown` target has none, and the WASI clocks can be
+//! virtualized by the runtime. The host answers with the time of the policy
+//! server instead. Like the responses of the other capabilities, the time is
+//! memoized for the duration of the evaluation: all the checks of a request
+//! see the same instant.
+//!
+//! Policies built with the `time` feature should use
+//! [`time::now`](crate::time::now), which returns a chrono timestamp.
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::time;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//! use std::time::{Duration, UNIX_EPOCH};
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "host",
+//!     "v1/now",
+//!     &json!({"unixNanos": 1704103200000000000i64}),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let now = time::now().unwrap();
+//!     assert_eq!(now, UNIX_EPOCH + Duration::from_secs(1704103200));
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Response of the `host.v1/now` capability
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NowResponse {
+    /// Nanoseconds elapsed since the Unix epoch
+    pub unix_nanos: i64,
+}
+
+impl From<NowResponse> for SystemTime {
+    fn from(response: NowResponse) -> Self {
+        let offset = Duration::from_nanos(response.unix_nanos.unsigned_abs());
+        if response.unix_nanos >= 0 {
+            UNIX_EPOCH + offset
+        } else {
+            UNIX_EPOCH - offset
+        }
+    }
+}
+
+/// The current time of the host
+pub fn now() -> Result<SystemTime> {
+    let response_raw = host_call("host", "v1/now", &[])?;
+    let response: NowResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the current time", e))?;
+    Ok(response.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use std::rc::Rc;
+
+    #[test]
+    fn read_the_time_of_the_host() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "host",
+            "v1/now",
+            &NowResponse {
+                unix_nanos: 1_500_000_000,
+            },
+        ));
+        let now = with_host_client(client.clone(), now).unwrap();
+        assert_eq!(now, UNIX_EPOCH + Duration::from_millis(1500));
+        assert_eq!(client.calls()[0].payload, b"");
+
+        let before_epoch = NowResponse {
+            unix_nanos: -1_000_000_000,
+        };
+        assert_eq!(
+            SystemTime::from(before_epoch),
+            UNIX_EPOCH - Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn invalid_responses() {
+        let client = Rc::new(MockHostClient::new().respond("host", "v1/now", &"yesterday"));
+        assert!(with_host_client(client, now).is_err());
+
+        let client =
+            Rc::new(MockHostClient::new().fail("host", "v1/now", "unknown operation: v1/now"));
+        assert!(with_host_client(client, now)
+            .unwrap_err()
+            .is_unsupported_capability());
+    }
+}
@@ -1,9 +1,13 @@
-use crate::host_capabilities::SigstoreVerificationInputV2;
-use anyhow::{anyhow, Result};
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use crate::host_capabilities::protocol_version::{
+    host_protocol_version, set_host_protocol_version,
+};
+use crate::host_capabilities::{SigstoreVerificationInputV1, SigstoreVerificationInputV2};
+use crate::metadata::ProtocolVersion;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-#[cfg(test)]
-use tests::mock_wapc as wapc_guest;
+use std::convert::TryFrom;
 
 /// VerificationResponse holds the response of a sigstore signatures verification
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -41,16 +45,14 @@ pub struct KeylessPrefixInfo {
 /// * `annotations` - annotations that must have been provided by all signers when they signed the OCI artifact
 pub fn verify_pub_keys_image(
     image: &str,
-    pub_keys: Vec<String>,
-    annotations: Option<HashMap<String, String>>,
+    pub_keys: &[String],
+    annotations: Option<&HashMap<String, String>>,
 ) -> Result<VerificationResponse> {
-    let input = SigstoreVerificationInputV2::SigstorePubKeyVerify {
-        image: image.to_string(),
+    verify(&VerificationRequestV2::SigstorePubKeyVerify {
+        image,
         pub_keys,
         annotations,
-    };
-
-    verify(input)
+    })
 }
 
 /// verify sigstore signatures of an image using keyless
@@ -60,16 +62,14 @@ pub fn verify_pub_keys_image(
 /// * `annotations` - annotations that must have been provided by all signers when they signed the OCI artifact
 pub fn verify_keyless_exact_match(
     image: &str,
-    keyless: Vec<KeylessInfo>,
-    annotations: Option<HashMap<String, String>>,
+    keyless: &[KeylessInfo],
+    annotations: Option<&HashMap<String, String>>,
 ) -> Result<VerificationResponse> {
-    let input = SigstoreVerificationInputV2::SigstoreKeylessVerify {
-        image: image.to_string(),
+    verify(&VerificationRequestV2::SigstoreKeylessVerify {
+        image,
         keyless,
         annotations,
-    };
-
-    verify(input)
+    })
 }
 
 /// verify sigstore signatures of an image using keyless. Here, the provided
@@ -82,16 +82,14 @@ pub fn verify_keyless_exact_match(
 /// * `annotations` - annotations that must have been provided by all signers when they signed the OCI artifact
 pub fn verify_keyless_prefix_match(
     image: &str,
-    keyless_prefix: Vec<KeylessPrefixInfo>,
-    annotations: Option<HashMap<String, String>>,
+    keyless_prefix: &[KeylessPrefixInfo],
+    annotations: Option<&HashMap<String, String>>,
 ) -> Result<VerificationResponse> {
-    let input = SigstoreVerificationInputV2::SigstoreKeylessPrefixVerify {
-        image: image.to_string(),
+    verify(&VerificationRequestV2::SigstoreKeylessPrefixVerify {
+        image,
         keyless_prefix,
         annotations,
-    };
-
-    verify(input)
+    })
 }
 
 /// verify sigstore signatures of an image using keyless signatures made via
@@ -103,18 +101,16 @@ pub fn verify_keyless_prefix_match(
 /// * `annotations` - annotations that must have been provided by all signers when they signed the OCI artifact
 pub fn verify_keyless_github_actions(
     image: &str,
-    owner: String,
-    repo: Option<String>,
-    annotations: Option<HashMap<String, String>>,
+    owner: &str,
+    repo: Option<&str>,
+    annotations: Option<&HashMap<String, String>>,
 ) -> Result<VerificationResponse> {
-    let input = SigstoreVerificationInputV2::SigstoreGithubActionsVerify {
-        image: image.to_string(),
+    verify(&VerificationRequestV2::SigstoreGithubActionsVerify {
+        image,
         owner,
         repo,
         annotations,
-    };
-
-    verify(input)
+    })
 }
 
 /// verify sigstore signatures of an image using a user provided certificate
@@ -122,238 +118,534 @@ pub fn verify_keyless_github_actions(
 /// * `image` -  image to be verified
 /// * `certificate` - PEM encoded certificate used to verify the signature
 /// * `certificate_chain` - Optional. PEM encoded certificates used to verify `certificate`.
-///    When not specified, the certificate is assumed to be trusted
+///   When not specified, the certificate is assumed to be trusted
 /// * `require_rekor_bundle` - require the  signature layer to have a Rekor bundle.
-///    Having a Rekor bundle allows further checks to be performed,
-///    like ensuring the signature has been produced during the validity
-///    time frame of the certificate.
-///    It is recommended to set this value to `true` to have a more secure
-///    verification process.
+///   Having a Rekor bundle allows further checks to be performed,
+///   like ensuring the signature has been produced during the validity
+///   time frame of the certificate.
+///   It is recommended to set this value to `true` to have a more secure
+///   verification process.
 /// * `annotations` - annotations that must have been provided by all signers when they signed the OCI artifact
 pub fn verify_certificate(
     image: &str,
-    certificate: String,
-    certificate_chain: Option<Vec<String>>,
+    certificate: &str,
+    certificate_chain: Option<&[String]>,
     require_rekor_bundle: bool,
-    annotations: Option<HashMap<String, String>>,
+    annotations: Option<&HashMap<String, String>>,
 ) -> Result<VerificationResponse> {
-    let chain: Option<Vec<Vec<u8>>> =
-        certificate_chain.map(|c| c.iter().map(|cert| cert.as_bytes().to_vec()).collect());
-
-    let input = SigstoreVerificationInputV2::SigstoreCertificateVerify {
-        image: image.to_string(),
-        certificate: certificate.as_bytes().to_vec(),
-        certificate_chain: chain,
+    verify(&VerificationRequestV2::SigstoreCertificateVerify {
+        image,
+        certificate: certificate.as_bytes(),
+        certificate_chain: certificate_chain.map(CertificateChain::Pem),
         require_rekor_bundle,
         annotations,
-    };
+    })
+}
 
-    verify(input)
+/// verify sigstore signatures of an image, adapting to the capabilities of the host.
+///
+/// The `v2/verify` capability is attempted first. When the host reports it as
+/// unsupported, the host implements only [`ProtocolVersion::V1`] and is queried
+/// using the `v1/verify` capability, provided the verification mode is
+/// supported by it. The outcome is remembered, see [`host_protocol_version`],
+/// so that old hosts receive a single request for the following verifications.
+/// # Arguments
+/// * `input` - the verification to be performed
+pub fn verify_with_fallback(input: &SigstoreVerificationInputV2) -> Result<VerificationResponse> {
+    let request = VerificationRequestV2::from(input);
+    if host_protocol_version() != Some(ProtocolVersion::V1) {
+        match verify(&request) {
+            Err(e) if e.is_unsupported_capability() => {
+                set_host_protocol_version(ProtocolVersion::V1)
+            }
+            Err(e) => return Err(e),
+            Ok(response) => {
+                set_host_protocol_version(ProtocolVersion::V2);
+                return Ok(response);
+            }
+        }
+    }
+    verify_request_v1(&VerificationRequestV1::try_from(request)?)
 }
-fn verify(input: SigstoreVerificationInputV2) -> Result<VerificationResponse> {
-    let msg = serde_json::to_vec(&input)
-        .map_err(|e| anyhow!("error serializing the validation request: {}", e))?;
-    let response_raw = wapc_guest::host_call("kubewarden", "oci", "v2/verify", &msg)
-        .map_err(|e| anyhow!("{}", e))?;
 
-    let response: VerificationResponse = serde_json::from_slice(&response_raw)?;
+/// verify sigstore signatures of an image using the `v1/verify` capability.
+/// This is meant to be used only when interacting with old hosts,
+/// [`verify_with_fallback`] should be preferred.
+/// # Arguments
+/// * `input` - the verification to be performed
+pub fn verify_v1(input: &SigstoreVerificationInputV1) -> Result<VerificationResponse> {
+    verify_request_v1(&VerificationRequestV1::from(input))
+}
+
+fn verify_request_v1(request: &VerificationRequestV1) -> Result<VerificationResponse> {
+    let msg = serde_json::to_vec(request)
+        .map_err(|e| SdkError::serialization("the verification request", e))?;
+    let response_raw = host_call("oci", "v1/verify", &msg)?;
+
+    let response: VerificationResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the verification response", e))?;
+
+    Ok(response)
+}
+
+fn verify(request: &VerificationRequestV2) -> Result<VerificationResponse> {
+    let msg = serde_json::to_vec(request)
+        .map_err(|e| SdkError::serialization("the verification request", e))?;
+    let response_raw = host_call("oci", "v2/verify", &msg)?;
+
+    let response: VerificationResponse = serde_json::from_slice(&response_raw)
+        .map_err(|e| SdkError::deserialization("the verification response", e))?;
 
     Ok(response)
 }
 
+/// Borrowed form of [`SigstoreVerificationInputV1`]: it is serialized in the
+/// same way, without copying the data provided by the caller
+#[derive(Serialize, Debug)]
+enum VerificationRequestV1<'a> {
+    SigstorePubKeyVerify {
+        image: &'a str,
+        pub_keys: &'a [String],
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+    SigstoreKeylessVerify {
+        image: &'a str,
+        keyless: &'a [KeylessInfo],
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+}
+
+/// Borrowed form of [`SigstoreVerificationInputV2`]: it is serialized in the
+/// same way, without copying the data provided by the caller
+#[derive(Serialize, Debug)]
+#[serde(tag = "type")]
+// the names of the variants are part of the wire format
+#[allow(clippy::enum_variant_names)]
+enum VerificationRequestV2<'a> {
+    SigstorePubKeyVerify {
+        image: &'a str,
+        pub_keys: &'a [String],
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+    SigstoreKeylessVerify {
+        image: &'a str,
+        keyless: &'a [KeylessInfo],
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+    SigstoreKeylessPrefixVerify {
+        image: &'a str,
+        keyless_prefix: &'a [KeylessPrefixInfo],
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+    SigstoreGithubActionsVerify {
+        image: &'a str,
+        owner: &'a str,
+        repo: Option<&'a str>,
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+    SigstoreCertificateVerify {
+        image: &'a str,
+        certificate: &'a [u8],
+        certificate_chain: Option<CertificateChain<'a>>,
+        require_rekor_bundle: bool,
+        annotations: Option<&'a HashMap<String, String>>,
+    },
+}
+
+/// Certificate chain, serialized as a list of byte arrays
+#[derive(Debug)]
+enum CertificateChain<'a> {
+    Bytes(&'a [Vec<u8>]),
+    Pem(&'a [String]),
+}
+
+impl Serialize for CertificateChain<'_> {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            CertificateChain::Bytes(chain) => serializer.collect_seq(chain.iter()),
+            CertificateChain::Pem(chain) => {
+                serializer.collect_seq(chain.iter().map(|c| c.as_bytes()))
+            }
+        }
+    }
+}
+
+impl<'a> From<&'a SigstoreVerificationInputV1> for VerificationRequestV1<'a> {
+    fn from(input: &'a SigstoreVerificationInputV1) -> Self {
+        match input {
+            SigstoreVerificationInputV1::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            } => VerificationRequestV1::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations: annotations.as_ref(),
+            },
+            SigstoreVerificationInputV1::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            } => VerificationRequestV1::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations: annotations.as_ref(),
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a SigstoreVerificationInputV2> for VerificationRequestV2<'a> {
+    fn from(input: &'a SigstoreVerificationInputV2) -> Self {
+        match input {
+            SigstoreVerificationInputV2::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            } => VerificationRequestV2::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations: annotations.as_ref(),
+            },
+            SigstoreVerificationInputV2::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            } => VerificationRequestV2::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations: annotations.as_ref(),
+            },
+            SigstoreVerificationInputV2::SigstoreKeylessPrefixVerify {
+                image,
+                keyless_prefix,
+                annotations,
+            } => VerificationRequestV2::SigstoreKeylessPrefixVerify {
+                image,
+                keyless_prefix,
+                annotations: annotations.as_ref(),
+            },
+            SigstoreVerificationInputV2::SigstoreGithubActionsVerify {
+                image,
+                owner,
+                repo,
+                annotations,
+            } => VerificationRequestV2::SigstoreGithubActionsVerify {
+                image,
+                owner,
+                repo: repo.as_deref(),
+                annotations: annotations.as_ref(),
+            },
+            SigstoreVerificationInputV2::SigstoreCertificateVerify {
+                image,
+                certificate,
+                certificate_chain,
+                require_rekor_bundle,
+                annotations,
+            } => VerificationRequestV2::SigstoreCertificateVerify {
+                image,
+                certificate,
+                certificate_chain: certificate_chain.as_deref().map(CertificateChain::Bytes),
+                require_rekor_bundle: *require_rekor_bundle,
+                annotations: annotations.as_ref(),
+            },
+        }
+    }
+}
+
+impl<'a> TryFrom<VerificationRequestV2<'a>> for VerificationRequestV1<'a> {
+    type Error = SdkError;
+
+    fn try_from(request: VerificationRequestV2<'a>) -> Result<Self> {
+        match request {
+            VerificationRequestV2::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            } => Ok(VerificationRequestV1::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            }),
+            VerificationRequestV2::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            } => Ok(VerificationRequestV1::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            }),
+            _ => Err(SdkError::UnsupportedCapability {
+                capability: "oci.v1/verify".to_string(),
+                message: "verification mode not supported".to_string(),
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use mockall::automock;
-    use serial_test::serial;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use std::rc::Rc;
+
+    fn trusted(operation: &str) -> Rc<MockHostClient> {
+        Rc::new(MockHostClient::new().respond(
+            "oci",
+            operation,
+            &VerificationResponse {
+                is_trusted: true,
+                digest: "digest".to_string(),
+            },
+        ))
+    }
 
-    #[automock()]
-    pub mod wapc {
-        use wapc_guest::CallResult;
+    fn failing() -> Rc<MockHostClient> {
+        Rc::new(MockHostClient::new().fail("oci", "v2/verify", "verification failed"))
+    }
 
-        // needed for creating mocks
-        #[allow(dead_code)]
-        pub fn host_call(_binding: &str, _ns: &str, _op: &str, _msg: &[u8]) -> CallResult {
-            Ok(vec![u8::from(true)])
-        }
+    fn keyless() -> Vec<KeylessInfo> {
+        vec![KeylessInfo {
+            subject: "subject".to_string(),
+            issuer: "issuer".to_string(),
+        }]
+    }
+
+    fn keyless_prefix() -> Vec<KeylessPrefixInfo> {
+        vec![KeylessPrefixInfo {
+            url_prefix: "urlprefix".to_string(),
+            issuer: "issuer".to_string(),
+        }]
     }
 
-    // these tests need to run sequentially because mockall creates a global context to create the mocks
-    #[serial]
     #[test]
     fn verify_pub_keys_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect().times(1).returning(|_, _, _, _| {
-            Ok(serde_json::to_vec(&{
-                VerificationResponse {
-                    is_trusted: true,
-                    digest: "digest".to_string(),
-                }
-            })
-            .unwrap())
+        let client = trusted("v2/verify");
+        let res = with_host_client(client.clone(), || {
+            verify_pub_keys_image("image", &["key".to_string()], None)
         });
-        let res = verify_pub_keys_image("image", vec!["key".to_string()], None);
 
-        assert_eq!(res.unwrap().is_trusted, true)
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_pub_keys_not_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .times(1)
-            .returning(|_, _, _, _| Err(Box::new(core::fmt::Error {})));
-        let res = verify_pub_keys_image("image", vec!["key".to_string()], None);
+        let client = failing();
+        let res = with_host_client(client.clone(), || {
+            verify_pub_keys_image("image", &["key".to_string()], None)
+        });
 
-        assert!(res.is_err())
+        assert!(res.is_err());
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect().times(1).returning(|_, _, _, _| {
-            Ok(serde_json::to_vec(&{
-                VerificationResponse {
-                    is_trusted: true,
-                    digest: "digest".to_string(),
-                }
-            })
-            .unwrap())
+        let client = trusted("v2/verify");
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_exact_match("image", &keyless(), None)
         });
-        let res = verify_keyless_exact_match(
-            "image",
-            vec![KeylessInfo {
-                subject: "subject".to_string(),
-                issuer: "issuer".to_string(),
-            }],
-            None,
-        );
 
-        assert_eq!(res.unwrap().is_trusted, true)
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_not_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .times(1)
-            .returning(|_, _, _, _| Err(Box::new(core::fmt::Error {})));
-        let res = verify_keyless_exact_match(
-            "image",
-            vec![KeylessInfo {
-                subject: "subject".to_string(),
-                issuer: "issuer".to_string(),
-            }],
-            None,
-        );
+        let client = failing();
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_exact_match("image", &keyless(), None)
+        });
 
-        assert!(res.is_err())
+        assert!(res.is_err());
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_prefix_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect().times(1).returning(|_, _, _, _| {
-            Ok(serde_json::to_vec(&{
-                VerificationResponse {
-                    is_trusted: true,
-                    digest: "digest".to_string(),
-                }
-            })
-            .unwrap())
+        let client = trusted("v2/verify");
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_prefix_match("image", &keyless_prefix(), None)
         });
-        let res = verify_keyless_prefix_match(
-            "image",
-            vec![KeylessPrefixInfo {
-                url_prefix: "urlprefix".to_string(),
-                issuer: "issuer".to_string(),
-            }],
-            None,
-        );
 
-        assert_eq!(res.unwrap().is_trusted, true)
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_prefix_not_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .times(1)
-            .returning(|_, _, _, _| Err(Box::new(core::fmt::Error {})));
-        let res = verify_keyless_prefix_match(
-            "image",
-            vec![KeylessPrefixInfo {
-                url_prefix: "urlprefix".to_string(),
-                issuer: "issuer".to_string(),
-            }],
-            None,
-        );
+        let client = failing();
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_prefix_match("image", &keyless_prefix(), None)
+        });
 
-        assert!(res.is_err())
+        assert!(res.is_err());
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_github_actions_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect().times(1).returning(|_, _, _, _| {
-            Ok(serde_json::to_vec(&{
-                VerificationResponse {
-                    is_trusted: true,
-                    digest: "digest".to_string(),
-                }
-            })
-            .unwrap())
+        let client = trusted("v2/verify");
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_github_actions("image", "owner", None, None)
         });
-        let res = verify_keyless_github_actions("image", "owner".to_string(), None, None);
 
-        assert_eq!(res.unwrap().is_trusted, true)
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_keyless_github_actions_not_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .times(1)
-            .returning(|_, _, _, _| Err(Box::new(core::fmt::Error {})));
-        let res = verify_keyless_github_actions("image", "owner".to_string(), None, None);
+        let client = failing();
+        let res = with_host_client(client.clone(), || {
+            verify_keyless_github_actions("image", "owner", None, None)
+        });
 
-        assert!(res.is_err())
+        assert!(res.is_err());
+        assert_eq!(client.calls().len(), 1);
     }
 
-    #[serial]
     #[test]
     fn verify_certificate_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect().times(1).returning(|_, _, _, _| {
-            Ok(serde_json::to_vec(&{
-                VerificationResponse {
-                    is_trusted: true,
-                    digest: "digest".to_string(),
-                }
-            })
-            .unwrap())
+        let client = trusted("v2/verify");
+        let res = with_host_client(client.clone(), || {
+            verify_certificate("image", "CERT", None, true, None)
+        });
+
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls().len(), 1);
+    }
+
+    #[test]
+    fn verify_v1_uses_v1_capability() {
+        let client = trusted("v1/verify");
+        let input = SigstoreVerificationInputV2::pub_key("image", vec!["key".to_string()], None);
+        let res = with_host_client(client.clone(), || {
+            verify_v1(&SigstoreVerificationInputV1::try_from(input).unwrap())
         });
-        let res = verify_certificate("image", "CERT".to_string(), None, true, None);
 
-        assert_eq!(res.unwrap().is_trusted, true)
+        assert!(res.unwrap().is_trusted);
+        assert_eq!(client.calls_to("oci", "v1/verify").len(), 1);
+    }
+
+    #[test]
+    fn verify_with_fallback_uses_v1_on_old_hosts() {
+        let client = Rc::new(
+            MockHostClient::new()
+                .fail("oci", "v2/verify", "unknown operation: v2/verify")
+                .respond(
+                    "oci",
+                    "v1/verify",
+                    &VerificationResponse {
+                        is_trusted: true,
+                        digest: "digest".to_string(),
+                    },
+                ),
+        );
+        let input = SigstoreVerificationInputV2::pub_key("image", vec!["key".to_string()], None);
+
+        with_host_client(client.clone(), || {
+            assert!(verify_with_fallback(&input).unwrap().is_trusted);
+            assert!(verify_with_fallback(&input).unwrap().is_trusted);
+        });
+
+        assert_eq!(client.calls_to("oci", "v2/verify").len(), 1);
+        assert_eq!(client.calls_to("oci", "v1/verify").len(), 2);
+        assert_eq!(host_protocol_version(), Some(ProtocolVersion::V1));
+    }
+
+    #[test]
+    fn verify_with_fallback_does_not_downgrade_on_transient_errors() {
+        let client = Rc::new(MockHostClient::new().fail_with(
+            "oci",
+            "v2/verify",
+            &crate::error::HostError {
+                code: crate::error::HostErrorCode::Unavailable,
+                message: "registry unreachable".to_string(),
+                retryable: true,
+            },
+        ));
+        let input = SigstoreVerificationInputV2::pub_key("image", vec!["key".to_string()], None);
+
+        let res = with_host_client(client.clone(), || verify_with_fallback(&input));
+
+        assert!(res.is_err_and(|e| e.is_retryable()));
+        assert!(client.calls_to("oci", "v1/verify").is_empty());
+        assert_eq!(host_protocol_version(), None);
+
+        let client = trusted("v2/verify");
+        with_host_client(client, || verify_with_fallback(&input)).unwrap();
+        assert_eq!(host_protocol_version(), Some(ProtocolVersion::V2));
+    }
+
+    #[test]
+    fn v2_only_input_cannot_be_converted_to_v1() {
+        let input = SigstoreVerificationInputV2::github_actions("image", "owner", None, None);
+
+        assert!(SigstoreVerificationInputV1::try_from(input).is_err())
     }
 
-    #[serial]
     #[test]
     fn verify_certificate_not_trusted() {
-        let ctx = mock_wapc::host_call_context();
-        ctx.expect()
-            .times(1)
-            .returning(|_, _, _, _| Err(Box::new(core::fmt::Error {})));
-        let res = verify_certificate("image", "CERT".to_string(), None, true, None);
+        let client = failing();
+        let res = with_host_client(client.clone(), || {
+            verify_certificate("image", "CERT", None, true, None)
+        });
+
+        assert!(res.is_err());
+        assert_eq!(client.calls().len(), 1);
+    }
+
+    #[test]
+    fn borrowed_requests_are_serialized_like_the_inputs() {
+        let annotations = Some(HashMap::from([("env".to_string(), "prod".to_string())]));
+        let inputs = [
+            SigstoreVerificationInputV2::pub_key("image", vec!["key".to_string()], None),
+            SigstoreVerificationInputV2::keyless("image", keyless(), annotations.clone()),
+            SigstoreVerificationInputV2::keyless_prefix("image", keyless_prefix(), None),
+            SigstoreVerificationInputV2::github_actions(
+                "image",
+                "owner",
+                Some("repo".to_string()),
+                annotations.clone(),
+            ),
+            SigstoreVerificationInputV2::certificate(
+                "image",
+                b"CERT".to_vec(),
+                Some(vec![b"CA".to_vec()]),
+                true,
+                None,
+            ),
+        ];
+        for input in &inputs {
+            assert_eq!(
+                serde_json::to_value(VerificationRequestV2::from(input)).unwrap(),
+                serde_json::to_value(input).unwrap()
+            );
+        }
 
-        assert!(res.is_err())
+        let input = SigstoreVerificationInputV1::keyless("image", keyless(), annotations);
+        assert_eq!(
+            serde_json::to_value(VerificationRequestV1::from(&input)).unwrap(),
+            serde_json::to_value(&input).unwrap()
+        );
+
+        let chain = ["CA".to_string()];
+        let request = VerificationRequestV2::SigstoreCertificateVerify {
+            image: "image",
+            certificate: b"CERT",
+            certificate_chain: Some(CertificateChain::Pem(&chain)),
+            require_rekor_bundle: true,
+            annotations: None,
+        };
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            serde_json::to_value(&inputs[4]).unwrap()
+        );
     }
 }
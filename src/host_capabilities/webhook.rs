@@ -0,0 +1,340 @@
+//! Consult an external HTTP service during the evaluation.
+//!
+//! Organizations migrating from an existing policy engine can keep it in
+//! the loop while the policies are ported: the admission request is POSTed
+//! by the host to the service, and its verdict is mapped into the response
+//! of the policy. The host decides which services can be reached, usually
+//! only internal ones.
+//!
+//! The credentials found inside of the request are masked before it leaves
+//! the policy, see [`redact_value`](crate::logging::redact_value).
+//!
+//! The service can answer with any JSON document, the [`ResponseMapping`]
+//! locates the verdict and the message inside of it:
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::{with_host_client, MockHostClient};
+//! use kubewarden_policy_sdk::host_capabilities::webhook::{ResponseMapping, Webhook};
+//! use kubewarden_policy_sdk::request::KubernetesAdmissionRequest;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! let webhook = Webhook::new("http://legacy-engine.policies.svc/review").mapping(ResponseMapping {
+//!     allowed: "/result/allow".to_string(),
+//!     message: Some("/result/reason".to_string()),
+//! });
+//!
+//! let client = Rc::new(MockHostClient::new().respond(
+//!     "webhook",
+//!     "v1/post",
+//!     &json!({"status": 200, "body": {"result": {"allow": false, "reason": "denied by rule 42"}}}),
+//! ));
+//!
+//! with_host_client(client, || {
+//!     let response = webhook.review(&KubernetesAdmissionRequest::default()).unwrap();
+//!     assert!(!response.accepted);
+//!     assert_eq!(response.message.as_deref(), Some("denied by rule 42"));
+//! });
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::client::host_call;
+use crate::request::KubernetesAdmissionRequest;
+use crate::response::ValidationResponse;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Request sent to the host by [`Webhook::review`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookRequest {
+    /// The URL of the service
+    pub url: String,
+    /// How long the host waits for the answer of the service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u32>,
+    /// The document POSTed to the service
+    pub body: Value,
+}
+
+/// Response of the host: the status code and the JSON body returned by the
+/// service
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebhookResponse {
+    /// The HTTP status code
+    pub status: u16,
+    /// The body of the response
+    #[serde(default)]
+    pub body: Value,
+}
+
+/// Where the verdict is found inside of the body returned by the service,
+/// as [JSON pointers](https://datatracker.ietf.org/doc/html/rfc6901)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct ResponseMapping {
+    /// Pointer to the boolean telling whether the request is allowed
+    pub allowed: String,
+    /// Pointer to the message explaining the verdict
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl Default for ResponseMapping {
+    /// The shape of the AdmissionReview responses:
+    /// `{"allowed": bool, "status": {"message": string}}`
+    fn default() -> Self {
+        ResponseMapping {
+            allowed: "/allowed".to_string(),
+            message: Some("/status/message".to_string()),
+        }
+    }
+}
+
+/// What to do when the service cannot be reached or gives an unexpected
+/// answer, like the `failurePolicy` of the Kubernetes webhooks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Return the error
+    #[default]
+    Fail,
+    /// Accept the request
+    Ignore,
+}
+
+/// An HTTP service consulted by the policy. It can be part of the policy
+/// settings
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    /// The URL of the service, either `http` or `https`
+    pub url: String,
+    /// How long the host waits for the answer of the service, the default
+    /// of the host is used when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u32>,
+    /// Where the verdict is found inside of the answer of the service
+    #[serde(default)]
+    pub mapping: ResponseMapping,
+    /// What to do when the service fails
+    #[serde(default)]
+    pub failure_policy: FailurePolicy,
+}
+
+impl Webhook {
+    /// A webhook using the default [`ResponseMapping`] and failing closed
+    pub fn new(url: impl Into<String>) -> Self {
+        Webhook {
+            url: url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set how long the host waits for the answer of the service
+    pub fn timeout_seconds(mut self, timeout_seconds: u32) -> Self {
+        self.timeout_seconds = Some(timeout_seconds);
+        self
+    }
+
+    /// Set where the verdict is found inside of the answer of the service
+    pub fn mapping(mut self, mapping: ResponseMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Set what to do when the service fails
+    pub fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Validate the webhook, meant to be used by the settings validation
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(format!("{} is not an http or https URL", self.url));
+        }
+        if self.timeout_seconds == Some(0) {
+            return Err("timeoutSeconds must be greater than 0".to_string());
+        }
+        for pointer in std::iter::once(&self.mapping.allowed).chain(&self.mapping.message) {
+            if !pointer.is_empty() && !pointer.starts_with('/') {
+                return Err(format!("{} is not a JSON pointer", pointer));
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the redacted request to the service, and map its answer into a
+    /// response. The [`FailurePolicy`] decides the outcome when the service
+    /// fails, an invalid webhook is always an error
+    pub fn review(&self, request: &KubernetesAdmissionRequest) -> Result<ValidationResponse> {
+        self.validate().map_err(SdkError::InvalidInput)?;
+        match self.call(request) {
+            Err(_) if self.failure_policy == FailurePolicy::Ignore => {
+                Ok(ValidationResponse::accept().response())
+            }
+            result => result,
+        }
+    }
+
+    fn call(&self, request: &KubernetesAdmissionRequest) -> Result<ValidationResponse> {
+        let mut body = serde_json::to_value(request)
+            .map_err(|e| SdkError::serialization("the admission request", e))?;
+        crate::logging::redact_value(&mut body);
+
+        let req = WebhookRequest {
+            url: self.url.clone(),
+            timeout_seconds: self.timeout_seconds,
+            body,
+        };
+        let msg = serde_json::to_vec(&req)
+            .map_err(|e| SdkError::serialization("the webhook request", e))?;
+        let response_raw = host_call("webhook", "v1/post", &msg)?;
+        let response: WebhookResponse = serde_json::from_slice(&response_raw)
+            .map_err(|e| SdkError::deserialization("the webhook response", e))?;
+
+        if !(200..300).contains(&response.status) {
+            return Err(SdkError::InvalidInput(format!(
+                "{} answered with status {}",
+                self.url, response.status
+            )));
+        }
+        let allowed = response
+            .body
+            .pointer(&self.mapping.allowed)
+            .and_then(Value::as_bool)
+            .ok_or_else(|| {
+                SdkError::InvalidInput(format!(
+                    "the answer of {} has no boolean at {}",
+                    self.url, self.mapping.allowed
+                ))
+            })?;
+        let message = self
+            .mapping
+            .message
+            .as_ref()
+            .and_then(|pointer| response.body.pointer(pointer))
+            .and_then(Value::as_str)
+            .filter(|message| !message.is_empty());
+
+        Ok(match (allowed, message) {
+            (true, _) => ValidationResponse::accept().response(),
+            (false, Some(message)) => ValidationResponse::reject(message).response(),
+            (false, None) => {
+                ValidationResponse::reject(format!("request rejected by {}", self.url)).response()
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn review(webhook: &Webhook, response: Value) -> Result<ValidationResponse> {
+        let client = Rc::new(MockHostClient::new().respond("webhook", "v1/post", &response));
+        with_host_client(client, || {
+            webhook.review(&KubernetesAdmissionRequest::default())
+        })
+    }
+
+    #[test]
+    fn map_verdicts() {
+        let webhook = Webhook::new("https://engine.policies.svc");
+
+        let response = review(&webhook, json!({"status": 200, "body": {"allowed": true}})).unwrap();
+        assert!(response.accepted);
+
+        let response = review(
+            &webhook,
+            json!({"status": 200, "body": {"allowed": false, "status": {"message": "no"}}}),
+        )
+        .unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.as_deref(), Some("no"));
+
+        let response =
+            review(&webhook, json!({"status": 201, "body": {"allowed": false}})).unwrap();
+        assert_eq!(
+            response.message.as_deref(),
+            Some("request rejected by https://engine.policies.svc")
+        );
+    }
+
+    #[test]
+    fn send_redacted_request() {
+        let webhook = Webhook::new("http://engine").timeout_seconds(2);
+        let request: KubernetesAdmissionRequest = serde_json::from_value(json!({
+            "uid": "1234",
+            "object": {"kind": "ConfigMap", "data": {"password": "hunter2"}}
+        }))
+        .unwrap();
+        let client = Rc::new(MockHostClient::new().respond(
+            "webhook",
+            "v1/post",
+            &json!({"status": 200, "body": {"allowed": true}}),
+        ));
+        with_host_client(client.clone(), || webhook.review(&request)).unwrap();
+
+        let calls = client.calls_to("webhook", "v1/post");
+        let sent: WebhookRequest = serde_json::from_slice(&calls[0].payload).unwrap();
+        assert_eq!(sent.url, "http://engine");
+        assert_eq!(sent.timeout_seconds, Some(2));
+        assert_eq!(sent.body["uid"], "1234");
+        assert_eq!(
+            sent.body["object"]["data"]["password"],
+            crate::logging::REDACTED
+        );
+    }
+
+    #[test]
+    fn failures() {
+        let webhook = Webhook::new("http://engine");
+        for response in [
+            json!({"status": 500, "body": {"allowed": true}}),
+            json!({"status": 200, "body": {"allowed": "yes"}}),
+            json!({"status": 200}),
+        ] {
+            assert!(review(&webhook, response.clone()).is_err(), "{}", response);
+            let response = review(
+                &webhook.clone().failure_policy(FailurePolicy::Ignore),
+                response,
+            )
+            .unwrap();
+            assert!(response.accepted);
+        }
+
+        let client = Rc::new(MockHostClient::new().fail("webhook", "v1/post", "unreachable"));
+        assert!(with_host_client(client, || webhook
+            .review(&KubernetesAdmissionRequest::default()))
+        .is_err());
+    }
+
+    #[test]
+    fn validate_webhooks() {
+        assert!(Webhook::new("https://engine").validate().is_ok());
+        assert!(Webhook::new("engine:8080").validate().is_err());
+        assert!(Webhook::new("http://engine")
+            .timeout_seconds(0)
+            .validate()
+            .is_err());
+        assert!(Webhook::new("http://engine")
+            .mapping(ResponseMapping {
+                allowed: "allowed".to_string(),
+                message: None
+            })
+            .validate()
+            .is_err());
+
+        let webhook: Webhook = serde_json::from_value(json!({
+            "url": "http://engine",
+            "mapping": {"allowed": "/allow"},
+            "failurePolicy": "Ignore"
+        }))
+        .unwrap();
+        assert_eq!(webhook.mapping.message.as_deref(), Some("/status/message"));
+        assert_eq!(webhook.failure_policy, FailurePolicy::Ignore);
+    }
+}
@@ -0,0 +1,240 @@
+//! String interning for the deserialization of large resource lists.
+//!
+//! Lists of Kubernetes resources repeat the same strings over and over:
+//! namespaces, label keys and values, owner kinds. Deserializing them as
+//! [`IStr`] inside of [`with_interner`] stores each distinct string only
+//! once, which reduces the peak memory of context aware policies evaluated
+//! against big clusters.
+//!
+//! ```
+//! use kubewarden_policy_sdk::intern::{with_interner, IStr};
+//! use serde::Deserialize;
+//! use std::collections::BTreeMap;
+//!
+//! #[derive(Deserialize)]
+//! struct Metadata {
+//!     name: IStr,
+//!     namespace: IStr,
+//!     #[serde(default)]
+//!     labels: BTreeMap<IStr, IStr>,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Item {
+//!     metadata: Metadata,
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct List {
+//!     items: Vec<Item>,
+//! }
+//!
+//! let raw = br#"{"items": [
+//!   {"metadata": {"name": "a", "namespace": "prod", "labels": {"app": "web"}}},
+//!   {"metadata": {"name": "b", "namespace": "prod", "labels": {"app": "web"}}}
+//! ]}"#;
+//! let (list, interned) = with_interner(|| serde_json::from_slice::<List>(raw).unwrap());
+//!
+//! assert!(IStr::ptr_eq(&list.items[0].metadata.namespace, &list.items[1].metadata.namespace));
+//! // "a", "b", "prod", "app" and "web"
+//! assert_eq!(interned, 5);
+//! ```
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::Rc;
+
+/// Set of the distinct strings seen so far
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Rc<str>>,
+}
+
+impl Interner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the interned copy of `s`, adding it to the interner when it's
+    /// seen for the first time
+    pub fn intern(&mut self, s: &str) -> IStr {
+        if let Some(interned) = self.strings.get(s) {
+            return IStr(interned.clone());
+        }
+        let interned: Rc<str> = Rc::from(s);
+        self.strings.insert(interned.clone());
+        IStr(interned)
+    }
+
+    /// Number of distinct strings
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns true when no string has been interned
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Option<Interner>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, interning all the [`IStr`] deserialized by it. Returns the
+/// result of `f` and the number of distinct strings that have been interned.
+///
+/// The interned strings are released once they are no longer referenced
+/// by the deserialized values.
+pub fn with_interner<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    struct Restore(Option<Interner>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            INTERNER.with(|i| *i.borrow_mut() = self.0.take());
+        }
+    }
+
+    let restore = Restore(INTERNER.with(|i| i.borrow_mut().replace(Interner::new())));
+    let result = f();
+    let interned = INTERNER.with(|i| i.borrow().as_ref().map(Interner::len).unwrap_or_default());
+    drop(restore);
+    (result, interned)
+}
+
+/// An immutable, reference counted, string. Strings deserialized inside of
+/// [`with_interner`] share their allocation with the identical ones
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IStr(Rc<str>);
+
+impl IStr {
+    /// Returns true when the two strings share the same allocation
+    pub fn ptr_eq(a: &IStr, b: &IStr) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// The string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for IStr {
+    /// Create a new string, interned when invoked inside of [`with_interner`]
+    fn from(s: &str) -> Self {
+        INTERNER.with(|i| match i.borrow_mut().as_mut() {
+            Some(interner) => interner.intern(s),
+            None => IStr(Rc::from(s)),
+        })
+    }
+}
+
+impl Deref for IStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for IStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for IStr {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for IStr {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for IStr {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl fmt::Debug for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for IStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl Serialize for IStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for IStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = IStr;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<IStr, E> {
+                Ok(IStr::from(v))
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn strings_are_shared_inside_of_the_scope() {
+        let raw = r#"[{"ns": "prod"}, {"ns": "prod"}, {"ns": "dev"}]"#;
+
+        let (values, interned) =
+            with_interner(|| serde_json::from_str::<Vec<HashMap<IStr, IStr>>>(raw).unwrap());
+        assert_eq!(interned, 3);
+        assert!(IStr::ptr_eq(&values[0]["ns"], &values[1]["ns"]));
+        assert_eq!(values[2]["ns"], "dev");
+
+        let values = serde_json::from_str::<Vec<HashMap<IStr, IStr>>>(raw).unwrap();
+        assert!(!IStr::ptr_eq(&values[0]["ns"], &values[1]["ns"]));
+        assert_eq!(
+            serde_json::to_string(&values[0]).unwrap(),
+            r#"{"ns":"prod"}"#
+        );
+    }
+
+    #[test]
+    fn interner_is_restored_after_nested_scopes() {
+        let (_, outer) = with_interner(|| {
+            let _ = IStr::from("a");
+            let (_, inner) = with_interner(|| IStr::from("b"));
+            assert_eq!(inner, 1);
+            let _ = IStr::from("c");
+        });
+        assert_eq!(outer, 2);
+        INTERNER.with(|i| assert!(i.borrow().is_none()));
+    }
+}
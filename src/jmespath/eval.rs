@@ -0,0 +1,512 @@
+use super::parser::{Ast, Comparator};
+use crate::error::{Result, SdkError};
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// Evaluate `ast` against the `current` node
+pub(crate) fn eval(ast: &Ast, current: &Value) -> Result<Value> {
+    match ast {
+        Ast::Current => Ok(current.clone()),
+        Ast::Field(name) => Ok(current.get(name).cloned().unwrap_or(Value::Null)),
+        Ast::Literal(value) => Ok(value.clone()),
+        Ast::Subexpression(left, right) | Ast::IndexExpression(left, right) => {
+            eval(right, &eval(left, current)?)
+        }
+        Ast::Index(index) => Ok(match current {
+            Value::Array(items) => resolve_index(*index, items.len())
+                .and_then(|i| items.get(i))
+                .cloned()
+                .unwrap_or(Value::Null),
+            _ => Value::Null,
+        }),
+        Ast::Slice(start, stop, step) => Ok(match current {
+            Value::Array(items) => Value::Array(slice(items, *start, *stop, *step)),
+            _ => Value::Null,
+        }),
+        Ast::Projection(left, right) => match eval(left, current)? {
+            Value::Array(items) => project(&items, right),
+            _ => Ok(Value::Null),
+        },
+        Ast::ValueProjection(left, right) => match eval(left, current)? {
+            Value::Object(map) => {
+                project(&map.into_iter().map(|(_, v)| v).collect::<Vec<_>>(), right)
+            }
+            _ => Ok(Value::Null),
+        },
+        Ast::FilterProjection(left, right, condition) => match eval(left, current)? {
+            Value::Array(items) => {
+                let mut matching = Vec::new();
+                for item in items {
+                    if is_truthy(&eval(condition, &item)?) {
+                        matching.push(item);
+                    }
+                }
+                project(&matching, right)
+            }
+            _ => Ok(Value::Null),
+        },
+        Ast::Flatten(operand) => match eval(operand, current)? {
+            Value::Array(items) => Ok(Value::Array(
+                items
+                    .into_iter()
+                    .flat_map(|item| match item {
+                        Value::Array(inner) => inner,
+                        other => vec![other],
+                    })
+                    .collect(),
+            )),
+            _ => Ok(Value::Null),
+        },
+        Ast::Comparator(comparator, left, right) => {
+            let left = eval(left, current)?;
+            let right = eval(right, current)?;
+            Ok(compare(*comparator, &left, &right))
+        }
+        Ast::Or(left, right) => {
+            let left = eval(left, current)?;
+            if is_truthy(&left) {
+                Ok(left)
+            } else {
+                eval(right, current)
+            }
+        }
+        Ast::And(left, right) => {
+            let left = eval(left, current)?;
+            if is_truthy(&left) {
+                eval(right, current)
+            } else {
+                Ok(left)
+            }
+        }
+        Ast::Not(operand) => Ok(Value::Bool(!is_truthy(&eval(operand, current)?))),
+        Ast::Pipe(left, right) => eval(right, &eval(left, current)?),
+        Ast::MultiSelectList(items) => {
+            if current.is_null() {
+                return Ok(Value::Null);
+            }
+            items
+                .iter()
+                .map(|item| eval(item, current))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array)
+        }
+        Ast::MultiSelectHash(entries) => {
+            if current.is_null() {
+                return Ok(Value::Null);
+            }
+            let mut map = Map::new();
+            for (key, value) in entries {
+                map.insert(key.clone(), eval(value, current)?);
+            }
+            Ok(Value::Object(map))
+        }
+        Ast::Function(name, args) => call(name, args, current),
+        Ast::ExpRef(_) => Err(SdkError::InvalidInput(
+            "expression references can only be function arguments".to_string(),
+        )),
+    }
+}
+
+/// The truthiness rules of JMESPath: false, null and the empty strings,
+/// arrays and objects are false
+pub(crate) fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        Value::Number(_) => true,
+    }
+}
+
+/// Evaluate `right` against all the items, dropping the null results
+fn project(items: &[Value], right: &Ast) -> Result<Value> {
+    let mut projected = Vec::new();
+    for item in items {
+        let value = eval(right, item)?;
+        if !value.is_null() {
+            projected.push(value);
+        }
+    }
+    Ok(Value::Array(projected))
+}
+
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(usize::try_from(index.unsigned_abs()).ok()?)
+    } else {
+        usize::try_from(index).ok()
+    }
+}
+
+fn slice(items: &[Value], start: Option<i64>, stop: Option<i64>, step: Option<i64>) -> Vec<Value> {
+    let len = items.len() as i64;
+    let step = step.unwrap_or(1);
+    // clamp the bounds the way Python does
+    let bound = |value: Option<i64>, default: i64| match value {
+        None => default,
+        Some(v) if v < 0 => (len + v).max(if step < 0 { -1 } else { 0 }),
+        Some(v) => v.min(if step < 0 { len - 1 } else { len }),
+    };
+    let (start, stop) = if step > 0 {
+        (bound(start, 0), bound(stop, len))
+    } else {
+        (bound(start, len - 1), bound(stop, -1))
+    };
+
+    let mut sliced = Vec::new();
+    let mut i = start;
+    while (step > 0 && i < stop) || (step < 0 && i > stop) {
+        if let Some(item) = usize::try_from(i).ok().and_then(|i| items.get(i)) {
+            sliced.push(item.clone());
+        }
+        i += step;
+    }
+    sliced
+}
+
+fn compare(comparator: Comparator, left: &Value, right: &Value) -> Value {
+    match comparator {
+        Comparator::Eq => Value::Bool(equals(left, right)),
+        Comparator::Ne => Value::Bool(!equals(left, right)),
+        _ => match (left.as_f64(), right.as_f64()) {
+            (Some(l), Some(r)) => Value::Bool(match comparator {
+                Comparator::Lt => l < r,
+                Comparator::Le => l <= r,
+                Comparator::Gt => l > r,
+                _ => l >= r,
+            }),
+            // ordering is defined only for numbers
+            _ => Value::Null,
+        },
+    }
+}
+
+/// Equality where numbers are compared by value, `1` equals `1.0`
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64() == r.as_f64(),
+        (Value::Array(l), Value::Array(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| equals(l, r))
+        }
+        (Value::Object(l), Value::Object(r)) => {
+            l.len() == r.len()
+                && l.iter()
+                    .all(|(k, v)| r.get(k).is_some_and(|r| equals(v, r)))
+        }
+        _ => left == right,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn number(value: f64) -> Value {
+    // integral results are returned as integers, like the JSON input
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Number::from_f64(value)
+            .map(Value::Number)
+            .unwrap_or(Value::Null)
+    }
+}
+
+fn invalid(name: &str, value: &Value) -> SdkError {
+    SdkError::InvalidInput(format!(
+        "invalid type for {}(): {} is not supported",
+        name,
+        type_name(value)
+    ))
+}
+
+/// The sort key of the elements of `sort`, `sort_by`, `max` and `min`:
+/// either all numbers or all strings
+fn sort_key(name: &str, values: &[Value]) -> Result<Vec<Key>> {
+    let keys: Vec<Key> = values
+        .iter()
+        .map(|value| match value {
+            Value::Number(n) => Ok(Key::Number(n.as_f64().unwrap_or_default())),
+            Value::String(s) => Ok(Key::String(s.clone())),
+            other => Err(invalid(name, other)),
+        })
+        .collect::<Result<_>>()?;
+    let numbers = keys.iter().filter(|k| matches!(k, Key::Number(_))).count();
+    if numbers != 0 && numbers != keys.len() {
+        return Err(SdkError::InvalidInput(format!(
+            "{}() cannot mix numbers and strings",
+            name
+        )));
+    }
+    Ok(keys)
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+enum Key {
+    Number(f64),
+    String(String),
+}
+
+fn compare_keys(a: &Key, b: &Key) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+fn call(name: &str, args: &[Ast], current: &Value) -> Result<Value> {
+    let arity = |expected: usize| -> Result<()> {
+        if args.len() == expected {
+            Ok(())
+        } else {
+            Err(SdkError::InvalidInput(format!(
+                "{}() takes {} arguments, {} given",
+                name,
+                expected,
+                args.len()
+            )))
+        }
+    };
+    let expref = |i: usize| -> Result<&Ast> {
+        match &args[i] {
+            Ast::ExpRef(ast) => Ok(ast),
+            _ => Err(SdkError::InvalidInput(format!(
+                "{}() expects an expression reference",
+                name
+            ))),
+        }
+    };
+    let values = || -> Result<Vec<Value>> {
+        args.iter()
+            .map(|arg| match arg {
+                Ast::ExpRef(_) => Err(SdkError::InvalidInput(format!(
+                    "{}() doesn't accept expression references",
+                    name
+                ))),
+                arg => eval(arg, current),
+            })
+            .collect()
+    };
+
+    match name {
+        "abs" | "ceil" | "floor" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            let n = value.as_f64().ok_or_else(|| invalid(name, &value))?;
+            Ok(number(match name {
+                "abs" => n.abs(),
+                "ceil" => n.ceil(),
+                _ => n.floor(),
+            }))
+        }
+        "avg" | "sum" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            let items = value.as_array().ok_or_else(|| invalid(name, &value))?;
+            let numbers = items
+                .iter()
+                .map(|item| item.as_f64().ok_or_else(|| invalid(name, item)))
+                .collect::<Result<Vec<f64>>>()?;
+            let sum: f64 = numbers.iter().sum();
+            Ok(match name {
+                "sum" => number(sum),
+                _ if numbers.is_empty() => Value::Null,
+                _ => number(sum / numbers.len() as f64),
+            })
+        }
+        "contains" => {
+            arity(2)?;
+            let values = values()?;
+            match (&values[0], &values[1]) {
+                (Value::Array(items), search) => {
+                    Ok(Value::Bool(items.iter().any(|item| equals(item, search))))
+                }
+                (Value::String(s), Value::String(search)) => {
+                    Ok(Value::Bool(s.contains(search.as_str())))
+                }
+                (Value::String(_), _) => Ok(Value::Bool(false)),
+                (other, _) => Err(invalid(name, other)),
+            }
+        }
+        "starts_with" | "ends_with" => {
+            arity(2)?;
+            let values = values()?;
+            match (&values[0], &values[1]) {
+                (Value::String(s), Value::String(affix)) => {
+                    Ok(Value::Bool(if name == "starts_with" {
+                        s.starts_with(affix.as_str())
+                    } else {
+                        s.ends_with(affix.as_str())
+                    }))
+                }
+                (Value::String(_), other) | (other, _) => Err(invalid(name, other)),
+            }
+        }
+        "join" => {
+            arity(2)?;
+            let values = values()?;
+            let separator = values[0]
+                .as_str()
+                .ok_or_else(|| invalid(name, &values[0]))?;
+            let items = values[1]
+                .as_array()
+                .ok_or_else(|| invalid(name, &values[1]))?;
+            let strings = items
+                .iter()
+                .map(|item| item.as_str().ok_or_else(|| invalid(name, item)))
+                .collect::<Result<Vec<&str>>>()?;
+            Ok(Value::String(strings.join(separator)))
+        }
+        "keys" | "values" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            let Value::Object(map) = value else {
+                return Err(invalid(name, &value));
+            };
+            Ok(Value::Array(if name == "keys" {
+                map.into_iter().map(|(k, _)| Value::String(k)).collect()
+            } else {
+                map.into_iter().map(|(_, v)| v).collect()
+            }))
+        }
+        "length" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            Ok(Value::from(match &value {
+                Value::String(s) => s.chars().count(),
+                Value::Array(items) => items.len(),
+                Value::Object(map) => map.len(),
+                other => return Err(invalid(name, other)),
+            }))
+        }
+        "map" => {
+            arity(2)?;
+            let ast = expref(0)?;
+            let value = eval(&args[1], current)?;
+            let items = value.as_array().ok_or_else(|| invalid(name, &value))?;
+            items
+                .iter()
+                .map(|item| eval(ast, item))
+                .collect::<Result<Vec<_>>>()
+                .map(Value::Array)
+        }
+        "max" | "min" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            let items = value.as_array().ok_or_else(|| invalid(name, &value))?;
+            let keys = sort_key(name, items)?;
+            let best = keys.iter().enumerate().reduce(|best, candidate| {
+                let ordering = compare_keys(candidate.1, best.1);
+                let better = if name == "max" {
+                    ordering == Ordering::Greater
+                } else {
+                    ordering == Ordering::Less
+                };
+                if better {
+                    candidate
+                } else {
+                    best
+                }
+            });
+            Ok(best.map(|(i, _)| items[i].clone()).unwrap_or(Value::Null))
+        }
+        "max_by" | "min_by" | "sort_by" => {
+            arity(2)?;
+            let value = eval(&args[0], current)?;
+            let ast = expref(1)?;
+            let items = value.as_array().ok_or_else(|| invalid(name, &value))?;
+            let keys = sort_key(
+                name,
+                &items
+                    .iter()
+                    .map(|item| eval(ast, item))
+                    .collect::<Result<Vec<_>>>()?,
+            )?;
+            let mut indexes: Vec<usize> = (0..items.len()).collect();
+            indexes.sort_by(|a, b| compare_keys(&keys[*a], &keys[*b]));
+            Ok(match name {
+                "sort_by" => Value::Array(indexes.into_iter().map(|i| items[i].clone()).collect()),
+                "max_by" => indexes
+                    .last()
+                    .map(|i| items[*i].clone())
+                    .unwrap_or(Value::Null),
+                _ => indexes
+                    .first()
+                    .map(|i| items[*i].clone())
+                    .unwrap_or(Value::Null),
+            })
+        }
+        "merge" => {
+            let mut merged = Map::new();
+            for value in values()? {
+                let Value::Object(map) = value else {
+                    return Err(invalid(name, &value));
+                };
+                merged.extend(map);
+            }
+            Ok(Value::Object(merged))
+        }
+        "not_null" => Ok(values()?
+            .into_iter()
+            .find(|value| !value.is_null())
+            .unwrap_or(Value::Null)),
+        "reverse" => {
+            arity(1)?;
+            match values()?.remove(0) {
+                Value::Array(mut items) => {
+                    items.reverse();
+                    Ok(Value::Array(items))
+                }
+                Value::String(s) => Ok(Value::String(s.chars().rev().collect())),
+                other => Err(invalid(name, &other)),
+            }
+        }
+        "sort" => {
+            arity(1)?;
+            let value = values()?.remove(0);
+            let items = value.as_array().ok_or_else(|| invalid(name, &value))?;
+            let keys = sort_key(name, items)?;
+            let mut indexes: Vec<usize> = (0..items.len()).collect();
+            indexes.sort_by(|a, b| compare_keys(&keys[*a], &keys[*b]));
+            Ok(Value::Array(
+                indexes.into_iter().map(|i| items[i].clone()).collect(),
+            ))
+        }
+        "to_array" => {
+            arity(1)?;
+            Ok(match values()?.remove(0) {
+                Value::Array(items) => Value::Array(items),
+                other => Value::Array(vec![other]),
+            })
+        }
+        "to_number" => {
+            arity(1)?;
+            Ok(match values()?.remove(0) {
+                Value::Number(n) => Value::Number(n),
+                Value::String(s) => s.trim().parse::<f64>().map(number).unwrap_or(Value::Null),
+                _ => Value::Null,
+            })
+        }
+        "to_string" => {
+            arity(1)?;
+            Ok(match values()?.remove(0) {
+                Value::String(s) => Value::String(s),
+                other => Value::String(other.to_string()),
+            })
+        }
+        "type" => {
+            arity(1)?;
+            Ok(Value::String(type_name(&values()?[0]).to_string()))
+        }
+        _ => Err(SdkError::InvalidInput(format!(
+            "unknown function {}()",
+            name
+        ))),
+    }
+}
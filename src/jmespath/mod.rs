@@ -0,0 +1,289 @@
+//! Evaluation of [JMESPath](https://jmespath.org/specification.html) queries,
+//! the same query language used by Kyverno and by the AWS CLI.
+//!
+//! Queries can be part of the policy settings, so that users can select the
+//! parts of the request to check without recompiling the policy. This module
+//! implements JMESPath without requiring any additional dependency:
+//!
+//! * identifiers, sub-expressions, indexes, slices and pipes
+//! * list, object and filter projections, plus flattening
+//! * multi-select lists and hashes
+//! * comparisons, `||`, `&&` and `!`
+//! * the functions of the specification: `abs`, `avg`, `ceil`, `contains`,
+//!   `ends_with`, `floor`, `join`, `keys`, `length`, `map`, `max`, `max_by`,
+//!   `merge`, `min`, `min_by`, `not_null`, `reverse`, `sort`, `sort_by`,
+//!   `starts_with`, `sum`, `to_array`, `to_number`, `to_string`, `type`
+//!   and `values`
+//!
+//! Queries are evaluated against [`serde_json::Value`] objects. Errors, like
+//! calling a function with the wrong types, are reported with an [`SdkError::InvalidInput`].
+//!
+//! This module is available when the `jmespath` feature is enabled.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::jmespath::Expression;
+//! use serde_json::json;
+//!
+//! let expression = Expression::compile("spec.containers[?ends_with(image, ':latest')].name").unwrap();
+//!
+//! let pod = json!({
+//!     "spec": {
+//!         "containers": [
+//!             {"name": "nginx", "image": "nginx:latest"},
+//!             {"name": "sidecar", "image": "sidecar:1.0"}
+//!         ]
+//!     }
+//! });
+//! assert_eq!(expression.search(&pod).unwrap(), json!(["nginx"]));
+//! ```
+use crate::error::{Result, SdkError};
+use crate::request::KubernetesAdmissionRequest;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+mod eval;
+mod parser;
+
+/// A compiled JMESPath expression, which can be evaluated multiple times.
+///
+/// Expressions can be deserialized from strings, which makes them usable as
+/// fields of the policy settings: invalid queries are rejected when the
+/// settings are loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    ast: parser::Ast,
+}
+
+impl Expression {
+    /// Parse the given expression. An error is returned when the expression
+    /// is not valid
+    pub fn compile(expression: &str) -> Result<Self> {
+        let ast = parser::parse(expression).map_err(|e| {
+            SdkError::InvalidInput(format!("cannot compile '{}': {}", expression, e))
+        })?;
+        Ok(Expression {
+            source: expression.to_string(),
+            ast,
+        })
+    }
+
+    /// The source of the expression
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Evaluate the expression against `data`
+    pub fn search(&self, data: &Value) -> Result<Value> {
+        eval::eval(&self.ast, data)
+    }
+
+    /// Evaluate the expression against the admission request. The query
+    /// can reference `request`, `object` and `oldObject`, the same variables
+    /// bound to CEL expressions. `object` and `oldObject` are `null` when they
+    /// are not part of the request
+    pub fn search_request(&self, request: &KubernetesAdmissionRequest) -> Result<Value> {
+        let data = json!({
+            "request": serde_json::to_value(request)
+                .map_err(|e| SdkError::serialization("the admission request", e))?,
+            "object": request.object.value(),
+            "oldObject": request.old_object.value(),
+        });
+        self.search(&data)
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = SdkError;
+
+    fn try_from(expression: String) -> Result<Self> {
+        Expression::compile(&expression)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(expression: Expression) -> Self {
+        expression.source
+    }
+}
+
+/// Whether `value` is true according to JMESPath: `false`, `null` and the
+/// empty strings, arrays and objects are false, everything else is true
+pub fn is_truthy(value: &Value) -> bool {
+    eval::is_truthy(value)
+}
+
+/// Compile and evaluate the given expression. The compiled expression is
+/// cached, see [`lazy_cache!`](crate::lazy_cache)
+pub fn search(expression: &str, data: &Value) -> Result<Value> {
+    crate::lazy_cache!(Expression, expression, Expression::compile)?.search(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(expression: &str) -> Result<Value> {
+        let data = json!({
+            "metadata": {"name": "nginx", "labels": {"app": "web", "tier": "frontend"}},
+            "spec": {
+                "replicas": 3,
+                "containers": [
+                    {"name": "nginx", "image": "nginx:1.25", "ports": [{"containerPort": 80}]},
+                    {"name": "sidecar", "image": "ghcr.io/sidecar:latest", "ports": [{"containerPort": 9090}, {"containerPort": 9091}]}
+                ]
+            }
+        });
+        super::search(expression, &data)
+    }
+
+    #[test]
+    fn fields_and_indexes() {
+        assert_eq!(search("metadata.name").unwrap(), json!("nginx"));
+        assert_eq!(search("metadata.missing.field").unwrap(), json!(null));
+        assert_eq!(search("spec.containers[0].name").unwrap(), json!("nginx"));
+        assert_eq!(
+            search("spec.containers[-1].name").unwrap(),
+            json!("sidecar")
+        );
+        assert_eq!(search("spec.containers[5]").unwrap(), json!(null));
+        assert_eq!(search("metadata.\"labels\".app").unwrap(), json!("web"));
+        assert_eq!(search("spec.replicas | @").unwrap(), json!(3));
+    }
+
+    #[test]
+    fn projections() {
+        assert_eq!(
+            search("spec.containers[*].name").unwrap(),
+            json!(["nginx", "sidecar"])
+        );
+        assert_eq!(
+            search("spec.containers[].ports[].containerPort").unwrap(),
+            json!([80, 9090, 9091])
+        );
+        assert_eq!(
+            search("spec.containers[?name == 'sidecar'].image").unwrap(),
+            json!(["ghcr.io/sidecar:latest"])
+        );
+        assert_eq!(
+            search("spec.containers[?length(ports) > `1`] | [0].name").unwrap(),
+            json!("sidecar")
+        );
+        assert_eq!(
+            search("sort(metadata.labels.*)").unwrap(),
+            json!(["frontend", "web"])
+        );
+        assert_eq!(search("`[1, 2, 3, 4, 5]`[1:4:2]").unwrap(), json!([2, 4]));
+        assert_eq!(search("`[1, 2, 3]`[::-1]").unwrap(), json!([3, 2, 1]));
+    }
+
+    #[test]
+    fn multi_select_and_logic() {
+        assert_eq!(
+            search("{name: metadata.name, images: spec.containers[*].image}").unwrap(),
+            json!({"name": "nginx", "images": ["nginx:1.25", "ghcr.io/sidecar:latest"]})
+        );
+        assert_eq!(
+            search("[metadata.name, spec.replicas]").unwrap(),
+            json!(["nginx", 3])
+        );
+        assert_eq!(
+            search("metadata.missing || 'default'").unwrap(),
+            json!("default")
+        );
+        assert_eq!(search("metadata.name && spec.replicas").unwrap(), json!(3));
+        assert_eq!(search("!(metadata.missing)").unwrap(), json!(true));
+        assert_eq!(search("spec.replicas >= `3`").unwrap(), json!(true));
+        assert_eq!(search("metadata.name < `3`").unwrap(), json!(null));
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(search("length(spec.containers)").unwrap(), json!(2));
+        assert_eq!(
+            search("join(',', spec.containers[*].name)").unwrap(),
+            json!("nginx,sidecar")
+        );
+        assert_eq!(
+            search("map(&name, spec.containers)").unwrap(),
+            json!(["nginx", "sidecar"])
+        );
+        assert_eq!(
+            search("max_by(spec.containers, &length(ports)).name").unwrap(),
+            json!("sidecar")
+        );
+        assert_eq!(
+            search("sort_by(spec.containers, &image)[0].name").unwrap(),
+            json!("sidecar")
+        );
+        assert_eq!(
+            search("contains(keys(metadata.labels), 'app')").unwrap(),
+            json!(true)
+        );
+        assert_eq!(search("avg(`[1, 2, 4, 5]`)").unwrap(), json!(3));
+        assert_eq!(search("sum(`[0.5, 1]`)").unwrap(), json!(1.5));
+        assert_eq!(search("to_number('42')").unwrap(), json!(42));
+        assert_eq!(search("type(metadata)").unwrap(), json!("object"));
+        assert_eq!(
+            search("merge(metadata.labels, `{\"app\": \"api\"}`).app").unwrap(),
+            json!("api")
+        );
+        assert!(search("length(spec.replicas)").is_err());
+        assert!(search("unknown(@)").is_err());
+        assert!(search("sort(`[1, \"a\"]`)").is_err());
+    }
+
+    #[test]
+    fn search_admission_requests() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(json!({
+            "uid": "uid",
+            "operation": "UPDATE",
+            "userInfo": {"username": "alice"},
+            "object": {"metadata": {"labels": {"env": "prod"}}},
+            "oldObject": {"metadata": {"labels": {"env": "dev"}}}
+        }))
+        .unwrap();
+
+        let expression =
+            Expression::compile("object.metadata.labels.env != oldObject.metadata.labels.env")
+                .unwrap();
+        assert_eq!(expression.search_request(&request).unwrap(), json!(true));
+
+        let expression = Expression::compile("request.userInfo.username").unwrap();
+        assert_eq!(expression.search_request(&request).unwrap(), json!("alice"));
+
+        let expression = Expression::compile("request.user_info.username").unwrap();
+        assert_eq!(expression.search_request(&request).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn expressions_in_settings() {
+        #[derive(Deserialize, Serialize)]
+        struct Settings {
+            selector: Expression,
+        }
+
+        let settings: Settings =
+            serde_json::from_value(json!({"selector": "object.metadata.name"})).unwrap();
+        assert_eq!(settings.selector.as_str(), "object.metadata.name");
+        assert_eq!(
+            serde_json::to_value(&settings).unwrap(),
+            json!({"selector": "object.metadata.name"})
+        );
+
+        let err = serde_json::from_value::<Settings>(json!({"selector": "object.["}))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("cannot compile 'object.['"));
+        assert!(!is_truthy(&json!([])));
+    }
+}
@@ -0,0 +1,537 @@
+use crate::error::{Result, SdkError};
+use serde_json::Value;
+
+/// Abstract syntax tree of a JMESPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Ast {
+    Current,
+    Field(String),
+    Literal(Value),
+    Subexpression(Box<Ast>, Box<Ast>),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    IndexExpression(Box<Ast>, Box<Ast>),
+    /// Evaluate the right side against all the elements of the list
+    /// produced by the left side
+    Projection(Box<Ast>, Box<Ast>),
+    /// Like [`Ast::Projection`], over the values of an object
+    ValueProjection(Box<Ast>, Box<Ast>),
+    /// Like [`Ast::Projection`], over the elements matching the condition
+    FilterProjection(Box<Ast>, Box<Ast>, Box<Ast>),
+    Flatten(Box<Ast>),
+    Comparator(Comparator, Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Not(Box<Ast>),
+    Pipe(Box<Ast>, Box<Ast>),
+    MultiSelectList(Vec<Ast>),
+    MultiSelectHash(Vec<(String, Ast)>),
+    Function(String, Vec<Ast>),
+    ExpRef(Box<Ast>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    QuotedIdentifier(String),
+    Literal(Value),
+    Number(i64),
+    Punct(&'static str),
+    Eof,
+}
+
+/// The punctuation, longest first
+const PUNCTUATION: &[&str] = &[
+    "[?", "[]", "||", "&&", "==", "!=", "<=", ">=", ".", "*", "[", "]", "{", "}", "(", ")", ",",
+    ":", "|", "<", ">", "!", "@", "&",
+];
+
+/// Binding power of the tokens, the ones not listed don't bind
+fn binding_power(token: &Token) -> u8 {
+    match token {
+        Token::Punct(punct) => match *punct {
+            "|" => 1,
+            "||" => 2,
+            "&&" => 3,
+            "==" | "!=" | "<" | "<=" | ">" | ">=" => 5,
+            "[]" => 9,
+            "*" => 20,
+            "[?" => 21,
+            "." => 40,
+            "!" => 45,
+            "{" => 50,
+            "[" => 55,
+            "(" => 60,
+            _ => 0,
+        },
+        _ => 0,
+    }
+}
+
+/// Tokens with a binding power lower than this one stop a projection
+const PROJECTION_STOP: u8 = 10;
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    // the raw text up to the closing `delimiter`, which can be escaped with
+    // a backslash
+    let delimited = |pos: &mut usize, delimiter: char| -> Result<String> {
+        let mut raw = String::new();
+        *pos += 1;
+        loop {
+            let c = *chars
+                .get(*pos)
+                .ok_or_else(|| SdkError::InvalidInput(format!("unterminated {}", delimiter)))?;
+            *pos += 1;
+            match c {
+                '\\' => {
+                    raw.push(c);
+                    if let Some(next) = chars.get(*pos) {
+                        raw.push(*next);
+                        *pos += 1;
+                    }
+                }
+                c if c == delimiter => return Ok(raw),
+                c => raw.push(c),
+            }
+        }
+    };
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            tokens.push(Token::Identifier(chars[start..pos].iter().collect()));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(pos + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && chars[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            let text: String = chars[start..pos].iter().collect();
+            let number = text
+                .parse()
+                .map_err(|_| SdkError::InvalidInput(format!("number out of range: {}", text)))?;
+            tokens.push(Token::Number(number));
+        } else if c == '"' {
+            // the escape sequences of quoted identifiers are the JSON ones
+            let raw = delimited(&mut pos, '"')?;
+            let name = serde_json::from_str(&format!("\"{}\"", raw)).map_err(|e| {
+                SdkError::InvalidInput(format!("invalid quoted identifier \"{}\": {}", raw, e))
+            })?;
+            tokens.push(Token::QuotedIdentifier(name));
+        } else if c == '\'' {
+            let raw = delimited(&mut pos, '\'')?;
+            tokens.push(Token::Literal(Value::String(raw.replace("\\'", "'"))));
+        } else if c == '`' {
+            let raw = delimited(&mut pos, '`')?;
+            let value = serde_json::from_str(raw.replace("\\`", "`").trim()).map_err(|e| {
+                SdkError::InvalidInput(format!("invalid JSON literal `{}`: {}", raw, e))
+            })?;
+            tokens.push(Token::Literal(value));
+        } else {
+            let rest: String = chars[pos..chars.len().min(pos + 2)].iter().collect();
+            let punct = PUNCTUATION
+                .iter()
+                .find(|p| rest.starts_with(**p))
+                .ok_or_else(|| SdkError::InvalidInput(format!("unexpected character '{}'", c)))?;
+            pos += punct.chars().count();
+            tokens.push(Token::Punct(punct));
+        }
+    }
+
+    tokens.push(Token::Eof);
+    Ok(tokens)
+}
+
+/// Parse a JMESPath expression
+pub(crate) fn parse(input: &str) -> Result<Ast> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    let ast = parser.expression(0)?;
+    match parser.peek() {
+        Token::Eof => Ok(ast),
+        token => Err(SdkError::InvalidInput(format!(
+            "unexpected token {:?}",
+            token
+        ))),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        self.tokens.get(self.pos + offset).unwrap_or(&Token::Eof)
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        self.pos += 1;
+        token
+    }
+
+    fn is(&self, punct: &str) -> bool {
+        matches!(self.peek(), Token::Punct(p) if *p == punct)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<()> {
+        if self.is(punct) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(SdkError::InvalidInput(format!(
+                "expected '{}', found {:?}",
+                punct,
+                self.peek()
+            )))
+        }
+    }
+
+    fn expression(&mut self, power: u8) -> Result<Ast> {
+        let token = self.next();
+        let mut left = self.nud(token)?;
+        while power < binding_power(self.peek()) {
+            let token = self.next();
+            left = self.led(token, left)?;
+        }
+        Ok(left)
+    }
+
+    /// Parse the tokens starting an expression
+    fn nud(&mut self, token: Token) -> Result<Ast> {
+        match token {
+            Token::Identifier(name) => Ok(Ast::Field(name)),
+            Token::QuotedIdentifier(name) => {
+                if self.is("(") {
+                    return Err(SdkError::InvalidInput(
+                        "quoted identifiers cannot be function names".to_string(),
+                    ));
+                }
+                Ok(Ast::Field(name))
+            }
+            Token::Literal(value) => Ok(Ast::Literal(value)),
+            Token::Punct("@") => Ok(Ast::Current),
+            Token::Punct("&") => Ok(Ast::ExpRef(Box::new(self.expression(0)?))),
+            Token::Punct("!") => Ok(Ast::Not(Box::new(self.expression(45)?))),
+            Token::Punct("(") => {
+                let ast = self.expression(0)?;
+                self.expect(")")?;
+                Ok(ast)
+            }
+            Token::Punct("*") => {
+                let right = self.projection_rhs(20)?;
+                Ok(Ast::ValueProjection(
+                    Box::new(Ast::Current),
+                    Box::new(right),
+                ))
+            }
+            Token::Punct("[]") => {
+                let left = Ast::Flatten(Box::new(Ast::Current));
+                let right = self.projection_rhs(9)?;
+                Ok(Ast::Projection(Box::new(left), Box::new(right)))
+            }
+            Token::Punct("[?") => self.filter(Ast::Current),
+            Token::Punct("{") => self.multi_select_hash(),
+            Token::Punct("[") => match self.peek() {
+                Token::Number(_) | Token::Punct(":") => {
+                    let right = self.index()?;
+                    self.project_if_slice(Ast::Current, right)
+                }
+                Token::Punct("*") if matches!(self.peek_at(1), Token::Punct("]")) => {
+                    self.pos += 2;
+                    let right = self.projection_rhs(20)?;
+                    Ok(Ast::Projection(Box::new(Ast::Current), Box::new(right)))
+                }
+                _ => self.multi_select_list(),
+            },
+            token => Err(SdkError::InvalidInput(format!(
+                "unexpected token {:?}",
+                token
+            ))),
+        }
+    }
+
+    /// Parse the tokens continuing the `left` expression
+    fn led(&mut self, token: Token, left: Ast) -> Result<Ast> {
+        let Token::Punct(punct) = token else {
+            return Err(SdkError::InvalidInput(format!(
+                "unexpected token {:?}",
+                token
+            )));
+        };
+        match punct {
+            "." => {
+                if self.is("*") {
+                    self.pos += 1;
+                    let right = self.projection_rhs(40)?;
+                    return Ok(Ast::ValueProjection(Box::new(left), Box::new(right)));
+                }
+                let right = self.dot_rhs(40)?;
+                Ok(Ast::Subexpression(Box::new(left), Box::new(right)))
+            }
+            "|" => Ok(Ast::Pipe(Box::new(left), Box::new(self.expression(1)?))),
+            "||" => Ok(Ast::Or(Box::new(left), Box::new(self.expression(2)?))),
+            "&&" => Ok(Ast::And(Box::new(left), Box::new(self.expression(3)?))),
+            "==" | "!=" | "<" | "<=" | ">" | ">=" => {
+                let comparator = match punct {
+                    "==" => Comparator::Eq,
+                    "!=" => Comparator::Ne,
+                    "<" => Comparator::Lt,
+                    "<=" => Comparator::Le,
+                    ">" => Comparator::Gt,
+                    _ => Comparator::Ge,
+                };
+                let right = self.expression(5)?;
+                Ok(Ast::Comparator(comparator, Box::new(left), Box::new(right)))
+            }
+            "[]" => {
+                let left = Ast::Flatten(Box::new(left));
+                let right = self.projection_rhs(9)?;
+                Ok(Ast::Projection(Box::new(left), Box::new(right)))
+            }
+            "[?" => self.filter(left),
+            "[" => match self.peek() {
+                Token::Number(_) | Token::Punct(":") => {
+                    let right = self.index()?;
+                    self.project_if_slice(left, right)
+                }
+                _ => {
+                    self.expect("*")?;
+                    self.expect("]")?;
+                    let right = self.projection_rhs(20)?;
+                    Ok(Ast::Projection(Box::new(left), Box::new(right)))
+                }
+            },
+            "(" => {
+                let Ast::Field(name) = left else {
+                    return Err(SdkError::InvalidInput(format!(
+                        "invalid function name {:?}",
+                        left
+                    )));
+                };
+                let mut args = Vec::new();
+                while !self.is(")") {
+                    args.push(self.expression(0)?);
+                    if !self.is(")") {
+                        self.expect(",")?;
+                    }
+                }
+                self.pos += 1;
+                Ok(Ast::Function(name, args))
+            }
+            _ => Err(SdkError::InvalidInput(format!(
+                "unexpected token '{}'",
+                punct
+            ))),
+        }
+    }
+
+    fn filter(&mut self, left: Ast) -> Result<Ast> {
+        let condition = self.expression(0)?;
+        self.expect("]")?;
+        let right = if self.is("[]") {
+            Ast::Current
+        } else {
+            self.projection_rhs(21)?
+        };
+        Ok(Ast::FilterProjection(
+            Box::new(left),
+            Box::new(right),
+            Box::new(condition),
+        ))
+    }
+
+    /// Parse an index or a slice, the opening bracket is already consumed
+    fn index(&mut self) -> Result<Ast> {
+        let mut parts = [None, None, None];
+        let mut part = 0;
+        loop {
+            match self.next() {
+                Token::Number(n) if parts[part].is_none() => parts[part] = Some(n),
+                Token::Punct(":") if part < 2 => part += 1,
+                Token::Punct("]") => break,
+                token => {
+                    return Err(SdkError::InvalidInput(format!(
+                        "unexpected token {:?} in index",
+                        token
+                    )))
+                }
+            }
+        }
+        match (part, parts) {
+            (0, [Some(index), _, _]) => Ok(Ast::Index(index)),
+            (0, _) => Err(SdkError::InvalidInput("empty index".to_string())),
+            (_, [_, _, Some(0)]) => Err(SdkError::InvalidInput(
+                "the step of a slice cannot be 0".to_string(),
+            )),
+            (_, [start, stop, step]) => Ok(Ast::Slice(start, stop, step)),
+        }
+    }
+
+    fn project_if_slice(&mut self, left: Ast, right: Ast) -> Result<Ast> {
+        let is_slice = matches!(right, Ast::Slice(..));
+        let index = Ast::IndexExpression(Box::new(left), Box::new(right));
+        if is_slice {
+            let right = self.projection_rhs(20)?;
+            Ok(Ast::Projection(Box::new(index), Box::new(right)))
+        } else {
+            Ok(index)
+        }
+    }
+
+    /// The expression applied to the elements of a projection
+    fn projection_rhs(&mut self, power: u8) -> Result<Ast> {
+        if binding_power(self.peek()) < PROJECTION_STOP {
+            return Ok(Ast::Current);
+        }
+        if self.is("[") || self.is("[?") {
+            return self.expression(power);
+        }
+        if self.is(".") {
+            self.pos += 1;
+            return self.dot_rhs(power);
+        }
+        Err(SdkError::InvalidInput(format!(
+            "unexpected token {:?} after a projection",
+            self.peek()
+        )))
+    }
+
+    fn dot_rhs(&mut self, power: u8) -> Result<Ast> {
+        match self.peek() {
+            Token::Identifier(_) | Token::QuotedIdentifier(_) | Token::Punct("*") => {
+                self.expression(power)
+            }
+            Token::Punct("[") => {
+                self.pos += 1;
+                self.multi_select_list()
+            }
+            Token::Punct("{") => {
+                self.pos += 1;
+                self.multi_select_hash()
+            }
+            token => Err(SdkError::InvalidInput(format!(
+                "unexpected token {:?} after '.'",
+                token
+            ))),
+        }
+    }
+
+    fn multi_select_list(&mut self) -> Result<Ast> {
+        let mut items = Vec::new();
+        loop {
+            items.push(self.expression(0)?);
+            if self.is("]") {
+                self.pos += 1;
+                return Ok(Ast::MultiSelectList(items));
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn multi_select_hash(&mut self) -> Result<Ast> {
+        let mut entries = Vec::new();
+        loop {
+            let key = match self.next() {
+                Token::Identifier(key) | Token::QuotedIdentifier(key) => key,
+                token => {
+                    return Err(SdkError::InvalidInput(format!(
+                        "expected a key, found {:?}",
+                        token
+                    )))
+                }
+            };
+            self.expect(":")?;
+            entries.push((key, self.expression(0)?));
+            if self.is("}") {
+                self.pos += 1;
+                return Ok(Ast::MultiSelectHash(entries));
+            }
+            self.expect(",")?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_projections() {
+        let ast = parse("containers[*].image").unwrap();
+        match ast {
+            Ast::Projection(left, right) => {
+                assert_eq!(*left, Ast::Field("containers".to_string()));
+                assert_eq!(*right, Ast::Field("image".to_string()));
+            }
+            _ => panic!("unexpected expression {:?}", ast),
+        }
+
+        // pipes stop the projections
+        assert!(matches!(
+            parse("items[*].name | [0]").unwrap(),
+            Ast::Pipe(..)
+        ));
+    }
+
+    #[test]
+    fn parse_literals_and_indexes() {
+        assert_eq!(
+            parse("`{\"a\": [1]}`").unwrap(),
+            Ast::Literal(serde_json::json!({"a": [1]}))
+        );
+        assert_eq!(
+            parse("'it''s'").unwrap_err().to_string(),
+            "unexpected token Literal(String(\"s\"))"
+        );
+        assert_eq!(
+            parse("\"with space\"").unwrap(),
+            Ast::Field("with space".to_string())
+        );
+        assert!(matches!(
+            parse("[-1]").unwrap(),
+            Ast::IndexExpression(_, index) if *index == Ast::Index(-1)
+        ));
+        assert!(matches!(parse("[::2]").unwrap(), Ast::Projection(..)));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("a.").is_err());
+        assert!(parse("a[").is_err());
+        assert!(parse("[::0]").is_err());
+        assert!(parse("`{invalid`").is_err());
+        assert!(parse("\"quoted\"(a)").is_err());
+        assert!(parse("a #").is_err());
+    }
+}
@@ -0,0 +1,252 @@
+//! Interoperability with the [kube-rs](https://kube.rs) types, which eases
+//! the reuse of controller code inside of policies.
+//!
+//! The module provides conversions between:
+//!
+//! * [`GroupVersionKind`] and [`kube_core::GroupVersionKind`]
+//! * [`GroupVersionResource`] and [`kube_core::GroupVersionResource`]
+//! * [`LazyValue`], the objects of the requests, and [`DynamicObject`]
+//!
+//! The objects of the requests can also be decoded into any type
+//! implementing [`Resource`], like the ones derived with
+//! `kube::CustomResource`, see [`KubernetesAdmissionRequest::extract_resource`]:
+//!
+//! ```
+//! use k8s_openapi::api::apps::v1::Deployment;
+//! use kubewarden_policy_sdk::request::KubernetesAdmissionRequest;
+//!
+//! fn replicas(request: &KubernetesAdmissionRequest) -> kubewarden_policy_sdk::error::Result<i32> {
+//!     let deployment = request.extract_resource::<Deployment>()?;
+//!     Ok(deployment
+//!         .and_then(|deployment| deployment.spec)
+//!         .and_then(|spec| spec.replicas)
+//!         .unwrap_or(1))
+//! }
+//! ```
+//!
+//! This module is available when the `kube` feature is enabled.
+use crate::error::{Result, SdkError};
+use crate::request::{
+    GroupVersionKind, GroupVersionResource, KubernetesAdmissionRequest, LazyValue,
+};
+use kube_core::{DynamicObject, Resource};
+use serde::de::DeserializeOwned;
+
+impl From<GroupVersionKind> for kube_core::GroupVersionKind {
+    fn from(gvk: GroupVersionKind) -> Self {
+        kube_core::GroupVersionKind::gvk(&gvk.group, &gvk.version, &gvk.kind)
+    }
+}
+
+impl From<kube_core::GroupVersionKind> for GroupVersionKind {
+    fn from(gvk: kube_core::GroupVersionKind) -> Self {
+        GroupVersionKind {
+            group: gvk.group,
+            version: gvk.version,
+            kind: gvk.kind,
+        }
+    }
+}
+
+impl From<GroupVersionResource> for kube_core::GroupVersionResource {
+    fn from(gvr: GroupVersionResource) -> Self {
+        kube_core::GroupVersionResource::gvr(&gvr.group, &gvr.version, &gvr.resource)
+    }
+}
+
+impl From<kube_core::GroupVersionResource> for GroupVersionResource {
+    fn from(gvr: kube_core::GroupVersionResource) -> Self {
+        GroupVersionResource {
+            group: gvr.group,
+            version: gvr.version,
+            resource: gvr.resource,
+        }
+    }
+}
+
+impl TryFrom<&LazyValue> for DynamicObject {
+    type Error = SdkError;
+
+    fn try_from(value: &LazyValue) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl TryFrom<&DynamicObject> for LazyValue {
+    type Error = SdkError;
+
+    fn try_from(object: &DynamicObject) -> Result<Self> {
+        serde_json::to_value(object)
+            .map(LazyValue::from)
+            .map_err(|e| SdkError::serialization("the DynamicObject", e))
+    }
+}
+
+impl KubernetesAdmissionRequest {
+    /// Decode the object being submitted into the kube-rs resource `K`, like
+    /// a k8s-openapi type or a type derived with `kube::CustomResource`.
+    ///
+    /// Returns `None` when the request carries no object, like the DELETE
+    /// ones, and an [`SdkError::UnsupportedKind`] error when the kind of the
+    /// request is not the one of `K`.
+    pub fn extract_resource<K>(&self) -> Result<Option<K>>
+    where
+        K: Resource<DynamicType = ()> + DeserializeOwned,
+    {
+        self.decode_resource(&self.object)
+    }
+
+    /// Decode the old object into the kube-rs resource `K`, see
+    /// [`extract_resource`](Self::extract_resource)
+    pub fn extract_old_resource<K>(&self) -> Result<Option<K>>
+    where
+        K: Resource<DynamicType = ()> + DeserializeOwned,
+    {
+        self.decode_resource(&self.old_object)
+    }
+
+    /// The object being submitted as a kube-rs [`DynamicObject`], `None` when
+    /// the request carries no object
+    pub fn dynamic_object(&self) -> Result<Option<DynamicObject>> {
+        if self.object.is_null() {
+            return Ok(None);
+        }
+        DynamicObject::try_from(&self.object).map(Some)
+    }
+
+    fn decode_resource<K>(&self, object: &LazyValue) -> Result<Option<K>>
+    where
+        K: Resource<DynamicType = ()> + DeserializeOwned,
+    {
+        let expected = GroupVersionKind::new(&K::group(&()), &K::version(&()), &K::kind(&()));
+        if self.kind != expected {
+            return Err(SdkError::UnsupportedKind {
+                kind: self.kind.to_string(),
+                expected: vec![expected.to_string()],
+            });
+        }
+        if object.is_null() {
+            return Ok(None);
+        }
+        object.parse().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::{apps::v1::Deployment, core::v1::Pod};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::borrow::Cow;
+
+    /// A resource like the ones derived with `kube::CustomResource`
+    #[derive(Deserialize, Debug)]
+    struct Database {
+        metadata: ObjectMeta,
+        spec: DatabaseSpec,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct DatabaseSpec {
+        engine: String,
+    }
+
+    impl Resource for Database {
+        type DynamicType = ();
+        type Scope = kube_core::NamespaceResourceScope;
+
+        fn kind(_: &()) -> Cow<'_, str> {
+            "Database".into()
+        }
+
+        fn group(_: &()) -> Cow<'_, str> {
+            "example.com".into()
+        }
+
+        fn version(_: &()) -> Cow<'_, str> {
+            "v1".into()
+        }
+
+        fn plural(_: &()) -> Cow<'_, str> {
+            "databases".into()
+        }
+
+        fn meta(&self) -> &ObjectMeta {
+            &self.metadata
+        }
+
+        fn meta_mut(&mut self) -> &mut ObjectMeta {
+            &mut self.metadata
+        }
+    }
+
+    fn request(kind: GroupVersionKind, object: serde_json::Value) -> KubernetesAdmissionRequest {
+        KubernetesAdmissionRequest {
+            kind,
+            object: object.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn convert_gvk_and_gvr() {
+        let gvk = GroupVersionKind::new("apps", "v1", "Deployment");
+        let kube_gvk = kube_core::GroupVersionKind::from(gvk.clone());
+        assert_eq!(kube_gvk.api_version(), "apps/v1");
+        assert_eq!(GroupVersionKind::from(kube_gvk), gvk);
+
+        let gvr = GroupVersionResource::new("", "v1", "pods");
+        let kube_gvr = kube_core::GroupVersionResource::from(gvr.clone());
+        assert_eq!(kube_gvr.resource, "pods");
+        assert_eq!(GroupVersionResource::from(kube_gvr), gvr);
+    }
+
+    #[test]
+    fn convert_dynamic_objects() {
+        let object: LazyValue = json!({
+            "apiVersion": "example.com/v1",
+            "kind": "Database",
+            "metadata": {"name": "orders"},
+            "spec": {"engine": "postgres"}
+        })
+        .into();
+
+        let dynamic = DynamicObject::try_from(&object).unwrap();
+        assert_eq!(dynamic.metadata.name.as_deref(), Some("orders"));
+        assert_eq!(dynamic.data["spec"]["engine"], "postgres");
+        assert_eq!(LazyValue::try_from(&dynamic).unwrap(), object);
+    }
+
+    #[test]
+    fn extract_custom_resources() {
+        let request = request(
+            GroupVersionKind::new("example.com", "v1", "Database"),
+            json!({"metadata": {"name": "orders"}, "spec": {"engine": "postgres"}}),
+        );
+
+        let database = request.extract_resource::<Database>().unwrap().unwrap();
+        assert_eq!(database.metadata.name.as_deref(), Some("orders"));
+        assert_eq!(database.spec.engine, "postgres");
+        assert!(request
+            .extract_old_resource::<Database>()
+            .unwrap()
+            .is_none());
+        assert!(request.dynamic_object().unwrap().is_some());
+    }
+
+    #[test]
+    fn extract_resources_of_another_kind() {
+        let request = request(
+            GroupVersionKind::new("apps", "v1", "Deployment"),
+            json!({"metadata": {"name": "nginx"}}),
+        );
+
+        assert!(request.extract_resource::<Deployment>().unwrap().is_some());
+        assert!(matches!(
+            request.extract_resource::<Pod>(),
+            Err(SdkError::UnsupportedKind { .. })
+        ));
+    }
+}
@@ -4,15 +4,86 @@ use anyhow::anyhow;
 
 pub use wapc_guest;
 
+/// Attribute macro that generates the waPC entry point of a [`policy::Policy`].
+///
+/// The attribute can be placed either on the type implementing the `Policy` trait,
+/// or on the `impl Policy` block. The path of the SDK crate can be changed
+/// with `#[policy(crate = path::to::sdk)]`, which is useful when the crate
+/// has been renamed inside of `Cargo.toml`.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::{accept_request, policy::Policy, request::ValidationRequest};
+///
+/// struct MyPolicy;
+///
+/// #[kubewarden_policy_sdk::policy]
+/// impl Policy for MyPolicy {
+///     type Settings = ();
+///
+///     fn validate(_request: &ValidationRequest<()>) -> wapc_guest::CallResult {
+///         accept_request()
+///     }
+/// }
+/// ```
+#[cfg(feature = "macros")]
+pub use kubewarden_policy_sdk_macros::policy;
+
+pub mod cache;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+#[cfg(feature = "cel")]
+pub mod cel;
+mod clock;
+#[cfg(feature = "component-model")]
+pub mod component;
+pub mod constraints;
+pub mod conversion;
+pub mod cosign;
+pub mod deadline;
+pub mod diff;
+pub mod docker_config;
+pub mod error;
+pub mod gatekeeper;
+pub mod glob;
+pub mod helm;
 pub mod host_capabilities;
+pub mod intern;
+#[cfg(feature = "jmespath")]
+pub mod jmespath;
+#[cfg(feature = "kube")]
+pub mod kube;
 pub mod logging;
 pub mod metadata;
+pub mod metrics;
 #[cfg(not(target_arch = "wasm32"))]
 mod non_wasm;
+pub mod oci;
+#[cfg(feature = "cluster-context")]
+pub mod params;
+pub mod path;
+pub mod pattern;
+pub mod policy;
+pub mod quantity;
+#[cfg(feature = "cluster-context")]
+pub mod rbac;
+pub mod report;
 pub mod request;
 pub mod response;
+pub mod semver;
 pub mod settings;
+pub mod spiffe;
+pub mod terraform;
 pub mod test;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "time")]
+pub mod time;
+pub mod trace_context;
+pub mod validator;
+pub mod well_known;
+pub mod yaml;
 
 use crate::metadata::ProtocolVersion;
 #[cfg(feature = "cluster-context")]
@@ -30,28 +101,14 @@ cfg_if::cfg_if! {
 
 /// Create an acceptance response
 pub fn accept_request() -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
-        accepted: true,
-        message: None,
-        code: None,
-        mutated_object: None,
-        audit_annotations: None,
-        warnings: None,
-    })?)
+    ValidationResponse::accept().build()
 }
 
 /// Create an acceptance response that mutates the original object
 /// # Arguments
 /// * `mutated_object` - the mutated Object
 pub fn mutate_request(mutated_object: serde_json::Value) -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
-        accepted: true,
-        message: None,
-        code: None,
-        mutated_object: Some(mutated_object),
-        audit_annotations: None,
-        warnings: None,
-    })?)
+    ValidationResponse::accept().mutate(mutated_object).build()
 }
 
 #[cfg(feature = "cluster-context")]
@@ -67,7 +124,7 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
     match validation_request.request.kind.kind.as_str() {
         Deployment::KIND => {
             let mut deployment =
-                serde_json::from_value::<Deployment>(validation_request.request.object.clone())?;
+                validation_request.request.object.decode::<Deployment>()?;
             let mut deployment_spec = deployment.spec.unwrap_or_default();
             deployment_spec.template.spec = Some(pod_spec);
             deployment.spec = Some(deployment_spec);
@@ -75,7 +132,7 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
         }
         ReplicaSet::KIND => {
             let mut replicaset =
-                serde_json::from_value::<ReplicaSet>(validation_request.request.object.clone())?;
+                validation_request.request.object.decode::<ReplicaSet>()?;
             let mut replicaset_spec = replicaset.spec.unwrap_or_default();
             let mut template = replicaset_spec.template.unwrap_or_default();
             template.spec = Some(pod_spec);
@@ -85,7 +142,7 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
         }
         StatefulSet::KIND => {
             let mut statefulset =
-                serde_json::from_value::<StatefulSet>(validation_request.request.object.clone())?;
+                validation_request.request.object.decode::<StatefulSet>()?;
             let mut statefulset_spec = statefulset.spec.unwrap_or_default();
             statefulset_spec.template.spec = Some(pod_spec);
             statefulset.spec = Some(statefulset_spec);
@@ -93,16 +150,17 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
         }
         DaemonSet::KIND => {
             let mut daemonset =
-                serde_json::from_value::<DaemonSet>(validation_request.request.object.clone())?;
+                validation_request.request.object.decode::<DaemonSet>()?;
             let mut daemonset_spec = daemonset.spec.unwrap_or_default();
             daemonset_spec.template.spec = Some(pod_spec);
             daemonset.spec = Some(daemonset_spec);
             mutate_request(serde_json::to_value(daemonset)?)
         }
         ReplicationController::KIND => {
-            let mut replication_controller = serde_json::from_value::<ReplicationController>(
-                validation_request.request.object.clone(),
-            )?;
+            let mut replication_controller = validation_request
+                .request
+                .object
+                .decode::<ReplicationController>()?;
             let mut replication_controller_spec = replication_controller.spec.unwrap_or_default();
             let mut template = replication_controller_spec.template.unwrap_or_default();
             template.spec = Some(pod_spec);
@@ -112,7 +170,7 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
         }
         CronJob::KIND => {
             let mut cronjob =
-                serde_json::from_value::<CronJob>(validation_request.request.object.clone())?;
+                validation_request.request.object.decode::<CronJob>()?;
             let mut cronjob_spec = cronjob.spec.unwrap_or_default();
             let mut job_template_spec = cronjob_spec.job_template;
             let mut job_spec = job_template_spec.spec.unwrap_or_default();
@@ -125,14 +183,14 @@ pub fn mutate_pod_spec_from_request<T: std::default::Default>(
             mutate_request(serde_json::to_value(cronjob)?)
         }
         Job::KIND => {
-            let mut job = serde_json::from_value::<Job>(validation_request.request.object.clone())?;
+            let mut job = validation_request.request.object.decode::<Job>()?;
             let mut job_spec = job.spec.unwrap_or_default();
             job_spec.template.spec = Some(pod_spec);
             job.spec = Some(job_spec);
             mutate_request(serde_json::to_value(job)?)
         }
         Pod::KIND => {
-            let mut pod = serde_json::from_value::<Pod>(validation_request.request.object.clone())?;
+            let mut pod = validation_request.request.object.decode::<Pod>()?;
             pod.spec = Some(pod_spec);
             mutate_request(serde_json::to_value(pod)?)
         }
@@ -154,14 +212,14 @@ pub fn reject_request(
     audit_annotations: Option<HashMap<String, String>>,
     warnings: Option<Vec<String>>,
 ) -> wapc_guest::CallResult {
-    Ok(serde_json::to_vec(&ValidationResponse {
+    response::serialize(&ValidationResponse {
         accepted: false,
         mutated_object: None,
         message,
         code,
         audit_annotations,
         warnings,
-    })?)
+    })
 }
 
 /// waPC guest function to register under the name `validate_settings`
@@ -199,6 +257,13 @@ pub fn validate_settings<T>(payload: &[u8]) -> wapc_guest::CallResult
 where
     T: serde::de::DeserializeOwned + settings::Validatable,
 {
+    if let Err(e) = logging::configure_from_settings(payload) {
+        return Ok(serde_json::to_vec(&settings::SettingsValidationResponse {
+            valid: false,
+            message: Some(e),
+        })?);
+    }
+
     let settings: T = serde_json::from_slice::<T>(payload).map_err(|e| {
         anyhow!(
             "Error decoding validation payload {}: {:?}",
@@ -239,6 +304,27 @@ pub fn protocol_version_guest(_payload: &[u8]) -> wapc_guest::CallResult {
     Ok(serde_json::to_vec(&ProtocolVersion::default())?)
 }
 
+/// Register the `protocol_version` waPC function, which answers the host
+/// with the protocol version implemented by the policy.
+///
+/// The protocol version implemented by the host, once learned from the host
+/// capabilities, is reported by
+/// [`host_capabilities::protocol_version::host_protocol_version`].
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::register_protocol_version;
+///
+/// #[no_mangle]
+/// pub extern "C" fn wapc_init() {
+///     register_protocol_version();
+///     // register other waPC functions
+/// }
+/// ```
+pub fn register_protocol_version() {
+    wapc_guest::register_function("protocol_version", protocol_version_guest);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -310,7 +396,7 @@ mod tests {
     #[test]
     fn test_reject_request() -> Result<(), ()> {
         let code = 500;
-        let expected_code = code.clone();
+        let expected_code = code;
 
         let message = String::from("internal error");
         let expected_message = message.clone();
@@ -352,17 +438,17 @@ mod tests {
     #[cfg(feature = "cluster-context")]
     fn create_validation_request<T: Serialize>(object: T, kind: &str) -> ValidationRequest<()> {
         let value = serde_json::to_value(object).unwrap();
-        ValidationRequest {
-            settings: (),
-            request: KubernetesAdmissionRequest {
+        ValidationRequest::from_parts(
+            (),
+            KubernetesAdmissionRequest {
                 kind: GroupVersionKind {
                     kind: kind.to_string(),
                     ..Default::default()
                 },
-                object: value,
+                object: value.into(),
                 ..Default::default()
             },
-        }
+        )
     }
 
     #[cfg(feature = "cluster-context")]
@@ -661,7 +747,7 @@ mod tests {
         let raw_response = mutate_pod_spec_from_request(validation_request, new_pod_spec);
         assert!(raw_response.is_ok());
         let response: ValidationResponse = serde_json::from_slice(&raw_response.unwrap()).unwrap();
-        assert_eq!(response.accepted, false);
+        assert!(!response.accepted);
         let error_message = response.message.unwrap_or_default();
         let expected_error_message = "Object should be one of these kinds: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod";
         assert_eq!(error_message, expected_error_message);
@@ -0,0 +1,136 @@
+use log::kv::{self, VisitSource};
+use serde_json::{json, Map, Value};
+
+use super::drain;
+
+/// A [`log::Log`] implementation forwarding the records emitted through the
+/// `log` facade to the host, like the [`KubewardenDrain`](super::KubewardenDrain)
+/// does for the `slog` ones.
+///
+/// The key-value pairs of the records are sent as structured fields.
+#[derive(Debug, Default)]
+pub struct KubewardenLogger;
+
+static LOGGER: KubewardenLogger = KubewardenLogger;
+
+/// Install the [`KubewardenLogger`] as the logger of the `log` facade,
+/// discarding the records more verbose than `level`.
+///
+/// Fails when another logger has already been installed.
+pub fn init_log_bridge(level: log::LevelFilter) -> Result<(), log::SetLoggerError> {
+    log::set_logger(&LOGGER)?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+struct FieldVisitor<'a> {
+    data: &'a mut Map<String, Value>,
+}
+
+impl<'kvs> VisitSource<'kvs> for FieldVisitor<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(v) = value.to_bool() {
+            json!(v)
+        } else if let Some(v) = value.to_i64() {
+            json!(v)
+        } else if let Some(v) = value.to_u64() {
+            json!(v)
+        } else if let Some(v) = value.to_f64() {
+            json!(v)
+        } else {
+            json!(value.to_string())
+        };
+        self.data.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+/// Build the event sent to the host, using the same fields of the `slog`
+/// events
+pub(crate) fn event(record: &log::Record) -> Map<String, Value> {
+    let mut data = Map::new();
+    let _ = record
+        .key_values()
+        .visit(&mut FieldVisitor { data: &mut data });
+
+    let level = match record.level() {
+        log::Level::Error => "error",
+        log::Level::Warn => "warning",
+        log::Level::Info => "info",
+        // the host does not have a trace level
+        log::Level::Debug | log::Level::Trace => "debug",
+    };
+    data.insert(String::from("level"), json!(level));
+    data.insert(String::from("message"), json!(record.args().to_string()));
+    data.insert(String::from("target"), json!(record.target()));
+    data.insert(String::from("line"), json!(record.line()));
+    data.insert(String::from("file"), json!(record.file()));
+    crate::trace_context::add_to_event(&mut data);
+    data
+}
+
+fn slog_level(level: log::Level) -> slog::Level {
+    match level {
+        log::Level::Error => slog::Level::Error,
+        log::Level::Warn => slog::Level::Warning,
+        log::Level::Info => slog::Level::Info,
+        log::Level::Debug => slog::Level::Debug,
+        log::Level::Trace => slog::Level::Trace,
+    }
+}
+
+impl log::Log for KubewardenLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level() && super::level::enabled(slog_level(metadata.level()))
+    }
+
+    fn log(&self, record: &log::Record) {
+        // sampling counts the events, it must be checked only once per record
+        if self.enabled(record.metadata()) && super::sampling::sample(slog_level(record.level())) {
+            // logging must never break the evaluation of the policy
+            let _ = drain::send(&event(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_to_event() {
+        let fields: [(&str, kv::Value); 4] = [
+            ("image", kv::Value::from("busybox:latest")),
+            ("containers", kv::Value::from(3u64)),
+            ("ratio", kv::Value::from(0.5)),
+            ("privileged", kv::Value::from(true)),
+        ];
+        let event = event(
+            &log::Record::builder()
+                .args(format_args!("image {} is not pinned", "busybox"))
+                .level(log::Level::Warn)
+                .target("policy")
+                .file(Some("src/lib.rs"))
+                .line(Some(42))
+                .key_values(&fields)
+                .build(),
+        );
+
+        assert_eq!(
+            Value::Object(event),
+            json!({
+                "image": "busybox:latest",
+                "containers": 3,
+                "ratio": 0.5,
+                "privileged": true,
+                "level": "warning",
+                "message": "image busybox is not pinned",
+                "target": "policy",
+                "line": 42,
+                "file": "src/lib.rs",
+            })
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use crate::error::{Result, SdkError};
 use slog::{Drain, OwnedKVList, Record};
 
 use super::event;
@@ -42,20 +42,52 @@ impl slog::Drain for KubewardenDrain {
     type Ok = ();
     type Err = anyhow::Error;
 
-    #[cfg(not(target_arch = "wasm32"))]
-    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> Result<()> {
+    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> anyhow::Result<()> {
+        if !super::level::enabled(rinfo.level()) || !super::sampling::sample(rinfo.level()) {
+            return Ok(());
+        }
         let event = event::new(rinfo, logger_values).unwrap();
-        println!("{}", serde_json::to_string(&event)?);
-
-        Ok(())
+        Ok(send(&event)?)
     }
+}
 
-    #[cfg(target_arch = "wasm32")]
-    fn log(&self, rinfo: &Record, logger_values: &OwnedKVList) -> Result<()> {
-        let event = event::new(rinfo, logger_values).unwrap();
-        let msg = serde_json::to_vec(&event).unwrap();
-        wapc_guest::host_call("kubewarden", "tracing", "log", &msg)
-            .map(|_| ())
-            .map_err(|e| anyhow::anyhow!("erorr invoking wapc logging facility: {:?}", e))
+/// Send the log event to the host, or print it on the standard output when
+/// not running inside of a wasm host
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn send(event: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    let event = &redacted(event);
+    let event =
+        serde_json::to_string(event).map_err(|e| SdkError::serialization("the log event", e))?;
+    println!("{}", event);
+
+    Ok(())
+}
+
+/// Send the log event to the host, or print it on the standard output when
+/// not running inside of a wasm host
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn send(event: &serde_json::Map<String, serde_json::Value>) -> Result<()> {
+    let event = &redacted(event);
+    let msg = serde_json::to_vec(event).unwrap();
+    #[cfg(feature = "component-model")]
+    if crate::component::is_running() {
+        crate::component::log(&msg);
+        return Ok(());
     }
+    wapc_guest::host_call("kubewarden", "tracing", "log", &msg)
+        .map(|_| ())
+        .map_err(|source| SdkError::HostCallback {
+            namespace: "tracing".to_string(),
+            operation: "log".to_string(),
+            source,
+        })
+}
+
+/// Mask the credentials of the event. See [`redact_object`](super::redact_object)
+fn redacted(
+    event: &serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut event = event.clone();
+    super::redact_object(&mut event);
+    event
 }
@@ -29,6 +29,81 @@ pub(crate) fn new(
     data.insert(String::from("line"), json!(rinfo.line()));
     data.insert(String::from("column"), json!(rinfo.column()));
     data.insert(String::from("file"), json!(rinfo.file()));
+    crate::trace_context::add_to_event(&mut data);
 
     Ok(data)
 }
+
+/// Build a warning event with string fields, emitted at the given location
+pub(crate) fn warning(
+    message: &str,
+    fields: &[(&str, &str)],
+    location: &std::panic::Location,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut data: serde_json::Map<String, serde_json::Value> = fields
+        .iter()
+        .map(|(key, value)| (key.to_string(), json!(value)))
+        .collect();
+
+    data.insert(String::from("level"), json!("warning"));
+    data.insert(String::from("message"), json!(message));
+    data.insert(String::from("line"), json!(location.line()));
+    data.insert(String::from("column"), json!(location.column()));
+    data.insert(String::from("file"), json!(location.file()));
+    crate::trace_context::add_to_event(&mut data);
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{o, Drain, Logger};
+    use std::sync::{Arc, Mutex};
+
+    type Events = Arc<Mutex<Vec<serde_json::Map<String, serde_json::Value>>>>;
+
+    struct CollectingDrain(Events);
+
+    impl Drain for CollectingDrain {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, rinfo: &slog::Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+            self.0.lock().unwrap().push(new(rinfo, values).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn structured_fields() {
+        let events = Events::default();
+        let log = Logger::root(
+            CollectingDrain(events.clone()),
+            o!("request_uid" => "1234", "namespace" => "default"),
+        );
+        slog::info!(log, "unpinned images"; "images" => slog::Serde(vec!["busybox", "nginx"]), "count" => 2);
+
+        let event = &events.lock().unwrap()[0];
+        assert_eq!(event["request_uid"], "1234");
+        assert_eq!(event["namespace"], "default");
+        assert_eq!(event["images"], json!(["busybox", "nginx"]));
+        assert_eq!(event["count"], 2);
+        assert_eq!(event["level"], "info");
+        assert_eq!(event["message"], "unpinned images");
+    }
+
+    #[test]
+    fn warning_fields() {
+        let event = warning(
+            "ignoring malformed evaluation setting",
+            &[("error", "invalid settings")],
+            std::panic::Location::caller(),
+        );
+
+        assert_eq!(event["error"], "invalid settings");
+        assert_eq!(event["level"], "warning");
+        assert_eq!(event["message"], "ignoring malformed evaluation setting");
+        assert_eq!(event["file"], file!());
+    }
+}
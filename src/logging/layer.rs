@@ -0,0 +1,223 @@
+use serde_json::{json, Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::drain;
+
+/// A [`tracing_subscriber::Layer`] forwarding the events emitted through the
+/// `tracing` macros to the host, like the [`KubewardenDrain`](super::KubewardenDrain)
+/// does for the `slog` ones.
+///
+/// The fields of the events are sent as structured fields. The spans the
+/// event belongs to are sent too: `span` holds the name of the current one,
+/// `spans` the `name` and the `fields` of all of them, from the root one.
+#[derive(Debug, Default)]
+pub struct KubewardenLayer;
+
+/// Install a subscriber made of the [`KubewardenLayer`] as the global
+/// default of `tracing`.
+///
+/// Fails when another global subscriber has already been installed.
+pub fn init_tracing_bridge() -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(KubewardenLayer))
+}
+
+/// The fields of a span, stored inside of its extensions
+struct SpanFields(Map<String, Value>);
+
+struct FieldVisitor<'a> {
+    data: &'a mut Map<String, Value>,
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.data.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.data.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.data.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.data.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.data.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.data
+            .insert(field.name().to_string(), json!(format!("{:?}", value)));
+    }
+}
+
+fn slog_level(level: &tracing::Level) -> slog::Level {
+    match *level {
+        tracing::Level::ERROR => slog::Level::Error,
+        tracing::Level::WARN => slog::Level::Warning,
+        tracing::Level::INFO => slog::Level::Info,
+        tracing::Level::DEBUG => slog::Level::Debug,
+        tracing::Level::TRACE => slog::Level::Trace,
+    }
+}
+
+/// Build the event sent to the host, using the same fields of the `slog`
+/// events. `spans` holds the names and the fields of the spans the event
+/// belongs to, from the root one
+pub(crate) fn event(event: &Event, spans: Vec<(&str, Map<String, Value>)>) -> Map<String, Value> {
+    let mut data = Map::new();
+    event.record(&mut FieldVisitor { data: &mut data });
+
+    let metadata = event.metadata();
+    let level = match *metadata.level() {
+        tracing::Level::ERROR => "error",
+        tracing::Level::WARN => "warning",
+        tracing::Level::INFO => "info",
+        // the host does not have a trace level
+        tracing::Level::DEBUG | tracing::Level::TRACE => "debug",
+    };
+    let message = data.remove("message").unwrap_or_default();
+    data.insert(String::from("level"), json!(level));
+    data.insert(String::from("message"), message);
+    data.insert(String::from("target"), json!(metadata.target()));
+    data.insert(String::from("line"), json!(metadata.line()));
+    data.insert(String::from("file"), json!(metadata.file()));
+    if let Some((name, _)) = spans.last() {
+        data.insert(String::from("span"), json!(name));
+        let spans = spans
+            .into_iter()
+            .map(|(name, fields)| json!({"name": name, "fields": fields}))
+            .collect();
+        data.insert(String::from("spans"), Value::Array(spans));
+    }
+    crate::trace_context::add_to_event(&mut data);
+    data
+}
+
+/// The names and the fields of the spans the event belongs to, from the
+/// root one
+fn spans<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> Vec<(&'static str, Map<String, Value>)>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    ctx.event_scope(event)
+        .map(|scope| {
+            scope
+                .from_root()
+                .map(|span| {
+                    let fields = span
+                        .extensions()
+                        .get::<SpanFields>()
+                        .map(|SpanFields(fields)| fields.clone())
+                        .unwrap_or_default();
+                    (span.name(), fields)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl<S> Layer<S> for KubewardenLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        !metadata.is_event() || super::level::enabled(slog_level(metadata.level()))
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut FieldVisitor { data: &mut fields });
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut FieldVisitor { data: fields });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // sampling counts the events, it must be checked only once per event
+        if !super::sampling::sample(slog_level(event.metadata().level())) {
+            return;
+        }
+        // logging must never break the evaluation of the policy
+        let _ = drain::send(&self::event(event, spans(event, &ctx)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Collects the events built by the layer, instead of sending them
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<Map<String, Value>>>>);
+
+    impl<S> Layer<S> for Collector
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(super::event(event, spans(event, &ctx)));
+        }
+    }
+
+    #[test]
+    fn events_with_spans() {
+        let collector = Collector::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(KubewardenLayer)
+            .with(collector.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span =
+                tracing::info_span!("validate", uid = "1234", containers = tracing::field::Empty);
+            let _enter = span.enter();
+            span.record("containers", 2);
+            let _inner = tracing::debug_span!("image", image = "busybox").entered();
+            tracing::warn!(
+                pinned = false,
+                ratio = 0.5,
+                "image {} is not pinned",
+                "busybox"
+            );
+        });
+
+        let events = collector.0.lock().unwrap();
+        let event = Value::Object(events[0].clone());
+        assert_eq!(event["level"], "warning");
+        assert_eq!(event["message"], "image busybox is not pinned");
+        assert_eq!(event["pinned"], false);
+        assert_eq!(event["ratio"], 0.5);
+        assert_eq!(event["span"], "image");
+        assert_eq!(
+            event["spans"],
+            json!([
+                {"name": "validate", "fields": {"uid": "1234", "containers": 2}},
+                {"name": "image", "fields": {"image": "busybox"}},
+            ])
+        );
+    }
+}
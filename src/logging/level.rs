@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use std::cell::Cell;
+
+/// Key of the policy settings holding the log level of the policy, e.g.
+/// `logLevel: debug`
+pub const LOG_LEVEL_SETTING: &str = "logLevel";
+
+thread_local! {
+    static LEVEL: Cell<Option<slog::Level>> = const { Cell::new(None) };
+}
+
+/// Set the most verbose level of the events sent to the host. All the events
+/// are sent when the level is `None`, which is the default.
+///
+/// This is done automatically by the SDK, using the [`LOG_LEVEL_SETTING`]
+/// key of the policy settings.
+pub fn set_level(level: Option<slog::Level>) {
+    LEVEL.with(|l| l.set(level));
+}
+
+/// The most verbose level of the events sent to the host, `None` when all
+/// the events are sent
+pub fn level() -> Option<slog::Level> {
+    LEVEL.with(|l| l.get())
+}
+
+/// Returns true when the events of the given level are sent to the host
+pub(crate) fn enabled(level: slog::Level) -> bool {
+    self::level().is_none_or(|max| level.is_at_least(max))
+}
+
+/// Parse a log level, as written inside of the policy settings
+pub fn parse_level(level: &str) -> Result<slog::Level, String> {
+    match level.to_lowercase().as_str() {
+        "trace" => Ok(slog::Level::Trace),
+        "debug" => Ok(slog::Level::Debug),
+        "info" => Ok(slog::Level::Info),
+        "warn" | "warning" => Ok(slog::Level::Warning),
+        "error" => Ok(slog::Level::Error),
+        "critical" => Ok(slog::Level::Critical),
+        _ => Err(format!(
+            "invalid {} `{}`, must be one of: trace, debug, info, warning, error, critical",
+            LOG_LEVEL_SETTING, level
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+struct LevelSettings {
+    #[serde(rename = "logLevel")]
+    log_level: Option<String>,
+}
+
+/// Configure the log level using the settings of the policy, given the
+/// payload of the `validate_settings` waPC function. Fails when the level is
+/// not valid
+pub(crate) fn configure_from_settings(payload: &[u8]) -> Result<(), String> {
+    let log_level = serde_json::from_slice::<LevelSettings>(payload)
+        .ok()
+        .and_then(|s| s.log_level);
+    let level = log_level.as_deref().map(parse_level).transpose()?;
+    set_level(level);
+    super::sampling::reset_sampling();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_from_settings() {
+        set_level(Some(slog::Level::Warning));
+        assert!(enabled(slog::Level::Error));
+        assert!(!enabled(slog::Level::Debug));
+        set_level(None);
+        assert!(enabled(slog::Level::Trace));
+
+        assert!(configure_from_settings(br#"{"logLevel": "debug"}"#).is_ok());
+        assert_eq!(level(), Some(slog::Level::Debug));
+        assert!(configure_from_settings(br#"{"logLevel": "chatty"}"#).is_err());
+        assert!(configure_from_settings(br#"{}"#).is_ok());
+        assert_eq!(level(), None);
+    }
+}
@@ -36,8 +36,161 @@
 //!   accept_request()
 //! }
 //! ```
+//!
+//! ## Global logger
+//!
+//! Instead of creating a logger inside of each function, the SDK provides
+//! a global one that is initialized the first time [`logger`] is invoked:
+//!
+//! ```rust
+//! use kubewarden_policy_sdk::logging;
+//! use slog::info;
+//!
+//! info!(logging::logger(), "just a message");
+//! ```
+//!
+//! ## Structured fields
+//!
+//! Key-value pairs attached to the log events are sent to the host as
+//! structured data, which makes the logs of the policy server filterable.
+//! Values implementing `serde::Serialize` can be attached too, by wrapping
+//! them into [`slog::Serde`].
+//!
+//! [`request_logger`] returns a logger that attaches the identity of the
+//! request being evaluated to all its events:
+//!
+//! ```rust
+//! use kubewarden_policy_sdk::{logging, request::ValidationRequest};
+//! use slog::info;
+//!
+//! fn validate(payload: &[u8]) -> wapc_guest::CallResult {
+//!     let validation_request = ValidationRequest::<serde_json::Value>::new(payload)?;
+//!     let log = logging::request_logger(&validation_request.request);
+//!     let images = vec!["busybox:latest".to_string()];
+//!     info!(log, "unpinned images found"; "images" => slog::Serde(images));
+//!
+//!     kubewarden_policy_sdk::accept_request()
+//! }
+//! ```
+//!
+//! ## Sampling
+//!
+//! The number of events sent to the host can be limited per level with
+//! [`set_sampling`], e.g. at most 100 debug events per evaluation, or one
+//! info event out of 10. This protects the policy server from the log floods
+//! of hot policies.
+//!
+//! ## Redaction
+//!
+//! Credentials are masked from all the events before they leave the policy:
+//! the data of Secret objects, the fields named like `password` or `token`,
+//! the values of HTTP `Authorization` headers and JSON Web Tokens. The same
+//! rules can be applied to other values through [`redact_value`].
+//!
+//! ## Log level
+//!
+//! All the events are sent to the host by default. Operators can reduce the
+//! verbosity of a policy, or turn on its debug logs, with the reserved
+//! `logLevel` key of the policy settings, e.g. `logLevel: info`.
+//!
+//! Settings types rejecting unknown fields, through
+//! `#[serde(deny_unknown_fields)]`, must declare the `logLevel` field to
+//! support it.
+//!
+//! ## The `log` facade
+//!
+//! When the `log` feature is enabled, the events emitted through the macros
+//! of the [log](https://crates.io/crates/log) crate are forwarded to the host
+//! too, key-value pairs included. The bridge is installed by [`init`].
+//!
+//! ```rust,ignore
+//! log::info!(image = "busybox:latest"; "image is not pinned");
+//! ```
+//!
+//! ## The `tracing` crate
+//!
+//! When the `tracing` feature is enabled, the events emitted through the
+//! macros of the [tracing](https://crates.io/crates/tracing) crate are
+//! forwarded to the host by the `KubewardenLayer`, fields included. The
+//! events also carry the names and the fields of the spans they belong to.
+//! The layer is installed as the global subscriber by [`init`], policies
+//! composing their own subscriber can add it to theirs.
+//!
+//! ```rust,ignore
+//! let span = tracing::info_span!("containers", count = 2);
+//! let _enter = span.enter();
+//! tracing::warn!(image = "busybox:latest", "image is not pinned");
+//! ```
+use crate::request::KubernetesAdmissionRequest;
+use slog::{o, Logger};
+use std::sync::OnceLock;
+
+#[cfg(feature = "log")]
+mod bridge;
 mod drain;
 mod event;
+#[cfg(feature = "tracing")]
+mod layer;
+mod level;
+mod redact;
+mod sampling;
 mod ser;
 
+#[cfg(feature = "log")]
+pub use bridge::{init_log_bridge, KubewardenLogger};
 pub use drain::KubewardenDrain;
+#[cfg(feature = "tracing")]
+pub use layer::{init_tracing_bridge, KubewardenLayer};
+pub(crate) use level::configure_from_settings;
+pub use level::{level, parse_level, set_level, LOG_LEVEL_SETTING};
+pub use redact::{redact_object, redact_str, redact_value, REDACTED};
+pub(crate) use sampling::reset_sampling;
+pub use sampling::{dropped_events, set_sampling, Sampling};
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Returns the global logger of the policy, which sends log events to
+/// the host via the [`KubewardenDrain`]
+pub fn logger() -> &'static Logger {
+    LOGGER.get_or_init(|| Logger::root(KubewardenDrain::new(), o!()))
+}
+
+/// Returns a child of the global [`logger`] that attaches the uid, the
+/// operation, the kind, the namespace and the name of the given request to
+/// all the events
+pub fn request_logger(request: &KubernetesAdmissionRequest) -> Logger {
+    logger().new(o!(
+        "request_uid" => request.uid.clone(),
+        "operation" => request.operation.clone(),
+        "kind" => request.kind.kind.clone(),
+        "namespace" => request.namespace.clone(),
+        "name" => request.name.clone(),
+    ))
+}
+
+/// Log a warning with string fields, without going through slog. The SDK
+/// uses it while decoding the requests, code linked by every policy, which
+/// keeps slog out of the policies not logging anything
+#[track_caller]
+pub(crate) fn warn(message: &str, fields: &[(&str, &str)]) {
+    if !level::enabled(slog::Level::Warning) || !sampling::sample(slog::Level::Warning) {
+        return;
+    }
+    let event = event::warning(message, fields, std::panic::Location::caller());
+    // a failure to log is not a failure of the evaluation
+    let _ = drain::send(&event);
+}
+
+/// Initialize the global logger, the bridge of the `log` facade when the
+/// `log` feature is enabled, and the one of `tracing` when the `tracing`
+/// feature is enabled. This is done automatically by the
+/// [`setup!`](crate::setup) macro.
+pub fn init() {
+    logger();
+    #[cfg(feature = "log")]
+    // another logger could have been installed by the policy, which is fine
+    let _ = init_log_bridge(log::LevelFilter::Debug);
+    #[cfg(feature = "tracing")]
+    // likewise for the tracing subscriber
+    let _ = init_tracing_bridge();
+}
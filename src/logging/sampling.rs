@@ -0,0 +1,133 @@
+use std::cell::{Cell, RefCell};
+
+const LEVELS: usize = 7;
+
+/// Limits on the number of events sent to the host, protecting it from the
+/// log floods of policies evaluating many requests.
+///
+/// Limits are set per level: at most `limit` events per evaluation, and one
+/// out of `one_in` events. The counters are reset at the beginning of each
+/// evaluation.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::logging::{set_sampling, Sampling};
+///
+/// set_sampling(
+///     Sampling::new()
+///         .limit(slog::Level::Debug, 100)
+///         .one_in(slog::Level::Info, 10),
+/// );
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sampling {
+    limits: [Option<usize>; LEVELS],
+    one_in: [Option<u64>; LEVELS],
+}
+
+impl Sampling {
+    /// Send all the events, which is the default
+    pub fn new() -> Self {
+        Sampling::default()
+    }
+
+    /// Send at most `max` events of the given level per evaluation
+    pub fn limit(mut self, level: slog::Level, max: usize) -> Self {
+        self.limits[level.as_usize()] = Some(max);
+        self
+    }
+
+    /// Send only one event out of `n` of the given level, starting from the
+    /// first one
+    pub fn one_in(mut self, level: slog::Level, n: u64) -> Self {
+        self.one_in[level.as_usize()] = Some(n.max(1));
+        self
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    seen: [u64; LEVELS],
+    sent: [usize; LEVELS],
+}
+
+thread_local! {
+    static SAMPLING: RefCell<Sampling> = RefCell::new(Sampling::default());
+    static COUNTERS: RefCell<Counters> = RefCell::new(Counters::default());
+    static DROPPED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Configure the sampling of the events sent to the host
+pub fn set_sampling(sampling: Sampling) {
+    SAMPLING.with(|s| *s.borrow_mut() = sampling);
+    reset_sampling();
+}
+
+/// Number of events dropped by the sampling during the current evaluation
+pub fn dropped_events() -> usize {
+    DROPPED.with(|d| d.get())
+}
+
+/// Reset the counters of the sampling, done at the beginning of each
+/// evaluation
+pub(crate) fn reset_sampling() {
+    COUNTERS.with(|c| *c.borrow_mut() = Counters::default());
+    DROPPED.with(|d| d.set(0));
+}
+
+/// Returns true when an event of the given level must be sent, counting it
+pub(crate) fn sample(level: slog::Level) -> bool {
+    let index = level.as_usize();
+    let (limit, one_in) = SAMPLING.with(|s| {
+        let s = s.borrow();
+        (s.limits[index], s.one_in[index])
+    });
+    if limit.is_none() && one_in.is_none() {
+        return true;
+    }
+
+    let send = COUNTERS.with(|c| {
+        let mut counters = c.borrow_mut();
+        let seen = counters.seen[index];
+        counters.seen[index] += 1;
+        let send = one_in.is_none_or(|n| seen.is_multiple_of(n))
+            && limit.is_none_or(|max| counters.sent[index] < max);
+        if send {
+            counters.sent[index] += 1;
+        }
+        send
+    });
+    if !send {
+        DROPPED.with(|d| d.set(d.get() + 1));
+    }
+    send
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_per_level() {
+        set_sampling(
+            Sampling::new()
+                .limit(slog::Level::Debug, 3)
+                .one_in(slog::Level::Info, 4)
+                .limit(slog::Level::Info, 2),
+        );
+
+        let debug: Vec<bool> = (0..5).map(|_| sample(slog::Level::Debug)).collect();
+        assert_eq!(debug, vec![true, true, true, false, false]);
+        let info: Vec<bool> = (0..12).map(|_| sample(slog::Level::Info)).collect();
+        assert_eq!(info.iter().filter(|s| **s).count(), 2);
+        assert!(info[0] && info[4] && !info[8]);
+        assert!((0..10).all(|_| sample(slog::Level::Error)));
+        assert_eq!(dropped_events(), 12);
+
+        reset_sampling();
+        assert!(sample(slog::Level::Debug));
+        assert_eq!(dropped_events(), 0);
+        set_sampling(Sampling::new());
+    }
+}
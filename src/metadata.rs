@@ -1,5 +1,9 @@
+use crate::error::SdkError;
 use num_derive::{FromPrimitive, ToPrimitive};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
 use std::{convert::TryFrom, fmt};
 
 /// ProtocolVersion describes the version of the communication protocol
@@ -7,28 +11,67 @@ use std::{convert::TryFrom, fmt};
 ///
 /// Policies built with this SDK provide the right value via the `protocol_version_guest`
 /// function.
-#[derive(Deserialize, Serialize, Debug, Clone, FromPrimitive, ToPrimitive, PartialEq, Eq)]
+///
+/// Versions are ordered, a host implementing a given version implements all the
+/// previous ones too. [`crate::host_capabilities::protocol_version::host_protocol_version`] reports
+/// the version implemented by the host running the policy, once it is known.
+#[derive(
+    Deserialize,
+    Serialize,
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    FromPrimitive,
+    ToPrimitive,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[non_exhaustive]
 pub enum ProtocolVersion {
     /// This is an invalid version
     #[serde(rename = "Unknown")]
     Unknown = 0,
+    #[default]
     #[serde(rename = "v1")]
     V1,
+    /// The host provides the `v2` flavour of the host capabilities,
+    /// like the `v2/verify` Sigstore verification
+    #[serde(rename = "v2")]
+    V2,
 }
 
-impl Default for ProtocolVersion {
-    fn default() -> Self {
-        Self::V1
+impl ProtocolVersion {
+    /// Returns true when `self` is equal or newer than `other`
+    pub fn supports(&self, other: ProtocolVersion) -> bool {
+        *self >= other
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "v1" | "1" => Ok(ProtocolVersion::V1),
+            "v2" | "2" => Ok(ProtocolVersion::V2),
+            _ => Err(SdkError::InvalidInput(format!(
+                "Unknown protocol version: {}",
+                s
+            ))),
+        }
     }
 }
 
 impl TryFrom<Vec<u8>> for ProtocolVersion {
-    type Error = anyhow::Error;
+    type Error = SdkError;
 
     fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
-        let version: ProtocolVersion = serde_json::from_slice(&value)
-            .map_err(|e| anyhow::anyhow!("Cannot convert value to ProtocolVersion: {:?}", e))?;
-        Ok(version)
+        serde_json::from_slice(&value)
+            .map_err(|e| SdkError::deserialization("the protocol version", e))
     }
 }
 
@@ -39,6 +82,242 @@ impl fmt::Display for ProtocolVersion {
     }
 }
 
+/// The operations a policy [`Rule`] can be registered against
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+    #[serde(rename = "CREATE")]
+    Create,
+    #[serde(rename = "UPDATE")]
+    Update,
+    #[serde(rename = "DELETE")]
+    Delete,
+    #[serde(rename = "CONNECT")]
+    Connect,
+    #[serde(rename = "*")]
+    All,
+}
+
+/// A Rule describes the Kubernetes resources and operations the policy
+/// is interested in
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Rule {
+    /// API groups the resources belong to. The core group is represented by `""`
+    pub api_groups: Vec<String>,
+    /// API versions the resources belong to
+    pub api_versions: Vec<String>,
+    /// Resources this rule applies to (e.g. `pods`, `deployments/scale`)
+    pub resources: Vec<String>,
+    /// Operations this rule applies to
+    pub operations: Vec<Operation>,
+}
+
+impl Rule {
+    /// Create a new rule for the given API group, version and resources
+    pub fn new(api_group: &str, api_version: &str, resources: &[&str]) -> Self {
+        Rule {
+            api_groups: vec![api_group.to_string()],
+            api_versions: vec![api_version.to_string()],
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            operations: vec![],
+        }
+    }
+
+    /// Set the operations this rule applies to
+    pub fn operations(mut self, operations: &[Operation]) -> Self {
+        self.operations = operations.to_vec();
+        self
+    }
+}
+
+/// A Kubernetes resource a context aware policy has access to
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextAwareResource {
+    /// apiVersion of the resource (v1 for core group, groupName/groupVersions for other).
+    pub api_version: String,
+    /// Singular PascalCase name of the resource
+    pub kind: String,
+}
+
+/// The way the policy is executed by the policy evaluator. Policies built
+/// with this SDK always use `kubewarden-wapc`
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExecutionMode {
+    #[default]
+    #[serde(rename = "kubewarden-wapc")]
+    KubewardenWapc,
+}
+
+/// The kind of requests evaluated by the policy
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PolicyType {
+    /// The policy evaluates Kubernetes admission requests
+    #[default]
+    #[serde(rename = "kubernetes")]
+    Kubernetes,
+    /// The policy evaluates arbitrary JSON documents
+    #[serde(rename = "raw")]
+    Raw,
+}
+
+/// Well known annotations used inside of the policy metadata
+pub mod annotations {
+    pub const TITLE: &str = "io.kubewarden.policy.title";
+    pub const DESCRIPTION: &str = "io.kubewarden.policy.description";
+    pub const AUTHOR: &str = "io.kubewarden.policy.author";
+    pub const URL: &str = "io.kubewarden.policy.url";
+    pub const SOURCE: &str = "io.kubewarden.policy.source";
+    pub const LICENSE: &str = "io.kubewarden.policy.license";
+    pub const USAGE: &str = "io.kubewarden.policy.usage";
+    pub const VERSION: &str = "io.kubewarden.policy.version";
+    pub const SEVERITY: &str = "io.kubewarden.policy.severity";
+    pub const CATEGORY: &str = "io.kubewarden.policy.category";
+}
+
+/// Metadata describes the policy. It is the Rust representation of the
+/// `metadata.yml` file that is consumed by `kwctl annotate`.
+///
+/// Declaring the metadata inside of the policy code and generating the
+/// `metadata.yml` file from it, for example from a `build.rs` script,
+/// prevents the two from drifting apart.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::metadata::{annotations, Metadata, Operation, Rule};
+///
+/// let metadata = Metadata::builder()
+///     .rule(Rule::new("", "v1", &["pods"]).operations(&[Operation::Create, Operation::Update]))
+///     .mutating(false)
+///     .context_aware_resource("v1", "Namespace")
+///     .annotation(annotations::TITLE, "my-policy")
+///     .build();
+///
+/// let yaml = metadata.to_yaml().unwrap();
+/// assert!(yaml.contains("contextAwareResources"));
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    /// The protocol version used by the policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Rules describing the resources and operations the policy targets
+    pub rules: Vec<Rule>,
+    /// True if the policy can mutate the incoming requests
+    pub mutating: bool,
+    /// True if the policy can be used by the audit scanner
+    pub background_audit: bool,
+    /// Kubernetes resources the policy can access at evaluation time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub context_aware_resources: Vec<ContextAwareResource>,
+    /// How the policy is executed
+    pub execution_mode: ExecutionMode,
+    /// The kind of requests evaluated by the policy
+    pub policy_type: PolicyType,
+    /// Annotations to be added to the policy
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Create a new [`MetadataBuilder`]
+    pub fn builder() -> MetadataBuilder {
+        MetadataBuilder::default()
+    }
+
+    /// Serialize the metadata using the `metadata.yml` format
+    pub fn to_yaml(&self) -> crate::error::Result<String> {
+        serde_yaml::to_string(self)
+            .map_err(|e| SdkError::serialization("the metadata", serde::ser::Error::custom(e)))
+    }
+
+    /// Write the metadata to `path` using the `metadata.yml` format.
+    /// This is meant to be used from the `build.rs` script of the policy.
+    pub fn write_yaml<P: AsRef<Path>>(&self, path: P) -> crate::error::Result<()> {
+        let yaml = self.to_yaml()?;
+        std::fs::write(path.as_ref(), yaml).map_err(|e| {
+            SdkError::InvalidInput(format!(
+                "Cannot write metadata to {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })
+    }
+}
+
+/// Builder used to create [`Metadata`] instances
+#[derive(Debug, Clone)]
+pub struct MetadataBuilder {
+    metadata: Metadata,
+}
+
+impl Default for MetadataBuilder {
+    fn default() -> Self {
+        MetadataBuilder {
+            metadata: Metadata {
+                protocol_version: Some(ProtocolVersion::default()),
+                background_audit: true,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl MetadataBuilder {
+    /// Add a rule
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.metadata.rules.push(rule);
+        self
+    }
+
+    /// Set whether the policy is mutating
+    pub fn mutating(mut self, mutating: bool) -> Self {
+        self.metadata.mutating = mutating;
+        self
+    }
+
+    /// Set whether the policy can be used by the audit scanner.
+    /// Defaults to `true`
+    pub fn background_audit(mut self, background_audit: bool) -> Self {
+        self.metadata.background_audit = background_audit;
+        self
+    }
+
+    /// Add a Kubernetes resource the policy can access at evaluation time
+    pub fn context_aware_resource(mut self, api_version: &str, kind: &str) -> Self {
+        self.metadata
+            .context_aware_resources
+            .push(ContextAwareResource {
+                api_version: api_version.to_string(),
+                kind: kind.to_string(),
+            });
+        self
+    }
+
+    /// Set the kind of requests evaluated by the policy
+    pub fn policy_type(mut self, policy_type: PolicyType) -> Self {
+        self.metadata.policy_type = policy_type;
+        self
+    }
+
+    /// Add an annotation. See the [`annotations`] module for the well known keys
+    pub fn annotation(mut self, key: &str, value: &str) -> Self {
+        self.metadata
+            .annotations
+            .insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Create the [`Metadata`] object
+    pub fn build(self) -> Metadata {
+        self.metadata
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,4 +343,57 @@ mod tests {
         let version = ProtocolVersion::try_from(b"\"v100\"".to_vec());
         assert!(version.is_err());
     }
+
+    #[test]
+    fn protocol_version_ordering() {
+        assert!(ProtocolVersion::V2.supports(ProtocolVersion::V1));
+        assert!(ProtocolVersion::V1.supports(ProtocolVersion::V1));
+        assert!(!ProtocolVersion::V1.supports(ProtocolVersion::V2));
+        assert!(!ProtocolVersion::Unknown.supports(ProtocolVersion::V1));
+    }
+
+    #[test]
+    fn protocol_version_from_str() {
+        assert_eq!(
+            "v2".parse::<ProtocolVersion>().unwrap(),
+            ProtocolVersion::V2
+        );
+        assert_eq!("1".parse::<ProtocolVersion>().unwrap(), ProtocolVersion::V1);
+        assert!("v100".parse::<ProtocolVersion>().is_err());
+    }
+
+    #[test]
+    fn metadata_to_yaml() {
+        let metadata = Metadata::builder()
+            .rule(
+                Rule::new("apps", "v1", &["deployments"])
+                    .operations(&[Operation::Create, Operation::Update]),
+            )
+            .mutating(true)
+            .context_aware_resource("v1", "Namespace")
+            .annotation(annotations::TITLE, "test")
+            .build();
+
+        let yaml = metadata.to_yaml().unwrap();
+        let value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(value["protocolVersion"], "v1");
+        assert_eq!(value["rules"][0]["apiGroups"][0], "apps");
+        assert_eq!(value["rules"][0]["operations"][1], "UPDATE");
+        assert_eq!(value["mutating"], true);
+        assert_eq!(value["backgroundAudit"], true);
+        assert_eq!(value["contextAwareResources"][0]["kind"], "Namespace");
+        assert_eq!(value["executionMode"], "kubewarden-wapc");
+        assert_eq!(value["policyType"], "kubernetes");
+        assert_eq!(value["annotations"]["io.kubewarden.policy.title"], "test");
+
+        let decoded: Metadata = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(decoded, metadata);
+    }
+
+    #[test]
+    fn metadata_omits_empty_fields() {
+        let yaml = Metadata::builder().build().to_yaml().unwrap();
+        assert!(!yaml.contains("contextAwareResources"));
+        assert!(!yaml.contains("annotations"));
+    }
 }
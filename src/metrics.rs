@@ -0,0 +1,153 @@
+//! Emission of policy-level metrics, like the number of rejected images or
+//! the latency of signature verifications.
+//!
+//! The samples are sent to the host through the `metrics.v1/record` host
+//! capability. Failures are logged and otherwise ignored: telemetry must not
+//! change the outcome of an evaluation. Use
+//! [`host_capabilities::metrics::record`](crate::host_capabilities::metrics::record)
+//! to handle them.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::metrics;
+//!
+//! fn verify(image: &str) {
+//!     let _timer = metrics::timer("signature_verification_seconds", &[("registry", "ghcr.io")]);
+//!     // verify the signatures of the image...
+//! }
+//!
+//! metrics::increment("images_rejected_total", &[("reason", "unsigned")]);
+//! ```
+use crate::host_capabilities::metrics::{record, MetricKind, MetricSample};
+use std::time::Instant;
+
+fn emit(name: &str, kind: MetricKind, value: f64, labels: &[(&str, &str)]) {
+    let sample = MetricSample {
+        name: name.to_string(),
+        kind,
+        value,
+        labels: labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+    };
+    if let Err(e) = record(vec![sample]) {
+        slog::debug!(crate::logging::logger(), "cannot record metric"; "metric" => name, "error" => e.to_string());
+    }
+}
+
+/// Add `value` to the given counter
+pub fn counter(name: &str, value: f64, labels: &[(&str, &str)]) {
+    emit(name, MetricKind::Counter, value, labels);
+}
+
+/// Add 1 to the given counter
+pub fn increment(name: &str, labels: &[(&str, &str)]) {
+    counter(name, 1.0, labels);
+}
+
+/// Set the given gauge to `value`
+pub fn gauge(name: &str, value: f64, labels: &[(&str, &str)]) {
+    emit(name, MetricKind::Gauge, value, labels);
+}
+
+/// Record `value` into the given histogram
+pub fn histogram(name: &str, value: f64, labels: &[(&str, &str)]) {
+    emit(name, MetricKind::Histogram, value, labels);
+}
+
+/// Records the time elapsed since its creation, in seconds, into a histogram
+/// when dropped. See [`timer`].
+///
+/// Nothing is recorded on targets without a clock, like `wasm32-unknown-unknown`
+#[derive(Debug)]
+pub struct Timer {
+    name: String,
+    labels: Vec<(String, String)>,
+    start: Option<Instant>,
+}
+
+impl Timer {
+    /// Stop the timer, recording the elapsed time
+    pub fn stop(self) {}
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let Some(start) = self.start else {
+            return;
+        };
+        let labels: Vec<(&str, &str)> = self
+            .labels
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        histogram(&self.name, start.elapsed().as_secs_f64(), &labels);
+    }
+}
+
+/// Start measuring the duration of an operation. The duration is recorded
+/// into the given histogram when the returned [`Timer`] is dropped
+pub fn timer(name: &str, labels: &[(&str, &str)]) -> Timer {
+    Timer {
+        name: name.to_string(),
+        labels: labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        start: crate::clock::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use crate::host_capabilities::metrics::MetricsRequest;
+    use std::rc::Rc;
+
+    fn samples(client: &MockHostClient) -> Vec<MetricSample> {
+        client
+            .calls_to("metrics", "v1/record")
+            .iter()
+            .flat_map(|c| {
+                serde_json::from_slice::<MetricsRequest>(&c.payload)
+                    .unwrap()
+                    .samples
+            })
+            .collect()
+    }
+
+    #[test]
+    fn emit_metrics() {
+        let client = Rc::new(MockHostClient::new().respond("metrics", "v1/record", &()));
+
+        with_host_client(client.clone(), || {
+            increment("images_rejected_total", &[("reason", "unsigned")]);
+            gauge("cache_entries", 3.0, &[]);
+            timer("verification_seconds", &[("registry", "ghcr.io")]).stop();
+            // invalid samples are not sent
+            increment("images-rejected", &[]);
+            increment("images_rejected_total", &[("1reason", "unsigned")]);
+        });
+
+        let samples = samples(&client);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].name, "images_rejected_total");
+        assert_eq!(samples[0].kind, MetricKind::Counter);
+        assert_eq!(samples[0].value, 1.0);
+        assert_eq!(samples[0].labels["reason"], "unsigned");
+        assert_eq!(samples[1].kind, MetricKind::Gauge);
+        assert_eq!(samples[2].kind, MetricKind::Histogram);
+        assert!(samples[2].value >= 0.0);
+        assert_eq!(samples[2].labels["registry"], "ghcr.io");
+    }
+
+    #[test]
+    fn host_failures_are_ignored() {
+        let client = Rc::new(MockHostClient::new().fail("metrics", "v1/record", "unsupported"));
+        with_host_client(client.clone(), || histogram("latency_seconds", 0.5, &[]));
+        assert_eq!(client.calls().len(), 1);
+    }
+}
@@ -0,0 +1,565 @@
+//! Match container images against the registries allowed by the policy
+//! settings.
+//!
+//! Nearly every image policy restricts where the images can come from.
+//! [`RegistryMatcher`] gives all of them the same semantics. Each entry of
+//! its `allow` and `deny` lists is a registry host, optionally followed by a
+//! repository prefix:
+//!
+//! * `registry.example.com` matches all the images of the host. The port is
+//!   part of the host: `registry.example.com:5000` is another registry
+//! * `*.corp.example.com` matches the hosts inside of `corp.example.com`,
+//!   like `eu.corp.example.com`, but not `corp.example.com` itself
+//! * `ghcr.io/org` matches the repositories inside of `org`, like
+//!   `ghcr.io/org/app` and `ghcr.io/org/team/app`, but not `ghcr.io/org-b/app`.
+//!   The segments of the path can contain the `*` and `?` wildcards, which
+//!   never cross a `/`, e.g. `ghcr.io/org/*-prod`
+//!
+//! Images are normalized like the container runtimes do: `nginx` is
+//! `docker.io/library/nginx`.
+//!
+//! When several entries match an image the most specific one wins: the one
+//! with the longest path, then the one with more literal path segments, then
+//! an exact host over a wildcard one, then the wildcard with more labels. A
+//! deny entry wins over an allow entry as specific as it. An empty `allow`
+//! list allows all the images that are not denied. Finally, `requireDigest`
+//! requires the allowed images to be pinned by digest.
+//!
+//! ```
+//! use kubewarden_policy_sdk::oci::RegistryMatcher;
+//! use serde_json::json;
+//!
+//! let matcher: RegistryMatcher = serde_json::from_value(json!({
+//!     "allow": ["*.corp.example.com", "ghcr.io/org"],
+//!     "deny": ["ghcr.io/org/experimental"],
+//!     "requireDigest": true
+//! }))
+//! .unwrap();
+//!
+//! let digest = "@sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b";
+//! assert!(matcher.is_allowed(&format!("eu.corp.example.com/app{}", digest)));
+//! assert!(matcher.is_allowed(&format!("ghcr.io/org/app{}", digest)));
+//! assert!(!matcher.is_allowed(&format!("ghcr.io/org/experimental/app{}", digest)));
+//! assert!(!matcher.is_allowed("ghcr.io/org/app:1.0"));
+//! assert!(!matcher.is_allowed(&format!("nginx{}", digest)));
+//! ```
+use crate::constraints::image_registry;
+use crate::docker_config::normalize_registry;
+use crate::error::{Result, SdkError};
+use crate::glob;
+use crate::response::ValidationResponse;
+use crate::semver::Version;
+use crate::settings::Validatable;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+const DOCKER_HUB: &str = "docker.io";
+
+/// A container image reference, normalized like the container runtimes do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// The registry host, with the port if any (e.g. `docker.io`)
+    pub registry: String,
+    /// The repository inside of the registry (e.g. `library/nginx`)
+    pub repository: String,
+    /// The tag, if any
+    pub tag: Option<String>,
+    /// The digest, if any (e.g. `sha256:...`)
+    pub digest: Option<String>,
+}
+
+impl Reference {
+    /// Parse an image reference, like `nginx:1.25` or
+    /// `ghcr.io/org/app@sha256:...`
+    pub fn parse(image: &str) -> Result<Reference> {
+        let invalid = |reason: &str| {
+            SdkError::InvalidInput(format!("invalid image reference {}: {}", image, reason))
+        };
+
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        if let Some(digest) = digest {
+            let valid = digest.split_once(':').is_some_and(|(algorithm, hex)| {
+                !algorithm.is_empty()
+                    && hex.len() >= 32
+                    && hex.chars().all(|c| c.is_ascii_hexdigit())
+            });
+            if !valid {
+                return Err(invalid("malformed digest"));
+            }
+        }
+        let (name, tag) = match name.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, Some(tag)),
+            _ => (name, None),
+        };
+        if tag.is_some_and(str::is_empty) {
+            return Err(invalid("empty tag"));
+        }
+
+        let registry = image_registry(name);
+        let repository = name
+            .strip_prefix(registry)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .unwrap_or(name);
+        if repository.split('/').any(str::is_empty) {
+            return Err(invalid("empty repository"));
+        }
+        if repository.chars().any(|c| c.is_ascii_uppercase()) {
+            return Err(invalid("the repository must be lowercase"));
+        }
+        if let Some(component) = repository.split('/').find(|c| !is_path_component(c)) {
+            return Err(invalid(&format!(
+                "invalid repository component {}",
+                component
+            )));
+        }
+
+        let registry = normalize_registry(registry).to_ascii_lowercase();
+        let repository = if registry == DOCKER_HUB && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository.to_string()
+        };
+        Ok(Reference {
+            registry,
+            repository,
+            tag: tag.map(str::to_string),
+            digest: digest.map(str::to_string),
+        })
+    }
+
+    /// The version of the tag, see [`Version::from_tag`]
+    pub fn version(&self) -> Option<Version> {
+        self.tag.as_deref().and_then(Version::from_tag)
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.registry, self.repository)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `component` is a valid component of a repository path, as
+/// defined by the OCI distribution specification:
+/// `[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*`
+fn is_path_component(component: &str) -> bool {
+    let is_alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    let mut rest = component;
+    loop {
+        let end = rest.find(|c| !is_alphanumeric(c)).unwrap_or(rest.len());
+        if end == 0 {
+            return false;
+        }
+        rest = &rest[end..];
+        if rest.is_empty() {
+            return true;
+        }
+        let end = rest.find(is_alphanumeric).unwrap_or(rest.len());
+        match &rest[..end] {
+            "." | "_" | "__" => {}
+            separator if separator.bytes().all(|b| b == b'-') => {}
+            _ => return false,
+        }
+        rest = &rest[end..];
+    }
+}
+
+/// The registries the images can come from, see the [module](self)
+/// documentation for the matching rules. Invalid entries are rejected
+/// when deserializing the matcher
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RegistryMatcher {
+    /// The registries, or repository prefixes, allowed. When empty all the
+    /// images that are not denied are allowed
+    #[serde(deserialize_with = "deserialize_entries")]
+    pub allow: Vec<String>,
+    /// The registries, or repository prefixes, denied
+    #[serde(deserialize_with = "deserialize_entries")]
+    pub deny: Vec<String>,
+    /// Require the images to be pinned by digest
+    pub require_digest: bool,
+}
+
+fn deserialize_entries<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let entries = Vec::<String>::deserialize(deserializer)?;
+    for entry in &entries {
+        Rule::parse(entry).map_err(serde::de::Error::custom)?;
+    }
+    Ok(entries)
+}
+
+impl Validatable for RegistryMatcher {
+    fn validate(&self) -> std::result::Result<(), String> {
+        for (field, entries) in [("allow", &self.allow), ("deny", &self.deny)] {
+            for entry in entries {
+                Rule::parse(entry).map_err(|e| format!("{}: {}", field, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RegistryMatcher {
+    /// Whether `image` is allowed
+    pub fn is_allowed(&self, image: &str) -> bool {
+        self.check_image(image).is_ok()
+    }
+
+    /// Check a single image, the error describes why it's not allowed
+    pub fn check_image(&self, image: &str) -> std::result::Result<(), String> {
+        let reference = Reference::parse(image).map_err(|e| e.to_string())?;
+
+        let allowed = most_specific(&self.allow, &reference);
+        if let Some((denied, entry)) = most_specific(&self.deny, &reference) {
+            if allowed.is_none_or(|(allowed, _)| denied >= allowed) {
+                return Err(format!("image {} is denied by {}", image, entry));
+            }
+        }
+        if allowed.is_none() && !self.allow.is_empty() {
+            return Err(format!(
+                "image {} doesn't come from an allowed registry",
+                image
+            ));
+        }
+
+        if self.require_digest && reference.digest.is_none() {
+            return Err(format!("image {} is not pinned by digest", image));
+        }
+        Ok(())
+    }
+
+    /// Check all the images. Returns all the violations, an empty list means
+    /// all the images are allowed
+    pub fn check<I, S>(&self, images: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        images
+            .into_iter()
+            .filter_map(|image| self.check_image(image.as_ref()).err())
+            .collect()
+    }
+
+    /// Accept the request when all the images are allowed, reject it with all
+    /// the violations otherwise
+    pub fn evaluate<I, S>(&self, images: I) -> ValidationResponse
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let violations = self.check(images);
+        if violations.is_empty() {
+            ValidationResponse::accept().response()
+        } else {
+            ValidationResponse::reject(violations.join("; ")).response()
+        }
+    }
+}
+
+/// The specificity of the entry matching `reference` that wins, with the entry
+fn most_specific<'a>(
+    entries: &'a [String],
+    reference: &Reference,
+) -> Option<(Specificity, &'a str)> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let rule = Rule::parse(entry).ok()?;
+            rule.matches(reference)
+                .then(|| (rule.specificity(), entry.as_str()))
+        })
+        .max_by_key(|(specificity, _)| *specificity)
+}
+
+/// Path segments, literal path segments, exact host, host labels
+type Specificity = (usize, usize, bool, usize);
+
+/// An entry of the `allow` or `deny` lists
+#[derive(Debug)]
+struct Rule {
+    host: Host,
+    path: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Host {
+    Exact(String),
+    /// `*.corp.example.com`, stored as `.corp.example.com`
+    Suffix(String),
+}
+
+impl Rule {
+    fn parse(entry: &str) -> std::result::Result<Rule, String> {
+        let entry = entry.trim().trim_end_matches('/');
+        if entry.is_empty() {
+            return Err("entries cannot be empty".to_string());
+        }
+        if entry.contains("://") {
+            return Err(format!("{}: expected a registry host, not a URL", entry));
+        }
+        if entry.contains('@') {
+            return Err(format!("{}: entries cannot contain a digest", entry));
+        }
+
+        let (host, path) = entry.split_once('/').unwrap_or((entry, ""));
+        let host = if let Some(domain) = host.strip_prefix("*.") {
+            if domain.is_empty() || domain.contains(['*', '?']) {
+                return Err(format!("{}: invalid wildcard host", entry));
+            }
+            Host::Suffix(format!(".{}", domain.to_ascii_lowercase()))
+        } else if host.contains(['*', '?']) {
+            return Err(format!(
+                "{}: wildcards are allowed only as the first label of the host",
+                entry
+            ));
+        } else {
+            Host::Exact(normalize_registry(host).to_ascii_lowercase())
+        };
+
+        let path: Vec<String> = if path.is_empty() {
+            Vec::new()
+        } else {
+            path.split('/').map(str::to_string).collect()
+        };
+        if path.iter().any(|segment| segment.is_empty()) {
+            return Err(format!("{}: empty path segment", entry));
+        }
+        if path.iter().any(|segment| segment.contains(':')) {
+            return Err(format!("{}: entries cannot contain a tag", entry));
+        }
+        if let Some(segment) = path
+            .iter()
+            .find(|segment| !glob::is_pattern(segment) && !is_path_component(segment))
+        {
+            return Err(format!("{}: invalid path segment {}", entry, segment));
+        }
+        Ok(Rule { host, path })
+    }
+
+    fn matches(&self, reference: &Reference) -> bool {
+        let host_matches = match &self.host {
+            Host::Exact(host) => reference.registry == *host,
+            Host::Suffix(suffix) => reference
+                .registry
+                .strip_suffix(suffix.as_str())
+                .is_some_and(|label| !label.is_empty()),
+        };
+        let segments: Vec<&str> = reference.repository.split('/').collect();
+        host_matches
+            && self.path.len() <= segments.len()
+            && self
+                .path
+                .iter()
+                .zip(segments)
+                .all(|(pattern, segment)| glob::matches(pattern, segment))
+    }
+
+    fn specificity(&self) -> Specificity {
+        let literal = self
+            .path
+            .iter()
+            .filter(|segment| !glob::is_pattern(segment))
+            .count();
+        let (exact, labels) = match &self.host {
+            Host::Exact(host) => (true, host.split('.').count()),
+            Host::Suffix(suffix) => (false, suffix.split('.').count()),
+        };
+        (self.path.len(), literal, exact, labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const DIGEST: &str = "sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b";
+
+    fn matcher(settings: serde_json::Value) -> RegistryMatcher {
+        let matcher: RegistryMatcher = serde_json::from_value(settings).unwrap();
+        matcher.validate().unwrap();
+        matcher
+    }
+
+    #[test]
+    fn parse_references() {
+        let reference = Reference::parse("nginx").unwrap();
+        assert_eq!(reference.registry, "docker.io");
+        assert_eq!(reference.repository, "library/nginx");
+        assert_eq!(reference.tag, None);
+
+        let reference = Reference::parse("index.docker.io/bitnami/redis:7.2").unwrap();
+        assert_eq!(reference.to_string(), "docker.io/bitnami/redis:7.2");
+
+        let image = format!("registry.example.com:5000/team/app:1.0@{}", DIGEST);
+        let reference = Reference::parse(&image).unwrap();
+        assert_eq!(reference.registry, "registry.example.com:5000");
+        assert_eq!(reference.repository, "team/app");
+        assert_eq!(reference.tag.as_deref(), Some("1.0"));
+        assert_eq!(reference.digest.as_deref(), Some(DIGEST));
+        assert_eq!(reference.to_string(), image);
+        assert_eq!(reference.version(), Some(Version::new(1, 0, 0)));
+        assert_eq!(Reference::parse("nginx:latest").unwrap().version(), None);
+
+        for invalid in [
+            "",
+            "nginx:",
+            "ghcr.io//app",
+            "ghcr.io/Org/app",
+            "nginx@sha256:xyz",
+            "ghcr.io/org/../evil",
+            "ghcr.io/org/.hidden",
+            "ghcr.io/org/app-",
+            "ghcr.io/org/a..b",
+            "ghcr.io/org/a___b",
+        ] {
+            assert!(Reference::parse(invalid).is_err(), "{}", invalid);
+        }
+        for valid in [
+            "ghcr.io/org/my_app",
+            "ghcr.io/org/my__app",
+            "ghcr.io/org/my--app.v2",
+        ] {
+            assert!(Reference::parse(valid).is_ok(), "{}", valid);
+        }
+
+        let matcher = matcher(json!({"allow": ["ghcr.io/org"]}));
+        assert!(!matcher.is_allowed("ghcr.io/org/../evil"));
+    }
+
+    #[test]
+    fn hosts_and_prefixes() {
+        let matcher = matcher(json!({
+            "allow": ["registry.example.com", "*.corp.example.com", "ghcr.io/org", "docker.io/library"]
+        }));
+        for allowed in [
+            "registry.example.com/app",
+            "eu.corp.example.com/app",
+            "a.b.corp.example.com/app",
+            "ghcr.io/org/app",
+            "ghcr.io/org/team/app:1.0",
+            "nginx:1.25",
+        ] {
+            assert!(matcher.is_allowed(allowed), "{}", allowed);
+        }
+        for denied in [
+            "registry.example.com:5000/app",
+            "corp.example.com/app",
+            "evilcorp.example.com/app",
+            "ghcr.io/org-b/app",
+            "ghcr.io/app",
+            "bitnami/redis",
+        ] {
+            assert!(!matcher.is_allowed(denied), "{}", denied);
+        }
+
+        let globs = self::matcher(json!({"allow": ["ghcr.io/org/*-prod"]}));
+        assert!(globs.is_allowed("ghcr.io/org/api-prod"));
+        assert!(globs.is_allowed("ghcr.io/org/api-prod/worker"));
+        assert!(!globs.is_allowed("ghcr.io/org/api-dev"));
+    }
+
+    #[test]
+    fn the_most_specific_entry_wins() {
+        let matcher = matcher(json!({
+            "allow": [
+                "ghcr.io",
+                "ghcr.io/org/public",
+                "ghcr.io/org/experimental/approved",
+                "*.corp.example.com"
+            ],
+            "deny": ["ghcr.io/org/experimental", "ghcr.io/org/*", "eu.corp.example.com"]
+        }));
+        assert!(matcher.is_allowed("ghcr.io/other/app"));
+        assert!(matcher.is_allowed("ghcr.io/org/experimental/approved/app"));
+        // the literal segment is more specific than the wildcard one
+        assert!(matcher.is_allowed("ghcr.io/org/public/app"));
+        assert!(!matcher.is_allowed("ghcr.io/org/experimental/app"));
+        assert!(!matcher.is_allowed("ghcr.io/org/app"));
+        // the exact host is more specific than the wildcard one
+        assert!(!matcher.is_allowed("eu.corp.example.com/app"));
+        assert!(matcher.is_allowed("us.corp.example.com/app"));
+
+        // deny wins ties
+        let tie = self::matcher(json!({"allow": ["ghcr.io/org"], "deny": ["ghcr.io/org"]}));
+        assert!(!tie.is_allowed("ghcr.io/org/app"));
+
+        // an empty allow list allows everything but the denied images
+        let deny_only = self::matcher(json!({"deny": ["docker.io"]}));
+        assert!(deny_only.is_allowed("quay.io/org/app"));
+        assert_eq!(
+            deny_only.check_image("nginx").unwrap_err(),
+            "image nginx is denied by docker.io"
+        );
+    }
+
+    #[test]
+    fn require_digest() {
+        let matcher = matcher(json!({"allow": ["ghcr.io"], "requireDigest": true}));
+        assert!(matcher.is_allowed(&format!("ghcr.io/org/app@{}", DIGEST)));
+        assert!(matcher.is_allowed(&format!("ghcr.io/org/app:1.0@{}", DIGEST)));
+
+        let response = matcher.evaluate(["ghcr.io/org/app:1.0", "quay.io/org/app"]);
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.as_deref(),
+            Some(
+                "image ghcr.io/org/app:1.0 is not pinned by digest; \
+                 image quay.io/org/app doesn't come from an allowed registry"
+            )
+        );
+        assert!(matcher.evaluate(Vec::<String>::new()).accepted);
+    }
+
+    #[test]
+    fn validate_entries() {
+        for invalid in [
+            "",
+            "https://ghcr.io",
+            "ghcr.io/org@sha256:abc",
+            "ghcr.io/org:1.0",
+            "*.",
+            "registry.*.example.com",
+            "ghcr.io//org",
+            "ghcr.io/org/..",
+        ] {
+            let matcher = RegistryMatcher {
+                allow: vec![invalid.to_string()],
+                ..Default::default()
+            };
+            assert!(matcher.validate().is_err(), "{:?}", invalid);
+        }
+        let matcher = RegistryMatcher {
+            deny: vec!["*".to_string()],
+            ..Default::default()
+        };
+        assert!(matcher.validate().unwrap_err().starts_with("deny: "));
+    }
+
+    #[test]
+    fn reject_invalid_entries_when_deserializing() {
+        for settings in [
+            json!({"allow": ["https://ghcr.io"]}),
+            json!({"deny": ["*"]}),
+            json!({"deny": ["ghcr.io/org", "ghcr.io/org:1.0"]}),
+        ] {
+            let result = serde_json::from_value::<RegistryMatcher>(settings.clone());
+            assert!(result.is_err(), "{}", settings);
+        }
+    }
+}
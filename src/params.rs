@@ -0,0 +1,346 @@
+//! Parameter resources, in the style of ValidatingAdmissionPolicy.
+//!
+//! A ValidatingAdmissionPolicy can be configured by parameter resources,
+//! usually custom resources, referenced by its bindings. The same policy
+//! can then be deployed several times with different parameters, which are
+//! managed with the Kubernetes API instead of being embedded into the
+//! settings.
+//!
+//! Policies can offer the same experience by adding a [`ParamSource`] to
+//! their settings and resolving it while evaluating a request:
+//!
+//! ```
+//! use kubewarden_policy_sdk::host_capabilities::client::with_host_client;
+//! use kubewarden_policy_sdk::params::ParamSource;
+//! use kubewarden_policy_sdk::request::KubernetesAdmissionRequest;
+//! use kubewarden_policy_sdk::testing::FakeCluster;
+//! use serde::Deserialize;
+//! use serde_json::json;
+//! use std::rc::Rc;
+//!
+//! #[derive(Deserialize, Clone)]
+//! #[serde(rename_all = "camelCase")]
+//! struct ReplicaLimit {
+//!     max_replicas: u32,
+//! }
+//!
+//! // part of the policy settings
+//! let source: ParamSource = serde_json::from_value(json!({
+//!     "paramKind": {"apiVersion": "rules.example.com/v1", "kind": "ReplicaLimit"},
+//!     "paramRef": {"name": "default", "parameterNotFoundAction": "Deny"}
+//! }))
+//! .unwrap();
+//!
+//! let cluster = Rc::new(FakeCluster::new());
+//! cluster.insert_value(json!({
+//!     "apiVersion": "rules.example.com/v1",
+//!     "kind": "ReplicaLimit",
+//!     "metadata": {"name": "default", "namespace": "team-a"},
+//!     "maxReplicas": 3
+//! }));
+//!
+//! let request = KubernetesAdmissionRequest {
+//!     namespace: "team-a".to_string(),
+//!     ..Default::default()
+//! };
+//! let params = with_host_client(cluster, || source.resolve::<ReplicaLimit>(&request)).unwrap();
+//! assert_eq!(params.unwrap()[0].max_replicas, 3);
+//! ```
+use crate::error::{Result, SdkError};
+use crate::host_capabilities::encoding;
+use crate::host_capabilities::kubernetes::{
+    ListAllResourcesRequest, ListResourcesByNamespaceRequest,
+};
+use crate::request::KubernetesAdmissionRequest;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The kind of the parameter resources
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamKind {
+    /// apiVersion of the resources (v1 for core group, groupName/groupVersions for other)
+    pub api_version: String,
+    /// Singular PascalCase name of the resources
+    pub kind: String,
+    /// True when the resources are cluster-wide. Unlike ValidatingAdmissionPolicy,
+    /// which discovers it from the API server, the SDK must be told about it
+    #[serde(default)]
+    pub cluster_scoped: bool,
+}
+
+/// The action taken when no parameter resource is found
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ParameterNotFoundAction {
+    /// The request is evaluated without parameters
+    Allow,
+    /// The request is rejected
+    #[default]
+    Deny,
+}
+
+/// Reference to the parameter resources. Either `name` or `selector` must
+/// be set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamRef {
+    /// Name of the parameter resource
+    pub name: Option<String>,
+    /// Namespace of the parameter resources. Defaults to the namespace of
+    /// the object being evaluated; ignored for cluster-wide resources
+    pub namespace: Option<String>,
+    /// Selects all the parameter resources matching the labels
+    pub selector: Option<LabelSelector>,
+    /// The action taken when no parameter resource is found
+    #[serde(default)]
+    pub parameter_not_found_action: ParameterNotFoundAction,
+}
+
+/// The parameters of a policy: the kind of the resources and the reference
+/// to them
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamSource {
+    /// The kind of the parameter resources
+    pub param_kind: ParamKind,
+    /// The reference to the parameter resources
+    pub param_ref: ParamRef,
+}
+
+#[derive(Deserialize)]
+struct ParamList<T> {
+    items: Vec<T>,
+}
+
+impl ParamSource {
+    /// Ensures the reference can be resolved, meant to be invoked by the
+    /// validation of the settings
+    pub fn validate(&self) -> Result<(), String> {
+        if self.param_kind.api_version.is_empty() || self.param_kind.kind.is_empty() {
+            return Err("paramKind must have both apiVersion and kind".to_string());
+        }
+        match (&self.param_ref.name, &self.param_ref.selector) {
+            (Some(_), Some(_)) => Err("paramRef cannot have both name and selector".to_string()),
+            (None, None) => Err("paramRef must have either name or selector".to_string()),
+            (Some(_), None) => Ok(()),
+            (None, Some(selector)) => label_selector(selector).map(|_| ()),
+        }
+    }
+
+    /// Fetch the parameter resources referenced by the source, for the given
+    /// request.
+    ///
+    /// Returns `None` when no resource is found and the action is
+    /// [`ParameterNotFoundAction::Allow`], and an error when the action is
+    /// [`ParameterNotFoundAction::Deny`].
+    pub fn resolve<T>(&self, request: &KubernetesAdmissionRequest) -> Result<Option<Vec<T>>>
+    where
+        T: DeserializeOwned,
+    {
+        self.validate().map_err(SdkError::InvalidInput)?;
+        let label_selector = self
+            .param_ref
+            .selector
+            .as_ref()
+            .map(label_selector)
+            .transpose()
+            .map_err(SdkError::InvalidInput)?;
+        let field_selector = self
+            .param_ref
+            .name
+            .as_ref()
+            .map(|name| format!("metadata.name={}", name));
+
+        let namespace = self
+            .param_ref
+            .namespace
+            .as_deref()
+            .filter(|ns| !ns.is_empty())
+            .unwrap_or(&request.namespace);
+        let params: ParamList<T> = if self.param_kind.cluster_scoped || namespace.is_empty() {
+            let req = ListAllResourcesRequest {
+                api_version: self.param_kind.api_version.clone(),
+                kind: self.param_kind.kind.clone(),
+                label_selector,
+                field_selector,
+            };
+            encoding::call(
+                "kubernetes",
+                "list_resources_all",
+                &req,
+                "the list all resources request",
+                "the parameter resources",
+            )?
+        } else {
+            let req = ListResourcesByNamespaceRequest {
+                api_version: self.param_kind.api_version.clone(),
+                kind: self.param_kind.kind.clone(),
+                namespace: namespace.to_string(),
+                label_selector,
+                field_selector,
+            };
+            encoding::call(
+                "kubernetes",
+                "list_resources_by_namespace",
+                &req,
+                "the list resources by namespace request",
+                "the parameter resources",
+            )?
+        };
+        if !params.items.is_empty() {
+            return Ok(Some(params.items));
+        }
+        match self.param_ref.parameter_not_found_action {
+            ParameterNotFoundAction::Allow => Ok(None),
+            ParameterNotFoundAction::Deny => Err(SdkError::InvalidInput(format!(
+                "no {} parameter resources found",
+                self.param_kind.kind
+            ))),
+        }
+    }
+}
+
+/// Render a label selector using the syntax of `kubectl get -l`
+fn label_selector(selector: &LabelSelector) -> Result<String, String> {
+    let mut requirements: Vec<String> = selector
+        .match_labels
+        .iter()
+        .flatten()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    for expression in selector.match_expressions.iter().flatten() {
+        let values = expression.values.as_deref().unwrap_or_default().join(",");
+        requirements.push(match expression.operator.as_str() {
+            "In" => format!("{} in ({})", expression.key, values),
+            "NotIn" => format!("{} notin ({})", expression.key, values),
+            "Exists" => expression.key.clone(),
+            "DoesNotExist" => format!("!{}", expression.key),
+            operator => return Err(format!("unsupported selector operator {}", operator)),
+        });
+    }
+    Ok(requirements.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::with_host_client;
+    use crate::testing::FakeCluster;
+    use serde_json::{json, Value};
+    use std::rc::Rc;
+
+    fn cluster() -> Rc<FakeCluster> {
+        let cluster = Rc::new(FakeCluster::new());
+        for (name, namespace, tier) in [
+            ("a", Some("team-a"), "gold"),
+            ("b", Some("team-a"), "silver"),
+            ("c", Some("team-b"), "gold"),
+            ("global", None, "gold"),
+        ] {
+            let mut object = json!({
+                "apiVersion": "example.com/v1",
+                "kind": if namespace.is_some() { "Limit" } else { "ClusterLimit" },
+                "metadata": {"name": name, "labels": {"tier": tier}},
+            });
+            if let Some(namespace) = namespace {
+                object["metadata"]["namespace"] = json!(namespace);
+            }
+            cluster.insert_value(object);
+        }
+        cluster
+    }
+
+    fn source(value: Value) -> ParamSource {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn names(params: Option<Vec<Value>>) -> Vec<String> {
+        params
+            .unwrap_or_default()
+            .iter()
+            .map(|p| p["metadata"]["name"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn resolve_params() {
+        let request = KubernetesAdmissionRequest {
+            namespace: "team-a".to_string(),
+            ..Default::default()
+        };
+        let kind = json!({"apiVersion": "example.com/v1", "kind": "Limit"});
+
+        with_host_client(cluster(), || {
+            let by_name = source(json!({"paramKind": kind, "paramRef": {"name": "b"}}));
+            assert_eq!(names(by_name.resolve(&request).unwrap()), ["b"]);
+
+            let by_selector = source(json!({
+                "paramKind": kind,
+                "paramRef": {"selector": {"matchExpressions": [
+                    {"key": "tier", "operator": "In", "values": ["gold"]}
+                ]}}
+            }));
+            assert_eq!(names(by_selector.resolve(&request).unwrap()), ["a"]);
+
+            let other_namespace = source(json!({
+                "paramKind": kind,
+                "paramRef": {"namespace": "team-b", "selector": {"matchLabels": {"tier": "gold"}}}
+            }));
+            assert_eq!(names(other_namespace.resolve(&request).unwrap()), ["c"]);
+
+            let cluster_scoped = source(json!({
+                "paramKind": {"apiVersion": "example.com/v1", "kind": "ClusterLimit", "clusterScoped": true},
+                "paramRef": {"name": "global"}
+            }));
+            assert_eq!(names(cluster_scoped.resolve(&request).unwrap()), ["global"]);
+        });
+    }
+
+    #[test]
+    fn missing_params() {
+        let request = KubernetesAdmissionRequest {
+            namespace: "team-b".to_string(),
+            ..Default::default()
+        };
+        let kind = json!({"apiVersion": "example.com/v1", "kind": "Limit"});
+
+        with_host_client(cluster(), || {
+            let allow = source(json!({
+                "paramKind": kind,
+                "paramRef": {"name": "a", "parameterNotFoundAction": "Allow"}
+            }));
+            assert!(allow.resolve::<Value>(&request).unwrap().is_none());
+
+            let deny = source(json!({"paramKind": kind, "paramRef": {"name": "a"}}));
+            assert_eq!(
+                deny.resolve::<Value>(&request).unwrap_err().to_string(),
+                "no Limit parameter resources found"
+            );
+        });
+    }
+
+    #[test]
+    fn validate_source() {
+        let kind = json!({"apiVersion": "example.com/v1", "kind": "Limit"});
+        assert!(source(json!({"paramKind": kind, "paramRef": {}}))
+            .validate()
+            .is_err());
+        assert!(source(json!({
+            "paramKind": kind,
+            "paramRef": {"name": "a", "selector": {}}
+        }))
+        .validate()
+        .is_err());
+        assert!(source(json!({
+            "paramKind": kind,
+            "paramRef": {"selector": {"matchExpressions": [{"key": "tier", "operator": "Gt"}]}}
+        }))
+        .validate()
+        .is_err());
+        assert!(
+            source(json!({"paramKind": kind, "paramRef": {"selector": {}}}))
+                .validate()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! A small query API over [`serde_json::Value`] objects.
+//!
+//! Queries are written using the JSON Pointer syntax (RFC 6901), where the `*`
+//! segment matches all the elements of an array or all the values of an object.
+//! This allows to write generic policies, for example policies targeting
+//! Custom Resources, without deep chains of `get()` calls.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::path::PathQuery;
+//! use serde_json::json;
+//!
+//! let deployment = json!({
+//!     "spec": {"template": {"spec": {"containers": [
+//!         {"name": "app", "image": "nginx"},
+//!         {"name": "sidecar", "image": "busybox"}
+//!     ]}}}
+//! });
+//!
+//! let images: Vec<_> = deployment
+//!     .path("/spec/template/spec/containers/*/image")
+//!     .into_iter()
+//!     .map(|m| (m.pointer, m.value.as_str().unwrap()))
+//!     .collect();
+//!
+//! assert_eq!(
+//!     images,
+//!     vec![
+//!         ("/spec/template/spec/containers/0/image".to_string(), "nginx"),
+//!         ("/spec/template/spec/containers/1/image".to_string(), "busybox"),
+//!     ]
+//! );
+//! ```
+use crate::diff::escape_pointer_token;
+use serde_json::Value;
+
+/// A value matched by a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMatch<'a> {
+    /// The JSON Pointer of the matched value
+    pub pointer: String,
+    /// The matched value
+    pub value: &'a Value,
+}
+
+/// Extension trait providing path queries over [`serde_json::Value`]
+pub trait PathQuery {
+    /// Returns all the values matching the given query, together with their
+    /// JSON Pointers. See the [module documentation](crate::path) for the
+    /// syntax of the query.
+    fn path(&self, query: &str) -> Vec<PathMatch<'_>>;
+
+    /// Returns the values matching the given query
+    fn path_values(&self, query: &str) -> Vec<&Value> {
+        self.path(query).into_iter().map(|m| m.value).collect()
+    }
+}
+
+impl PathQuery for Value {
+    fn path(&self, query: &str) -> Vec<PathMatch<'_>> {
+        let tokens: Vec<String> = if query.is_empty() {
+            vec![]
+        } else {
+            query
+                .strip_prefix('/')
+                .unwrap_or(query)
+                .split('/')
+                .map(unescape_pointer_token)
+                .collect()
+        };
+
+        let mut matches = Vec::new();
+        collect_matches(self, &tokens, String::new(), &mut matches);
+        matches
+    }
+}
+
+fn collect_matches<'a>(
+    value: &'a Value,
+    tokens: &[String],
+    pointer: String,
+    matches: &mut Vec<PathMatch<'a>>,
+) {
+    let Some((token, rest)) = tokens.split_first() else {
+        matches.push(PathMatch { pointer, value });
+        return;
+    };
+
+    match value {
+        Value::Object(map) if token == "*" => {
+            for (key, child) in map {
+                let pointer = format!("{}/{}", pointer, escape_pointer_token(key));
+                collect_matches(child, rest, pointer, matches);
+            }
+        }
+        Value::Object(map) => {
+            if let Some(child) = map.get(token) {
+                let pointer = format!("{}/{}", pointer, escape_pointer_token(token));
+                collect_matches(child, rest, pointer, matches);
+            }
+        }
+        Value::Array(items) if token == "*" => {
+            for (index, child) in items.iter().enumerate() {
+                collect_matches(child, rest, format!("{}/{}", pointer, index), matches);
+            }
+        }
+        Value::Array(items) => {
+            if let Some(child) = token.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                collect_matches(child, rest, format!("{}/{}", pointer, token), matches);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn unescape_pointer_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn exact_path() {
+        let value = json!({"metadata": {"labels": {"app.kubernetes.io/name": "demo"}}});
+        let matches = value.path("/metadata/labels/app.kubernetes.io~1name");
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].pointer,
+            "/metadata/labels/app.kubernetes.io~1name"
+        );
+        assert_eq!(matches[0].value, &json!("demo"));
+        assert_eq!(value.pointer(&matches[0].pointer), Some(matches[0].value));
+    }
+
+    #[test]
+    fn wildcards_over_arrays_and_objects() {
+        let value = json!({
+            "spec": {
+                "containers": [
+                    {"ports": [{"containerPort": 80}, {"containerPort": 443}]},
+                    {"ports": [{"containerPort": 8080}]},
+                    {"name": "no-ports"}
+                ]
+            },
+            "metadata": {"labels": {"a": "1", "b": "2"}}
+        });
+
+        assert_eq!(
+            value.path_values("/spec/containers/*/ports/*/containerPort"),
+            vec![&json!(80), &json!(443), &json!(8080)]
+        );
+        assert_eq!(value.path("/metadata/labels/*").len(), 2);
+    }
+
+    #[test]
+    fn array_index_and_missing_paths() {
+        let value = json!({"items": [{"name": "a"}, {"name": "b"}]});
+
+        assert_eq!(value.path_values("/items/1/name"), vec![&json!("b")]);
+        assert!(value.path("/items/5/name").is_empty());
+        assert!(value.path("/items/x").is_empty());
+        assert!(value.path("/missing/*").is_empty());
+    }
+
+    #[test]
+    fn empty_query_matches_root() {
+        let value = json!({"a": 1});
+        let matches = value.path("");
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].pointer, "");
+        assert_eq!(matches[0].value, &value);
+    }
+}
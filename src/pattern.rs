@@ -0,0 +1,516 @@
+//! Kyverno-style patterns over JSON values.
+//!
+//! Kyverno `validate.pattern` rules describe the allowed shape of a resource
+//! with an overlay: every field of the pattern must be found inside of the
+//! resource, with a value matching the pattern value. This module implements
+//! the same semantics, so patterns can be ported as they are:
+//!
+//! * string values can use the `*` and `?` wildcards, the `!`, `>`, `>=`,
+//!   `<` and `<=` operators, the `a-b` and `!a-b` ranges, and combine them
+//!   with `|` (or) and `&` (and). Operands are compared as Kubernetes
+//!   quantities when possible, and as durations when the `time` feature is
+//!   enabled
+//! * `*` matches any value, missing fields included, while `?*` requires a
+//!   non-empty one
+//! * a pattern array holding an object is matched by all the elements of
+//!   the resource array, other arrays are matched element by element
+//! * the keys of the pattern can carry the anchors of Kyverno: `(key)`
+//!   conditional, `<(key)` global, `=(key)` equality, `^(key)` existence,
+//!   `X(key)` negation and `+(key)`, which is handled like `=(key)`
+//!
+//! ```
+//! use kubewarden_policy_sdk::pattern::{validate, Outcome};
+//! use serde_json::json;
+//!
+//! let pattern = json!({
+//!     "spec": {
+//!         "containers": [{
+//!             "(image)": "*:latest",
+//!             "imagePullPolicy": "Always"
+//!         }]
+//!     }
+//! });
+//!
+//! let pod = json!({
+//!     "spec": {
+//!         "containers": [
+//!             {"image": "nginx:latest", "imagePullPolicy": "IfNotPresent"},
+//!             {"image": "busybox:1.36"}
+//!         ]
+//!     }
+//! });
+//!
+//! assert_eq!(
+//!     validate(&pod, &pattern),
+//!     Outcome::Fail {
+//!         path: "/spec/containers/0/imagePullPolicy".to_string(),
+//!         message: "value IfNotPresent doesn't match the pattern Always".to_string(),
+//!     }
+//! );
+//! ```
+use crate::diff::escape_pointer_token;
+use crate::glob;
+use crate::quantity::Quantity;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+/// The result of matching a resource against a pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The resource matches the pattern
+    Pass,
+    /// The resource doesn't match the pattern, `path` is the JSON pointer
+    /// of the first mismatch
+    Fail { path: String, message: String },
+    /// A conditional or global anchor is not satisfied: the pattern
+    /// doesn't apply to the resource
+    Skip { path: String },
+}
+
+impl Outcome {
+    /// Returns true unless the outcome is a failure, skipped patterns don't
+    /// reject resources
+    pub fn is_allowed(&self) -> bool {
+        !matches!(self, Outcome::Fail { .. })
+    }
+}
+
+/// Match `resource` against `pattern`
+pub fn validate(resource: &Value, pattern: &Value) -> Outcome {
+    match element(Some(resource), pattern, "") {
+        Ok(()) => Outcome::Pass,
+        Err(Mismatch::Fail { path, message }) => Outcome::Fail { path, message },
+        Err(Mismatch::Skip { path, .. }) => Outcome::Skip { path },
+    }
+}
+
+/// Returns true when `value` matches a string pattern, see the
+/// [module documentation](self) for the syntax
+pub fn matches_value(value: &Value, pattern: &str) -> bool {
+    value_matches(Some(value), pattern)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Anchor {
+    None,
+    Conditional,
+    Global,
+    Equality,
+    Existence,
+    Negation,
+}
+
+/// Split the anchor of a key from the name of the field
+fn anchor(key: &str) -> (Anchor, &str) {
+    let Some(inner) = key.strip_suffix(')') else {
+        return (Anchor::None, key);
+    };
+    for (prefix, anchor) in [
+        ("(", Anchor::Conditional),
+        ("<(", Anchor::Global),
+        ("=(", Anchor::Equality),
+        ("+(", Anchor::Equality),
+        ("^(", Anchor::Existence),
+        ("X(", Anchor::Negation),
+    ] {
+        if let Some(field) = inner.strip_prefix(prefix) {
+            return (anchor, field);
+        }
+    }
+    (Anchor::None, key)
+}
+
+enum Mismatch {
+    Fail { path: String, message: String },
+    Skip { path: String, global: bool },
+}
+
+fn fail(path: &str, message: String) -> Mismatch {
+    Mismatch::Fail {
+        path: path.to_string(),
+        message,
+    }
+}
+
+fn element(resource: Option<&Value>, pattern: &Value, path: &str) -> Result<(), Mismatch> {
+    match pattern {
+        Value::Object(pattern) => match resource {
+            Some(Value::Object(resource)) => object(resource, pattern, path),
+            None => Err(fail(path, "the field is required".to_string())),
+            Some(_) => Err(fail(path, "expected an object".to_string())),
+        },
+        Value::Array(pattern) => match resource {
+            Some(Value::Array(resource)) => array(resource, pattern, path),
+            None => Err(fail(path, "the field is required".to_string())),
+            Some(_) => Err(fail(path, "expected an array".to_string())),
+        },
+        Value::String(pattern) if value_matches(resource, pattern) => Ok(()),
+        Value::String(_) | Value::Bool(_) | Value::Number(_) | Value::Null
+            if scalar_matches(resource, pattern) =>
+        {
+            Ok(())
+        }
+        _ => Err(fail(
+            path,
+            format!(
+                "value {} doesn't match the pattern {}",
+                resource.map(display).unwrap_or_else(|| "null".to_string()),
+                display(pattern)
+            ),
+        )),
+    }
+}
+
+fn object(
+    resource: &Map<String, Value>,
+    pattern: &Map<String, Value>,
+    path: &str,
+) -> Result<(), Mismatch> {
+    // the anchors deciding whether the pattern applies are evaluated first
+    for (key, value) in pattern {
+        let (anchor, field) = anchor(key);
+        if !matches!(anchor, Anchor::Conditional | Anchor::Global) {
+            continue;
+        }
+        let field_path = format!("{}/{}", path, escape_pointer_token(field));
+        if let Err(mismatch) = element(resource.get(field), value, &field_path) {
+            let global =
+                anchor == Anchor::Global || matches!(mismatch, Mismatch::Skip { global: true, .. });
+            return Err(Mismatch::Skip {
+                path: field_path,
+                global,
+            });
+        }
+    }
+
+    for (key, value) in pattern {
+        let (anchor, field) = anchor(key);
+        let field_path = format!("{}/{}", path, escape_pointer_token(field));
+        let found = resource.get(field);
+        match anchor {
+            Anchor::Conditional | Anchor::Global => {}
+            Anchor::Negation if found.is_some() => {
+                return Err(fail(&field_path, "the field is not allowed".to_string()))
+            }
+            Anchor::Negation => {}
+            Anchor::Equality if found.is_none() => {}
+            Anchor::Existence => existence(found, value, &field_path)?,
+            Anchor::Equality | Anchor::None => element(found, value, &field_path)?,
+        }
+    }
+    Ok(())
+}
+
+fn array(resource: &[Value], pattern: &[Value], path: &str) -> Result<(), Mismatch> {
+    match pattern {
+        [] => Ok(()),
+        [pattern @ Value::Object(_)] => {
+            // elements not satisfying the conditional anchors are ignored,
+            // the pattern is skipped when it applies to none of them
+            let mut skipped = None;
+            let mut applied = false;
+            for (i, item) in resource.iter().enumerate() {
+                match element(Some(item), pattern, &format!("{}/{}", path, i)) {
+                    Ok(()) => applied = true,
+                    Err(Mismatch::Skip {
+                        path,
+                        global: false,
+                    }) => {
+                        skipped.get_or_insert(path);
+                    }
+                    Err(mismatch) => return Err(mismatch),
+                }
+            }
+            match skipped {
+                Some(path) if !applied => Err(Mismatch::Skip {
+                    path,
+                    global: false,
+                }),
+                _ => Ok(()),
+            }
+        }
+        _ => {
+            if pattern.len() > resource.len() {
+                return Err(fail(
+                    path,
+                    format!(
+                        "expected at least {} elements, found {}",
+                        pattern.len(),
+                        resource.len()
+                    ),
+                ));
+            }
+            pattern
+                .iter()
+                .zip(resource)
+                .enumerate()
+                .try_for_each(|(i, (pattern, item))| {
+                    element(Some(item), pattern, &format!("{}/{}", path, i))
+                })
+        }
+    }
+}
+
+/// `^(key)`: at least one element of the array matches the pattern
+fn existence(resource: Option<&Value>, pattern: &Value, path: &str) -> Result<(), Mismatch> {
+    let Value::Array(patterns) = pattern else {
+        return element(resource, pattern, path);
+    };
+    let Some(Value::Array(items)) = resource else {
+        return Err(fail(path, "expected an array".to_string()));
+    };
+    let found = patterns.iter().all(|pattern| {
+        items
+            .iter()
+            .any(|item| element(Some(item), pattern, path).is_ok())
+    });
+    if found {
+        Ok(())
+    } else {
+        Err(fail(path, "no element matches the pattern".to_string()))
+    }
+}
+
+fn scalar_matches(resource: Option<&Value>, pattern: &Value) -> bool {
+    match (resource, pattern) {
+        (None | Some(Value::Null), Value::Null) => true,
+        (Some(Value::Bool(value)), Value::Bool(expected)) => value == expected,
+        (Some(Value::Number(value)), Value::Number(expected)) => {
+            value == expected || value.as_f64() == expected.as_f64()
+        }
+        (Some(Value::String(value)), Value::Number(expected)) => {
+            compare(value, &expected.to_string()) == Some(Ordering::Equal)
+        }
+        _ => false,
+    }
+}
+
+fn value_matches(resource: Option<&Value>, pattern: &str) -> bool {
+    pattern.split('|').any(|alternative| {
+        alternative
+            .split('&')
+            .all(|operand| operand_matches(resource, operand.trim()))
+    })
+}
+
+fn operand_matches(resource: Option<&Value>, operand: &str) -> bool {
+    let value = match resource {
+        Some(Value::String(value)) => value.clone(),
+        Some(Value::Number(value)) => value.to_string(),
+        Some(Value::Bool(value)) => value.to_string(),
+        // missing fields, nulls, objects and arrays match only `*`
+        _ => return operand == "*",
+    };
+
+    for (operator, accepted) in [
+        (">=", &[Ordering::Greater, Ordering::Equal][..]),
+        ("<=", &[Ordering::Less, Ordering::Equal][..]),
+        (">", &[Ordering::Greater][..]),
+        ("<", &[Ordering::Less][..]),
+    ] {
+        if let Some(operand) = operand.strip_prefix(operator) {
+            return compare(&value, operand.trim()).is_some_and(|o| accepted.contains(&o));
+        }
+    }
+    if let Some(operand) = operand.strip_prefix('!') {
+        return match range(operand) {
+            Some((low, high)) => !in_range(&value, low, high),
+            None => !equals(&value, operand),
+        };
+    }
+    match range(operand) {
+        Some((low, high)) => in_range(&value, low, high),
+        None => equals(&value, operand),
+    }
+}
+
+fn equals(value: &str, pattern: &str) -> bool {
+    if glob::is_pattern(pattern) {
+        return glob::matches(pattern, value);
+    }
+    value == pattern || compare(value, pattern) == Some(Ordering::Equal)
+}
+
+/// Compare two operands as quantities, or as durations
+fn compare(value: &str, operand: &str) -> Option<Ordering> {
+    if let (Ok(value), Ok(operand)) = (value.parse::<Quantity>(), operand.parse::<Quantity>()) {
+        return Some(value.cmp(&operand));
+    }
+    #[cfg(feature = "time")]
+    if let (Ok(value), Ok(operand)) = (
+        value.parse::<crate::time::Duration>(),
+        operand.parse::<crate::time::Duration>(),
+    ) {
+        return Some(value.cmp(&operand));
+    }
+    None
+}
+
+/// The bounds of a `low-high` range
+fn range(operand: &str) -> Option<(&str, &str)> {
+    let (low, high) = operand.split_once('-')?;
+    if low.is_empty() || high.is_empty() || compare(low, high).is_none() {
+        return None;
+    }
+    Some((low, high))
+}
+
+fn in_range(value: &str, low: &str, high: &str) -> bool {
+    compare(value, low).is_some_and(|o| o != Ordering::Less)
+        && compare(value, high).is_some_and(|o| o != Ordering::Greater)
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fails_at(resource: Value, pattern: Value) -> String {
+        match validate(&resource, &pattern) {
+            Outcome::Fail { path, .. } => path,
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+
+    #[test]
+    fn string_patterns() {
+        for (value, pattern, expected) in [
+            (json!("nginx:latest"), "*:latest", true),
+            (json!("nginx:1.27"), "!*:latest", true),
+            (json!("nginx"), "ngin?", true),
+            (json!("nginx"), "?*", true),
+            (json!(""), "?*", false),
+            (json!(null), "*", true),
+            (json!(null), "?*", false),
+            (json!("Always"), "Always|IfNotPresent", true),
+            (json!("Never"), "Always|IfNotPresent", false),
+            (json!(3), ">2", true),
+            (json!(3), ">=4", false),
+            (json!("512Mi"), "<=1Gi", true),
+            (json!("2Gi"), "<=1Gi", false),
+            (json!("1024Mi"), "1Gi", true),
+            (json!(5), "1-10", true),
+            (json!(11), "1-10", false),
+            (json!(11), "!1-10", true),
+            (json!(5), ">1 & <4", false),
+            (json!(true), "true", true),
+            (json!("my-app"), "my-app", true),
+        ] {
+            assert_eq!(
+                matches_value(&value, pattern),
+                expected,
+                "{} {}",
+                value,
+                pattern
+            );
+        }
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn duration_patterns() {
+        assert!(matches_value(&json!("30m"), "<1h"));
+        assert!(!matches_value(&json!("2h"), "<1h"));
+    }
+
+    #[test]
+    fn objects_and_arrays() {
+        let pattern = json!({
+            "metadata": {"labels": {"app": "?*"}},
+            "spec": {"containers": [{"name": "*", "resources": {"limits": {"memory": "<=1Gi"}}}]}
+        });
+        let mut pod = json!({
+            "metadata": {"labels": {"app": "web"}},
+            "spec": {"containers": [
+                {"name": "web", "resources": {"limits": {"memory": "512Mi"}}},
+                {"name": "sidecar", "resources": {"limits": {"memory": "2Gi"}}}
+            ]}
+        });
+        assert_eq!(
+            fails_at(pod.clone(), pattern.clone()),
+            "/spec/containers/1/resources/limits/memory"
+        );
+
+        pod["spec"]["containers"][1]["resources"]["limits"]["memory"] = json!("1Gi");
+        assert_eq!(validate(&pod, &pattern), Outcome::Pass);
+
+        pod["metadata"]["labels"] = json!({});
+        assert_eq!(fails_at(pod, pattern), "/metadata/labels/app");
+
+        assert_eq!(
+            fails_at(json!({"args": ["a"]}), json!({"args": ["a", "b"]})),
+            "/args"
+        );
+        assert_eq!(
+            validate(&json!({"args": ["a", "b"]}), &json!({"args": ["a"]})),
+            Outcome::Pass
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        // conditional: the pattern applies only to the privileged containers
+        let pattern = json!({"containers": [{
+            "(securityContext)": {"privileged": true},
+            "name": "allowed-*"
+        }]});
+        let pod = |name: &str, privileged: bool| {
+            json!({"containers": [
+                {"name": "app"},
+                {"name": name, "securityContext": {"privileged": privileged}}
+            ]})
+        };
+        assert_eq!(validate(&pod("allowed-x", true), &pattern), Outcome::Pass);
+        assert_eq!(
+            fails_at(pod("other", true), pattern.clone()),
+            "/containers/1/name"
+        );
+        assert!(matches!(
+            validate(&pod("other", false), &pattern),
+            Outcome::Skip { .. }
+        ));
+
+        // equality: optional fields, checked when present
+        let pattern = json!({"=(hostNetwork)": false});
+        assert_eq!(validate(&json!({}), &pattern), Outcome::Pass);
+        assert_eq!(
+            fails_at(json!({"hostNetwork": true}), pattern),
+            "/hostNetwork"
+        );
+
+        // negation
+        let pattern = json!({"spec": {"X(hostPath)": "null"}});
+        assert_eq!(validate(&json!({"spec": {}}), &pattern), Outcome::Pass);
+        assert_eq!(
+            fails_at(json!({"spec": {"hostPath": {}}}), pattern),
+            "/spec/hostPath"
+        );
+
+        // existence
+        let pattern = json!({"^(containers)": [{"image": "*:v1"}]});
+        let pod = json!({"containers": [{"image": "a:v2"}, {"image": "b:v1"}]});
+        assert_eq!(validate(&pod, &pattern), Outcome::Pass);
+        assert_eq!(
+            fails_at(json!({"containers": [{"image": "a:v2"}]}), pattern),
+            "/containers"
+        );
+
+        // global: the whole pattern is skipped
+        let pattern = json!({
+            "spec": {"containers": [{"<(image)": "registry.local/*"}]},
+            "metadata": {"labels": {"team": "?*"}}
+        });
+        let pod = json!({"metadata": {}, "spec": {"containers": [{"image": "docker.io/nginx"}]}});
+        assert!(validate(&pod, &pattern).is_allowed());
+        let pod =
+            json!({"metadata": {}, "spec": {"containers": [{"image": "registry.local/app"}]}});
+        assert_eq!(fails_at(pod, pattern), "/metadata/labels");
+    }
+}
@@ -0,0 +1,453 @@
+//! This module provides the [`Policy`] trait, which allows to write a policy
+//! without having to deal with the waPC protocol details.
+//!
+//! The waPC functions required by Kubewarden are registered by
+//! [`setup`], which is usually invoked via the [`policy`](macro@crate::policy) attribute macro
+//! or the [`crate::setup!`] macro.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::{accept_request, reject_request};
+//! use kubewarden_policy_sdk::policy::Policy;
+//! use kubewarden_policy_sdk::request::ValidationRequest;
+//! use kubewarden_policy_sdk::settings::Validatable;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, Default)]
+//! struct Settings {}
+//!
+//! impl Validatable for Settings {
+//!     fn validate(&self) -> Result<(), String> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct MyPolicy;
+//!
+//! impl Policy for MyPolicy {
+//!     type Settings = Settings;
+//!
+//!     fn validate(request: &ValidationRequest<Settings>) -> wapc_guest::CallResult {
+//!         if request.request.namespace == "kube-system" {
+//!             return reject_request(Some("kube-system is off limits".to_string()), None, None, None);
+//!         }
+//!         accept_request()
+//!     }
+//! }
+//!
+//! kubewarden_policy_sdk::setup!(MyPolicy);
+//! ```
+use crate::logging;
+use crate::request::ValidationRequest;
+use crate::response::ValidationResponse;
+use crate::settings::{SettingsValidationResponse, Validatable};
+use anyhow::anyhow;
+use serde::de::DeserializeOwned;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Trait implemented by Kubewarden policies
+pub trait Policy {
+    /// The settings of the policy
+    type Settings: Default + DeserializeOwned + Validatable;
+
+    /// Evaluate the incoming request
+    fn validate(request: &ValidationRequest<Self::Settings>) -> wapc_guest::CallResult;
+
+    /// Mutate the incoming request. This is invoked only when [`Policy::validate`]
+    /// accepted the request without mutating it.
+    ///
+    /// Returns the mutated object, or `None` when no mutation is required.
+    /// The default implementation doesn't mutate the request.
+    fn mutate(
+        _request: &ValidationRequest<Self::Settings>,
+    ) -> anyhow::Result<Option<serde_json::Value>> {
+        Ok(None)
+    }
+
+    /// Validate the settings provided by the user. The default implementation
+    /// relies on the [`Validatable`] implementation of the settings
+    fn validate_settings(settings: &Self::Settings) -> Result<(), String> {
+        settings.validate()
+    }
+}
+
+/// Generate the `wapc_init` function of the given [`Policy`], which
+/// invokes [`setup`]. See the [module documentation](mod@crate::policy) for an example.
+#[macro_export]
+macro_rules! setup {
+    ($policy:ty) => {
+        #[no_mangle]
+        pub extern "C" fn wapc_init() {
+            $crate::policy::setup::<$policy>();
+        }
+    };
+}
+
+/// Prepare the policy for execution: initialize the global logger,
+/// install the panic hook and register all the waPC functions required
+/// by Kubewarden.
+///
+/// This must be invoked from the `wapc_init` function. The `#[policy]`
+/// attribute macro and the [`setup!`](crate::setup) macro take care of that.
+pub fn setup<P: Policy>() {
+    logging::init();
+    install_panic_hook();
+    register::<P>();
+}
+
+/// Register the `validate`, `validate_settings` and `protocol_version`
+/// waPC functions for the given policy. With the `cbor` feature, the
+/// `payload_encodings` function is registered too, see the `cbor` module.
+pub fn register<P: Policy>() {
+    wapc_guest::register_function("validate", validate::<P>);
+    wapc_guest::register_function("validate_settings", validate_settings::<P>);
+    crate::register_protocol_version();
+    #[cfg(feature = "cbor")]
+    crate::cbor::register_payload_encodings();
+}
+
+/// Install a panic hook that sends the panic message and its location
+/// to the host via the global logger
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_default();
+        slog::error!(
+            logging::logger(),
+            "policy panicked";
+            "panic" => panic_message(info.payload()),
+            "location" => location
+        );
+    }));
+}
+
+/// waPC guest function that evaluates a request using the given policy.
+///
+/// On native targets, like the unit tests of the policy, a panic of the
+/// policy results in a rejection of the request with the
+/// [`INTERNAL_ERROR_CODE`] code. The wasm targets don't support unwinding:
+/// there the panic hook logs the panic, then the instance traps and the host
+/// reports the evaluation as failed. A waPC host discards the responses of
+/// the guests that trap, hence the hook cannot reply with a rejection.
+///
+/// With the `cbor` feature, requests encoded with CBOR are accepted too and
+/// the response uses the same encoding, see the `cbor` module.
+pub fn validate<P: Policy>(payload: &[u8]) -> wapc_guest::CallResult {
+    #[cfg(feature = "cbor")]
+    if crate::cbor::is_cbor(payload) {
+        return crate::cbor::transcode(payload, validate::<P>);
+    }
+    let response = catch_panic(
+        || {
+            let request = ValidationRequest::<P::Settings>::new(payload)?;
+            let response_raw = P::validate(&request)?;
+
+            // plain acceptances are recognized without decoding them
+            if response_raw != crate::response::ACCEPTED {
+                let response: ValidationResponse = serde_json::from_slice(&response_raw)?;
+                if !response.accepted || response.mutated_object.is_some() {
+                    return Ok(response_raw);
+                }
+            }
+            match P::mutate(&request)? {
+                Some(mutated_object) => crate::mutate_request(mutated_object),
+                None => Ok(response_raw),
+            }
+        },
+        |message| {
+            ValidationResponse::reject(message)
+                .code(INTERNAL_ERROR_CODE)
+                .build()
+        },
+    );
+    // the evaluation is over, including the mutation of the request
+    crate::host_capabilities::client::end_memoization();
+    response
+}
+
+/// waPC guest function that validates the settings of the given policy.
+///
+/// On native targets, a panic of the policy results in the settings being
+/// reported as not valid. On the wasm targets the instance traps after the
+/// panic hook logs the panic.
+pub fn validate_settings<P: Policy>(payload: &[u8]) -> wapc_guest::CallResult {
+    #[cfg(feature = "cbor")]
+    if crate::cbor::is_cbor(payload) {
+        return crate::cbor::transcode(payload, validate_settings::<P>);
+    }
+    catch_panic(
+        || {
+            if let Err(e) = logging::configure_from_settings(payload) {
+                return Ok(serde_json::to_vec(&SettingsValidationResponse {
+                    valid: false,
+                    message: Some(e),
+                })?);
+            }
+            let settings: P::Settings = serde_json::from_slice(payload).map_err(|e| {
+                anyhow!(
+                    "Error decoding validation payload {}: {:?}",
+                    String::from_utf8_lossy(payload),
+                    e
+                )
+            })?;
+
+            let res = match P::validate_settings(&settings) {
+                Ok(_) => SettingsValidationResponse {
+                    valid: true,
+                    message: None,
+                },
+                Err(e) => SettingsValidationResponse {
+                    valid: false,
+                    message: Some(e),
+                },
+            };
+
+            Ok(serde_json::to_vec(&res)?)
+        },
+        |message| {
+            Ok(serde_json::to_vec(&SettingsValidationResponse {
+                valid: false,
+                message: Some(message),
+            })?)
+        },
+    )
+}
+
+/// Code of the rejections caused by a panic of the policy, on the targets
+/// supporting unwinding
+pub const INTERNAL_ERROR_CODE: u16 = 500;
+
+/// Run `f`, turning a panic into the response built by `on_panic`, instead of
+/// letting it reach the waPC host. `on_panic` receives a message describing
+/// the panic, its location is logged by the panic hook.
+///
+/// Panics are caught only on the targets supporting unwinding. The wasm
+/// targets always abort on panic, even with `panic = "unwind"`: the panic
+/// hook logs the message and the location, then the instance traps. A waPC
+/// host discards the responses of the guests that trap, hence the hook
+/// cannot reply with a rejection either
+pub(crate) fn catch_panic<F, P>(f: F, on_panic: P) -> wapc_guest::CallResult
+where
+    F: FnOnce() -> wapc_guest::CallResult,
+    P: FnOnce(String) -> wapc_guest::CallResult,
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| {
+        on_panic(format!(
+            "internal policy error: policy panicked: {}",
+            panic_message(&*e)
+        ))
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Default)]
+    struct Settings {
+        forbidden_namespace: String,
+    }
+
+    impl Validatable for Settings {
+        fn validate(&self) -> Result<(), String> {
+            if self.forbidden_namespace.is_empty() {
+                Err("forbidden_namespace cannot be empty".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    struct TestPolicy;
+
+    impl Policy for TestPolicy {
+        type Settings = Settings;
+
+        fn validate(request: &ValidationRequest<Settings>) -> wapc_guest::CallResult {
+            if request.request.namespace == "panic" {
+                panic!("boom");
+            }
+            if request.request.namespace == request.settings.forbidden_namespace {
+                crate::reject_request(Some("forbidden".to_string()), None, None, None)
+            } else {
+                crate::accept_request()
+            }
+        }
+
+        fn mutate(
+            request: &ValidationRequest<Settings>,
+        ) -> anyhow::Result<Option<serde_json::Value>> {
+            let mut object = request.request.object.clone();
+            object["metadata"]["labels"] = json!({"mutated": "true"});
+            Ok(Some(object.into()))
+        }
+    }
+
+    fn payload(namespace: &str) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "settings": {"forbidden_namespace": "kube-system"},
+            "request": {
+                "namespace": namespace,
+                "object": {"metadata": {"name": "test"}}
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_accepts_and_mutates() {
+        let response_raw = validate::<TestPolicy>(&payload("default")).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert!(response.accepted);
+        assert_eq!(
+            response.mutated_object.unwrap()["metadata"]["labels"]["mutated"],
+            "true"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_without_mutating() {
+        let response_raw = validate::<TestPolicy>(&payload("kube-system")).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert!(!response.accepted);
+        assert!(response.mutated_object.is_none());
+    }
+
+    #[test]
+    fn validate_and_mutate_share_the_memoized_responses() {
+        use crate::host_capabilities::client::{host_call, with_host_client, MockHostClient};
+        use std::rc::Rc;
+
+        struct MemoizingPolicy;
+
+        impl Policy for MemoizingPolicy {
+            type Settings = ();
+
+            fn validate(_request: &ValidationRequest<()>) -> wapc_guest::CallResult {
+                host_call("crypto", "v1/is_certificate_trusted", b"{}")?;
+                crate::accept_request()
+            }
+
+            fn mutate(
+                _request: &ValidationRequest<()>,
+            ) -> anyhow::Result<Option<serde_json::Value>> {
+                host_call("crypto", "v1/is_certificate_trusted", b"{}")?;
+                Ok(None)
+            }
+        }
+
+        let client = Rc::new(
+            MockHostClient::new()
+                .respond("crypto", "v1/is_certificate_trusted", &true)
+                .expect("crypto", "v1/is_certificate_trusted", 2),
+        );
+        with_host_client(client.clone(), || {
+            let payload = serde_json::to_vec(&json!({"request": {}})).unwrap();
+            validate::<MemoizingPolicy>(&payload).unwrap();
+            // the responses are dropped once the evaluation is over
+            host_call("crypto", "v1/is_certificate_trusted", b"{}").unwrap();
+        });
+        client.verify();
+    }
+
+    #[test]
+    fn validate_turns_panics_into_rejections() {
+        let response_raw = validate::<TestPolicy>(&payload("panic")).unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert!(!response.accepted);
+        assert_eq!(response.code, Some(INTERNAL_ERROR_CODE));
+        assert_eq!(
+            response.message.unwrap(),
+            "internal policy error: policy panicked: boom"
+        );
+    }
+
+    #[test]
+    fn validate_settings_turns_panics_into_invalid_settings() {
+        struct PanickingSettings;
+
+        impl Policy for PanickingSettings {
+            type Settings = ();
+
+            fn validate(_request: &ValidationRequest<()>) -> wapc_guest::CallResult {
+                crate::accept_request()
+            }
+
+            fn validate_settings(_settings: &()) -> Result<(), String> {
+                panic!("boom")
+            }
+        }
+
+        let response_raw = validate_settings::<PanickingSettings>(b"null").unwrap();
+        let response: SettingsValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+        assert!(!response.valid);
+        assert_eq!(
+            response.message.unwrap(),
+            "internal policy error: policy panicked: boom"
+        );
+    }
+
+    #[test]
+    fn validate_settings_uses_policy_implementation() {
+        let response_raw =
+            validate_settings::<TestPolicy>(br#"{"forbidden_namespace": ""}"#).unwrap();
+        let response: SettingsValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+
+        assert!(!response.valid);
+        assert_eq!(
+            response.message.unwrap(),
+            "forbidden_namespace cannot be empty"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "cbor")]
+    fn validate_cbor_requests() {
+        let request: serde_json::Value = serde_json::from_slice(&payload("kube-system")).unwrap();
+        let mut encoded = Vec::new();
+        ciborium::into_writer(&request, &mut encoded).unwrap();
+
+        let response_raw = validate::<TestPolicy>(&encoded).unwrap();
+        let response: ValidationResponse = ciborium::from_reader(response_raw.as_slice()).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.as_deref(), Some("forbidden"));
+    }
+
+    #[test]
+    fn log_level_from_settings() {
+        let response_raw = validate_settings::<TestPolicy>(
+            br#"{"forbidden_namespace": "kube-system", "logLevel": "verbose"}"#,
+        )
+        .unwrap();
+        let response: SettingsValidationResponse = serde_json::from_slice(&response_raw).unwrap();
+        assert!(!response.valid);
+        assert!(response.message.unwrap().starts_with("invalid logLevel"));
+
+        let quiet = serde_json::to_vec(&json!({
+            "settings": {"forbidden_namespace": "kube-system", "logLevel": "error"},
+            "request": {"namespace": "default", "object": {}}
+        }))
+        .unwrap();
+        validate::<TestPolicy>(&quiet).unwrap();
+        assert_eq!(logging::level(), Some(slog::Level::Error));
+        validate::<TestPolicy>(&payload("default")).unwrap();
+        assert_eq!(logging::level(), None);
+    }
+}
@@ -0,0 +1,460 @@
+//! Parsing and arithmetic of Kubernetes resource quantities, like the ones
+//! used by the `resources` section of a container (e.g. `500m`, `2Gi`, `1.5`).
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::quantity::Quantity;
+//!
+//! let request: Quantity = "500m".parse().unwrap();
+//! let limit: Quantity = "1".parse().unwrap();
+//! assert!(request < limit);
+//! assert_eq!((request + request).to_string(), "1");
+//!
+//! let memory: Quantity = "1.5Gi".parse().unwrap();
+//! assert_eq!(memory.to_bytes(), 1610612736);
+//! assert_eq!(memory.to_string(), "1536Mi");
+//! ```
+use crate::error::SdkError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::Sum;
+use std::ops::{Add, Sub};
+use std::str::FromStr;
+
+const NANOS_PER_UNIT: i128 = 1_000_000_000;
+
+/// The notation used to write a quantity, which is preserved when the
+/// quantity is formatted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Power of two suffixes: `Ki`, `Mi`, `Gi`, `Ti`, `Pi`, `Ei`
+    BinarySI,
+    /// Power of ten suffixes: `n`, `u`, `m`, `k`, `M`, `G`, `T`, `P`, `E`
+    #[default]
+    DecimalSI,
+    /// Scientific notation, like `1e3`
+    DecimalExponent,
+}
+
+/// A Kubernetes resource quantity.
+///
+/// Values are stored with nano precision, like Kubernetes does: quantities with
+/// a higher precision are rounded up. Quantities are compared by their value,
+/// regardless of the notation used to write them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quantity {
+    nanos: i128,
+    format: Format,
+}
+
+impl Quantity {
+    /// Create a quantity holding `value` units
+    pub fn from_units(value: i64, format: Format) -> Self {
+        Quantity {
+            nanos: i128::from(value) * NANOS_PER_UNIT,
+            format,
+        }
+    }
+
+    /// Create a quantity holding `value` thousandths of unit (e.g. millicores)
+    pub fn from_millis(value: i64) -> Self {
+        Quantity {
+            nanos: i128::from(value) * 1_000_000,
+            format: Format::DecimalSI,
+        }
+    }
+
+    /// The notation used by the quantity
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns true when the quantity is zero
+    pub fn is_zero(&self) -> bool {
+        self.nanos == 0
+    }
+
+    /// The value, expressed in billionths of unit
+    pub fn to_nanos(&self) -> i128 {
+        self.nanos
+    }
+
+    /// The value, expressed in thousandths of unit and rounded up. This is
+    /// the canonical unit of cpu quantities
+    pub fn to_millis(&self) -> i128 {
+        div_ceil(self.nanos, 1_000_000)
+    }
+
+    /// The value, expressed in units and rounded up. This is the canonical
+    /// unit of memory and storage quantities
+    pub fn to_bytes(&self) -> i128 {
+        div_ceil(self.nanos, NANOS_PER_UNIT)
+    }
+
+    /// The value as a floating point number, which can be lossy
+    pub fn to_f64(&self) -> f64 {
+        self.nanos as f64 / NANOS_PER_UNIT as f64
+    }
+
+    /// Add two quantities, returning `None` on overflow. The result uses
+    /// the notation of `self`
+    pub fn checked_add(&self, other: &Quantity) -> Option<Quantity> {
+        self.nanos.checked_add(other.nanos).map(|nanos| Quantity {
+            nanos,
+            format: self.format,
+        })
+    }
+
+    /// Subtract two quantities, returning `None` on overflow. The result
+    /// uses the notation of `self`
+    pub fn checked_sub(&self, other: &Quantity) -> Option<Quantity> {
+        self.nanos.checked_sub(other.nanos).map(|nanos| Quantity {
+            nanos,
+            format: self.format,
+        })
+    }
+}
+
+fn div_ceil(value: i128, divisor: i128) -> i128 {
+    let quotient = value / divisor;
+    if value % divisor > 0 {
+        quotient + 1
+    } else {
+        quotient
+    }
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Self) -> bool {
+        self.nanos == other.nanos
+    }
+}
+
+impl Eq for Quantity {}
+
+impl Hash for Quantity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.nanos.hash(state);
+    }
+}
+
+impl PartialOrd for Quantity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Quantity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.nanos.cmp(&other.nanos)
+    }
+}
+
+/// Saturating addition, see [`Quantity::checked_add`]
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity {
+            nanos: self.nanos.saturating_add(other.nanos),
+            format: self.format,
+        }
+    }
+}
+
+/// Saturating subtraction, see [`Quantity::checked_sub`]
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity {
+            nanos: self.nanos.saturating_sub(other.nanos),
+            format: self.format,
+        }
+    }
+}
+
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Self {
+        let mut iter = iter.peekable();
+        let format = iter.peek().map(|q| q.format).unwrap_or_default();
+        iter.fold(Quantity { nanos: 0, format }, |acc, q| acc + q)
+    }
+}
+
+/// Binary suffixes, with their power of 1024
+const BINARY_SUFFIXES: &[(&str, u32)] = &[
+    ("Ki", 1),
+    ("Mi", 2),
+    ("Gi", 3),
+    ("Ti", 4),
+    ("Pi", 5),
+    ("Ei", 6),
+];
+
+/// Decimal suffixes, with their power of 10
+const DECIMAL_SUFFIXES: &[(&str, i32)] = &[
+    ("n", -9),
+    ("u", -6),
+    ("m", -3),
+    ("", 0),
+    ("k", 3),
+    ("M", 6),
+    ("G", 9),
+    ("T", 12),
+    ("P", 15),
+    ("E", 18),
+];
+
+impl FromStr for Quantity {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SdkError::InvalidInput(format!("Invalid quantity: {}", s));
+
+        let number_end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '+' || c == '-'))
+            .unwrap_or(s.len());
+        let (number, suffix) = s.split_at(number_end);
+
+        let (negative, digits) = match number.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if (integer.is_empty() && fraction.is_empty())
+            || !integer
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        // the number is handled as `mantissa * 10^exponent`
+        let mantissa: i128 = format!("{}{}", integer, fraction)
+            .trim_start_matches('0')
+            .parse()
+            .or_else(|_| {
+                if integer.chars().chain(fraction.chars()).all(|c| c == '0') {
+                    Ok(0)
+                } else {
+                    Err(invalid())
+                }
+            })?;
+        let mut exponent = -(fraction.len() as i32);
+        let mut multiplier: i128 = 1;
+
+        let format = if let Some((_, power)) =
+            BINARY_SUFFIXES.iter().find(|(sfx, _)| *sfx == suffix)
+        {
+            multiplier = 1024_i128.pow(*power);
+            Format::BinarySI
+        } else if let Some((_, power)) = DECIMAL_SUFFIXES.iter().find(|(sfx, _)| *sfx == suffix) {
+            exponent += power;
+            Format::DecimalSI
+        } else if let Some(e) = suffix.strip_prefix(['e', 'E']) {
+            exponent += e.parse::<i32>().map_err(|_| invalid())?;
+            Format::DecimalExponent
+        } else {
+            return Err(invalid());
+        };
+
+        // convert to nanos, rounding up values having a higher precision
+        exponent += 9;
+        let magnitude = if exponent >= 0 {
+            10_i128
+                .checked_pow(exponent as u32)
+                .and_then(|scale| mantissa.checked_mul(scale))
+                .and_then(|value| value.checked_mul(multiplier))
+        } else {
+            let value = mantissa.checked_mul(multiplier);
+            match 10_i128.checked_pow(exponent.unsigned_abs()) {
+                Some(scale) => value.map(|v| div_ceil(v, scale)),
+                // the value is smaller than a nano
+                None => value.map(|v| i128::from(v > 0)),
+            }
+        }
+        .ok_or_else(invalid)?;
+
+        Ok(Quantity {
+            nanos: if negative { -magnitude } else { magnitude },
+            format,
+        })
+    }
+}
+
+/// Quantities are written using the largest suffix that represents the value
+/// exactly, using the notation of the quantity. Binary quantities that cannot
+/// be represented by a binary suffix are written using the decimal notation.
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.nanos < 0 { "-" } else { "" };
+        let nanos = self.nanos.unsigned_abs();
+
+        if nanos == 0 {
+            return write!(f, "0");
+        }
+
+        if self.format == Format::BinarySI && nanos.is_multiple_of(1024 * NANOS_PER_UNIT as u128) {
+            let mut units = nanos / NANOS_PER_UNIT as u128;
+            let mut suffix = "";
+            for (sfx, _) in BINARY_SUFFIXES {
+                if !units.is_multiple_of(1024) {
+                    break;
+                }
+                units /= 1024;
+                suffix = sfx;
+            }
+            return write!(f, "{}{}{}", sign, units, suffix);
+        }
+
+        // find the largest power of ten, multiple of 3, dividing the value
+        let mut value = nanos;
+        let mut exponent = -9;
+        while value.is_multiple_of(1000) && exponent < 18 {
+            value /= 1000;
+            exponent += 3;
+        }
+
+        if self.format == Format::DecimalExponent {
+            if exponent == 0 {
+                write!(f, "{}{}", sign, value)
+            } else {
+                write!(f, "{}{}e{}", sign, value, exponent)
+            }
+        } else {
+            let suffix = DECIMAL_SUFFIXES
+                .iter()
+                .find(|(_, power)| *power == exponent)
+                .map(|(sfx, _)| *sfx)
+                .unwrap_or_default();
+            write!(f, "{}{}{}", sign, value, suffix)
+        }
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+/// Quantities can be deserialized both from strings and from numbers
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            String(String),
+            Number(serde_json::Number),
+        }
+
+        let raw = match Raw::deserialize(deserializer)? {
+            Raw::String(s) => s,
+            Raw::Number(n) => n.to_string(),
+        };
+        raw.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "cluster-context")]
+impl TryFrom<&k8s_openapi::apimachinery::pkg::api::resource::Quantity> for Quantity {
+    type Error = SdkError;
+
+    fn try_from(
+        quantity: &k8s_openapi::apimachinery::pkg::api::resource::Quantity,
+    ) -> Result<Self, Self::Error> {
+        quantity.0.parse()
+    }
+}
+
+#[cfg(feature = "cluster-context")]
+impl From<Quantity> for k8s_openapi::apimachinery::pkg::api::resource::Quantity {
+    fn from(quantity: Quantity) -> Self {
+        k8s_openapi::apimachinery::pkg::api::resource::Quantity(quantity.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q(s: &str) -> Quantity {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(q("1").to_millis(), 1000);
+        assert_eq!(q("500m").to_millis(), 500);
+        assert_eq!(q("0.5").to_millis(), 500);
+        assert_eq!(q("1.5k").to_bytes(), 1500);
+        assert_eq!(q("2Gi").to_bytes(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(q("1e3").to_bytes(), 1000);
+        assert_eq!(q("1E-3").to_millis(), 1);
+        assert_eq!(q("-100Mi").to_bytes(), -100 * 1024 * 1024);
+        assert_eq!(q("+.5").to_millis(), 500);
+        assert_eq!(q("1n").to_nanos(), 1);
+        assert_eq!(
+            q("0.1n").to_nanos(),
+            1,
+            "precision higher than nano is rounded up"
+        );
+        assert_eq!(q("0").to_nanos(), 0);
+        assert_eq!(q("1.5Gi").format(), Format::BinarySI);
+
+        for invalid in ["", "m", "1.2.3", "1Zi", "1 Gi", "1e", "--1", "1e100"] {
+            assert!(invalid.parse::<Quantity>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn comparison_and_arithmetic() {
+        assert_eq!(q("1"), q("1000m"));
+        assert_eq!(q("1Ki"), q("1024"));
+        assert!(q("1Gi") > q("1G"));
+        assert!(q("100m") < q("0.2"));
+
+        assert_eq!(q("1Gi") + q("512Mi"), q("1.5Gi"));
+        assert_eq!(q("1") - q("250m"), q("750m"));
+        assert_eq!(
+            vec![q("100m"), q("200m"), q("1")]
+                .into_iter()
+                .sum::<Quantity>(),
+            q("1.3")
+        );
+        assert!(Quantity::from_units(i64::MAX, Format::DecimalSI)
+            .checked_add(&q("1"))
+            .is_some());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(q("1000m").to_string(), "1");
+        assert_eq!(q("1500m").to_string(), "1500m");
+        assert_eq!(q("0.5").to_string(), "500m");
+        assert_eq!(q("1024Mi").to_string(), "1Gi");
+        assert_eq!(q("1.5Gi").to_string(), "1536Mi");
+        assert_eq!(q("100Ki").to_string(), "100Ki");
+        assert_eq!(q("0.5Ki").to_string(), "512");
+        assert_eq!(q("2000k").to_string(), "2M");
+        assert_eq!(q("1e3").to_string(), "1e3");
+        assert_eq!(q("-1.5").to_string(), "-1500m");
+        assert_eq!(q("0Gi").to_string(), "0");
+    }
+
+    #[test]
+    fn serde() {
+        let quantities: Vec<Quantity> = serde_json::from_str(r#"["100m", 2, 0.5]"#).unwrap();
+        assert_eq!(quantities, vec![q("100m"), q("2"), q("500m")]);
+        assert_eq!(
+            serde_json::to_string(&quantities).unwrap(),
+            r#"["100m","2","500m"]"#
+        );
+        assert!(serde_json::from_str::<Quantity>(r#""10 apples""#).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! Helpers to evaluate RBAC objects.
+//!
+//! The main use case is the detection of privilege escalations performed
+//! by CREATE and UPDATE requests of `Role` and `ClusterRole` objects.
+//!
+//! # Example
+//!
+//! ```
+//! use k8s_openapi::api::rbac::v1::PolicyRule;
+//! use kubewarden_policy_sdk::rbac::extra_permissions;
+//!
+//! let old = vec![PolicyRule {
+//!     api_groups: Some(vec!["".to_string()]),
+//!     resources: Some(vec!["pods".to_string()]),
+//!     verbs: vec!["get".to_string(), "list".to_string()],
+//!     ..Default::default()
+//! }];
+//! let new = vec![PolicyRule {
+//!     api_groups: Some(vec!["".to_string()]),
+//!     resources: Some(vec!["pods".to_string(), "secrets".to_string()]),
+//!     verbs: vec!["get".to_string()],
+//!     ..Default::default()
+//! }];
+//!
+//! let extra = extra_permissions(&new, &old);
+//! assert_eq!(extra.len(), 1);
+//! assert_eq!(extra[0].to_string(), "get secrets");
+//! ```
+use crate::error::{Result, SdkError};
+use crate::request::KubernetesAdmissionRequest;
+use k8s_openapi::api::rbac::v1::{ClusterRole, PolicyRule, Role};
+use k8s_openapi::Resource;
+use std::fmt;
+
+/// A single permission granted by a [`PolicyRule`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Permission to perform `verb` against a resource
+    Resource {
+        /// API group of the resource, the core group is represented by an empty string
+        api_group: String,
+        /// Name of the resource, including the subresource (e.g. `pods/exec`)
+        resource: String,
+        /// The verb allowed
+        verb: String,
+        /// The name of the object the permission is restricted to, if any
+        resource_name: Option<String>,
+    },
+    /// Permission to perform `verb` against a non resource URL (e.g. `/healthz`)
+    NonResourceUrl {
+        /// The URL
+        url: String,
+        /// The verb allowed
+        verb: String,
+    },
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::Resource {
+                api_group,
+                resource,
+                verb,
+                resource_name,
+            } => {
+                write!(f, "{} ", verb)?;
+                if api_group.is_empty() {
+                    write!(f, "{}", resource)?;
+                } else {
+                    write!(f, "{}.{}", resource, api_group)?;
+                }
+                if let Some(name) = resource_name {
+                    write!(f, " named {}", name)?;
+                }
+                Ok(())
+            }
+            Permission::NonResourceUrl { url, verb } => write!(f, "{} {}", verb, url),
+        }
+    }
+}
+
+impl Permission {
+    /// Returns true when the permission is granted by the given rule
+    pub fn is_granted_by(&self, rule: &PolicyRule) -> bool {
+        if !contains_or_wildcard(&rule.verbs, self.verb()) {
+            return false;
+        }
+
+        match self {
+            Permission::Resource {
+                api_group,
+                resource,
+                resource_name,
+                ..
+            } => {
+                let api_groups = rule.api_groups.as_deref().unwrap_or_default();
+                let resources = rule.resources.as_deref().unwrap_or_default();
+                let resource_names = rule.resource_names.as_deref().unwrap_or_default();
+
+                contains_or_wildcard(api_groups, api_group)
+                    && resources.iter().any(|r| resource_matches(r, resource))
+                    && (resource_names.is_empty()
+                        || resource_name
+                            .as_ref()
+                            .is_some_and(|name| resource_names.contains(name)))
+            }
+            Permission::NonResourceUrl { url, .. } => rule
+                .non_resource_urls
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .any(|pattern| non_resource_url_matches(pattern, url)),
+        }
+    }
+
+    fn verb(&self) -> &str {
+        match self {
+            Permission::Resource { verb, .. } => verb,
+            Permission::NonResourceUrl { verb, .. } => verb,
+        }
+    }
+}
+
+/// Expand the given rules into the list of single permissions they grant
+pub fn permissions(rules: &[PolicyRule]) -> Vec<Permission> {
+    let mut permissions = Vec::new();
+
+    for rule in rules {
+        for verb in &rule.verbs {
+            for api_group in rule.api_groups.as_deref().unwrap_or_default() {
+                for resource in rule.resources.as_deref().unwrap_or_default() {
+                    let resource_names = rule.resource_names.as_deref().unwrap_or_default();
+                    let names: Vec<Option<String>> = if resource_names.is_empty() {
+                        vec![None]
+                    } else {
+                        resource_names.iter().cloned().map(Some).collect()
+                    };
+                    for resource_name in names {
+                        permissions.push(Permission::Resource {
+                            api_group: api_group.clone(),
+                            resource: resource.clone(),
+                            verb: verb.clone(),
+                            resource_name,
+                        });
+                    }
+                }
+            }
+            for url in rule.non_resource_urls.as_deref().unwrap_or_default() {
+                permissions.push(Permission::NonResourceUrl {
+                    url: url.clone(),
+                    verb: verb.clone(),
+                });
+            }
+        }
+    }
+
+    permissions.dedup();
+    permissions
+}
+
+/// Returns the permissions granted by `rules` that are not granted by `baseline`.
+/// An empty result means `baseline` is a superset of `rules`.
+pub fn extra_permissions(rules: &[PolicyRule], baseline: &[PolicyRule]) -> Vec<Permission> {
+    permissions(rules)
+        .into_iter()
+        .filter(|permission| !baseline.iter().any(|rule| permission.is_granted_by(rule)))
+        .collect()
+}
+
+/// Returns the permissions added by a CREATE or UPDATE request of a `Role` or a
+/// `ClusterRole`, compared to the old version of the object. All the permissions
+/// are returned for CREATE requests.
+///
+/// An error is returned when the request doesn't target a `Role` or a `ClusterRole`.
+pub fn escalated_permissions(request: &KubernetesAdmissionRequest) -> Result<Vec<Permission>> {
+    let new_rules = rules_of(&request.kind.kind, &request.object)?;
+    let old_rules = if request.old_object.is_null() {
+        vec![]
+    } else {
+        rules_of(&request.kind.kind, &request.old_object)?
+    };
+
+    Ok(extra_permissions(&new_rules, &old_rules))
+}
+
+fn rules_of(kind: &str, object: &serde_json::Value) -> Result<Vec<PolicyRule>> {
+    let rules = match kind {
+        Role::KIND => {
+            serde_json::from_value::<Role>(object.clone())
+                .map_err(|e| SdkError::deserialization("the Role", e))?
+                .rules
+        }
+        ClusterRole::KIND => {
+            serde_json::from_value::<ClusterRole>(object.clone())
+                .map_err(|e| SdkError::deserialization("the ClusterRole", e))?
+                .rules
+        }
+        _ => {
+            return Err(SdkError::InvalidInput(
+                "Object should be one of these kinds: Role, ClusterRole".to_string(),
+            ))
+        }
+    };
+
+    Ok(rules.unwrap_or_default())
+}
+
+fn contains_or_wildcard(values: &[String], value: &str) -> bool {
+    values.iter().any(|v| v == "*" || v == value)
+}
+
+fn resource_matches(pattern: &str, resource: &str) -> bool {
+    if pattern == "*" || pattern == resource {
+        return true;
+    }
+    // `pods/*` grants all the subresources of pods, `*/scale` grants the
+    // scale subresource of all the resources
+    match (pattern.split_once('/'), resource.split_once('/')) {
+        (Some((p_resource, "*")), Some((resource, _))) => p_resource == resource,
+        (Some(("*", p_subresource)), Some((_, subresource))) => p_subresource == subresource,
+        _ => false,
+    }
+}
+
+fn non_resource_url_matches(pattern: &str, url: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => url.starts_with(prefix),
+        None => pattern == url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rule(api_groups: &[&str], resources: &[&str], verbs: &[&str]) -> PolicyRule {
+        PolicyRule {
+            api_groups: Some(api_groups.iter().map(|s| s.to_string()).collect()),
+            resources: Some(resources.iter().map(|s| s.to_string()).collect()),
+            verbs: verbs.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn subset_is_not_an_escalation() {
+        let baseline = vec![rule(&["", "apps"], &["*"], &["get", "list", "watch"])];
+        let rules = vec![
+            rule(&[""], &["pods", "pods/log"], &["get"]),
+            rule(&["apps"], &["deployments"], &["list"]),
+        ];
+
+        assert!(extra_permissions(&rules, &baseline).is_empty());
+    }
+
+    #[test]
+    fn wildcards_are_escalations_unless_granted() {
+        let baseline = vec![rule(&[""], &["pods"], &["get"])];
+        let rules = vec![rule(&[""], &["pods"], &["*"])];
+
+        let extra = extra_permissions(&rules, &baseline);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(extra[0].to_string(), "* pods");
+
+        assert!(extra_permissions(&rules, &[rule(&["*"], &["*"], &["*"])]).is_empty());
+    }
+
+    #[test]
+    fn subresources_and_resource_names() {
+        let baseline = vec![
+            rule(&[""], &["pods/*"], &["get"]),
+            PolicyRule {
+                resource_names: Some(vec!["allowed".to_string()]),
+                ..rule(&[""], &["configmaps"], &["update"])
+            },
+        ];
+
+        assert!(extra_permissions(&[rule(&[""], &["pods/exec"], &["get"])], &baseline).is_empty());
+
+        let named = PolicyRule {
+            resource_names: Some(vec!["allowed".to_string(), "other".to_string()]),
+            ..rule(&[""], &["configmaps"], &["update"])
+        };
+        let extra = extra_permissions(&[named], &baseline);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(extra[0].to_string(), "update configmaps named other");
+
+        // unrestricted access is not granted by a rule restricted to some names
+        let extra = extra_permissions(&[rule(&[""], &["configmaps"], &["update"])], &baseline);
+        assert_eq!(extra.len(), 1);
+    }
+
+    #[test]
+    fn non_resource_urls() {
+        let baseline = vec![PolicyRule {
+            non_resource_urls: Some(vec!["/healthz/*".to_string()]),
+            verbs: vec!["get".to_string()],
+            ..Default::default()
+        }];
+        let rules = vec![PolicyRule {
+            non_resource_urls: Some(vec!["/healthz/ready".to_string(), "/metrics".to_string()]),
+            verbs: vec!["get".to_string()],
+            ..Default::default()
+        }];
+
+        let extra = extra_permissions(&rules, &baseline);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(extra[0].to_string(), "get /metrics");
+    }
+
+    #[test]
+    fn escalation_from_update_request() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(json!({
+            "kind": {"group": "rbac.authorization.k8s.io", "version": "v1", "kind": "ClusterRole"},
+            "operation": "UPDATE",
+            "object": {
+                "metadata": {"name": "reader"},
+                "rules": [{"apiGroups": [""], "resources": ["pods", "secrets"], "verbs": ["get"]}]
+            },
+            "oldObject": {
+                "metadata": {"name": "reader"},
+                "rules": [{"apiGroups": [""], "resources": ["pods"], "verbs": ["get"]}]
+            }
+        }))
+        .unwrap();
+
+        let extra = escalated_permissions(&request).unwrap();
+        assert_eq!(
+            extra,
+            vec![Permission::Resource {
+                api_group: "".to_string(),
+                resource: "secrets".to_string(),
+                verb: "get".to_string(),
+                resource_name: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn escalation_from_unsupported_kind() {
+        let request = KubernetesAdmissionRequest {
+            object: json!({}).into(),
+            ..Default::default()
+        };
+
+        assert!(escalated_permissions(&request).is_err());
+    }
+}
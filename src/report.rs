@@ -0,0 +1,426 @@
+//! Types of the [PolicyReport API](https://github.com/kubernetes-sigs/wg-policy-prototypes/tree/master/policy-report)
+//! defined by the Kubernetes Policy Working Group (`wgpolicyk8s.io/v1alpha2`).
+//!
+//! Audit tools built around the SDK can turn the outcome of the evaluations
+//! into report results, and collect them inside of `PolicyReport` and
+//! `ClusterPolicyReport` objects:
+//!
+//! ```
+//! use kubewarden_policy_sdk::report::{ObjectReference, PolicyReport, PolicyReportResult, Severity};
+//! use kubewarden_policy_sdk::response::ValidationResponse;
+//!
+//! let response = ValidationResponse::reject("privileged containers are not allowed")
+//!     .response();
+//! let result = PolicyReportResult::from_response("pod-privileged", &response)
+//!     .rule("no-privileged-containers")
+//!     .severity(Severity::High)
+//!     .category("PSP")
+//!     .resource(ObjectReference::new("v1", "Pod", Some("default"), "nginx"));
+//!
+//! let mut report = PolicyReport::new("audit-default", "default");
+//! report.add_result(result);
+//! assert_eq!(report.summary.fail, 1);
+//! ```
+use crate::metadata::{annotations, Metadata};
+use crate::request::KubernetesAdmissionRequest;
+use crate::response::ValidationResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// apiVersion of the PolicyReport objects
+pub const API_VERSION: &str = "wgpolicyk8s.io/v1alpha2";
+
+/// Source of the results produced by the SDK
+pub const SOURCE: &str = "kubewarden";
+
+/// The outcome of a check
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyResult {
+    /// The resource complies with the policy
+    Pass,
+    /// The resource violates the policy
+    Fail,
+    /// The resource violates the policy, which is not enforced
+    Warn,
+    /// The policy could not be evaluated
+    Error,
+    /// The policy has not been evaluated
+    Skip,
+}
+
+/// Severity of a check
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "low" => Ok(Severity::Low),
+            "medium" => Ok(Severity::Medium),
+            "high" => Ok(Severity::High),
+            "critical" => Ok(Severity::Critical),
+            _ => Err(format!("unknown severity {}", s)),
+        }
+    }
+}
+
+/// Time of a result, with nanosecond precision
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timestamp {
+    /// Seconds since the Unix epoch
+    pub seconds: i64,
+    /// Non-negative fraction of a second
+    pub nanos: i32,
+}
+
+#[cfg(feature = "time")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(time: chrono::DateTime<chrono::Utc>) -> Self {
+        Timestamp {
+            seconds: time.timestamp(),
+            nanos: time.timestamp_subsec_nanos() as i32,
+        }
+    }
+}
+
+/// Reference to the object checked by a result
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectReference {
+    pub api_version: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+}
+
+impl ObjectReference {
+    /// Reference to the object with the given type and name. Cluster-wide
+    /// objects must use a `None` namespace
+    pub fn new(api_version: &str, kind: &str, namespace: Option<&str>, name: &str) -> Self {
+        ObjectReference {
+            api_version: api_version.to_string(),
+            kind: kind.to_string(),
+            namespace: namespace.map(str::to_string),
+            name: name.to_string(),
+            uid: None,
+        }
+    }
+
+    /// Reference to the object of the given request
+    pub fn from_request(request: &KubernetesAdmissionRequest) -> Self {
+        let api_version = if request.kind.group.is_empty() {
+            request.kind.version.clone()
+        } else {
+            format!("{}/{}", request.kind.group, request.kind.version)
+        };
+        let object = if request.object.is_null() {
+            &request.old_object
+        } else {
+            &request.object
+        };
+        ObjectReference {
+            api_version,
+            kind: request.kind.kind.clone(),
+            namespace: Some(request.namespace.clone()).filter(|ns| !ns.is_empty()),
+            name: request.name.clone(),
+            uid: object["metadata"]["uid"].as_str().map(str::to_string),
+        }
+    }
+}
+
+/// A single entry of a report
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReportResult {
+    /// The tool that produced the result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Name of the policy
+    pub policy: String,
+    /// Name of the rule of the policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// Category of the policy, e.g. `Pod Security Standards`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<Timestamp>,
+    pub result: PolicyResult,
+    /// False for informational results, which don't affect the compliance
+    #[serde(default)]
+    pub scored: bool,
+    /// The objects checked
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<ObjectReference>,
+    /// Description of the outcome
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Additional information
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+impl PolicyReportResult {
+    /// Create a scored result of the given policy
+    pub fn new(policy: &str, result: PolicyResult) -> Self {
+        PolicyReportResult {
+            source: Some(SOURCE.to_string()),
+            policy: policy.to_string(),
+            rule: None,
+            category: None,
+            severity: None,
+            timestamp: None,
+            result,
+            scored: true,
+            resources: Vec::new(),
+            message: None,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// Create the result corresponding to the response of the given policy:
+    /// accepted requests pass, rejected ones fail with the message of the
+    /// rejection
+    pub fn from_response(policy: &str, response: &ValidationResponse) -> Self {
+        let result = if response.accepted {
+            PolicyResult::Pass
+        } else {
+            PolicyResult::Fail
+        };
+        PolicyReportResult {
+            message: response.message.clone(),
+            ..PolicyReportResult::new(policy, result)
+        }
+    }
+
+    /// Set the rule
+    pub fn rule(mut self, rule: &str) -> Self {
+        self.rule = Some(rule.to_string());
+        self
+    }
+
+    /// Set the category
+    pub fn category(mut self, category: &str) -> Self {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    /// Set the severity
+    pub fn severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    /// Set the category and the severity declared by the annotations of
+    /// the policy metadata. Unknown severities are ignored
+    pub fn metadata(mut self, metadata: &Metadata) -> Self {
+        if let Some(category) = metadata.annotations.get(annotations::CATEGORY) {
+            self.category = Some(category.clone());
+        }
+        if let Some(severity) = metadata.annotations.get(annotations::SEVERITY) {
+            self.severity = severity.parse().ok().or(self.severity);
+        }
+        self
+    }
+
+    /// Set the time of the result
+    pub fn timestamp(mut self, timestamp: impl Into<Timestamp>) -> Self {
+        self.timestamp = Some(timestamp.into());
+        self
+    }
+
+    /// Add an object checked by the result
+    pub fn resource(mut self, resource: ObjectReference) -> Self {
+        self.resources.push(resource);
+        self
+    }
+
+    /// Add a property
+    pub fn property(mut self, key: &str, value: &str) -> Self {
+        self.properties.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Number of results of a report, by outcome
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct PolicyReportSummary {
+    pub pass: u32,
+    pub fail: u32,
+    pub warn: u32,
+    pub error: u32,
+    pub skip: u32,
+}
+
+impl PolicyReportSummary {
+    fn count(&mut self, result: PolicyResult) {
+        let counter = match result {
+            PolicyResult::Pass => &mut self.pass,
+            PolicyResult::Fail => &mut self.fail,
+            PolicyResult::Warn => &mut self.warn,
+            PolicyResult::Error => &mut self.error,
+            PolicyResult::Skip => &mut self.skip,
+        };
+        *counter += 1;
+    }
+}
+
+/// Metadata of a report
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct ReportMetadata {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// A `PolicyReport`, or a `ClusterPolicyReport` when it has no namespace
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PolicyReport {
+    pub api_version: String,
+    pub kind: String,
+    pub metadata: ReportMetadata,
+    /// The object all the results refer to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<ObjectReference>,
+    #[serde(default)]
+    pub summary: PolicyReportSummary,
+    #[serde(default)]
+    pub results: Vec<PolicyReportResult>,
+}
+
+impl PolicyReport {
+    /// Create an empty `PolicyReport` inside of the given namespace
+    pub fn new(name: &str, namespace: &str) -> Self {
+        PolicyReport {
+            api_version: API_VERSION.to_string(),
+            kind: "PolicyReport".to_string(),
+            metadata: ReportMetadata {
+                name: name.to_string(),
+                namespace: Some(namespace.to_string()),
+                ..Default::default()
+            },
+            scope: None,
+            summary: PolicyReportSummary::default(),
+            results: Vec::new(),
+        }
+    }
+
+    /// Create an empty `ClusterPolicyReport`
+    pub fn cluster(name: &str) -> Self {
+        PolicyReport {
+            kind: "ClusterPolicyReport".to_string(),
+            metadata: ReportMetadata {
+                name: name.to_string(),
+                ..Default::default()
+            },
+            ..PolicyReport::new(name, "")
+        }
+    }
+
+    /// Add a result, updating the summary
+    pub fn add_result(&mut self, result: PolicyReportResult) {
+        self.summary.count(result.result);
+        self.results.push(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn build_report() {
+        let metadata = Metadata::builder()
+            .annotation(annotations::SEVERITY, "critical")
+            .annotation(annotations::CATEGORY, "PSP")
+            .build();
+        let request = KubernetesAdmissionRequest {
+            kind: crate::request::GroupVersionKind::new("apps", "v1", "Deployment"),
+            name: "nginx".to_string(),
+            namespace: "default".to_string(),
+            object: json!({"metadata": {"uid": "1234"}}).into(),
+            ..Default::default()
+        };
+
+        let mut report = PolicyReport::cluster("audit");
+        report.add_result(
+            PolicyReportResult::from_response(
+                "replicas",
+                &ValidationResponse::reject("too many replicas").response(),
+            )
+            .metadata(&metadata)
+            .resource(ObjectReference::from_request(&request))
+            .timestamp(Timestamp {
+                seconds: 1700000000,
+                nanos: 0,
+            }),
+        );
+        report.add_result(
+            PolicyReportResult::from_response("labels", &ValidationResponse::accept().response())
+                .rule("team-label")
+                .property("checked", "team"),
+        );
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "apiVersion": "wgpolicyk8s.io/v1alpha2",
+                "kind": "ClusterPolicyReport",
+                "metadata": {"name": "audit"},
+                "summary": {"pass": 1, "fail": 1, "warn": 0, "error": 0, "skip": 0},
+                "results": [
+                    {
+                        "source": "kubewarden",
+                        "policy": "replicas",
+                        "category": "PSP",
+                        "severity": "critical",
+                        "timestamp": {"seconds": 1700000000, "nanos": 0},
+                        "result": "fail",
+                        "scored": true,
+                        "resources": [{
+                            "apiVersion": "apps/v1",
+                            "kind": "Deployment",
+                            "namespace": "default",
+                            "name": "nginx",
+                            "uid": "1234"
+                        }],
+                        "message": "too many replicas"
+                    },
+                    {
+                        "source": "kubewarden",
+                        "policy": "labels",
+                        "rule": "team-label",
+                        "result": "pass",
+                        "scored": true,
+                        "properties": {"checked": "team"}
+                    }
+                ]
+            })
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! Zero-copy flavor of the validation request.
+//!
+//! The types of this module borrow from the waPC payload instead of copying
+//! it: strings are decoded as [`Cow`] and the objects, which can be very large
+//! (think of ConfigMaps or CRDs), are kept as raw JSON. They are decoded on
+//! demand, into types that can borrow from the payload too.
+//!
+//! ```
+//! use kubewarden_policy_sdk::request::borrowed::ValidationRequest;
+//! use serde::Deserialize;
+//! use std::borrow::Cow;
+//! use std::collections::BTreeMap;
+//!
+//! #[derive(Deserialize)]
+//! struct ConfigMap<'a> {
+//!     #[serde(borrow, default)]
+//!     data: BTreeMap<&'a str, Cow<'a, str>>,
+//! }
+//!
+//! let payload = br#"{
+//!   "settings": {},
+//!   "request": {
+//!     "operation": "CREATE",
+//!     "object": {"kind": "ConfigMap", "data": {"key": "value"}}
+//!   }
+//! }"#;
+//! let request = ValidationRequest::new(payload).unwrap();
+//! let config_map: ConfigMap = request.request.object().unwrap().unwrap();
+//! assert_eq!(config_map.data["key"], "value");
+//! ```
+use super::{configure_evaluation, decode_payload, DRY_RUN};
+use crate::error::{Result, SdkError};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::borrow::Cow;
+
+/// ValidationRequest holds the data provided to the policy at evaluation
+/// time, borrowing it from the payload
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ValidationRequest<'a> {
+    /// The policy settings, decoded on demand via [`ValidationRequest::settings`]
+    #[serde(borrow, default, rename = "settings")]
+    pub raw_settings: Option<&'a RawValue>,
+
+    /// Kubernetes' [AdmissionReview](https://kubernetes.io/docs/reference/access-authn-authz/extensible-admission-controllers/) request
+    #[serde(borrow)]
+    pub request: KubernetesAdmissionRequest<'a>,
+
+    /// The W3C trace context of the evaluation, supplied by the host, see
+    /// [`ValidationRequest::traceparent`]
+    #[serde(borrow, default)]
+    traceparent: Option<Cow<'a, str>>,
+
+    /// The seconds granted by the host to the evaluation, see
+    /// [`ValidationRequest::timeout_seconds`]
+    #[serde(default, rename = "timeoutSeconds")]
+    timeout_seconds: Option<f64>,
+}
+
+impl<'a> ValidationRequest<'a> {
+    /// Crates a new `ValidationRequest` borrowing from the payload provided
+    /// to the policy at invocation time.
+    ///
+    /// Like [`super::ValidationRequest::new`], this configures the log level
+    /// and the trace context of the evaluation.
+    pub fn new(payload: &'a [u8]) -> Result<Self> {
+        let request = decode_payload::<ValidationRequest>(payload).inspect_err(|_| {
+            configure_evaluation(None, None, None);
+        })?;
+        configure_evaluation(
+            request.raw_settings,
+            request.traceparent.as_deref(),
+            request.timeout_seconds,
+        );
+        DRY_RUN.with(|dry_run| dry_run.set(request.request.dry_run));
+
+        Ok(request)
+    }
+
+    /// Decode the settings of the policy. `null` and missing settings are
+    /// decoded as the default value of `T`
+    pub fn settings<T>(&self) -> Result<T>
+    where
+        T: Deserialize<'a> + Default,
+    {
+        Ok(decode_raw(self.raw_settings, "the settings")?.unwrap_or_default())
+    }
+
+    /// Returns true when the request is a dry-run one, which means
+    /// modifications will definitely not be persisted
+    pub fn dry_run(&self) -> bool {
+        self.request.dry_run
+    }
+
+    /// The W3C trace context of the evaluation, supplied by the host
+    pub fn traceparent(&self) -> Option<&str> {
+        self.traceparent.as_deref()
+    }
+
+    /// The seconds granted by the host to the evaluation
+    pub fn timeout_seconds(&self) -> Option<f64> {
+        self.timeout_seconds
+    }
+}
+
+fn decode_raw<'a, T: Deserialize<'a>>(raw: Option<&'a RawValue>, what: &str) -> Result<Option<T>> {
+    match raw {
+        Some(raw) if raw.get() != "null" => serde_json::from_str(raw.get())
+            .map(Some)
+            .map_err(|e| SdkError::deserialization(what, e)),
+        _ => Ok(None),
+    }
+}
+
+/// Borrowed flavor of [`GroupVersionKind`](super::GroupVersionKind)
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct GroupVersionKind<'a> {
+    #[serde(borrow)]
+    pub group: Cow<'a, str>,
+    #[serde(borrow)]
+    pub version: Cow<'a, str>,
+    #[serde(borrow)]
+    pub kind: Cow<'a, str>,
+}
+
+impl GroupVersionKind<'_> {
+    /// Convert into the owned [`GroupVersionKind`](super::GroupVersionKind)
+    pub fn to_owned_gvk(&self) -> super::GroupVersionKind {
+        super::GroupVersionKind::new(&self.group, &self.version, &self.kind)
+    }
+}
+
+/// Borrowed flavor of [`GroupVersionResource`](super::GroupVersionResource)
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct GroupVersionResource<'a> {
+    #[serde(borrow)]
+    pub group: Cow<'a, str>,
+    #[serde(borrow)]
+    pub version: Cow<'a, str>,
+    #[serde(borrow)]
+    pub resource: Cow<'a, str>,
+}
+
+impl GroupVersionResource<'_> {
+    /// Convert into the owned [`GroupVersionResource`](super::GroupVersionResource)
+    pub fn to_owned_gvr(&self) -> super::GroupVersionResource {
+        super::GroupVersionResource::new(&self.group, &self.version, &self.resource)
+    }
+}
+
+/// Borrowed flavor of [`UserInfo`](super::UserInfo)
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct UserInfo<'a> {
+    /// The name that uniquely identifies this user among all active users.
+    #[serde(borrow)]
+    pub username: Cow<'a, str>,
+
+    /// A unique value that identifies this user across time.
+    #[serde(borrow)]
+    pub uid: Cow<'a, str>,
+
+    /// The names of groups this user is a part of.
+    #[serde(borrow)]
+    pub groups: Vec<Cow<'a, str>>,
+
+    /// Any additional information provided by the authenticator, kept as raw JSON.
+    #[serde(borrow)]
+    pub extra: Option<&'a RawValue>,
+}
+
+/// Borrowed flavor of [`KubernetesAdmissionRequest`](super::KubernetesAdmissionRequest).
+///
+/// See the owned type for the documentation of the fields. The object,
+/// the old object and the options are kept as raw JSON and are decoded on
+/// demand.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct KubernetesAdmissionRequest<'a> {
+    #[serde(borrow)]
+    pub uid: Cow<'a, str>,
+
+    #[serde(borrow)]
+    pub kind: GroupVersionKind<'a>,
+
+    #[serde(borrow)]
+    pub resource: GroupVersionResource<'a>,
+
+    #[serde(borrow, alias = "subResource")]
+    pub sub_resource: Cow<'a, str>,
+
+    #[serde(borrow, alias = "requestKind")]
+    pub request_kind: GroupVersionKind<'a>,
+
+    #[serde(borrow, alias = "requestResource")]
+    pub request_resource: GroupVersionResource<'a>,
+
+    #[serde(borrow, alias = "requestSubResource")]
+    pub request_sub_resource: Cow<'a, str>,
+
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+
+    #[serde(borrow)]
+    pub namespace: Cow<'a, str>,
+
+    #[serde(borrow)]
+    pub operation: Cow<'a, str>,
+
+    #[serde(borrow, alias = "userInfo")]
+    pub user_info: UserInfo<'a>,
+
+    #[serde(borrow, rename = "object")]
+    pub raw_object: Option<&'a RawValue>,
+
+    #[serde(borrow, alias = "oldObject", rename = "old_object")]
+    pub raw_old_object: Option<&'a RawValue>,
+
+    #[serde(alias = "dryRun")]
+    pub dry_run: bool,
+
+    #[serde(borrow, rename = "options")]
+    pub raw_options: Option<&'a RawValue>,
+}
+
+impl<'a> KubernetesAdmissionRequest<'a> {
+    /// Decode the object of the request. Returns `None` when there's no object
+    pub fn object<T: Deserialize<'a>>(&self) -> Result<Option<T>> {
+        decode_raw(self.raw_object, "the object")
+    }
+
+    /// Decode the old object of the request. Returns `None` when there's no
+    /// old object
+    pub fn old_object<T: Deserialize<'a>>(&self) -> Result<Option<T>> {
+        decode_raw(self.raw_old_object, "the old object")
+    }
+
+    /// Decode the options of the operation. Returns `None` when there are no
+    /// options
+    pub fn options<T: Deserialize<'a>>(&self) -> Result<Option<T>> {
+        decode_raw(self.raw_options, "the options")
+    }
+
+    /// Convert into the owned [`KubernetesAdmissionRequest`](super::KubernetesAdmissionRequest),
+    /// decoding all the raw fields
+    pub fn to_owned_request(&self) -> Result<super::KubernetesAdmissionRequest> {
+        let extra = decode_raw(self.user_info.extra, "the user extra information")?;
+        Ok(super::KubernetesAdmissionRequest {
+            uid: self.uid.to_string(),
+            kind: self.kind.to_owned_gvk(),
+            resource: self.resource.to_owned_gvr(),
+            sub_resource: self.sub_resource.to_string(),
+            request_kind: self.request_kind.to_owned_gvk(),
+            request_resource: self.request_resource.to_owned_gvr(),
+            request_sub_resource: self.request_sub_resource.to_string(),
+            name: self.name.to_string(),
+            namespace: self.namespace.to_string(),
+            operation: self.operation.to_string(),
+            user_info: super::UserInfo {
+                username: self.user_info.username.to_string(),
+                uid: self.user_info.uid.to_string(),
+                groups: self
+                    .user_info
+                    .groups
+                    .iter()
+                    .map(|g| g.to_string())
+                    .collect(),
+                extra: extra.unwrap_or_default(),
+            },
+            object: self.object()?.unwrap_or_default(),
+            old_object: self.old_object()?.unwrap_or_default(),
+            dry_run: self.dry_run,
+            options: self.options()?.unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    struct Metadata<'a> {
+        #[serde(borrow)]
+        name: &'a str,
+    }
+
+    #[derive(Deserialize)]
+    struct Object<'a> {
+        #[serde(borrow)]
+        metadata: Metadata<'a>,
+    }
+
+    #[derive(Deserialize, Default)]
+    struct Settings {
+        replicas: u32,
+    }
+
+    fn payload() -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "settings": {"replicas": 3},
+            "request": {
+                "uid": "1299d386-525b-4032-98ae-1949f69f9cfc",
+                "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "operation": "UPDATE",
+                "namespace": "default",
+                "userInfo": {"username": "alice", "groups": ["system:authenticated"]},
+                "object": {"metadata": {"name": "nginx"}},
+                "oldObject": {"metadata": {"name": "nginx-old"}},
+                "dryRun": true
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn borrows_from_the_payload() {
+        let payload = payload();
+        let request = ValidationRequest::new(&payload).unwrap();
+
+        assert!(matches!(request.request.uid, Cow::Borrowed(_)));
+        assert!(matches!(
+            request.request.kind.kind,
+            Cow::Borrowed("Deployment")
+        ));
+        assert_eq!(request.request.operation, "UPDATE");
+        assert_eq!(request.request.user_info.groups, ["system:authenticated"]);
+        assert!(request.dry_run());
+        assert!(crate::request::is_dry_run());
+
+        let object: Object = request.request.object().unwrap().unwrap();
+        assert_eq!(object.metadata.name, "nginx");
+        let old_object: Object = request.request.old_object().unwrap().unwrap();
+        assert_eq!(old_object.metadata.name, "nginx-old");
+        assert!(request
+            .request
+            .options::<serde_json::Value>()
+            .unwrap()
+            .is_none());
+
+        let settings: Settings = request.settings().unwrap();
+        assert_eq!(settings.replicas, 3);
+    }
+
+    #[test]
+    fn missing_settings_and_objects() {
+        let payload = br#"{"settings": null, "request": {"object": null}}"#;
+        let request = ValidationRequest::new(payload).unwrap();
+        assert_eq!(request.settings::<Settings>().unwrap().replicas, 0);
+        assert!(request
+            .request
+            .object::<serde_json::Value>()
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn to_owned_request() {
+        let payload = payload();
+        let request = ValidationRequest::new(&payload).unwrap();
+        let owned = request.request.to_owned_request().unwrap();
+
+        let expected =
+            serde_json::from_slice::<super::super::ValidationRequest<serde_json::Value>>(&payload)
+                .unwrap()
+                .request;
+        assert_eq!(
+            serde_json::to_value(owned).unwrap(),
+            serde_json::to_value(expected).unwrap()
+        );
+    }
+}
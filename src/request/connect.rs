@@ -0,0 +1,138 @@
+use crate::error::{Result, SdkError};
+use serde::{Deserialize, Serialize};
+
+/// PodExecOptions is the query options to a Pod's remote exec call.
+/// This is the object received by policies evaluating `kubectl exec` requests
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PodExecOptions {
+    /// Command is the remote command to execute. argv array. Not executed within a shell.
+    pub command: Vec<String>,
+    /// Container in which to execute the command.
+    /// Defaults to only container if there is only one container in the pod.
+    pub container: Option<String>,
+    /// Redirect the standard input stream of the pod for this call.
+    pub stdin: bool,
+    /// Redirect the standard output stream of the pod for this call.
+    pub stdout: bool,
+    /// Redirect the standard error stream of the pod for this call.
+    pub stderr: bool,
+    /// TTY if true indicates that a tty will be allocated for the exec call.
+    pub tty: bool,
+}
+
+/// PodAttachOptions is the query options to a Pod's remote attach call.
+/// This is the object received by policies evaluating `kubectl attach` requests
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PodAttachOptions {
+    /// The container in which to execute the command.
+    /// Defaults to only container if there is only one container in the pod.
+    pub container: Option<String>,
+    /// Stdin if true, redirects the standard input stream of the pod for this call.
+    pub stdin: bool,
+    /// Stdout if true indicates that stdout is to be redirected for the attach call.
+    pub stdout: bool,
+    /// Stderr if true indicates that stderr is to be redirected for the attach call.
+    pub stderr: bool,
+    /// TTY if true indicates that a tty will be allocated for the attach call.
+    pub tty: bool,
+}
+
+/// PodPortForwardOptions is the query options to a Pod's port forward call.
+/// This is the object received by policies evaluating `kubectl port-forward` requests
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PodPortForwardOptions {
+    /// List of ports to forward
+    pub ports: Vec<i32>,
+}
+
+/// The options of a CONNECT operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectOptions {
+    /// `pods/exec` subresource
+    Exec(PodExecOptions),
+    /// `pods/attach` subresource
+    Attach(PodAttachOptions),
+    /// `pods/portforward` subresource
+    PortForward(PodPortForwardOptions),
+}
+
+impl ConnectOptions {
+    /// Decode the object of a CONNECT request, given its kind
+    pub(crate) fn decode(kind: &str, object: &serde_json::Value) -> Result<Self> {
+        let options = match kind {
+            "PodExecOptions" => serde_json::from_value(object.clone()).map(ConnectOptions::Exec),
+            "PodAttachOptions" => {
+                serde_json::from_value(object.clone()).map(ConnectOptions::Attach)
+            }
+            "PodPortForwardOptions" => {
+                serde_json::from_value(object.clone()).map(ConnectOptions::PortForward)
+            }
+            _ => {
+                return Err(SdkError::unsupported_kind(
+                    kind,
+                    &[
+                        "PodExecOptions",
+                        "PodAttachOptions",
+                        "PodPortForwardOptions",
+                    ],
+                ))
+            }
+        };
+
+        options.map_err(|e| SdkError::deserialization(kind, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decode_exec_options() {
+        let object = json!({
+            "kind": "PodExecOptions",
+            "apiVersion": "v1",
+            "stdin": true,
+            "stdout": true,
+            "tty": true,
+            "container": "nginx",
+            "command": ["sh", "-c", "ls"]
+        });
+        let options = ConnectOptions::decode("PodExecOptions", &object).unwrap();
+
+        assert_eq!(
+            options,
+            ConnectOptions::Exec(PodExecOptions {
+                command: vec!["sh".to_string(), "-c".to_string(), "ls".to_string()],
+                container: Some("nginx".to_string()),
+                stdin: true,
+                stdout: true,
+                stderr: false,
+                tty: true,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_port_forward_options() {
+        let object = json!({"kind": "PodPortForwardOptions", "ports": [8080, 443]});
+        let options = ConnectOptions::decode("PodPortForwardOptions", &object).unwrap();
+
+        assert_eq!(
+            options,
+            ConnectOptions::PortForward(PodPortForwardOptions {
+                ports: vec![8080, 443]
+            })
+        );
+    }
+
+    #[test]
+    fn decode_unknown_options() {
+        assert!(ConnectOptions::decode("PodProxyOptions", &json!({})).is_err());
+    }
+}
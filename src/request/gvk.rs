@@ -0,0 +1,424 @@
+use crate::error::SdkError;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// GroupVersionKind unambiguously identifies a kind
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct GroupVersionKind {
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+}
+
+impl GroupVersionKind {
+    /// Create a new GroupVersionKind. The core group is represented by an empty string
+    pub fn new(group: &str, version: &str, kind: &str) -> Self {
+        GroupVersionKind {
+            group: group.to_string(),
+            version: version.to_string(),
+            kind: kind.to_string(),
+        }
+    }
+
+    /// Create a new GroupVersionKind starting from the `apiVersion` and `kind`
+    /// fields of a Kubernetes object (e.g. `apps/v1` and `Deployment`)
+    pub fn from_api_version_and_kind(api_version: &str, kind: &str) -> Self {
+        let (group, version) = split_api_version(api_version);
+        GroupVersionKind::new(group, version, kind)
+    }
+
+    /// The `apiVersion` of the kind (e.g. `apps/v1`, or `v1` for the core group)
+    pub fn api_version(&self) -> String {
+        join_api_version(&self.group, &self.version)
+    }
+}
+
+/// Parse strings like `apps/v1/Deployment` or `v1/Pod`
+impl FromStr for GroupVersionKind {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, version, kind) = split_segments(s)
+            .ok_or_else(|| SdkError::InvalidInput(format!("Invalid GroupVersionKind: {}", s)))?;
+        Ok(GroupVersionKind::new(group, version, kind))
+    }
+}
+
+impl fmt::Display for GroupVersionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.api_version(), self.kind)
+    }
+}
+
+/// GroupVersionResource unambiguously identifies a resource
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct GroupVersionResource {
+    pub group: String,
+    pub version: String,
+    /// The plural name of the resource, e.g. `deployments`. This used to be
+    /// the `kind` field, which is still accepted when deserializing
+    #[serde(alias = "kind")]
+    pub resource: String,
+}
+
+impl GroupVersionResource {
+    /// Create a new GroupVersionResource. The core group is represented by an empty string
+    pub fn new(group: &str, version: &str, resource: &str) -> Self {
+        GroupVersionResource {
+            group: group.to_string(),
+            version: version.to_string(),
+            resource: resource.to_string(),
+        }
+    }
+
+    /// The `apiVersion` of the resource (e.g. `apps/v1`, or `v1` for the core group)
+    pub fn api_version(&self) -> String {
+        join_api_version(&self.group, &self.version)
+    }
+
+    /// The plural name of the resource
+    #[deprecated(note = "the `kind` field has been renamed to `resource`")]
+    pub fn kind(&self) -> &str {
+        &self.resource
+    }
+}
+
+/// Parse strings like `apps/v1/deployments` or `v1/pods`
+impl FromStr for GroupVersionResource {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (group, version, resource) = split_segments(s).ok_or_else(|| {
+            SdkError::InvalidInput(format!("Invalid GroupVersionResource: {}", s))
+        })?;
+        Ok(GroupVersionResource::new(group, version, resource))
+    }
+}
+
+impl fmt::Display for GroupVersionResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.api_version(), self.resource)
+    }
+}
+
+/// A pattern matching [`GroupVersionKind`] objects.
+///
+/// Patterns are written either as `group/kind` or `group/version/kind`, where
+/// each segment can be `*` to match any value. The core group can be written
+/// either as `core` or as an empty string. For example:
+///
+/// * `apps/*`: any kind of the `apps` group
+/// * `*/Scale`: the `Scale` kind of any group
+/// * `core/v1/Pod`: only `v1` Pods
+///
+/// Two segments patterns starting with a version, like `v1/Pod`, are
+/// rejected: [`GroupVersionKind`] reads them as `version/kind`, while a
+/// pattern would read them as `group/kind`. Write `core/v1/Pod` instead.
+///
+/// Patterns can be deserialized from strings, which allows to use them inside
+/// of the policy settings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct GroupVersionKindPattern {
+    pattern: Pattern,
+}
+
+impl GroupVersionKindPattern {
+    /// Returns true when the given GroupVersionKind matches the pattern
+    pub fn matches(&self, gvk: &GroupVersionKind) -> bool {
+        self.pattern.matches(&gvk.group, &gvk.version, &gvk.kind)
+    }
+}
+
+impl FromStr for GroupVersionKindPattern {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(GroupVersionKindPattern {
+            pattern: s.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for GroupVersionKindPattern {
+    type Error = SdkError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<GroupVersionKindPattern> for String {
+    fn from(pattern: GroupVersionKindPattern) -> Self {
+        pattern.pattern.to_string()
+    }
+}
+
+impl fmt::Display for GroupVersionKindPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pattern.fmt(f)
+    }
+}
+
+/// A pattern matching [`GroupVersionResource`] objects.
+///
+/// Patterns are written either as `group/resource` or `group/version/resource`,
+/// following the same rules of [`GroupVersionKindPattern`]. For example
+/// `apps/*` or `*/deployments`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct GroupVersionResourcePattern {
+    pattern: Pattern,
+}
+
+impl GroupVersionResourcePattern {
+    /// Returns true when the given GroupVersionResource matches the pattern
+    pub fn matches(&self, gvr: &GroupVersionResource) -> bool {
+        self.pattern
+            .matches(&gvr.group, &gvr.version, &gvr.resource)
+    }
+}
+
+impl FromStr for GroupVersionResourcePattern {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(GroupVersionResourcePattern {
+            pattern: s.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for GroupVersionResourcePattern {
+    type Error = SdkError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<GroupVersionResourcePattern> for String {
+    fn from(pattern: GroupVersionResourcePattern) -> Self {
+        pattern.pattern.to_string()
+    }
+}
+
+impl fmt::Display for GroupVersionResourcePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.pattern.fmt(f)
+    }
+}
+
+/// A single segment of a pattern, `None` matches everything
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Segment(Option<String>);
+
+impl Segment {
+    fn parse(s: &str) -> Self {
+        if s == "*" {
+            Segment(None)
+        } else {
+            Segment(Some(s.to_string()))
+        }
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        self.0.as_ref().is_none_or(|s| s == value)
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.as_deref().unwrap_or("*"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    group: Segment,
+    version: Option<Segment>,
+    name: Segment,
+}
+
+impl Pattern {
+    fn matches(&self, group: &str, version: &str, name: &str) -> bool {
+        self.group.matches(group)
+            && self.version.as_ref().is_none_or(|v| v.matches(version))
+            && self.name.matches(name)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let segments: Vec<&str> = s.split('/').collect();
+        let (group, version, name) = match segments.as_slice() {
+            [group, _] if is_version(group) => {
+                return Err(SdkError::InvalidInput(format!(
+                    "Ambiguous pattern: {}, use core/{} to match the core group",
+                    s, s
+                )))
+            }
+            [group, name] => (group, None, name),
+            [group, version, name] => (group, Some(Segment::parse(version)), name),
+            _ => return Err(SdkError::InvalidInput(format!("Invalid pattern: {}", s))),
+        };
+        if name.is_empty() {
+            return Err(SdkError::InvalidInput(format!("Invalid pattern: {}", s)));
+        }
+        let group = if *group == "core" { "" } else { group };
+
+        Ok(Pattern {
+            group: Segment::parse(group),
+            version,
+            name: Segment::parse(name),
+        })
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.group.0 {
+            Some(group) if group.is_empty() => write!(f, "core")?,
+            _ => write!(f, "{}", self.group)?,
+        }
+        if let Some(version) = &self.version {
+            write!(f, "/{}", version)?;
+        }
+        write!(f, "/{}", self.name)
+    }
+}
+
+/// Split strings like `apps/v1/Deployment` or `v1/Pod` into their group,
+/// version and name
+fn split_segments(s: &str) -> Option<(&str, &str, &str)> {
+    let segments: Vec<&str> = s.split('/').collect();
+    let (group, version, name) = match segments.as_slice() {
+        [version, name] => ("", *version, *name),
+        [group, version, name] if !group.is_empty() => (*group, *version, *name),
+        _ => return None,
+    };
+    (!version.is_empty() && !name.is_empty()).then_some((group, version, name))
+}
+
+/// Whether `s` is a Kubernetes API version, like `v1` or `v2beta1`
+fn is_version(s: &str) -> bool {
+    let Some(rest) = s.strip_prefix('v') else {
+        return false;
+    };
+    let qualifier = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    if qualifier.len() == rest.len() {
+        return false;
+    }
+    qualifier.is_empty()
+        || qualifier
+            .strip_prefix("alpha")
+            .or_else(|| qualifier.strip_prefix("beta"))
+            .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn split_api_version(api_version: &str) -> (&str, &str) {
+    api_version.split_once('/').unwrap_or(("", api_version))
+}
+
+fn join_api_version(group: &str, version: &str) -> String {
+    if group.is_empty() {
+        version.to_string()
+    } else {
+        format!("{}/{}", group, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display_gvk() {
+        let gvk: GroupVersionKind = "apps/v1/Deployment".parse().unwrap();
+        assert_eq!(gvk, GroupVersionKind::new("apps", "v1", "Deployment"));
+        assert_eq!(gvk.api_version(), "apps/v1");
+        assert_eq!(gvk.to_string(), "apps/v1/Deployment");
+
+        let gvk: GroupVersionKind = "v1/Pod".parse().unwrap();
+        assert_eq!(gvk, GroupVersionKind::new("", "v1", "Pod"));
+        assert_eq!(gvk.api_version(), "v1");
+        assert_eq!(gvk.to_string(), "v1/Pod");
+
+        assert!("Pod".parse::<GroupVersionKind>().is_err());
+        assert!("v1/".parse::<GroupVersionKind>().is_err());
+        assert!("/v1/Pod".parse::<GroupVersionKind>().is_err());
+        assert!("a/b/c/Kind".parse::<GroupVersionKind>().is_err());
+        assert!("a/b/c/pods".parse::<GroupVersionResource>().is_err());
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn gvr_kind_is_the_resource() {
+        let gvr: GroupVersionResource =
+            serde_json::from_str(r#"{"group": "", "version": "v1", "kind": "pods"}"#).unwrap();
+        assert_eq!(gvr.resource, "pods");
+        assert_eq!(gvr.kind(), "pods");
+    }
+
+    #[test]
+    fn parse_gvr() {
+        let gvr: GroupVersionResource = "apps/v1/deployments".parse().unwrap();
+        assert_eq!(gvr, GroupVersionResource::new("apps", "v1", "deployments"));
+        assert_eq!(gvr.to_string(), "apps/v1/deployments");
+    }
+
+    #[test]
+    fn gvk_pattern_matching() {
+        let deployment = GroupVersionKind::new("apps", "v1", "Deployment");
+        let scale = GroupVersionKind::new("autoscaling", "v1", "Scale");
+        let pod = GroupVersionKind::new("", "v1", "Pod");
+
+        let apps: GroupVersionKindPattern = "apps/*".parse().unwrap();
+        assert!(apps.matches(&deployment));
+        assert!(!apps.matches(&scale));
+
+        let any_scale: GroupVersionKindPattern = "*/Scale".parse().unwrap();
+        assert!(any_scale.matches(&scale));
+        assert!(!any_scale.matches(&deployment));
+
+        let core_pod: GroupVersionKindPattern = "core/v1/Pod".parse().unwrap();
+        assert!(core_pod.matches(&pod));
+        assert!(!core_pod.matches(&GroupVersionKind::new("", "v2", "Pod")));
+
+        assert!("Pod".parse::<GroupVersionKindPattern>().is_err());
+        assert!("v1/Pod".parse::<GroupVersionKindPattern>().is_err());
+        assert!("v2beta1/*".parse::<GroupVersionResourcePattern>().is_err());
+        assert!("a/b/c/d".parse::<GroupVersionKindPattern>().is_err());
+    }
+
+    #[test]
+    fn detect_versions() {
+        for version in ["v1", "v12", "v1alpha1", "v2beta3"] {
+            assert!(is_version(version), "{}", version);
+        }
+        for other in ["v", "apps", "vx", "v1alpha", "v1gamma1", "1", "core"] {
+            assert!(!is_version(other), "{}", other);
+        }
+    }
+
+    #[test]
+    fn gvr_pattern_matching() {
+        let pattern: GroupVersionResourcePattern = "*/v1/deployments".parse().unwrap();
+        assert!(pattern.matches(&GroupVersionResource::new("apps", "v1", "deployments")));
+        assert!(!pattern.matches(&GroupVersionResource::new("apps", "v1beta1", "deployments")));
+    }
+
+    #[test]
+    fn pattern_serde_roundtrip() {
+        let patterns: Vec<GroupVersionKindPattern> =
+            serde_json::from_str(r#"["apps/*", "core/v1/Pod", "*/Scale"]"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&patterns).unwrap(),
+            r#"["apps/*","core/v1/Pod","*/Scale"]"#
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use crate::error::{Result, SdkError};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::OnceLock;
+
+/// A JSON value that is kept in its raw form and parsed on demand.
+///
+/// The object and the old object of the requests can be very large, while
+/// many policies only look at a small part of them (e.g. the metadata). A
+/// `LazyValue` stores the raw JSON text and parses it only when needed:
+///
+/// * [`LazyValue::parse`] decodes the raw text straight into the given type,
+///   skipping the fields the type doesn't have
+/// * dereferencing to [`serde_json::Value`] parses the whole value, once,
+///   and caches the result
+///
+/// `LazyValue` can also be used as the type of the settings of the
+/// [`ValidationRequest`](super::ValidationRequest), to defer their decoding.
+pub struct LazyValue {
+    raw: Box<RawValue>,
+    parsed: OnceLock<serde_json::Value>,
+}
+
+impl LazyValue {
+    /// Create a `LazyValue` holding the given raw JSON text
+    pub fn from_raw(raw: Box<RawValue>) -> Self {
+        LazyValue {
+            raw,
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// The raw JSON text of the value
+    ///
+    /// Note: changes done to the value via [`DerefMut`] are not reflected
+    /// by the raw text, use [`serde_json::to_string`] to get them.
+    pub fn raw(&self) -> &str {
+        self.raw.get()
+    }
+
+    /// Returns true when the value is `null`
+    pub fn is_null(&self) -> bool {
+        match self.parsed.get() {
+            Some(value) => value.is_null(),
+            None => self.raw.get() == "null",
+        }
+    }
+
+    /// Decode the value into the given type
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        self.decode()
+            .map_err(|e| SdkError::deserialization(std::any::type_name::<T>(), e))
+    }
+
+    pub(crate) fn decode<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        match self.parsed.get() {
+            Some(value) => T::deserialize(value),
+            None => serde_json::from_str(self.raw.get()),
+        }
+    }
+
+    /// The parsed value. Invalid values, which cannot be produced by the
+    /// deserialization, are parsed as `null`
+    pub fn value(&self) -> &serde_json::Value {
+        self.parsed
+            .get_or_init(|| serde_json::from_str(self.raw.get()).unwrap_or_default())
+    }
+
+    /// Consume the `LazyValue` and return the parsed value
+    pub fn into_value(self) -> serde_json::Value {
+        self.value();
+        self.parsed.into_inner().unwrap_or_default()
+    }
+}
+
+impl Default for LazyValue {
+    fn default() -> Self {
+        serde_json::Value::Null.into()
+    }
+}
+
+impl Clone for LazyValue {
+    fn clone(&self) -> Self {
+        LazyValue {
+            raw: self.raw.clone(),
+            parsed: self.parsed.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for LazyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.parsed.get() {
+            Some(value) => value.fmt(f),
+            None => f.debug_tuple("LazyValue").field(&self.raw.get()).finish(),
+        }
+    }
+}
+
+impl PartialEq for LazyValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.value() == other.value()
+    }
+}
+
+impl PartialEq<serde_json::Value> for LazyValue {
+    fn eq(&self, other: &serde_json::Value) -> bool {
+        self.value() == other
+    }
+}
+
+impl Deref for LazyValue {
+    type Target = serde_json::Value;
+
+    fn deref(&self) -> &Self::Target {
+        self.value()
+    }
+}
+
+impl DerefMut for LazyValue {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value();
+        self.parsed
+            .get_mut()
+            .expect("the value has just been parsed")
+    }
+}
+
+impl From<serde_json::Value> for LazyValue {
+    fn from(value: serde_json::Value) -> Self {
+        let raw = serde_json::value::to_raw_value(&value).expect("a Value is always valid JSON");
+        LazyValue {
+            raw,
+            parsed: OnceLock::from(value),
+        }
+    }
+}
+
+impl From<LazyValue> for serde_json::Value {
+    fn from(value: LazyValue) -> Self {
+        value.into_value()
+    }
+}
+
+impl Serialize for LazyValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.parsed.get() {
+            Some(value) => value.serialize(serializer),
+            None => self.raw.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LazyValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        Box::<RawValue>::deserialize(deserializer).map(LazyValue::from_raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Deserialize)]
+    struct Metadata {
+        name: String,
+    }
+
+    #[derive(Deserialize)]
+    struct Object {
+        metadata: Metadata,
+    }
+
+    #[test]
+    fn parse_on_demand() {
+        let value: LazyValue =
+            serde_json::from_str(r#"{"metadata": {"name": "nginx"}, "spec": {"replicas": 1}}"#)
+                .unwrap();
+        assert!(value.parsed.get().is_none());
+
+        let object: Object = value.parse().unwrap();
+        assert_eq!(object.metadata.name, "nginx");
+        assert!(value.parsed.get().is_none());
+        assert!(value.parse::<Vec<String>>().is_err());
+
+        assert_eq!(value["spec"]["replicas"], 1);
+        assert!(value.parsed.get().is_some());
+        assert_eq!(
+            value,
+            json!({"metadata": {"name": "nginx"}, "spec": {"replicas": 1}})
+        );
+    }
+
+    #[test]
+    fn mutations_are_serialized() {
+        let mut value: LazyValue = serde_json::from_str(r#"{"replicas": 1}"#).unwrap();
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"replicas": 1}"#);
+
+        value["replicas"] = json!(2);
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"replicas":2}"#);
+        assert_eq!(
+            value.parse::<serde_json::Value>().unwrap(),
+            json!({"replicas": 2})
+        );
+    }
+
+    #[test]
+    fn null_values() {
+        let value = LazyValue::default();
+        assert!(value.is_null());
+        assert_eq!(value.raw(), "null");
+
+        let value: LazyValue = serde_json::from_str("null").unwrap();
+        assert!(value.is_null());
+        assert_eq!(value.into_value(), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn lazy_settings() {
+        let payload = br#"{"settings": {"replicas": 3}, "request": {"object": {"kind": "Pod"}}}"#;
+        let request = super::super::ValidationRequest::<LazyValue>::new(payload).unwrap();
+        assert_eq!(request.settings.raw(), r#"{"replicas": 3}"#);
+        assert_eq!(request.request.object["kind"], "Pod");
+        assert!(request.request.old_object.is_null());
+    }
+
+    #[test]
+    fn requests_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<LazyValue>();
+        assert_send_sync::<super::super::KubernetesAdmissionRequest>();
+    }
+}
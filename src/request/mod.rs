@@ -0,0 +1,928 @@
+use crate::error::{Result, SdkError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+
+pub mod borrowed;
+mod connect;
+mod gvk;
+mod lazy;
+mod review;
+
+pub use connect::{ConnectOptions, PodAttachOptions, PodExecOptions, PodPortForwardOptions};
+pub use gvk::{
+    GroupVersionKind, GroupVersionKindPattern, GroupVersionResource, GroupVersionResourcePattern,
+};
+pub use lazy::LazyValue;
+pub use review::{AdmissionReview, ADMISSION_V1, ADMISSION_V1BETA1};
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "cluster-context")] {
+        use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, ReplicaSet, StatefulSet};
+        use k8s_openapi::api::autoscaling::v1::Scale;
+        use k8s_openapi::api::batch::v1::{CronJob, Job};
+        use k8s_openapi::api::core::v1::{Pod, PodSpec, ReplicationController};
+        use k8s_openapi::Resource;
+    }
+}
+
+/// ValidationRequest holds the data provided to the policy at evaluation time
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ValidationRequest<T: Default> {
+    /// The policy settings
+    pub settings: T,
+
+    /// Kubernetes' [AdmissionReview](https://kubernetes.io/docs/reference/access-authn-authz/extensible-admission-controllers/) request
+    pub request: KubernetesAdmissionRequest,
+
+    /// The W3C trace context of the evaluation, supplied by the host, see
+    /// [`ValidationRequest::traceparent`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    traceparent: Option<String>,
+
+    /// The seconds granted by the host to the evaluation, see
+    /// [`ValidationRequest::timeout_seconds`]
+    #[serde(
+        default,
+        rename = "timeoutSeconds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    timeout_seconds: Option<f64>,
+}
+
+impl<T: Default> ValidationRequest<T> {
+    /// Create a `ValidationRequest` made of the given settings and request,
+    /// without the evaluation data supplied by the host. Useful to evaluate
+    /// requests that do not come from the host, like inside of the tests
+    pub fn from_parts(settings: T, request: KubernetesAdmissionRequest) -> Self {
+        ValidationRequest {
+            settings,
+            request,
+            traceparent: None,
+            timeout_seconds: None,
+        }
+    }
+
+    /// The W3C trace context of the evaluation, supplied by the host
+    pub fn traceparent(&self) -> Option<&str> {
+        self.traceparent.as_deref()
+    }
+
+    /// The seconds granted by the host to the evaluation
+    pub fn timeout_seconds(&self) -> Option<f64> {
+        self.timeout_seconds
+    }
+}
+
+/// Kubernetes' [AdmissionReview](https://kubernetes.io/docs/reference/access-authn-authz/extensible-admission-controllers/)
+/// request.
+///
+/// The request is serialized using the camelCase keys of the AdmissionReview
+/// objects, the snake_case ones are still accepted when deserializing.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct KubernetesAdmissionRequest {
+    /// UID is an identifier for the individual request/response. It allows us to distinguish instances of requests which are
+    /// otherwise identical (parallel requests, requests when earlier requests did not modify etc)
+    /// The UID is meant to track the round trip (request/response) between the KAS and the WebHook, not the user request.
+    /// It is suitable for correlating log entries between the webhook and apiserver, for either auditing or debugging.
+    pub uid: String,
+
+    /// Kind is the fully-qualified type of object being submitted (for example, v1.Pod or autoscaling.v1.Scale)
+    pub kind: GroupVersionKind,
+
+    /// Resource is the fully-qualified resource being requested (for example, v1.pods)
+    pub resource: GroupVersionResource,
+
+    /// SubResource is the subresource being requested, if any (for example, "status" or "scale")
+    #[serde(rename = "subResource", alias = "sub_resource")]
+    pub sub_resource: String,
+
+    /// RequestKind is the fully-qualified type of the original API request (for example, v1.Pod or autoscaling.v1.Scale).
+    /// If this is specified and differs from the value in "kind", an equivalent match and conversion was performed.
+    ///
+    /// For example, if deployments can be modified via apps/v1 and apps/v1beta1, and a webhook registered a rule of
+    /// `apiGroups:["apps"], apiVersions:["v1"], resources: ["deployments"]` and `matchPolicy: Equivalent`,
+    /// an API request to apps/v1beta1 deployments would be converted and sent to the webhook
+    /// with `kind: {group:"apps", version:"v1", kind:"Deployment"}` (matching the rule the webhook registered for),
+    /// and `requestKind: {group:"apps", version:"v1beta1", kind:"Deployment"}` (indicating the kind of the original API request).
+    ///
+    /// See documentation for the "matchPolicy" field in the webhook configuration type for more details.
+    #[serde(rename = "requestKind", alias = "request_kind")]
+    pub request_kind: GroupVersionKind,
+
+    /// RequestResource is the fully-qualified resource of the original API request (for example, v1.pods).
+    /// If this is specified and differs from the value in "resource", an equivalent match and conversion was performed.
+    ///
+    /// For example, if deployments can be modified via apps/v1 and apps/v1beta1, and a webhook registered a rule of
+    /// `apiGroups:["apps"], apiVersions:["v1"], resources: ["deployments"]` and `matchPolicy: Equivalent`,
+    /// an API request to apps/v1beta1 deployments would be converted and sent to the webhook
+    /// with `resource: {group:"apps", version:"v1", resource:"deployments"}` (matching the resource the webhook registered for),
+    /// and `requestResource: {group:"apps", version:"v1beta1", resource:"deployments"}` (indicating the resource of the original API request).
+    ///
+    /// See documentation for the "matchPolicy" field in the webhook configuration type.
+    #[serde(rename = "requestResource", alias = "request_resource")]
+    pub request_resource: GroupVersionResource,
+
+    /// RequestSubResource is the name of the subresource of the original API request, if any (for example, "status" or "scale")
+    /// If this is specified and differs from the value in "subResource", an equivalent match and conversion was performed.
+    /// See documentation for the "matchPolicy" field in the webhook configuration type.
+    #[serde(rename = "requestSubResource", alias = "request_sub_resource")]
+    pub request_sub_resource: String,
+
+    /// Name is the name of the object as presented in the request.  On a CREATE operation, the client may omit name and
+    /// rely on the server to generate the name.  If that is the case, this field will contain an empty string.
+    pub name: String,
+
+    /// Namespace is the namespace associated with the request (if any).
+    pub namespace: String,
+
+    /// Operation is the operation being performed. This may be different than the operation
+    /// requested. e.g. a patch can result in either a CREATE or UPDATE Operation.
+    pub operation: String,
+
+    /// UserInfo is information about the requesting user
+    #[serde(rename = "userInfo", alias = "user_info")]
+    pub user_info: UserInfo,
+
+    /// Object is the object from the incoming request. It is parsed on demand,
+    /// see [`LazyValue`].
+    pub object: LazyValue,
+
+    /// OldObject is the existing object. Only populated for DELETE and UPDATE requests.
+    #[serde(rename = "oldObject", alias = "old_object")]
+    pub old_object: LazyValue,
+
+    /// DryRun indicates that modifications will definitely not be persisted for this request.
+    /// Defaults to false.
+    #[serde(rename = "dryRun", alias = "dry_run", default)]
+    pub dry_run: bool,
+
+    /// Options is the operation option structure of the operation being performed.
+    /// e.g. `meta.k8s.io/v1.DeleteOptions` or `meta.k8s.io/v1.CreateOptions`. This may be
+    /// different than the options the caller provided. e.g. for a patch request the performed
+    /// Operation might be a CREATE, in which case the Options will a
+    /// `meta.k8s.io/v1.CreateOptions` even though the caller provided `meta.k8s.io/v1.PatchOptions`.
+    pub options: HashMap<String, serde_json::Value>,
+}
+
+impl KubernetesAdmissionRequest {
+    /// Returns true when the kind of the object being submitted matches the
+    /// given pattern
+    pub fn matches_kind(&self, pattern: &GroupVersionKindPattern) -> bool {
+        pattern.matches(&self.kind)
+    }
+
+    /// Returns true when the resource being requested matches the given pattern
+    pub fn matches_resource(&self, pattern: &GroupVersionResourcePattern) -> bool {
+        pattern.matches(&self.resource)
+    }
+
+    /// Returns the typed options of a CONNECT operation, like the ones
+    /// produced by `kubectl exec`, `kubectl attach` and `kubectl port-forward`.
+    ///
+    /// Returns `None` when the operation is not a CONNECT one, and an error
+    /// when the options cannot be decoded.
+    pub fn connect_options(&self) -> Result<Option<ConnectOptions>> {
+        if self.operation != "CONNECT" {
+            return Ok(None);
+        }
+        ConnectOptions::decode(&self.kind.kind, &self.object).map(Some)
+    }
+
+    /// Returns the JSON Pointers of the fields that differ between `old_object`
+    /// and `object`. See [`crate::diff::changed_paths`]
+    pub fn changed_paths(&self) -> Vec<String> {
+        crate::diff::changed_paths(&self.old_object, &self.object)
+    }
+
+    /// Ensure the fields referenced by the given JSON Pointers have not been
+    /// changed by an UPDATE request. Returns an error listing the changed fields.
+    ///
+    /// ```
+    /// use kubewarden_policy_sdk::request::KubernetesAdmissionRequest;
+    /// use serde_json::json;
+    ///
+    /// let request = KubernetesAdmissionRequest {
+    ///     operation: "UPDATE".to_string(),
+    ///     old_object: json!({"spec": {"storageClassName": "fast"}}).into(),
+    ///     object: json!({"spec": {"storageClassName": "slow"}}).into(),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// assert!(request.assert_unchanged(&["/spec/storageClassName"]).is_err());
+    /// ```
+    pub fn assert_unchanged(&self, paths: &[&str]) -> crate::error::Result<()> {
+        crate::diff::assert_unchanged(&self.old_object, &self.object, paths)
+    }
+
+    /// Returns the subresource being requested, if any (for example, "status" or "scale")
+    pub fn subresource(&self) -> Option<&str> {
+        if self.sub_resource.is_empty() {
+            None
+        } else {
+            Some(self.sub_resource.as_str())
+        }
+    }
+
+    /// Returns true when the request targets the given subresource
+    pub fn is_subresource(&self, subresource: &str) -> bool {
+        self.sub_resource == subresource
+    }
+
+    /// Returns true when the request targets the `status` subresource
+    pub fn is_status_subresource(&self) -> bool {
+        self.is_subresource("status")
+    }
+
+    /// Returns true when the request targets the `scale` subresource,
+    /// like `deployments/scale` updates done by `kubectl scale`
+    pub fn is_scale_subresource(&self) -> bool {
+        self.is_subresource("scale")
+    }
+
+    #[cfg(feature = "cluster-context")]
+    /// Decode the `Scale` object of a request targeting the `scale` subresource.
+    /// Returns `None` when the request targets a different subresource.
+    pub fn scale(&self) -> Result<Option<Scale>> {
+        self.decode_scale(&self.object)
+    }
+
+    #[cfg(feature = "cluster-context")]
+    /// Decode the old `Scale` object of an UPDATE request targeting the `scale`
+    /// subresource. Returns `None` when the request targets a different subresource
+    /// or when the old object is not available.
+    pub fn old_scale(&self) -> Result<Option<Scale>> {
+        if self.old_object.is_null() {
+            return Ok(None);
+        }
+        self.decode_scale(&self.old_object)
+    }
+
+    #[cfg(feature = "cluster-context")]
+    fn decode_scale(&self, object: &serde_json::Value) -> Result<Option<Scale>> {
+        if !self.is_scale_subresource() {
+            return Ok(None);
+        }
+        serde_json::from_value::<Scale>(object.clone())
+            .map(Some)
+            .map_err(|e| SdkError::deserialization("the Scale object", e))
+    }
+}
+
+/// UserInfo holds information about the user who made the request
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct UserInfo {
+    /// The name that uniquely identifies this user among all active users.
+    pub username: String,
+
+    /// A unique value that identifies this user across time. If this user is
+    /// deleted and another user by the same name is added, they will have
+    /// different UIDs.
+    pub uid: String,
+
+    /// The names of groups this user is a part of.
+    pub groups: HashSet<String>,
+
+    /// Any additional information provided by the authenticator.
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+thread_local! {
+    static DRY_RUN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns true when the request being evaluated is a dry-run one.
+///
+/// The value is set when the request is decoded via [`ValidationRequest::new`].
+pub fn is_dry_run() -> bool {
+    DRY_RUN.with(|dry_run| dry_run.get())
+}
+
+/// Guard that must be checked before performing state-changing side effects,
+/// like emitting audit events via host capabilities.
+///
+/// Returns an error when the request being evaluated is a dry-run one: the
+/// API server guarantees dry-run requests don't persist any change, and
+/// policies must honor the same semantics.
+/// # Arguments
+/// * `action` - description of the side effect, used inside of the error message
+pub fn ensure_side_effects_allowed(action: &str) -> Result<()> {
+    if is_dry_run() {
+        Err(SdkError::DryRun {
+            action: action.to_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct EnvelopeSettings {
+    #[serde(rename = "logLevel")]
+    log_level: Option<String>,
+    #[serde(rename = "evaluationTimeoutSeconds")]
+    evaluation_timeout_seconds: Option<f64>,
+}
+
+/// The validation payload, with the settings left undecoded: they are
+/// decoded once the evaluation has been configured
+#[derive(Deserialize)]
+struct Payload<'a> {
+    #[serde(borrow, default)]
+    settings: Option<&'a RawValue>,
+    request: KubernetesAdmissionRequest,
+    #[serde(default)]
+    traceparent: Option<String>,
+    #[serde(default, rename = "timeoutSeconds")]
+    timeout_seconds: Option<f64>,
+}
+
+/// Decode the validation payload.
+///
+/// Both flavors of the validation request are decoded here, the errors
+/// report the payload received from the host.
+fn decode_payload<'a, T: Deserialize<'a>>(payload: &'a [u8]) -> Result<T> {
+    serde_json::from_slice::<T>(payload).map_err(|e| {
+        SdkError::deserialization(
+            &format!("validation payload {}", String::from_utf8_lossy(payload)),
+            e,
+        )
+    })
+}
+
+/// Configure the evaluation using the fields of the validation payload set
+/// by the host, and the ones of the settings shared by all the policies.
+/// Malformed values are logged and ignored, the settings validation is in
+/// charge of rejecting them
+fn configure_evaluation(
+    settings: Option<&RawValue>,
+    traceparent: Option<&str>,
+    timeout_seconds: Option<f64>,
+) {
+    let mut errors = Vec::new();
+
+    // only the settings that are objects can hold the shared keys
+    let settings = settings
+        .map(RawValue::get)
+        .filter(|s| s.trim_start().starts_with('{'))
+        .and_then(|s| {
+            serde_json::from_str::<EnvelopeSettings>(s)
+                .map_err(|e| errors.push(format!("invalid settings: {}", e)))
+                .ok()
+        });
+    let (log_level, evaluation_timeout_seconds) = settings
+        .map(|s| (s.log_level, s.evaluation_timeout_seconds))
+        .unwrap_or_default();
+    let level = log_level.and_then(|l| {
+        crate::logging::parse_level(&l)
+            .map_err(|e| errors.push(e))
+            .ok()
+    });
+    let trace_parent = traceparent.and_then(|t| t.parse().map_err(|e| errors.push(e)).ok());
+
+    crate::logging::set_level(level);
+    crate::logging::reset_sampling();
+    crate::trace_context::set_current(trace_parent);
+    crate::host_capabilities::client::start_memoization();
+    crate::deadline::start(timeout_seconds, evaluation_timeout_seconds);
+
+    for error in errors {
+        crate::logging::warn(
+            "ignoring malformed evaluation setting",
+            &[("error", &error)],
+        );
+    }
+}
+
+impl<T> ValidationRequest<T>
+where
+    T: Default + DeserializeOwned,
+{
+    /// Crates a new `ValidationRequest` starting from the payload provided
+    /// to the policy at invocation time.
+    ///
+    /// The log level of the policy is configured using the `logLevel` key of
+    /// the settings, see [`logging`](crate::logging), and the trace context
+    /// supplied by the host is recorded, see [`trace_context`](crate::trace_context).
+    /// The [`deadline`](crate::deadline) of the evaluation is started.
+    /// The responses of the host capabilities reading data are memoized until
+    /// the evaluation is over, see [`clear_memoized_responses`](crate::host_capabilities::client::clear_memoized_responses).
+    pub fn new(payload: &[u8]) -> Result<Self> {
+        let payload = decode_payload::<Payload>(payload).inspect_err(|_| {
+            configure_evaluation(None, None, None);
+        })?;
+        configure_evaluation(
+            payload.settings,
+            payload.traceparent.as_deref(),
+            payload.timeout_seconds,
+        );
+        let settings = serde_json::from_str(payload.settings.map_or("null", RawValue::get))
+            .map_err(|e| SdkError::deserialization("the settings", e))?;
+        DRY_RUN.with(|dry_run| dry_run.set(payload.request.dry_run));
+
+        Ok(ValidationRequest {
+            settings,
+            request: payload.request,
+            traceparent: payload.traceparent,
+            timeout_seconds: payload.timeout_seconds,
+        })
+    }
+
+    /// Returns true when the request is a dry-run one, which means
+    /// modifications will definitely not be persisted
+    pub fn dry_run(&self) -> bool {
+        self.request.dry_run
+    }
+
+    #[cfg(feature = "cluster-context")]
+    /// Extract PodSpec from high level objects. This method can be used to evaluate high level objects instead of just Pods.
+    /// For example, it can be used to reject Deployments or StatefulSets that violate a policy instead of the Pods created by them.
+    /// Objects supported are: Deployment, ReplicaSet, StatefulSet, DaemonSet, ReplicationController, Job, CronJob, Pod
+    /// It returns an error if the object is not one of those. If it is a supported object it returns the PodSpec if present, otherwise returns None.
+    pub fn extract_pod_spec_from_object(&self) -> Result<Option<PodSpec>> {
+        let decode_error = |e| SdkError::deserialization(&self.request.kind.kind, e);
+        match self.request.kind.kind.as_str() {
+            Deployment::KIND => {
+                let deployment = self
+                    .request
+                    .object
+                    .decode::<Deployment>()
+                    .map_err(decode_error)?;
+                Ok(deployment.spec.and_then(|spec| spec.template.spec))
+            }
+            ReplicaSet::KIND => {
+                let replicaset = self
+                    .request
+                    .object
+                    .decode::<ReplicaSet>()
+                    .map_err(decode_error)?;
+                Ok(replicaset
+                    .spec
+                    .and_then(|spec| spec.template.and_then(|template| template.spec)))
+            }
+            StatefulSet::KIND => {
+                let statefulset = self
+                    .request
+                    .object
+                    .decode::<StatefulSet>()
+                    .map_err(decode_error)?;
+                Ok(statefulset.spec.and_then(|spec| spec.template.spec))
+            }
+            DaemonSet::KIND => {
+                let daemonset = self
+                    .request
+                    .object
+                    .decode::<DaemonSet>()
+                    .map_err(decode_error)?;
+                Ok(daemonset.spec.and_then(|spec| spec.template.spec))
+            }
+            ReplicationController::KIND => {
+                let replication_controller = self
+                    .request
+                    .object
+                    .decode::<ReplicationController>()
+                    .map_err(decode_error)?;
+                Ok(replication_controller
+                    .spec
+                    .and_then(|spec| spec.template.and_then(|template| template.spec)))
+            }
+            CronJob::KIND => {
+                let cronjob = self
+                    .request
+                    .object
+                    .decode::<CronJob>()
+                    .map_err(decode_error)?;
+                Ok(cronjob
+                    .spec
+                    .and_then(|spec| spec.job_template.spec.and_then(|spec| spec.template.spec)))
+            }
+            Job::KIND => {
+                let job = self.request.object.decode::<Job>().map_err(decode_error)?;
+                Ok(job.spec.and_then(|spec| spec.template.spec))
+            }
+            Pod::KIND => {
+                let pod = self.request.object.decode::<Pod>().map_err(decode_error)?;
+                Ok(pod.spec)
+            }
+            _ => Err(SdkError::unsupported_kind(
+                &self.request.kind.kind,
+                &[
+                    Deployment::KIND,
+                    ReplicaSet::KIND,
+                    StatefulSet::KIND,
+                    DaemonSet::KIND,
+                    ReplicationController::KIND,
+                    Job::KIND,
+                    CronJob::KIND,
+                    Pod::KIND,
+                ],
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "cluster-context")]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::apps::v1::{
+        DaemonSetSpec, DeploymentSpec, ReplicaSetSpec, StatefulSetSpec,
+    };
+    use k8s_openapi::api::batch::v1::{CronJobSpec, JobSpec, JobTemplateSpec};
+    use k8s_openapi::api::core::v1::{ConfigMap, PodTemplateSpec};
+
+    use serde::Serialize;
+
+    #[test]
+    fn test_extract_pod_spec_from_deployment() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let deployment = Deployment {
+            spec: Some(DeploymentSpec {
+                template: PodTemplateSpec {
+                    spec: Some(pod_spec.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(deployment, "Deployment");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_deployment_without_pod_spec() {
+        let deployment = Deployment {
+            spec: Some(DeploymentSpec {
+                template: PodTemplateSpec {
+                    spec: None,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(deployment, "Deployment");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            None
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_deployment_without_deployment_spec() {
+        let deployment = Deployment {
+            spec: None,
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(deployment, "Deployment");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            None
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_replicaset() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let replicaset = ReplicaSet {
+            spec: Some(ReplicaSetSpec {
+                template: Some(PodTemplateSpec {
+                    spec: Some(pod_spec.clone()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(replicaset, "ReplicaSet");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_cronjob() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let cronjob = CronJob {
+            spec: Some(CronJobSpec {
+                job_template: JobTemplateSpec {
+                    spec: Some(JobSpec {
+                        template: PodTemplateSpec {
+                            spec: Some(pod_spec.clone()),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(cronjob, "CronJob");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_job() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let job = Job {
+            spec: Some(JobSpec {
+                template: PodTemplateSpec {
+                    spec: Some(pod_spec.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(job, "Job");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_pod() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let pod = Pod {
+            spec: Some(pod_spec.clone()),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(pod, "Pod");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_object_statefulset() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let statefulset = StatefulSet {
+            spec: Some(StatefulSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(pod_spec.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(statefulset, "StatefulSet");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_object_daemonset() {
+        let pod_spec = PodSpec {
+            ..Default::default()
+        };
+        let daemonset = DaemonSet {
+            spec: Some(DaemonSetSpec {
+                template: PodTemplateSpec {
+                    spec: Some(pod_spec.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(daemonset, "DaemonSet");
+
+        assert_eq!(
+            validation_request.extract_pod_spec_from_object().unwrap(),
+            Some(pod_spec)
+        )
+    }
+
+    #[test]
+    fn test_scale_subresource() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(serde_json::json!({
+            "kind": {"group": "autoscaling", "version": "v1", "kind": "Scale"},
+            "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+            "subResource": "scale",
+            "operation": "UPDATE",
+            "object": {"spec": {"replicas": 10}},
+            "oldObject": {"spec": {"replicas": 2}}
+        }))
+        .unwrap();
+
+        assert_eq!(request.subresource(), Some("scale"));
+        assert!(request.is_scale_subresource());
+        assert!(!request.is_status_subresource());
+        let replicas = |scale: Option<Scale>| scale.unwrap().spec.unwrap().replicas;
+        assert_eq!(replicas(request.scale().unwrap()), Some(10));
+        assert_eq!(replicas(request.old_scale().unwrap()), Some(2));
+    }
+
+    #[test]
+    fn test_scale_of_other_subresource() {
+        let request = KubernetesAdmissionRequest {
+            sub_resource: "status".to_string(),
+            ..Default::default()
+        };
+
+        assert!(request.scale().unwrap().is_none());
+        assert!(request.old_scale().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_object_not_supported() {
+        let configmap = ConfigMap {
+            ..Default::default()
+        };
+        let validation_request = create_validation_request(configmap, "ConfigMap");
+
+        assert!(validation_request.extract_pod_spec_from_object().is_err())
+    }
+
+    #[test]
+    fn test_extract_pod_spec_from_object_invalid() {
+        let validation_request = create_validation_request("invalid", "Pod");
+
+        assert!(validation_request.extract_pod_spec_from_object().is_err())
+    }
+
+    fn create_validation_request<T: Serialize>(object: T, kind: &str) -> ValidationRequest<()> {
+        let value = serde_json::to_value(object).unwrap();
+        ValidationRequest::from_parts(
+            (),
+            KubernetesAdmissionRequest {
+                kind: GroupVersionKind {
+                    kind: kind.to_string(),
+                    ..Default::default()
+                },
+                object: value.into(),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod dry_run_tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload(dry_run: bool) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "settings": null,
+            "request": {
+                "dryRun": dry_run
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn side_effects_are_forbidden_during_dry_run() {
+        let request = ValidationRequest::<()>::new(&payload(true)).unwrap();
+
+        assert!(request.dry_run());
+        assert!(is_dry_run());
+        assert!(ensure_side_effects_allowed("emitting events").is_err());
+    }
+
+    #[test]
+    fn side_effects_are_allowed_outside_of_dry_run() {
+        let request = ValidationRequest::<()>::new(&payload(false)).unwrap();
+
+        assert!(!request.dry_run());
+        assert!(ensure_side_effects_allowed("emitting events").is_ok());
+    }
+}
+
+#[cfg(test)]
+mod gvk_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decode_kind_and_resource() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(json!({
+            "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+            "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+            "requestResource": {"group": "apps", "version": "v1beta1", "resource": "deployments"}
+        }))
+        .unwrap();
+
+        assert!(request.matches_kind(&"apps/Deployment".parse().unwrap()));
+        assert!(!request.matches_kind(&"*/Scale".parse().unwrap()));
+        assert!(request.matches_resource(&"apps/v1/deployments".parse().unwrap()));
+        assert_eq!(request.request_resource.version, "v1beta1");
+    }
+}
+
+#[cfg(test)]
+mod connect_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn connect_options_of_exec_request() {
+        let request: KubernetesAdmissionRequest = serde_json::from_value(json!({
+            "kind": {"group": "", "version": "v1", "kind": "PodExecOptions"},
+            "subResource": "exec",
+            "operation": "CONNECT",
+            "object": {"kind": "PodExecOptions", "command": ["id"]}
+        }))
+        .unwrap();
+
+        match request.connect_options().unwrap() {
+            Some(ConnectOptions::Exec(options)) => assert_eq!(options.command, vec!["id"]),
+            other => panic!("unexpected options: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn connect_options_of_non_connect_request() {
+        let request = KubernetesAdmissionRequest {
+            operation: "CREATE".to_string(),
+            ..Default::default()
+        };
+
+        assert!(request.connect_options().unwrap().is_none());
+    }
+}
+
+#[cfg(test)]
+mod configuration_tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn configure_the_evaluation_while_decoding() {
+        let payload = json!({
+            "settings": {"logLevel": "debug", "allowed": ["a"]},
+            "request": {"uid": "1"},
+            "traceparent": "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "timeoutSeconds": 2.5
+        });
+        let request =
+            ValidationRequest::<serde_json::Value>::new(payload.to_string().as_bytes()).unwrap();
+        assert_eq!(request.settings["allowed"], json!(["a"]));
+        assert_eq!(request.timeout_seconds(), Some(2.5));
+        assert_eq!(crate::logging::level(), Some(slog::Level::Debug));
+        assert!(crate::trace_context::current().is_some());
+
+        let payload = payload.to_string();
+        let request = borrowed::ValidationRequest::new(payload.as_bytes()).unwrap();
+        assert_eq!(request.timeout_seconds(), Some(2.5));
+        assert!(request.traceparent().is_some());
+    }
+
+    #[test]
+    fn malformed_settings_of_the_evaluation_are_ignored() {
+        let payload = json!({
+            "settings": {"logLevel": 3},
+            "request": {"uid": "1"},
+            "traceparent": "nope"
+        });
+        let request =
+            ValidationRequest::<serde_json::Value>::new(payload.to_string().as_bytes()).unwrap();
+        assert_eq!(request.settings["logLevel"], 3);
+        assert_eq!(crate::logging::level(), None);
+        assert!(crate::trace_context::current().is_none());
+
+        // settings that are not objects don't configure the evaluation
+        let payload = json!({"settings": ["debug"], "request": {"uid": "1"}});
+        let request = ValidationRequest::<Vec<String>>::new(payload.to_string().as_bytes());
+        assert_eq!(request.unwrap().settings, vec!["debug"]);
+    }
+}
@@ -0,0 +1,158 @@
+use super::KubernetesAdmissionRequest;
+use crate::error::{Result, SdkError};
+use serde::{Deserialize, Serialize};
+
+/// apiVersion of the `admission.k8s.io/v1` AdmissionReview objects
+pub const ADMISSION_V1: &str = "admission.k8s.io/v1";
+
+/// apiVersion of the `admission.k8s.io/v1beta1` AdmissionReview objects
+pub const ADMISSION_V1BETA1: &str = "admission.k8s.io/v1beta1";
+
+/// An AdmissionReview object, as sent by the API server to the admission
+/// webhooks, either `admission.k8s.io/v1` or `admission.k8s.io/v1beta1`.
+///
+/// The v1beta1 flavor is still produced by older conformance tools and is
+/// found inside of audit logs. Its requests can be converted into the v1
+/// ones evaluated by the policies:
+///
+/// ```
+/// use kubewarden_policy_sdk::request::AdmissionReview;
+///
+/// let review = br#"{
+///   "apiVersion": "admission.k8s.io/v1beta1",
+///   "kind": "AdmissionReview",
+///   "request": {
+///     "uid": "705ab4f5-6393-11e8-b7cc-42010a800002",
+///     "kind": {"group": "", "version": "v1", "kind": "Pod"},
+///     "resource": {"group": "", "version": "v1", "resource": "pods"},
+///     "operation": "CREATE",
+///     "object": {"metadata": {"name": "nginx"}}
+///   }
+/// }"#;
+///
+/// let request = AdmissionReview::parse(review).unwrap().into_request().unwrap();
+/// assert_eq!(request.request_kind.kind, "Pod");
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmissionReview {
+    pub api_version: String,
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<KubernetesAdmissionRequest>,
+}
+
+impl AdmissionReview {
+    /// Decode an AdmissionReview object, ensuring it's either a v1 or a
+    /// v1beta1 one
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        let review: AdmissionReview = serde_json::from_slice(raw)
+            .map_err(|e| SdkError::deserialization("the AdmissionReview", e))?;
+        if review.kind != "AdmissionReview" {
+            return Err(SdkError::unsupported_kind(
+                &review.kind,
+                &["AdmissionReview"],
+            ));
+        }
+        if review.api_version != ADMISSION_V1 && review.api_version != ADMISSION_V1BETA1 {
+            return Err(SdkError::InvalidInput(format!(
+                "unsupported AdmissionReview version {}, expected {} or {}",
+                review.api_version, ADMISSION_V1, ADMISSION_V1BETA1
+            )));
+        }
+        Ok(review)
+    }
+
+    /// Returns true for the `admission.k8s.io/v1beta1` objects
+    pub fn is_v1beta1(&self) -> bool {
+        self.api_version == ADMISSION_V1BETA1
+    }
+
+    /// The request of the review, in its v1 form.
+    ///
+    /// The fields that are optional in v1beta1, and always set in v1, are
+    /// filled in the way the API server does: the kind, resource and
+    /// subresource of the original request default to the ones of the
+    /// request.
+    pub fn into_request(self) -> Result<KubernetesAdmissionRequest> {
+        let mut request = self.request.ok_or_else(|| {
+            SdkError::InvalidInput("the AdmissionReview has no request".to_string())
+        })?;
+        if request.request_kind.kind.is_empty() {
+            request.request_kind = request.kind.clone();
+        }
+        if request.request_resource.resource.is_empty() {
+            request.request_resource = request.resource.clone();
+            request.request_sub_resource = request.sub_resource.clone();
+        }
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn review(api_version: &str, request: serde_json::Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "apiVersion": api_version,
+            "kind": "AdmissionReview",
+            "request": request
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn convert_v1beta1_requests() {
+        let raw = review(
+            ADMISSION_V1BETA1,
+            json!({
+                "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+                "subResource": "scale",
+                "operation": "UPDATE",
+                "userInfo": {"username": "alice"}
+            }),
+        );
+        let review = AdmissionReview::parse(&raw).unwrap();
+        assert!(review.is_v1beta1());
+
+        let request = review.into_request().unwrap();
+        assert_eq!(request.request_kind, request.kind);
+        assert_eq!(request.request_resource.resource, "deployments");
+        assert_eq!(request.request_sub_resource, "scale");
+        assert_eq!(request.user_info.username, "alice");
+    }
+
+    #[test]
+    fn v1_requests_are_unchanged() {
+        let raw = review(
+            ADMISSION_V1,
+            json!({
+                "kind": {"group": "apps", "version": "v1", "kind": "Deployment"},
+                "requestKind": {"group": "apps", "version": "v1beta1", "kind": "Deployment"},
+                "resource": {"group": "apps", "version": "v1", "resource": "deployments"},
+                "requestResource": {"group": "apps", "version": "v1beta1", "resource": "deployments"}
+            }),
+        );
+        let request = AdmissionReview::parse(&raw)
+            .unwrap()
+            .into_request()
+            .unwrap();
+        assert_eq!(request.request_kind.version, "v1beta1");
+        assert_eq!(request.request_resource.version, "v1beta1");
+    }
+
+    #[test]
+    fn invalid_reviews() {
+        assert!(AdmissionReview::parse(&review("admission.k8s.io/v2", json!({}))).is_err());
+        assert!(AdmissionReview::parse(br#"{"apiVersion": "v1", "kind": "Pod"}"#).is_err());
+        assert!(AdmissionReview::parse(
+            br#"{"apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview"}"#
+        )
+        .unwrap()
+        .into_request()
+        .is_err());
+    }
+}
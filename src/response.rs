@@ -3,7 +3,23 @@ use std::collections::HashMap;
 
 /// A ValidationResponse object holds the outcome of policy
 /// evaluation.
-#[derive(Deserialize, Serialize, Debug)]
+///
+/// Responses can be created using a fluent interface, starting from either
+/// [`ValidationResponse::accept`] or [`ValidationResponse::reject`]:
+///
+/// ```
+/// use kubewarden_policy_sdk::response::ValidationResponse;
+/// use serde_json::json;
+///
+/// fn validate(_payload: &[u8]) -> wapc_guest::CallResult {
+///     ValidationResponse::accept()
+///         .mutate(json!({"kind": "Pod"}))
+///         .warning("the image tag has been replaced by its digest")
+///         .audit_annotation("image-digest", "sha256:abc")
+///         .build()
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct ValidationResponse {
     /// True if the request has been accepted, false otherwise
     pub accepted: bool,
@@ -24,3 +40,198 @@ pub struct ValidationResponse {
     /// Warnings over 256 characters and large numbers of warnings may be truncated.
     pub warnings: Option<Vec<String>>,
 }
+
+impl ValidationResponse {
+    /// Start building a response that accepts the request
+    pub fn accept() -> ResponseBuilder {
+        ResponseBuilder::new(true)
+    }
+
+    /// Start building a response that rejects the request
+    /// # Arguments
+    /// * `message` -  message shown to the user
+    pub fn reject(message: impl Into<String>) -> ResponseBuilder {
+        ResponseBuilder::new(false).message(message)
+    }
+}
+
+/// Builder used to create [`ValidationResponse`] objects
+#[derive(Debug, Clone)]
+pub struct ResponseBuilder {
+    response: ValidationResponse,
+}
+
+impl ResponseBuilder {
+    fn new(accepted: bool) -> Self {
+        ResponseBuilder {
+            response: ValidationResponse {
+                accepted,
+                message: None,
+                code: None,
+                mutated_object: None,
+                audit_annotations: None,
+                warnings: None,
+            },
+        }
+    }
+
+    /// Set the message shown to the user
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.response.message = Some(message.into());
+        self
+    }
+
+    /// Set the code shown to the user
+    pub fn code(mut self, code: u16) -> Self {
+        self.response.code = Some(code);
+        self
+    }
+
+    /// Set the mutated object. This is ignored when the request is rejected
+    pub fn mutate(mut self, mutated_object: serde_json::Value) -> Self {
+        if self.response.accepted {
+            self.response.mutated_object = Some(mutated_object);
+        }
+        self
+    }
+
+    /// Add a warning message to be returned to the API client
+    pub fn warning(mut self, warning: impl Into<String>) -> Self {
+        self.response
+            .warnings
+            .get_or_insert_with(Vec::new)
+            .push(warning.into());
+        self
+    }
+
+    /// Add an audit annotation
+    pub fn audit_annotation(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.response
+            .audit_annotations
+            .get_or_insert_with(HashMap::new)
+            .insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns the [`ValidationResponse`] object
+    pub fn response(self) -> ValidationResponse {
+        self.response
+    }
+
+    /// Serialize the response, ready to be returned by the `validate` function
+    pub fn build(self) -> wapc_guest::CallResult {
+        serialize(&self.response)
+    }
+}
+
+/// Serialize the response, using the pre-serialized forms of the common
+/// responses when possible
+pub(crate) fn serialize(response: &ValidationResponse) -> wapc_guest::CallResult {
+    if response.mutated_object.is_some()
+        || response.audit_annotations.is_some()
+        || response.warnings.is_some()
+    {
+        return Ok(serde_json::to_vec(response)?);
+    }
+    match (response.accepted, &response.message, response.code) {
+        (true, None, None) => Ok(ACCEPTED.to_vec()),
+        (false, Some(message), code) => serialize_rejection(message, code),
+        _ => Ok(serde_json::to_vec(response)?),
+    }
+}
+
+/// The serialized form of a plain acceptance, the most common response.
+/// It is shared by all the plain acceptances instead of being serialized
+/// every time
+pub(crate) const ACCEPTED: &[u8] = br#"{"accepted":true,"message":null,"code":null,"mutated_object":null,"audit_annotations":null,"warnings":null}"#;
+
+const REJECTED_PREFIX: &[u8] = br#"{"accepted":false,"message":"#;
+const REJECTED_SUFFIX: &[u8] =
+    br#","mutated_object":null,"audit_annotations":null,"warnings":null}"#;
+
+/// Serialize a rejection carrying only a message and a code, without
+/// building the whole response
+fn serialize_rejection(message: &str, code: Option<u16>) -> wapc_guest::CallResult {
+    let mut raw = Vec::with_capacity(REJECTED_PREFIX.len() + message.len() + 64);
+    raw.extend_from_slice(REJECTED_PREFIX);
+    serde_json::to_writer(&mut raw, message)?;
+    raw.extend_from_slice(b",\"code\":");
+    match code {
+        Some(code) => raw.extend_from_slice(code.to_string().as_bytes()),
+        None => raw.extend_from_slice(b"null"),
+    }
+    raw.extend_from_slice(REJECTED_SUFFIX);
+    Ok(raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn build_accept_response_with_warnings_and_annotations() {
+        let response = ValidationResponse::accept()
+            .mutate(json!({"kind": "Pod"}))
+            .warning("warning 1")
+            .warning("warning 2")
+            .audit_annotation("key", "value")
+            .response();
+
+        assert!(response.accepted);
+        assert_eq!(response.mutated_object, Some(json!({"kind": "Pod"})));
+        assert_eq!(
+            response.warnings,
+            Some(vec!["warning 1".to_string(), "warning 2".to_string()])
+        );
+        assert_eq!(
+            response.audit_annotations.unwrap().get("key"),
+            Some(&"value".to_string())
+        );
+        assert!(response.message.is_none());
+    }
+
+    #[test]
+    fn rejected_response_never_mutates() {
+        let response = ValidationResponse::reject("not allowed")
+            .code(403)
+            .mutate(json!({"kind": "Pod"}))
+            .response();
+
+        assert!(!response.accepted);
+        assert_eq!(response.message, Some("not allowed".to_string()));
+        assert_eq!(response.code, Some(403));
+        assert!(response.mutated_object.is_none());
+    }
+
+    #[test]
+    fn build_serializes_response() {
+        let raw = ValidationResponse::accept().build().unwrap();
+        let response: ValidationResponse = serde_json::from_slice(&raw).unwrap();
+
+        assert_eq!(response, ValidationResponse::accept().response());
+    }
+
+    #[test]
+    fn pre_serialized_responses_match_serde() {
+        let responses = [
+            ValidationResponse::accept().response(),
+            ValidationResponse::accept().code(200).response(),
+            ValidationResponse::reject("not allowed").response(),
+            ValidationResponse::reject("\"quoted\" \\ ünicode\n")
+                .code(403)
+                .response(),
+            ValidationResponse::reject("with warning")
+                .warning("careful")
+                .response(),
+        ];
+        for response in responses {
+            assert_eq!(
+                serialize(&response).unwrap(),
+                serde_json::to_vec(&response).unwrap(),
+                "{:?}",
+                response
+            );
+        }
+    }
+}
@@ -0,0 +1,792 @@
+//! [SemVer 2](https://semver.org) versions and version ranges, shared by the
+//! policies enforcing minimum or maximum versions: chart versions, image
+//! tags, Kubernetes releases.
+//!
+//! [`Version`] parses SemVer versions, tolerating a leading `v` and missing
+//! numbers as Helm does. Image tags often carry the name of a variant, like
+//! `1.25.3-alpine`: [`Version::from_tag`] reads their numbers and the
+//! pre-release identifiers, like the ones of `1.25.3-rc.1`.
+//! [`VersionConstraint`] holds range expressions like `>=1.25 <1.28`, and
+//! [`ApiVersion`] orders the versions of the Kubernetes APIs, like `v1beta1`.
+//!
+//! ```
+//! use kubewarden_policy_sdk::semver::{ApiVersion, Version, VersionConstraint};
+//!
+//! let supported: VersionConstraint = ">=1.25 <1.28".parse().unwrap();
+//! assert!(supported.matches(&"v1.27.3+k3s1".parse().unwrap()));
+//!
+//! let tag = Version::from_tag("1.25.3-alpine").unwrap();
+//! assert!(supported.matches(&tag));
+//! assert!(Version::from_tag("latest").is_none());
+//!
+//! let v1: ApiVersion = "v1".parse().unwrap();
+//! assert!(v1 > "v1beta2".parse().unwrap());
+//! ```
+use crate::error::{Result, SdkError};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A [SemVer 2](https://semver.org) version, like the ones of the charts.
+///
+/// A leading `v` and missing minor or patch numbers are accepted, as Helm
+/// does: `v1.2` is parsed as `1.2.0`. Build metadata is kept, but it is not
+/// considered when comparing versions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    /// The pre-release identifiers, e.g. `["rc", "1"]` for `1.0.0-rc.1`
+    pub pre: Vec<String>,
+    /// The build metadata, e.g. `20240501` for `1.0.0+20240501`
+    pub build: Option<String>,
+}
+
+impl Version {
+    /// Create a release version
+    pub fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+            pre: Vec::new(),
+            build: None,
+        }
+    }
+
+    /// Whether this is a pre-release version
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// The version of an image tag, like `1.25`, `v1.25.3` or
+    /// `1.25.3-alpine`. The suffix naming the variant of the image is
+    /// ignored, while pre-release suffixes, like `-rc.1`, `-beta2` or `-1`,
+    /// are kept. Returns `None` for the tags not starting with a version,
+    /// like `latest`, and for the ambiguous ones, like `1.2.3.4` or `1.2foo`
+    pub fn from_tag(tag: &str) -> Option<Version> {
+        let tag = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+        let end = tag
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(tag.len());
+        let (core, suffix) = tag.split_at(end);
+        let parts: Vec<&str> = core.trim_end_matches('.').split('.').collect();
+        if parts.len() > 3 {
+            return None;
+        }
+
+        let mut numbers = [0u64; 3];
+        for (number, part) in numbers.iter_mut().zip(parts) {
+            *number = part.parse().ok()?;
+        }
+        let mut version = Version::new(numbers[0], numbers[1], numbers[2]);
+
+        let (identifiers, attached) = match suffix.strip_prefix(['-', '_']) {
+            Some(suffix) => (suffix, false),
+            None => (suffix, true),
+        };
+        let identifiers: Vec<&str> = identifiers
+            .split(['-', '_', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .collect();
+        if is_pre_release_identifier(identifiers[0]) {
+            version.pre = identifiers.into_iter().map(str::to_string).collect();
+        } else if attached && !suffix.is_empty() && !suffix.starts_with('+') {
+            // `1.2foo` is neither a variant nor a pre-release
+            return None;
+        }
+        Some(version)
+    }
+}
+
+/// Whether the first identifier of the suffix of an image tag marks a
+/// pre-release: a number, like the `1` of `1.0.0-1`, or a label like `rc1`
+fn is_pre_release_identifier(identifier: &str) -> bool {
+    const LABELS: &[&str] = &["alpha", "beta", "rc", "pre", "dev", "snapshot"];
+    if !identifier.is_empty() && identifier.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+    let identifier = identifier.to_ascii_lowercase();
+    LABELS.iter().any(|label| {
+        identifier
+            .strip_prefix(label)
+            .is_some_and(|rest| rest.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+impl FromStr for Version {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let partial = Partial::parse(s)?;
+        if partial.specified == 0 {
+            return Err(invalid_version(s, "wildcards are not allowed"));
+        }
+        Ok(partial.lower())
+    }
+}
+
+impl TryFrom<String> for Version {
+    type Error = SdkError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Version> for String {
+    fn from(version: Version) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if self.is_prerelease() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                // a pre-release comes before the release
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => compare_prerelease(&self.pre, &other.pre),
+            })
+    }
+}
+
+/// Numeric identifiers are compared numerically and come before the
+/// alphanumeric ones. A shorter list comes first when all the identifiers
+/// are equal
+fn compare_prerelease(a: &[String], b: &[String]) -> Ordering {
+    for (a, b) in a.iter().zip(b) {
+        let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn invalid_version(s: &str, reason: &str) -> SdkError {
+    SdkError::InvalidInput(format!("invalid version '{}': {}", s, reason))
+}
+
+/// A version where the trailing numbers can be missing, or be wildcards:
+/// `1.2`, `1.2.x` and `1.*` are all valid
+#[derive(Debug, Clone)]
+struct Partial {
+    version: Version,
+    /// How many numbers are given, from 0 (`*`) to 3
+    specified: usize,
+}
+
+impl Partial {
+    fn parse(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let trimmed = trimmed.strip_prefix(['v', 'V']).unwrap_or(trimmed);
+        let (rest, build) = match trimmed.split_once('+') {
+            Some((rest, build)) => (rest, Some(build.to_string())),
+            None => (trimmed, None),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, pre.split('.').map(str::to_string).collect::<Vec<_>>()),
+            None => (rest, Vec::new()),
+        };
+        if core.is_empty() {
+            return Err(invalid_version(s, "the version is empty"));
+        }
+
+        let mut numbers = [0u64; 3];
+        let mut specified = 0;
+        let mut wildcard = false;
+        for (i, part) in core.split('.').enumerate() {
+            if i == 3 {
+                return Err(invalid_version(s, "too many numbers"));
+            }
+            if matches!(part, "x" | "X" | "*") {
+                wildcard = true;
+                continue;
+            }
+            if wildcard {
+                return Err(invalid_version(s, "numbers cannot follow a wildcard"));
+            }
+            if part.len() > 1 && part.starts_with('0') {
+                return Err(invalid_version(s, "numbers cannot have leading zeros"));
+            }
+            numbers[i] = part
+                .parse()
+                .map_err(|_| invalid_version(s, &format!("'{}' is not a number", part)))?;
+            specified += 1;
+        }
+        if pre.iter().any(|id| id.is_empty()) || build.as_deref() == Some("") {
+            return Err(invalid_version(s, "empty identifier"));
+        }
+
+        Ok(Partial {
+            version: Version {
+                major: numbers[0],
+                minor: numbers[1],
+                patch: numbers[2],
+                pre,
+                build,
+            },
+            specified,
+        })
+    }
+
+    /// The lowest version matched by the partial version
+    fn lower(&self) -> Version {
+        self.version.clone()
+    }
+
+    /// The lowest version not matched by the partial version, `None` when
+    /// all the versions are matched
+    fn upper(&self) -> Option<Version> {
+        let v = &self.version;
+        match self.specified {
+            0 => None,
+            1 => Some(Version::new(v.major + 1, 0, 0)),
+            2 => Some(Version::new(v.major, v.minor + 1, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// A single comparison between versions, the building block of the
+/// constraints
+#[derive(Debug, Clone)]
+enum Comparator {
+    Eq(Version),
+    Ne(Version),
+    Gt(Version),
+    Ge(Version),
+    Lt(Version),
+    Le(Version),
+    /// Outside of the `[lower, upper)` range, the upper bound is open when
+    /// missing
+    NotIn(Version, Option<Version>),
+}
+
+impl Comparator {
+    fn matches(&self, version: &Version) -> bool {
+        match self {
+            Comparator::Eq(v) => version == v,
+            Comparator::Ne(v) => version != v,
+            Comparator::Gt(v) => version > v,
+            Comparator::Ge(v) => version >= v,
+            Comparator::Lt(v) => version < v,
+            Comparator::Le(v) => version <= v,
+            Comparator::NotIn(lower, upper) => {
+                version < lower || upper.as_ref().is_some_and(|upper| version >= upper)
+            }
+        }
+    }
+
+    fn version(&self) -> &Version {
+        match self {
+            Comparator::Eq(v)
+            | Comparator::Ne(v)
+            | Comparator::Gt(v)
+            | Comparator::Ge(v)
+            | Comparator::Lt(v)
+            | Comparator::Le(v)
+            | Comparator::NotIn(v, _) => v,
+        }
+    }
+}
+
+/// A version constraint, with the syntax used by the `version` field of the
+/// chart dependencies:
+///
+/// * comparisons: `=`, `!=`, `>`, `>=`, `<`, `<=`
+/// * wildcards and partial versions: `1.2.x`, `1.*`, `1.2` (same as `1.2.x`)
+/// * tilde ranges, allowing patch updates: `~1.2.3` is `>=1.2.3, <1.3.0`
+/// * caret ranges, allowing updates not changing the leftmost non-zero number:
+///   `^1.2.3` is `>=1.2.3, <2.0.0` and `^0.2.3` is `>=0.2.3, <0.3.0`
+/// * hyphen ranges: `1.2 - 1.4.5` is `>=1.2.0, <=1.4.5`
+///
+/// Comparisons separated by commas, or by spaces, must all be satisfied,
+/// groups separated by `||` are alternatives. Pre-release versions are
+/// matched only by the groups mentioning a pre-release version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VersionConstraint {
+    source: String,
+    groups: Vec<Vec<Comparator>>,
+}
+
+impl VersionConstraint {
+    /// Whether `version` satisfies the constraint
+    pub fn matches(&self, version: &Version) -> bool {
+        self.groups.iter().any(|group| {
+            (!version.is_prerelease() || group.iter().any(|c| c.version().is_prerelease()))
+                && group.iter().all(|c| c.matches(version))
+        })
+    }
+}
+
+impl FromStr for VersionConstraint {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let groups = s
+            .split("||")
+            .map(parse_group)
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| match e {
+                SdkError::InvalidInput(message) => SdkError::InvalidInput(format!(
+                    "invalid version constraint '{}': {}",
+                    s, message
+                )),
+                e => e,
+            })?;
+        Ok(VersionConstraint {
+            source: s.to_string(),
+            groups,
+        })
+    }
+}
+
+impl TryFrom<String> for VersionConstraint {
+    type Error = SdkError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<VersionConstraint> for String {
+    fn from(constraint: VersionConstraint) -> Self {
+        constraint.source
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl PartialEq for VersionConstraint {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+const OPERATORS: [&str; 10] = [">=", "=>", "<=", "=<", "!=", "~>", ">", "<", "=", "~"];
+
+fn parse_group(group: &str) -> Result<Vec<Comparator>> {
+    // glue the operators to their version: `>= 1.2` becomes `>=1.2`
+    let mut tokens: Vec<String> = Vec::new();
+    for token in group.split([',', ' ']).filter(|t| !t.is_empty()) {
+        match tokens.last_mut() {
+            Some(last) if OPERATORS.contains(&last.as_str()) || last == "^" => last.push_str(token),
+            _ => tokens.push(token.to_string()),
+        }
+    }
+    if tokens.is_empty() {
+        return Err(SdkError::InvalidInput("empty constraint".to_string()));
+    }
+
+    let mut comparators = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens.get(i + 1).map(String::as_str) == Some("-") {
+            let upper = tokens
+                .get(i + 2)
+                .ok_or_else(|| SdkError::InvalidInput("incomplete hyphen range".to_string()))?;
+            let lower = Partial::parse(&tokens[i])?;
+            let upper = Partial::parse(upper)?;
+            comparators.push(Comparator::Ge(lower.lower()));
+            comparators.push(match upper.upper() {
+                Some(bound) => Comparator::Lt(bound),
+                None if upper.specified == 0 => Comparator::Ge(Version::new(0, 0, 0)),
+                None => Comparator::Le(upper.lower()),
+            });
+            i += 3;
+            continue;
+        }
+        comparators.extend(parse_comparator(&tokens[i])?);
+        i += 1;
+    }
+    Ok(comparators)
+}
+
+fn parse_comparator(token: &str) -> Result<Vec<Comparator>> {
+    let (operator, version) = OPERATORS
+        .iter()
+        .chain(&["^"])
+        .find_map(|op| token.strip_prefix(op).map(|rest| (*op, rest)))
+        .unwrap_or(("=", token));
+    let partial = Partial::parse(version)?;
+    let lower = partial.lower();
+    let upper = partial.upper();
+    let exact = partial.specified == 3;
+    let any = || vec![Comparator::Ge(Version::new(0, 0, 0))];
+
+    Ok(match operator {
+        "=" if exact => vec![Comparator::Eq(lower)],
+        "=" => match upper {
+            Some(upper) => vec![Comparator::Ge(lower), Comparator::Lt(upper)],
+            None => any(),
+        },
+        "!=" if exact => vec![Comparator::Ne(lower)],
+        "!=" => vec![Comparator::NotIn(lower, upper)],
+        ">" if exact => vec![Comparator::Gt(lower)],
+        ">" => match upper {
+            Some(upper) => vec![Comparator::Ge(upper)],
+            None => vec![Comparator::NotIn(Version::new(0, 0, 0), None)],
+        },
+        ">=" | "=>" => vec![Comparator::Ge(lower)],
+        "<" => vec![Comparator::Lt(lower)],
+        "<=" | "=<" if exact => vec![Comparator::Le(lower)],
+        "<=" | "=<" => match upper {
+            Some(upper) => vec![Comparator::Lt(upper)],
+            None => any(),
+        },
+        "~" | "~>" => {
+            let v = &partial.version;
+            match partial.specified {
+                0 => any(),
+                1 => vec![
+                    Comparator::Ge(lower.clone()),
+                    Comparator::Lt(Version::new(v.major + 1, 0, 0)),
+                ],
+                _ => vec![
+                    Comparator::Ge(lower.clone()),
+                    Comparator::Lt(Version::new(v.major, v.minor + 1, 0)),
+                ],
+            }
+        }
+        _ => {
+            // caret
+            let v = &partial.version;
+            let upper = match partial.specified {
+                0 => return Ok(any()),
+                1 => Version::new(v.major + 1, 0, 0),
+                _ if v.major > 0 => Version::new(v.major + 1, 0, 0),
+                2 => Version::new(0, v.minor + 1, 0),
+                _ if v.minor > 0 => Version::new(0, v.minor + 1, 0),
+                _ => Version::new(0, 0, v.patch + 1),
+            };
+            vec![Comparator::Ge(lower), Comparator::Lt(upper)]
+        }
+    })
+}
+
+/// The stability level of a Kubernetes API version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stability {
+    Alpha,
+    Beta,
+    Stable,
+}
+
+/// The version of a Kubernetes API, like `v1`, `v2beta1` or `v1alpha3`.
+///
+/// Versions are ordered as Kubernetes does when picking the preferred
+/// version of a group: the stable versions come after the beta ones, which
+/// come after the alpha ones, then the higher numbers come last
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ApiVersion {
+    pub major: u64,
+    pub stability: Stability,
+    /// The number following the stability level, 0 for stable versions
+    pub minor: u64,
+}
+
+impl FromStr for ApiVersion {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            SdkError::InvalidInput(format!(
+                "invalid API version '{}': expected a version like v1, v1beta1 or v2alpha3",
+                s
+            ))
+        };
+        let number = |digits: &str| -> Result<u64> {
+            if digits.is_empty() || digits.starts_with('0') {
+                return Err(invalid());
+            }
+            digits.parse().map_err(|_| invalid())
+        };
+
+        let rest = s.strip_prefix('v').ok_or_else(invalid)?;
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let major = number(&rest[..end])?;
+        let (stability, minor) = match &rest[end..] {
+            "" => (Stability::Stable, 0),
+            level => {
+                if let Some(minor) = level.strip_prefix("alpha") {
+                    (Stability::Alpha, number(minor)?)
+                } else if let Some(minor) = level.strip_prefix("beta") {
+                    (Stability::Beta, number(minor)?)
+                } else {
+                    return Err(invalid());
+                }
+            }
+        };
+        Ok(ApiVersion {
+            major,
+            stability,
+            minor,
+        })
+    }
+}
+
+impl TryFrom<String> for ApiVersion {
+    type Error = SdkError;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<ApiVersion> for String {
+    fn from(version: ApiVersion) -> Self {
+        version.to_string()
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stability {
+            Stability::Alpha => write!(f, "v{}alpha{}", self.major, self.minor),
+            Stability::Beta => write!(f, "v{}beta{}", self.major, self.minor),
+            Stability::Stable => write!(f, "v{}", self.major),
+        }
+    }
+}
+
+impl PartialOrd for ApiVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ApiVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.stability, self.major, self.minor).cmp(&(other.stability, other.major, other.minor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    fn matches(constraint: &str, version: &str) -> bool {
+        constraint
+            .parse::<VersionConstraint>()
+            .unwrap()
+            .matches(&v(version))
+    }
+
+    #[test]
+    fn parse_and_compare_versions() {
+        assert_eq!(v("v1.2"), Version::new(1, 2, 0));
+        assert_eq!(v("1.2.3+build").to_string(), "1.2.3+build");
+        assert_eq!(v("1.2.3+build"), v("1.2.3"));
+        assert_eq!(v("1.0.0-rc.1").pre, ["rc", "1"]);
+
+        let mut versions = vec![
+            v("1.0.0"),
+            v("1.0.0-rc.1"),
+            v("1.0.0-beta.11"),
+            v("1.0.0-beta.2"),
+            v("1.0.0-beta"),
+            v("1.0.0-alpha.beta"),
+            v("1.0.0-alpha.1"),
+            v("1.0.0-alpha"),
+            v("0.9.10"),
+        ];
+        versions.sort();
+        let sorted: Vec<String> = versions.iter().map(Version::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "0.9.10",
+                "1.0.0-alpha",
+                "1.0.0-alpha.1",
+                "1.0.0-alpha.beta",
+                "1.0.0-beta",
+                "1.0.0-beta.2",
+                "1.0.0-beta.11",
+                "1.0.0-rc.1",
+                "1.0.0"
+            ]
+        );
+
+        for invalid in ["", "a.b.c", "1.2.3.4", "01.2.3", "1.2.3-", "*", "1.x.3"] {
+            assert!(invalid.parse::<Version>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(matches("1.2.3", "1.2.3"));
+        assert!(matches(">= 1.2.3, < 2", "1.9.0"));
+        assert!(!matches(">=1.2.3 <2", "2.0.0"));
+        assert!(matches(">1.2", "1.3.0"));
+        assert!(!matches(">1.2", "1.2.9"));
+        assert!(matches("<=1.2", "1.2.9"));
+        assert!(matches("!=1.2.3", "1.2.4"));
+        assert!(!matches("!=1.2.x", "1.2.4"));
+        assert!(matches("!=1.2.x", "1.3.0"));
+        assert!(matches("<1.0 || >=3.0", "3.1.0"));
+        assert!(!matches("<1.0 || >=3.0", "2.0.0"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(matches("1.2.x", "1.2.7"));
+        assert!(!matches("1.2.x", "1.3.0"));
+        assert!(matches("*", "42.0.0"));
+        assert!(matches("~1.2.3", "1.2.9"));
+        assert!(!matches("~1.2.3", "1.3.0"));
+        assert!(matches("~1", "1.9.0"));
+        assert!(matches("^1.2.3", "1.9.0"));
+        assert!(!matches("^1.2.3", "2.0.0"));
+        assert!(matches("^0.2.3", "0.2.9"));
+        assert!(!matches("^0.2.3", "0.3.0"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(matches("^0.0", "0.0.9"));
+        assert!(matches("1.2 - 1.4.5", "1.4.5"));
+        assert!(!matches("1.2 - 1.4.5", "1.4.6"));
+        assert!(matches("1.2 - 1.4", "1.4.9"));
+    }
+
+    #[test]
+    fn prereleases() {
+        assert!(!matches(">=1.0.0", "1.1.0-rc.1"));
+        assert!(matches(">=1.1.0-rc.0", "1.1.0-rc.1"));
+        assert!(!matches(">=1.1.0-rc.2", "1.1.0-rc.1"));
+        assert!(matches("^1.0.0-0 || 0.9.x", "1.0.0-beta"));
+    }
+
+    #[test]
+    fn image_tags() {
+        assert_eq!(Version::from_tag("1.25"), Some(Version::new(1, 25, 0)));
+        assert_eq!(Version::from_tag("v1.25.3"), Some(Version::new(1, 25, 3)));
+        assert_eq!(
+            Version::from_tag("1.25.3-alpine3.19"),
+            Some(Version::new(1, 25, 3))
+        );
+        assert_eq!(Version::from_tag("3-slim"), Some(Version::new(3, 0, 0)));
+        assert_eq!(
+            Version::from_tag("1.2.3-rc.1"),
+            Some("1.2.3-rc.1".parse().unwrap())
+        );
+        assert_eq!(
+            Version::from_tag("v2.0.0-beta2-alpine"),
+            Some("2.0.0-beta2".parse().unwrap())
+        );
+        assert_eq!(
+            Version::from_tag("1.2.3-1"),
+            Some("1.2.3-1".parse().unwrap())
+        );
+        assert!(Version::from_tag("1.2.3-rc.1").unwrap().is_prerelease());
+        for tag in [
+            "latest", "alpine", "", "v", ".1", "1..2", "1.2.3.4", "1.2foo",
+        ] {
+            assert_eq!(Version::from_tag(tag), None, "{}", tag);
+        }
+        assert!(matches(
+            ">=1.25 <1.28",
+            &Version::from_tag("1.27-bookworm").unwrap().to_string()
+        ));
+    }
+
+    #[test]
+    fn api_versions() {
+        let mut versions: Vec<ApiVersion> = [
+            "v1beta1",
+            "v2",
+            "v1alpha1",
+            "v1",
+            "v2beta1",
+            "v1beta2",
+            "v10alpha1",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        versions.sort();
+        let sorted: Vec<String> = versions.iter().map(ApiVersion::to_string).collect();
+        assert_eq!(
+            sorted,
+            [
+                "v1alpha1",
+                "v10alpha1",
+                "v1beta1",
+                "v1beta2",
+                "v2beta1",
+                "v1",
+                "v2"
+            ]
+        );
+
+        for invalid in [
+            "", "1", "v", "v0", "v01", "v1gamma1", "v1beta", "v1beta0", "apps/v1",
+        ] {
+            assert!(invalid.parse::<ApiVersion>().is_err(), "{}", invalid);
+        }
+        let version: ApiVersion = serde_json::from_str(r#""v2beta3""#).unwrap();
+        assert_eq!(version.stability, Stability::Beta);
+        assert_eq!(serde_json::to_string(&version).unwrap(), r#""v2beta3""#);
+    }
+
+    #[test]
+    fn invalid_constraints() {
+        for invalid in ["", ">=", "1.2 -", ">=a", "1 || "] {
+            assert!(invalid.parse::<VersionConstraint>().is_err(), "{}", invalid);
+        }
+        let constraint: VersionConstraint = serde_json::from_str(r#""^1.2""#).unwrap();
+        assert_eq!(serde_json::to_string(&constraint).unwrap(), r#""^1.2""#);
+        assert!(serde_json::from_str::<VersionConstraint>(r#""~x.1""#).is_err());
+    }
+}
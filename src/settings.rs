@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /// Trait that must be implemented by setting
 /// object
@@ -7,6 +8,13 @@ pub trait Validatable {
     fn validate(&self) -> Result<(), String>;
 }
 
+/// Policies that do not have settings can use `()` as settings type
+impl Validatable for () {
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
 /// A SettingsValidationResponse object holds the outcome of settings
 /// validation.
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -16,3 +24,117 @@ pub struct SettingsValidationResponse {
     /// Message shown to the user when the settings are not valid
     pub message: Option<String>,
 }
+
+/// Check, from the `build.rs` of a policy, that the default settings and
+/// the example settings files shipped with it are valid. The build fails
+/// instead of producing a policy that rejects its own defaults.
+///
+/// The files can be either JSON or YAML, relative paths are resolved from
+/// the root of the policy crate. Cargo is told to run the build script again
+/// when one of them changes.
+///
+/// The settings type must be reachable from the build script, for example
+/// by keeping it inside of its own module:
+///
+/// ```no_run
+/// // build.rs
+/// # mod settings {
+/// #     #[derive(serde::Deserialize, Default)]
+/// #     pub struct Settings {}
+/// #     impl kubewarden_policy_sdk::settings::Validatable for Settings {
+/// #         fn validate(&self) -> Result<(), String> { Ok(()) }
+/// #     }
+/// # }
+/// // #[path = "src/settings.rs"]
+/// // mod settings;
+///
+/// fn main() {
+///     kubewarden_policy_sdk::settings::check_settings_files::<settings::Settings>(&[
+///         "examples/settings.yml",
+///     ])
+///     .unwrap();
+/// }
+/// ```
+pub fn check_settings_files<T>(paths: &[&str]) -> anyhow::Result<()>
+where
+    T: Default + DeserializeOwned + Validatable,
+{
+    T::default()
+        .validate()
+        .map_err(|e| anyhow!("the default settings are not valid: {}", e))?;
+
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path);
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read the settings of {}: {}", path, e))?;
+        // JSON documents are valid YAML ones too
+        let settings: T = crate::yaml::decode_single(&contents)
+            .map_err(|e| anyhow!("cannot decode the settings of {}: {}", path, e))?;
+        settings
+            .validate()
+            .map_err(|e| anyhow!("the settings of {} are not valid: {}", path, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Default)]
+    #[serde(deny_unknown_fields)]
+    struct Settings {
+        #[serde(default)]
+        denied_namespaces: Vec<String>,
+    }
+
+    impl Validatable for Settings {
+        fn validate(&self) -> Result<(), String> {
+            if self.denied_namespaces.iter().any(|ns| ns.is_empty()) {
+                return Err("empty namespace".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Deserialize, Default)]
+    struct NoDefaults;
+
+    impl Validatable for NoDefaults {
+        fn validate(&self) -> Result<(), String> {
+            Err("a value must be provided".to_string())
+        }
+    }
+
+    #[test]
+    fn check_shipped_settings() {
+        let dir = std::env::temp_dir().join(format!("settings-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, contents).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let valid = file("valid.yml", "denied_namespaces: [kube-system]");
+        let invalid = file("invalid.json", r#"{"denied_namespaces": [""]}"#);
+        let undecodable = file("undecodable.yml", "allowed_namespaces: []");
+
+        assert!(check_settings_files::<Settings>(&[&valid]).is_ok());
+        assert!(check_settings_files::<Settings>(&[&valid, &invalid])
+            .unwrap_err()
+            .to_string()
+            .contains("empty namespace"));
+        assert!(check_settings_files::<Settings>(&[&undecodable]).is_err());
+        assert!(check_settings_files::<Settings>(&["missing.json"]).is_err());
+        assert_eq!(
+            check_settings_files::<NoDefaults>(&[])
+                .unwrap_err()
+                .to_string(),
+            "the default settings are not valid: a value must be provided"
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,410 @@
+//! SPIFFE IDs and X.509 SVIDs.
+//!
+//! Service meshes identify their workloads with [SPIFFE IDs](https://github.com/spiffe/spiffe/blob/main/standards/SPIFFE-ID.md),
+//! like `spiffe://example.org/ns/default/sa/web`, carried by the URI SAN of
+//! the [X.509 SVIDs](https://github.com/spiffe/spiffe/blob/main/standards/X509-SVID.md).
+//! These helpers validate the identities found inside of annotations and
+//! Secrets:
+//!
+//! ```
+//! use kubewarden_policy_sdk::spiffe::SpiffeId;
+//!
+//! let id: SpiffeId = "spiffe://example.org/ns/default/sa/web".parse().unwrap();
+//! assert!(id.is_member_of("example.org"));
+//! assert_eq!(id.path(), "/ns/default/sa/web");
+//!
+//! assert!("spiffe://example.org/ns/../sa".parse::<SpiffeId>().is_err());
+//! ```
+//!
+//! Only the content of the SVIDs is validated: their signature can be
+//! verified against the bundle of the trust domain with
+//! [`verify_cert`](crate::host_capabilities::crypto::verify_cert).
+use crate::error::{Result, SdkError};
+#[cfg(feature = "crypto")]
+use crate::host_capabilities::crypto::{Certificate, CertificateEncoding};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const SCHEME: &str = "spiffe://";
+
+/// The maximum length of a SPIFFE ID, in bytes
+const MAX_LENGTH: usize = 2048;
+
+/// A SPIFFE ID: a trust domain and the path of a workload inside of it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct SpiffeId {
+    trust_domain: String,
+    path: String,
+}
+
+impl SpiffeId {
+    /// The trust domain, e.g. `example.org`
+    pub fn trust_domain(&self) -> &str {
+        &self.trust_domain
+    }
+
+    /// The path of the workload, e.g. `/ns/default/sa/web`. It's empty for
+    /// the ID of the trust domain itself
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns true when the ID belongs to the given trust domain
+    pub fn is_member_of(&self, trust_domain: &str) -> bool {
+        self.trust_domain == trust_domain
+    }
+}
+
+impl FromStr for SpiffeId {
+    type Err = String;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        if id.len() > MAX_LENGTH {
+            return Err(format!(
+                "SPIFFE IDs cannot be longer than {} bytes",
+                MAX_LENGTH
+            ));
+        }
+        let rest = id
+            .strip_prefix(SCHEME)
+            .ok_or_else(|| format!("{} doesn't start with {}", id, SCHEME))?;
+        let (trust_domain, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, ""),
+        };
+
+        if trust_domain.is_empty() {
+            return Err(format!("{} has no trust domain", id));
+        }
+        if let Some(c) = trust_domain
+            .chars()
+            .find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '.' | '-' | '_'))
+        {
+            return Err(format!(
+                "{} has an invalid trust domain: {:?} is not allowed",
+                id, c
+            ));
+        }
+        for segment in path.split('/').skip(1) {
+            match segment {
+                "" => return Err(format!("{} has an empty path segment", id)),
+                "." | ".." => return Err(format!("{} has a relative path segment", id)),
+                _ => {}
+            }
+            if let Some(c) = segment
+                .chars()
+                .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+            {
+                return Err(format!(
+                    "{} has an invalid path: {:?} is not allowed",
+                    id, c
+                ));
+            }
+        }
+
+        Ok(SpiffeId {
+            trust_domain: trust_domain.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+impl TryFrom<String> for SpiffeId {
+    type Error = String;
+
+    fn try_from(id: String) -> Result<Self, Self::Error> {
+        id.parse()
+    }
+}
+
+impl From<SpiffeId> for String {
+    fn from(id: SpiffeId) -> Self {
+        id.to_string()
+    }
+}
+
+impl fmt::Display for SpiffeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", SCHEME, self.trust_domain, self.path)
+    }
+}
+
+/// The parts of an X.509 SVID relevant to the policies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X509Svid {
+    /// The SPIFFE ID of the URI SAN
+    pub spiffe_id: SpiffeId,
+    /// True for the signing certificates, false for the leaf ones
+    pub is_ca: bool,
+    /// The DER encoding of the certificate
+    pub der: Vec<u8>,
+}
+
+impl X509Svid {
+    /// Parse a DER encoded SVID. It must have exactly one URI SAN, holding
+    /// a valid SPIFFE ID
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let invalid = || SdkError::InvalidInput("the certificate is not valid DER".to_string());
+        let extensions = extensions(der).ok_or_else(invalid)?;
+
+        let mut uris = Vec::new();
+        let mut is_ca = false;
+        for (oid, value) in extensions {
+            match oid {
+                OID_SUBJECT_ALT_NAME => uris = uri_sans(value).ok_or_else(invalid)?,
+                OID_BASIC_CONSTRAINTS => is_ca = basic_constraints_ca(value).ok_or_else(invalid)?,
+                _ => {}
+            }
+        }
+
+        let uri = match uris.as_slice() {
+            [uri] => uri,
+            _ => {
+                return Err(SdkError::InvalidInput(format!(
+                    "an SVID must have exactly one URI SAN, found {}",
+                    uris.len()
+                )))
+            }
+        };
+        let spiffe_id = uri.parse().map_err(SdkError::InvalidInput)?;
+        Ok(X509Svid {
+            spiffe_id,
+            is_ca,
+            der: der.to_vec(),
+        })
+    }
+
+    /// Parse the first certificate of a PEM bundle, which is the leaf one
+    /// inside of the chains
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let begin = "-----BEGIN CERTIFICATE-----";
+        let end = "-----END CERTIFICATE-----";
+        let body = pem
+            .find(begin)
+            .map(|start| &pem[start + begin.len()..])
+            .and_then(|rest| rest.find(end).map(|stop| &rest[..stop]))
+            .ok_or_else(|| SdkError::InvalidInput("no PEM certificate found".to_string()))?;
+        let body: String = body.split_whitespace().collect();
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(body)
+            .map_err(|e| SdkError::InvalidInput(format!("invalid PEM certificate: {}", e)))?;
+        Self::from_der(&der)
+    }
+
+    /// Parse the SVID stored under `key` inside of a Secret, e.g. `tls.crt`
+    pub fn from_secret(secret: &serde_json::Value, key: &str) -> Result<Self> {
+        let data = secret["data"][key]
+            .as_str()
+            .ok_or_else(|| SdkError::InvalidInput(format!("the Secret has no {} key", key)))?;
+        let pem = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|e| SdkError::InvalidInput(format!("invalid Secret data: {}", e)))?;
+        Self::from_pem(&String::from_utf8_lossy(&pem))
+    }
+
+    /// Ensure the SVID is a leaf one, belonging to the given trust domain
+    pub fn validate(&self, trust_domain: &str) -> Result<()> {
+        if self.is_ca {
+            return Err(SdkError::InvalidInput(format!(
+                "{} is a signing certificate, not a leaf SVID",
+                self.spiffe_id
+            )));
+        }
+        if !self.spiffe_id.is_member_of(trust_domain) {
+            return Err(SdkError::InvalidInput(format!(
+                "{} doesn't belong to the trust domain {}",
+                self.spiffe_id, trust_domain
+            )));
+        }
+        Ok(())
+    }
+
+    /// The certificate, in the form used by
+    /// [`verify_cert`](crate::host_capabilities::crypto::verify_cert)
+    #[cfg(feature = "crypto")]
+    pub fn certificate(&self) -> Certificate {
+        Certificate {
+            encoding: CertificateEncoding::Der,
+            data: self.der.clone(),
+        }
+    }
+}
+
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_OID: u8 = 0x06;
+const TAG_OCTET_STRING: u8 = 0x04;
+/// `[3] EXPLICIT`, the extensions of the TBSCertificate
+const TAG_EXTENSIONS: u8 = 0xa3;
+/// `[6] IMPLICIT IA5String`, the uniformResourceIdentifier GeneralName
+const TAG_URI: u8 = 0x86;
+
+/// 2.5.29.17
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+/// 2.5.29.19
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+
+/// Split the first DER element of `input`, returning its tag, its content
+/// and the remaining bytes
+fn next_element(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let n = (first & 0x7f) as usize;
+        if n == 0 || n > 4 || rest.len() < n {
+            return None;
+        }
+        let len = rest[..n].iter().fold(0, |len, b| len << 8 | *b as usize);
+        (len, &rest[n..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+/// The elements of a constructed DER value
+fn elements(mut input: &[u8]) -> Option<Vec<(u8, &[u8])>> {
+    let mut elements = Vec::new();
+    while !input.is_empty() {
+        let (tag, content, rest) = next_element(input)?;
+        elements.push((tag, content));
+        input = rest;
+    }
+    Some(elements)
+}
+
+/// The content of a DER value having the expected tag
+fn expect(input: &[u8], expected: u8) -> Option<&[u8]> {
+    match next_element(input)? {
+        (tag, content, _) if tag == expected => Some(content),
+        _ => None,
+    }
+}
+
+/// The OID and the value of the extensions of a certificate
+fn extensions(der: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let certificate = expect(der, TAG_SEQUENCE)?;
+    let tbs = expect(certificate, TAG_SEQUENCE)?;
+    let Some((_, explicit)) = elements(tbs)?
+        .into_iter()
+        .find(|(tag, _)| *tag == TAG_EXTENSIONS)
+    else {
+        return Some(Vec::new());
+    };
+
+    elements(expect(explicit, TAG_SEQUENCE)?)?
+        .into_iter()
+        .map(|(tag, extension)| {
+            if tag != TAG_SEQUENCE {
+                return None;
+            }
+            // extnID, critical (optional), extnValue
+            let fields = elements(extension)?;
+            match (fields.first()?, fields.last()?) {
+                ((TAG_OID, oid), (TAG_OCTET_STRING, value)) => Some((*oid, *value)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn uri_sans(value: &[u8]) -> Option<Vec<String>> {
+    Some(
+        elements(expect(value, TAG_SEQUENCE)?)?
+            .into_iter()
+            .filter(|(tag, _)| *tag == TAG_URI)
+            .map(|(_, uri)| String::from_utf8_lossy(uri).into_owned())
+            .collect(),
+    )
+}
+
+/// The `cA` field of the basic constraints, which defaults to false
+fn basic_constraints_ca(value: &[u8]) -> Option<bool> {
+    let fields = elements(expect(value, TAG_SEQUENCE)?)?;
+    Some(matches!(fields.first(), Some((TAG_BOOLEAN, ca)) if ca.first().is_some_and(|b| *b != 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SVID: &str = include_str!("../test_data/spiffe/svid.pem");
+    const CA: &str = include_str!("../test_data/spiffe/ca.pem");
+
+    #[test]
+    fn parse_spiffe_ids() {
+        let id: SpiffeId = "spiffe://example.org/ns/default/sa/web".parse().unwrap();
+        assert_eq!(id.trust_domain(), "example.org");
+        assert_eq!(id.to_string(), "spiffe://example.org/ns/default/sa/web");
+
+        let id: SpiffeId = "spiffe://example.org".parse().unwrap();
+        assert_eq!(id.path(), "");
+
+        for invalid in [
+            "http://example.org/web",
+            "spiffe://",
+            "spiffe:///web",
+            "spiffe://Example.org/web",
+            "spiffe://example.org:8080/web",
+            "spiffe://example.org/",
+            "spiffe://example.org//web",
+            "spiffe://example.org/./web",
+            "spiffe://example.org/web?x=1",
+        ] {
+            assert!(invalid.parse::<SpiffeId>().is_err(), "{}", invalid);
+        }
+
+        let ids: Vec<SpiffeId> = serde_json::from_value(json!(["spiffe://a/b"])).unwrap();
+        assert_eq!(serde_json::to_value(&ids).unwrap(), json!(["spiffe://a/b"]));
+        assert!(serde_json::from_value::<SpiffeId>(json!("spiffe://a/b/")).is_err());
+    }
+
+    #[test]
+    fn parse_svids() {
+        let svid = X509Svid::from_pem(SVID).unwrap();
+        assert_eq!(
+            svid.spiffe_id.to_string(),
+            "spiffe://example.org/ns/default/sa/web"
+        );
+        assert!(!svid.is_ca);
+        assert!(svid.validate("example.org").is_ok());
+        assert!(svid.validate("example.com").is_err());
+        #[cfg(feature = "crypto")]
+        assert_eq!(svid.certificate().encoding, CertificateEncoding::Der);
+
+        // the chain starts with the leaf certificate
+        let chain = format!("{}{}", SVID, CA);
+        assert_eq!(X509Svid::from_pem(&chain).unwrap(), svid);
+
+        let ca = X509Svid::from_pem(CA).unwrap();
+        assert!(ca.is_ca);
+        assert!(ca.validate("example.org").is_err());
+    }
+
+    #[test]
+    fn parse_svids_from_secrets() {
+        let secret = json!({
+            "kind": "Secret",
+            "data": {"tls.crt": base64::engine::general_purpose::STANDARD.encode(SVID)}
+        });
+        let svid = X509Svid::from_secret(&secret, "tls.crt").unwrap();
+        assert!(svid.spiffe_id.is_member_of("example.org"));
+        assert!(X509Svid::from_secret(&secret, "ca.crt").is_err());
+    }
+
+    #[test]
+    fn invalid_certificates() {
+        assert!(X509Svid::from_pem("not a certificate").is_err());
+        assert!(X509Svid::from_der(&[0x30, 0x03, 0x02, 0x01]).is_err());
+
+        let svid = X509Svid::from_pem(SVID).unwrap();
+        assert!(X509Svid::from_der(&svid.der[..svid.der.len() / 2]).is_err());
+    }
+}
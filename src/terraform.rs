@@ -0,0 +1,433 @@
+//! Types of the [Terraform plan JSON format](https://developer.hashicorp.com/terraform/internals/json-format),
+//! produced by `terraform show -json <plan>`, meant to be used by raw policies
+//! gating infrastructure changes.
+//!
+//! Only the fields describing the changes are decoded: each entry of
+//! `resource_changes` is a [`ResourceChange`], holding the planned
+//! [`Action`]s together with the resource before and after the change.
+//! [`PlanRules`] can be part of the policy settings, and rejects the plans
+//! deleting or replacing protected resources:
+//!
+//! ```
+//! use kubewarden_policy_sdk::terraform::{Plan, PlanRules};
+//! use serde_json::json;
+//!
+//! let plan = Plan::parse(json!({
+//!     "format_version": "1.2",
+//!     "resource_changes": [{
+//!         "address": "aws_db_instance.main",
+//!         "mode": "managed",
+//!         "type": "aws_db_instance",
+//!         "name": "main",
+//!         "provider_name": "registry.terraform.io/hashicorp/aws",
+//!         "change": {"actions": ["delete", "create"], "before": {}, "after": {}}
+//!     }]
+//! }).to_string().as_bytes())
+//! .unwrap();
+//!
+//! let rules: PlanRules = serde_json::from_value(json!({
+//!     "protectedResources": ["aws_db_instance.*"]
+//! }))
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     rules.check(&plan),
+//!     vec!["aws_db_instance.main is protected and cannot be replaced"]
+//! );
+//! ```
+use crate::error::{Result, SdkError};
+use crate::glob;
+use crate::response::ValidationResponse;
+use crate::settings::Validatable;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The major version of the plan format supported by this module
+pub const FORMAT_MAJOR_VERSION: &str = "1";
+
+/// The plan produced by `terraform show -json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Plan {
+    pub format_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terraform_version: Option<String>,
+    /// The planned changes of the resources
+    pub resource_changes: Vec<ResourceChange>,
+    /// The changes detected outside of Terraform since the last run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resource_drift: Vec<ResourceChange>,
+    /// The planned changes of the root module outputs
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub output_changes: BTreeMap<String, Change>,
+    /// The values of the input variables
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, Variable>,
+    /// Whether the planning failed, the plan is incomplete in that case
+    pub errored: bool,
+}
+
+/// The value of an input variable
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Variable {
+    pub value: Value,
+}
+
+/// The planned change of a resource
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ResourceChange {
+    /// The absolute address, e.g. `module.db.aws_db_instance.main[0]`
+    pub address: String,
+    /// The address before a `moved` block took effect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_address: Option<String>,
+    /// The module containing the resource, `None` for the root module
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_address: Option<String>,
+    /// `managed` for resources, `data` for data sources
+    pub mode: String,
+    /// The resource type, e.g. `aws_db_instance`
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub name: String,
+    /// The `count` or `for_each` key of the instance
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<Value>,
+    /// The fully qualified provider, e.g. `registry.terraform.io/hashicorp/aws`
+    pub provider_name: String,
+    /// Set for the deposed objects of a `create_before_destroy` replacement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deposed: Option<String>,
+    pub change: Change,
+    /// Why the action has been chosen, e.g. `replace_because_tainted`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_reason: Option<String>,
+}
+
+impl ResourceChange {
+    /// Whether this is a managed resource, not a data source
+    pub fn is_managed(&self) -> bool {
+        self.mode == "managed"
+    }
+}
+
+/// An action planned on an object
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    NoOp,
+    Create,
+    Read,
+    Update,
+    Delete,
+}
+
+/// The change of an object, either a resource or an output
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Change {
+    /// The planned actions. A replacement is made of a `delete` and a
+    /// `create`, their order tells whether the new object is created first
+    pub actions: Vec<Action>,
+    /// The object before the change, `null` when it is created
+    pub before: Value,
+    /// The object after the change, `null` when it is deleted. Values known
+    /// only after the apply are missing, see `after_unknown`
+    pub after: Value,
+    /// Mirrors `after`, with `true` for the values known only after the apply
+    pub after_unknown: Value,
+    /// Mirrors `before`, with `true` for the sensitive values
+    pub before_sensitive: Value,
+    /// Mirrors `after`, with `true` for the sensitive values
+    pub after_sensitive: Value,
+    /// The paths of the attributes forcing the replacement
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub replace_paths: Vec<Value>,
+}
+
+impl Change {
+    /// Whether nothing changes
+    pub fn is_no_op(&self) -> bool {
+        self.actions.is_empty() || self.actions == [Action::NoOp]
+    }
+
+    /// Whether the object is created, replacements excluded
+    pub fn is_create(&self) -> bool {
+        self.actions == [Action::Create]
+    }
+
+    /// Whether the object is updated in place
+    pub fn is_update(&self) -> bool {
+        self.actions == [Action::Update]
+    }
+
+    /// Whether the object is deleted, replacements excluded
+    pub fn is_delete(&self) -> bool {
+        self.actions == [Action::Delete]
+    }
+
+    /// Whether the object is destroyed and created again
+    pub fn is_replace(&self) -> bool {
+        self.actions.len() == 2
+            && self.actions.contains(&Action::Delete)
+            && self.actions.contains(&Action::Create)
+    }
+
+    /// Whether the current object is destroyed, either deleted or replaced
+    pub fn destroys(&self) -> bool {
+        self.actions.contains(&Action::Delete)
+    }
+}
+
+impl Plan {
+    /// Parse the output of `terraform show -json`. Plans using an unsupported
+    /// major version of the format are rejected
+    pub fn parse(contents: &[u8]) -> Result<Self> {
+        let plan: Plan = serde_json::from_slice(contents)
+            .map_err(|e| SdkError::deserialization("the Terraform plan", e))?;
+        let major = plan.format_version.split('.').next().unwrap_or_default();
+        if major != FORMAT_MAJOR_VERSION {
+            return Err(SdkError::InvalidInput(format!(
+                "unsupported Terraform plan format version '{}'",
+                plan.format_version
+            )));
+        }
+        Ok(plan)
+    }
+
+    /// The changes of the managed resources, data sources and no-op changes
+    /// excluded
+    pub fn changes(&self) -> impl Iterator<Item = &ResourceChange> {
+        self.resource_changes
+            .iter()
+            .filter(|rc| rc.is_managed() && !rc.change.is_no_op())
+    }
+
+    /// The managed resources whose current object is destroyed, either
+    /// deleted or replaced
+    pub fn destroyed(&self) -> impl Iterator<Item = &ResourceChange> {
+        self.changes().filter(|rc| rc.change.destroys())
+    }
+}
+
+/// Rules about the changes allowed by a plan, usually part of the policy
+/// settings.
+///
+/// The patterns are matched against the address of the resources, where `*`
+/// matches any sequence of characters: `aws_db_instance.*` protects all the
+/// database instances of the root module, `*aws_db_instance.*` the ones of
+/// all the modules
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PlanRules {
+    /// The resources that cannot be deleted nor replaced
+    pub protected_resources: Vec<String>,
+    /// Reject all the deletions and replacements
+    pub deny_destroy: bool,
+    /// The providers the changed resources can belong to, e.g.
+    /// `registry.terraform.io/hashicorp/aws`. All the providers are allowed
+    /// when the list is empty
+    pub allowed_providers: Vec<String>,
+}
+
+impl Validatable for PlanRules {
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.protected_resources.iter().any(|p| p.trim().is_empty()) {
+            return Err("protectedResources cannot contain empty entries".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl PlanRules {
+    /// Check the plan against the rules. Returns all the violations, an
+    /// empty list means the plan is allowed. Plans that errored are always
+    /// rejected, since they don't list all the changes
+    pub fn check(&self, plan: &Plan) -> Vec<String> {
+        let mut violations = Vec::new();
+        if plan.errored {
+            violations.push("the plan is incomplete, planning failed".to_string());
+        }
+        for rc in plan.changes() {
+            if !self.allowed_providers.is_empty()
+                && !self.allowed_providers.contains(&rc.provider_name)
+            {
+                violations.push(format!(
+                    "{} uses the provider {}, which is not allowed",
+                    rc.address, rc.provider_name
+                ));
+            }
+            if !rc.change.destroys() {
+                continue;
+            }
+            let verb = if rc.change.is_replace() {
+                "replaced"
+            } else {
+                "deleted"
+            };
+            if self.is_protected(&rc.address) {
+                violations.push(format!(
+                    "{} is protected and cannot be {}",
+                    rc.address, verb
+                ));
+            } else if self.deny_destroy {
+                violations.push(format!("{} cannot be {}", rc.address, verb));
+            }
+        }
+        violations
+    }
+
+    /// Accept the plan when there are no violations, otherwise reject it
+    /// with a message listing all of them
+    pub fn evaluate(&self, plan: &Plan) -> ValidationResponse {
+        let violations = self.check(plan);
+        if violations.is_empty() {
+            ValidationResponse::accept().response()
+        } else {
+            ValidationResponse::reject(violations.join("; ")).response()
+        }
+    }
+
+    /// Whether the resource with the given address is protected
+    pub fn is_protected(&self, address: &str) -> bool {
+        self.protected_resources
+            .iter()
+            .any(|pattern| glob::matches(pattern, address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const AWS: &str = "registry.terraform.io/hashicorp/aws";
+
+    fn resource(address: &str, actions: Value) -> Value {
+        let (resource_type, name) = address
+            .rsplit_once('.')
+            .map(|(t, n)| (t.rsplit('.').next().unwrap(), n))
+            .unwrap();
+        json!({
+            "address": address,
+            "mode": "managed",
+            "type": resource_type,
+            "name": name,
+            "provider_name": AWS,
+            "change": {"actions": actions, "before": {"id": "1"}, "after": null}
+        })
+    }
+
+    fn plan() -> Plan {
+        Plan::parse(
+            json!({
+                "format_version": "1.2",
+                "terraform_version": "1.8.0",
+                "variables": {"region": {"value": "eu-west-1"}},
+                "resource_changes": [
+                    resource("aws_s3_bucket.logs", json!(["no-op"])),
+                    resource("aws_instance.web", json!(["update"])),
+                    resource("module.db.aws_db_instance.main", json!(["create", "delete"])),
+                    resource("aws_iam_role.legacy", json!(["delete"])),
+                    {
+                        "address": "data.aws_ami.ubuntu",
+                        "mode": "data",
+                        "type": "aws_ami",
+                        "name": "ubuntu",
+                        "provider_name": AWS,
+                        "change": {"actions": ["read"]}
+                    }
+                ],
+                "output_changes": {"url": {"actions": ["create"], "after_unknown": true}}
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_plans() {
+        let plan = plan();
+        assert_eq!(plan.terraform_version.as_deref(), Some("1.8.0"));
+        assert_eq!(plan.variables["region"].value, json!("eu-west-1"));
+        assert!(plan.output_changes["url"].is_create());
+
+        let changed: Vec<&str> = plan.changes().map(|rc| rc.address.as_str()).collect();
+        assert_eq!(
+            changed,
+            [
+                "aws_instance.web",
+                "module.db.aws_db_instance.main",
+                "aws_iam_role.legacy"
+            ]
+        );
+        let destroyed: Vec<&str> = plan.destroyed().map(|rc| rc.address.as_str()).collect();
+        assert_eq!(
+            destroyed,
+            ["module.db.aws_db_instance.main", "aws_iam_role.legacy"]
+        );
+
+        let replaced = &plan.resource_changes[2];
+        assert_eq!(replaced.resource_type, "aws_db_instance");
+        assert!(replaced.change.is_replace());
+        assert!(!replaced.change.is_delete());
+        assert!(plan.resource_changes[1].change.is_update());
+        assert!(plan.resource_changes[0].change.is_no_op());
+
+        assert!(Plan::parse(br#"{"format_version": "2.0"}"#).is_err());
+        assert!(Plan::parse(br#"{"format_version": "1.0", "resource_changes": {}}"#).is_err());
+    }
+
+    #[test]
+    fn protect_resources() {
+        let plan = plan();
+        let rules: PlanRules = serde_json::from_value(json!({
+            "protectedResources": ["*aws_db_instance.*"]
+        }))
+        .unwrap();
+        assert!(rules.validate().is_ok());
+        assert_eq!(
+            rules.check(&plan),
+            vec!["module.db.aws_db_instance.main is protected and cannot be replaced"]
+        );
+
+        let rules = PlanRules {
+            deny_destroy: true,
+            ..rules
+        };
+        let response = rules.evaluate(&plan);
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "module.db.aws_db_instance.main is protected and cannot be replaced; \
+             aws_iam_role.legacy cannot be deleted"
+        );
+        assert!(PlanRules::default().evaluate(&plan).accepted);
+    }
+
+    #[test]
+    fn restrict_providers_and_errored_plans() {
+        let mut plan = plan();
+        let rules = PlanRules {
+            allowed_providers: vec!["registry.terraform.io/hashicorp/google".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(rules.check(&plan).len(), 3);
+
+        plan.errored = true;
+        assert_eq!(
+            PlanRules::default().check(&plan),
+            vec!["the plan is incomplete, planning failed"]
+        );
+
+        let invalid = PlanRules {
+            protected_resources: vec![String::new()],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
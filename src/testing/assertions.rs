@@ -0,0 +1,139 @@
+use crate::response::ValidationResponse;
+use std::collections::BTreeMap;
+
+fn warnings(response: &ValidationResponse) -> &[String] {
+    response.warnings.as_deref().unwrap_or_default()
+}
+
+fn audit_annotations(response: &ValidationResponse) -> BTreeMap<&str, &str> {
+    response
+        .audit_annotations
+        .iter()
+        .flatten()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect()
+}
+
+/// Ensure the response carries exactly the given warnings, in the same order
+///
+/// # Panics
+///
+/// Panics when the warnings of the response are not the expected ones
+pub fn assert_warnings(response: &ValidationResponse, expected: &[&str]) {
+    let actual = warnings(response);
+    if actual != expected {
+        panic!(
+            "unexpected warnings\n  expected: {:?}\n    actual: {:?}",
+            expected, actual
+        );
+    }
+}
+
+/// Ensure the response carries no warning
+///
+/// # Panics
+///
+/// Panics when the response carries at least one warning
+pub fn assert_no_warnings(response: &ValidationResponse) {
+    assert_warnings(response, &[]);
+}
+
+/// Ensure one of the warnings of the response contains `expected`
+///
+/// # Panics
+///
+/// Panics when none of the warnings contains `expected`
+pub fn assert_warning_contains(response: &ValidationResponse, expected: &str) {
+    let actual = warnings(response);
+    if !actual.iter().any(|w| w.contains(expected)) {
+        panic!(
+            "no warning contains `{}`, the warnings are: {:?}",
+            expected, actual
+        );
+    }
+}
+
+/// Ensure the response carries exactly the given audit annotations
+///
+/// # Panics
+///
+/// Panics when the audit annotations of the response are not the expected ones
+pub fn assert_audit_annotations(response: &ValidationResponse, expected: &[(&str, &str)]) {
+    let actual = audit_annotations(response);
+    let expected: BTreeMap<&str, &str> = expected.iter().copied().collect();
+    if actual != expected {
+        panic!(
+            "unexpected audit annotations\n  expected: {:?}\n    actual: {:?}",
+            expected, actual
+        );
+    }
+}
+
+/// Ensure the response carries the audit annotation `key` set to `value`.
+/// Other audit annotations are ignored
+///
+/// # Panics
+///
+/// Panics when the audit annotation is missing or has a different value
+pub fn assert_audit_annotation(response: &ValidationResponse, key: &str, value: &str) {
+    let actual = audit_annotations(response);
+    match actual.get(key) {
+        Some(v) if *v == value => {}
+        Some(v) => panic!(
+            "audit annotation `{}` is set to `{}`, expected `{}`",
+            key, v, value
+        ),
+        None => panic!(
+            "audit annotation `{}` is missing, the audit annotations are: {:?}",
+            key, actual
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::catch_unwind;
+
+    fn response() -> ValidationResponse {
+        ValidationResponse::accept()
+            .warning("image uses the latest tag")
+            .warning("no resource limits")
+            .audit_annotation("policy", "warn-mode")
+            .audit_annotation("image", "nginx:latest")
+            .response()
+    }
+
+    #[test]
+    fn matching_assertions() {
+        let response = response();
+        assert_warnings(
+            &response,
+            &["image uses the latest tag", "no resource limits"],
+        );
+        assert_warning_contains(&response, "latest");
+        assert_audit_annotations(
+            &response,
+            &[("image", "nginx:latest"), ("policy", "warn-mode")],
+        );
+        assert_audit_annotation(&response, "policy", "warn-mode");
+
+        let empty = ValidationResponse::accept().response();
+        assert_no_warnings(&empty);
+        assert_audit_annotations(&empty, &[]);
+    }
+
+    #[test]
+    fn failing_assertions() {
+        let response = response();
+        assert!(catch_unwind(|| assert_warnings(&response, &["no resource limits"])).is_err());
+        assert!(catch_unwind(|| assert_no_warnings(&response)).is_err());
+        assert!(catch_unwind(|| assert_warning_contains(&response, "privileged")).is_err());
+        assert!(
+            catch_unwind(|| assert_audit_annotations(&response, &[("policy", "warn-mode")]))
+                .is_err()
+        );
+        assert!(catch_unwind(|| assert_audit_annotation(&response, "policy", "deny")).is_err());
+        assert!(catch_unwind(|| assert_audit_annotation(&response, "missing", "")).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Measure the latency and the allocations of policy evaluations.
+//!
+//! [`Bench`] invokes the `validate` function of a policy repeatedly over a
+//! corpus of fixtures, natively, and reports latency percentiles for each
+//! one of them. Allocations are reported when the [`CountingAllocator`] is
+//! installed as the global allocator of the benchmark binary.
+//!
+//! Evaluations run natively: the numbers are meant to detect regressions
+//! between two versions of a policy, not to predict the latency of the
+//! WebAssembly module inside of a Kubewarden host.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use kubewarden_policy_sdk::testing::bench::{Bench, CountingAllocator};
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator = CountingAllocator;
+//!
+//! fn validate(payload: &[u8]) -> wapc_guest::CallResult {
+//!     // the policy implementation
+//!     # kubewarden_policy_sdk::accept_request()
+//! }
+//!
+//! let report = Bench::new(validate)
+//!     .fixtures_dir("test_data")
+//!     .unwrap()
+//!     .iterations(1000)
+//!     .run()
+//!     .unwrap();
+//! println!("{}", report);
+//! ```
+use super::{load_request_fixture, GuestFn};
+use anyhow::anyhow;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// A global allocator that counts the allocations performed by the
+/// process, relying on the system allocator to perform them
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+fn allocation_counters() -> (u64, u64) {
+    (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// Statistics about the evaluations of a single fixture
+#[derive(Debug, Clone)]
+pub struct FixtureStats {
+    /// Name of the fixture
+    pub name: String,
+    /// Number of measured evaluations
+    pub iterations: usize,
+    /// Mean latency
+    pub mean: Duration,
+    /// Median latency
+    pub p50: Duration,
+    /// 99th percentile of the latency
+    pub p99: Duration,
+    /// Fastest evaluation
+    pub min: Duration,
+    /// Slowest evaluation
+    pub max: Duration,
+    /// Average number of allocations per evaluation, available only when
+    /// the [`CountingAllocator`] is installed
+    pub allocations: Option<f64>,
+    /// Average number of bytes allocated per evaluation, available only
+    /// when the [`CountingAllocator`] is installed
+    pub allocated_bytes: Option<f64>,
+}
+
+/// The outcome of a [`Bench`] run
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    /// The statistics of each fixture, in the order they have been added
+    pub fixtures: Vec<FixtureStats>,
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<40} {:>10} {:>10} {:>10} {:>10} {:>12}",
+            "fixture", "mean", "p50", "p99", "max", "allocs/call"
+        )?;
+        for stats in &self.fixtures {
+            writeln!(
+                f,
+                "{:<40} {:>10.1?} {:>10.1?} {:>10.1?} {:>10.1?} {:>12}",
+                stats.name,
+                stats.mean,
+                stats.p50,
+                stats.p99,
+                stats.max,
+                stats
+                    .allocations
+                    .map(|a| format!("{:.1}", a))
+                    .unwrap_or_else(|| "-".to_string())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the `validate` function of a policy over a corpus of fixtures
+#[derive(Debug, Clone)]
+pub struct Bench {
+    validate: GuestFn,
+    settings: Value,
+    fixtures: Vec<(String, Value)>,
+    iterations: usize,
+    warmup: usize,
+}
+
+impl Bench {
+    /// Create a benchmark of the given `validate` function, which by
+    /// default is invoked 100 times per fixture after 10 warm-up calls
+    pub fn new(validate: GuestFn) -> Self {
+        Bench {
+            validate,
+            settings: json!({}),
+            fixtures: Vec::new(),
+            iterations: 100,
+            warmup: 10,
+        }
+    }
+
+    /// The settings given to the policy
+    ///
+    /// # Panics
+    ///
+    /// Panics when the settings cannot be serialized to JSON
+    pub fn settings<S: Serialize + ?Sized>(mut self, settings: &S) -> Self {
+        self.settings = serde_json::to_value(settings).expect("cannot serialize settings");
+        self
+    }
+
+    /// Number of measured evaluations per fixture
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations.max(1);
+        self
+    }
+
+    /// Number of evaluations per fixture performed before measuring
+    pub fn warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Add a fixture file to the corpus. See [`load_request_fixture`]
+    pub fn fixture<P: AsRef<Path>>(mut self, path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let request = load_request_fixture(path)?;
+        self.fixtures.push((path.display().to_string(), request));
+        Ok(self)
+    }
+
+    /// Add all the JSON and YAML files of the given directory to the corpus
+    pub fn fixtures_dir<P: AsRef<Path>>(mut self, dir: P) -> anyhow::Result<Self> {
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir.as_ref())?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<_, _>>()?;
+        paths.retain(|p| {
+            p.is_file()
+                && p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| matches!(ext, "json" | "yaml" | "yml"))
+        });
+        paths.sort();
+
+        for path in paths {
+            self = self.fixture(path)?;
+        }
+        Ok(self)
+    }
+
+    /// Add an admission request to the corpus
+    pub fn request<R: Serialize + ?Sized>(mut self, name: &str, request: &R) -> Self {
+        let request = serde_json::to_value(request).expect("cannot serialize request");
+        self.fixtures.push((name.to_string(), request));
+        self
+    }
+
+    /// Evaluate all the fixtures. Fails when any of the evaluations fails
+    pub fn run(&self) -> anyhow::Result<BenchReport> {
+        if self.fixtures.is_empty() {
+            return Err(anyhow!("no fixture has been provided"));
+        }
+
+        let fixtures = self
+            .fixtures
+            .iter()
+            .map(|(name, request)| self.run_fixture(name, request))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(BenchReport { fixtures })
+    }
+
+    fn run_fixture(&self, name: &str, request: &Value) -> anyhow::Result<FixtureStats> {
+        let payload = serde_json::to_vec(&json!({
+            "settings": self.settings,
+            "request": request,
+        }))?;
+        let validate = |payload: &[u8]| {
+            (self.validate)(payload).map_err(|e| anyhow!("evaluation of {} failed: {}", name, e))
+        };
+
+        for _ in 0..self.warmup {
+            validate(&payload)?;
+        }
+
+        let mut latencies = Vec::with_capacity(self.iterations);
+        let (allocations_before, bytes_before) = allocation_counters();
+        for _ in 0..self.iterations {
+            let start = Instant::now();
+            let response = validate(&payload)?;
+            latencies.push(start.elapsed());
+            drop(response);
+        }
+        let (allocations_after, bytes_after) = allocation_counters();
+        // the measurement loop allocates too, the counters do not move only
+        // when the counting allocator is not installed
+        let counted = allocations_after > allocations_before;
+        let per_call = |delta: u64| counted.then(|| delta as f64 / self.iterations as f64);
+
+        latencies.sort();
+        let percentile = |p: usize| latencies[(latencies.len() - 1) * p / 100];
+        Ok(FixtureStats {
+            name: name.to_string(),
+            iterations: self.iterations,
+            mean: latencies.iter().sum::<Duration>() / self.iterations as u32,
+            p50: percentile(50),
+            p99: percentile(99),
+            min: latencies[0],
+            max: latencies[latencies.len() - 1],
+            allocations: per_call(allocations_after - allocations_before),
+            allocated_bytes: per_call(bytes_after - bytes_before),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(payload: &[u8]) -> wapc_guest::CallResult {
+        let request: Value = serde_json::from_slice(payload)?;
+        if request["request"]["namespace"] == "kube-system" {
+            crate::reject_request(None, None, None, None)
+        } else {
+            crate::accept_request()
+        }
+    }
+
+    fn failing(_payload: &[u8]) -> wapc_guest::CallResult {
+        Err("boom".into())
+    }
+
+    #[test]
+    fn bench_fixtures() {
+        let report = Bench::new(validate)
+            .fixtures_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/test_data"))
+            .unwrap()
+            .request("inline", &json!({"namespace": "default"}))
+            .iterations(20)
+            .warmup(2)
+            .run()
+            .unwrap();
+
+        let names: Vec<&str> = report
+            .fixtures
+            .iter()
+            .map(|s| s.name.rsplit('/').next().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["pod_creation.json", "pod_creation_review.yaml", "inline"]
+        );
+        for stats in &report.fixtures {
+            assert_eq!(stats.iterations, 20);
+            assert!(stats.min <= stats.p50 && stats.p50 <= stats.p99 && stats.p99 <= stats.max);
+            // the counting allocator is not installed by the test binary
+            assert!(stats.allocations.is_none());
+        }
+        assert_eq!(report.to_string().lines().count(), 4);
+    }
+
+    #[test]
+    fn bench_errors() {
+        assert!(Bench::new(validate).run().is_err());
+        let error = Bench::new(failing)
+            .request("inline", &json!({}))
+            .run()
+            .unwrap_err();
+        assert_eq!(error.to_string(), "evaluation of inline failed: boom");
+    }
+}
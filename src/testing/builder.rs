@@ -0,0 +1,296 @@
+use crate::request::{
+    GroupVersionKind, GroupVersionResource, KubernetesAdmissionRequest, UserInfo, ValidationRequest,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Builds realistic [`KubernetesAdmissionRequest`] objects for tests.
+///
+/// The kind, resource, name and namespace of the request are derived from
+/// the object given to [`AdmissionRequestBuilder::object`] (or to
+/// [`AdmissionRequestBuilder::old_object`] for `DELETE` requests), and can be
+/// overridden afterwards.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::testing::AdmissionRequestBuilder;
+/// use serde_json::json;
+///
+/// let pod = json!({
+///     "apiVersion": "v1",
+///     "kind": "Pod",
+///     "metadata": {"name": "nginx"},
+///     "spec": {"containers": [{"name": "nginx", "image": "nginx"}]}
+/// });
+///
+/// let request = AdmissionRequestBuilder::create()
+///     .namespace("default")
+///     .object(&pod)
+///     .build();
+/// assert_eq!(request.operation, "CREATE");
+/// assert_eq!(request.kind.kind, "Pod");
+/// assert_eq!(request.resource.resource, "pods");
+/// assert_eq!(request.name, "nginx");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AdmissionRequestBuilder {
+    request: KubernetesAdmissionRequest,
+}
+
+impl AdmissionRequestBuilder {
+    fn new(operation: &str) -> Self {
+        AdmissionRequestBuilder {
+            request: KubernetesAdmissionRequest {
+                uid: "705ab4f5-6393-11e8-b7cc-42010a800002".to_string(),
+                operation: operation.to_string(),
+                user_info: UserInfo {
+                    username: "kubernetes-admin".to_string(),
+                    groups: HashSet::from([
+                        "system:masters".to_string(),
+                        "system:authenticated".to_string(),
+                    ]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Start building a `CREATE` request
+    pub fn create() -> Self {
+        Self::new("CREATE")
+    }
+
+    /// Start building an `UPDATE` request, remember to provide both the
+    /// object and the old object
+    pub fn update() -> Self {
+        Self::new("UPDATE")
+    }
+
+    /// Start building a `DELETE` request, remember to provide the old object
+    pub fn delete() -> Self {
+        Self::new("DELETE")
+    }
+
+    /// Start building a `CONNECT` request
+    pub fn connect() -> Self {
+        Self::new("CONNECT")
+    }
+
+    /// Set the object of the request
+    ///
+    /// # Panics
+    ///
+    /// Panics when the object cannot be serialized to JSON
+    pub fn object<T: Serialize + ?Sized>(mut self, object: &T) -> Self {
+        let object = serde_json::to_value(object).expect("cannot serialize object");
+        self.fill_from_object(&object);
+        self.request.object = object.into();
+        self
+    }
+
+    /// Set the old object of the request
+    ///
+    /// # Panics
+    ///
+    /// Panics when the object cannot be serialized to JSON
+    pub fn old_object<T: Serialize + ?Sized>(mut self, old_object: &T) -> Self {
+        let old_object = serde_json::to_value(old_object).expect("cannot serialize old object");
+        if self.request.object.is_null() {
+            self.fill_from_object(&old_object);
+        }
+        self.request.old_object = old_object.into();
+        self
+    }
+
+    /// Set the namespace of the request. Overrides the one of the object
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.request.namespace = namespace.to_string();
+        self
+    }
+
+    /// Set the name of the request. Overrides the one of the object
+    pub fn name(mut self, name: &str) -> Self {
+        self.request.name = name.to_string();
+        self
+    }
+
+    /// Set the kind of the request. Overrides the one of the object
+    pub fn kind(mut self, kind: GroupVersionKind) -> Self {
+        self.request.request_kind = kind.clone();
+        self.request.kind = kind;
+        self
+    }
+
+    /// Set the resource of the request. Overrides the one derived from the
+    /// kind of the object, which relies on a naive pluralization
+    pub fn resource(mut self, resource: GroupVersionResource) -> Self {
+        self.request.request_resource = resource.clone();
+        self.request.resource = resource;
+        self
+    }
+
+    /// Set the subresource of the request, e.g. `status` or `scale`
+    pub fn sub_resource(mut self, sub_resource: &str) -> Self {
+        self.request.sub_resource = sub_resource.to_string();
+        self.request.request_sub_resource = sub_resource.to_string();
+        self
+    }
+
+    /// Set the UID of the request
+    pub fn uid(mut self, uid: &str) -> Self {
+        self.request.uid = uid.to_string();
+        self
+    }
+
+    /// Set the user performing the request
+    pub fn user(mut self, username: &str, groups: &[&str]) -> Self {
+        self.request.user_info = UserInfo {
+            username: username.to_string(),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            ..Default::default()
+        };
+        self
+    }
+
+    /// Mark the request as a dry-run one
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.request.dry_run = dry_run;
+        self
+    }
+
+    /// Returns the request
+    pub fn build(self) -> KubernetesAdmissionRequest {
+        self.request
+    }
+
+    /// Returns a [`ValidationRequest`] made of the request and of the given settings
+    pub fn build_validation_request<T: Default>(self, settings: T) -> ValidationRequest<T> {
+        ValidationRequest::from_parts(settings, self.request)
+    }
+
+    fn fill_from_object(&mut self, object: &Value) {
+        let api_version = object
+            .get("apiVersion")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let kind = object
+            .get("kind")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !kind.is_empty() {
+            let gvk = GroupVersionKind::from_api_version_and_kind(api_version, kind);
+            let gvr = GroupVersionResource::new(&gvk.group, &gvk.version, &pluralize(kind));
+            self.request.request_kind = gvk.clone();
+            self.request.kind = gvk;
+            self.request.request_resource = gvr.clone();
+            self.request.resource = gvr;
+        }
+
+        let metadata = object.get("metadata");
+        if let Some(name) = metadata.and_then(|m| m.get("name")).and_then(Value::as_str) {
+            self.request.name = name.to_string();
+        }
+        if let Some(namespace) = metadata
+            .and_then(|m| m.get("namespace"))
+            .and_then(Value::as_str)
+        {
+            self.request.namespace = namespace.to_string();
+        }
+    }
+}
+
+/// Naive conversion of a kind into the name of its resource, good enough for
+/// the built-in Kubernetes types
+fn pluralize(kind: &str) -> String {
+    let kind = kind.to_lowercase();
+    if kind.ends_with('s') || kind.ends_with("ch") || kind.ends_with('x') {
+        format!("{kind}es")
+    } else if let Some(stem) = kind
+        .strip_suffix('y')
+        .filter(|stem| !stem.ends_with(['a', 'e', 'i', 'o', 'u']))
+    {
+        format!("{stem}ies")
+    } else {
+        format!("{kind}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn deployment(replicas: u32) -> Value {
+        json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": {"name": "web", "namespace": "prod"},
+            "spec": {"replicas": replicas}
+        })
+    }
+
+    #[test]
+    fn update_request() {
+        let request = AdmissionRequestBuilder::update()
+            .object(&deployment(3))
+            .old_object(&deployment(1))
+            .build();
+
+        assert_eq!(request.operation, "UPDATE");
+        assert_eq!(
+            request.kind,
+            GroupVersionKind::new("apps", "v1", "Deployment")
+        );
+        assert_eq!(
+            request.resource,
+            GroupVersionResource::new("apps", "v1", "deployments")
+        );
+        assert_eq!(request.name, "web");
+        assert_eq!(request.namespace, "prod");
+        assert_eq!(request.object["spec"]["replicas"], 3);
+        assert_eq!(request.old_object["spec"]["replicas"], 1);
+    }
+
+    #[test]
+    fn delete_request_uses_old_object() {
+        let request = AdmissionRequestBuilder::delete()
+            .old_object(&deployment(1))
+            .namespace("staging")
+            .build();
+
+        assert_eq!(request.operation, "DELETE");
+        assert!(request.object.is_null());
+        assert_eq!(request.kind.kind, "Deployment");
+        assert_eq!(request.namespace, "staging");
+    }
+
+    #[test]
+    fn built_request_round_trips() {
+        let request = AdmissionRequestBuilder::create()
+            .object(&json!({"apiVersion": "networking.k8s.io/v1", "kind": "Ingress"}))
+            .user("alice", &["developers"])
+            .dry_run(true)
+            .build();
+        assert_eq!(request.resource.resource, "ingresses");
+
+        let decoded: KubernetesAdmissionRequest =
+            serde_json::from_value(serde_json::to_value(&request).unwrap()).unwrap();
+        assert_eq!(decoded.user_info.username, "alice");
+        assert!(decoded.dry_run);
+    }
+
+    #[test]
+    fn pluralize_kinds() {
+        for (kind, resource) in [
+            ("Pod", "pods"),
+            ("Ingress", "ingresses"),
+            ("NetworkPolicy", "networkpolicies"),
+            ("Gateway", "gateways"),
+        ] {
+            assert_eq!(pluralize(kind), resource);
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::host_capabilities::client::HostClient;
+use crate::host_capabilities::kubernetes::{
+    GetResourceRequest, ListAllResourcesRequest, ListResourcesByNamespaceRequest,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cell::RefCell;
+
+/// An in-memory Kubernetes cluster answering the `kubernetes` host
+/// capabilities, to write fast and deterministic unit tests of
+/// context-aware policies.
+///
+/// Label and field selectors are honored. Field selectors support the `=`,
+/// `==` and `!=` operators on any field of the objects, e.g.
+/// `metadata.name=nginx` or `spec.nodeName!=node-1`.
+///
+/// # Example
+///
+/// ```
+/// use k8s_openapi::api::core::v1::Namespace;
+/// use kubewarden_policy_sdk::host_capabilities::client::with_host_client;
+/// use kubewarden_policy_sdk::host_capabilities::kubernetes::{
+///     list_all_resources, ListAllResourcesRequest,
+/// };
+/// use kubewarden_policy_sdk::testing::FakeCluster;
+/// use serde_json::json;
+/// use std::rc::Rc;
+///
+/// let cluster = Rc::new(FakeCluster::new());
+/// cluster.insert_value(json!({
+///     "apiVersion": "v1",
+///     "kind": "Namespace",
+///     "metadata": {"name": "team-a", "labels": {"team": "a"}}
+/// }));
+///
+/// let namespaces = with_host_client(cluster.clone(), || {
+///     list_all_resources::<Namespace>(&ListAllResourcesRequest {
+///         api_version: "v1".to_string(),
+///         kind: "Namespace".to_string(),
+///         label_selector: Some("team in (a,b)".to_string()),
+///         field_selector: None,
+///     })
+/// })
+/// .unwrap();
+/// assert_eq!(namespaces.items.len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct FakeCluster {
+    objects: RefCell<Vec<Value>>,
+}
+
+impl FakeCluster {
+    /// Create an empty cluster
+    pub fn new() -> Self {
+        FakeCluster::default()
+    }
+
+    /// Store a typed resource inside of the given namespace. Cluster-wide
+    /// resources must use a `None` namespace. Objects with the same kind,
+    /// namespace and name are replaced.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the object cannot be serialized to JSON
+    pub fn insert<K>(&self, namespace: Option<&str>, object: &K)
+    where
+        K: k8s_openapi::Resource + Serialize,
+    {
+        let mut object = serde_json::to_value(object).expect("cannot serialize object");
+        object["apiVersion"] = json!(K::API_VERSION);
+        object["kind"] = json!(K::KIND);
+        if let Some(namespace) = namespace {
+            object["metadata"]["namespace"] = json!(namespace);
+        }
+        self.insert_value(object);
+    }
+
+    /// Store an untyped object, which must have the `apiVersion`, `kind` and
+    /// `metadata.name` fields. Objects with the same kind, namespace and
+    /// name are replaced.
+    pub fn insert_value(&self, object: Value) {
+        let mut objects = self.objects.borrow_mut();
+        objects.retain(|o| !same_object(o, &object));
+        objects.push(object);
+    }
+
+    /// Remove all the objects with the given kind, name and namespace
+    pub fn remove(&self, api_version: &str, kind: &str, namespace: Option<&str>, name: &str) {
+        self.objects.borrow_mut().retain(|o| {
+            !(type_matches(o, api_version, kind)
+                && o["metadata"]["name"] == name
+                && o["metadata"]["namespace"].as_str() == namespace)
+        });
+    }
+
+    /// Number of objects of the given kind, optionally restricted to a
+    /// namespace and to a label selector
+    pub fn count(
+        &self,
+        api_version: &str,
+        kind: &str,
+        namespace: Option<&str>,
+        label_selector: Option<&str>,
+    ) -> Result<usize, String> {
+        Ok(self
+            .list(api_version, kind, namespace, label_selector, None)?
+            .len())
+    }
+
+    fn list(
+        &self,
+        api_version: &str,
+        kind: &str,
+        namespace: Option<&str>,
+        label_selector: Option<&str>,
+        field_selector: Option<&str>,
+    ) -> Result<Vec<Value>, String> {
+        let label_selector = label_selector.map(LabelSelector::parse).transpose()?;
+        let field_selector = field_selector.map(FieldSelector::parse).transpose()?;
+
+        Ok(self
+            .objects
+            .borrow()
+            .iter()
+            .filter(|o| type_matches(o, api_version, kind))
+            .filter(|o| namespace.is_none_or(|ns| o["metadata"]["namespace"] == ns))
+            .filter(|o| label_selector.as_ref().is_none_or(|s| s.matches(o)))
+            .filter(|o| field_selector.as_ref().is_none_or(|s| s.matches(o)))
+            .cloned()
+            .collect())
+    }
+
+    fn list_response(
+        &self,
+        api_version: &str,
+        kind: &str,
+        namespace: Option<&str>,
+        label_selector: Option<&str>,
+        field_selector: Option<&str>,
+    ) -> wapc_guest::CallResult {
+        let items = self.list(api_version, kind, namespace, label_selector, field_selector)?;
+        Ok(serde_json::to_vec(&json!({
+            "apiVersion": api_version,
+            "kind": format!("{}List", kind),
+            "metadata": {},
+            "items": items,
+        }))?)
+    }
+}
+
+fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(payload).map_err(|e| format!("invalid request: {}", e))
+}
+
+impl HostClient for FakeCluster {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        if namespace != "kubernetes" {
+            return Err(format!("unknown namespace {}.{}", namespace, operation).into());
+        }
+
+        match operation {
+            "list_resources_by_namespace" => {
+                let req: ListResourcesByNamespaceRequest = decode(payload)?;
+                self.list_response(
+                    &req.api_version,
+                    &req.kind,
+                    Some(&req.namespace),
+                    req.label_selector.as_deref(),
+                    req.field_selector.as_deref(),
+                )
+            }
+            "list_resources_all" => {
+                let req: ListAllResourcesRequest = decode(payload)?;
+                self.list_response(
+                    &req.api_version,
+                    &req.kind,
+                    None,
+                    req.label_selector.as_deref(),
+                    req.field_selector.as_deref(),
+                )
+            }
+            "get_resource" => {
+                let req: GetResourceRequest = decode(payload)?;
+                let objects = self.objects.borrow();
+                let object = objects
+                    .iter()
+                    .find(|o| {
+                        type_matches(o, &req.api_version, &req.kind)
+                            && o["metadata"]["name"] == req.name.as_str()
+                            && o["metadata"]["namespace"].as_str() == req.namespace.as_deref()
+                    })
+                    .ok_or_else(|| format!("{} \"{}\" not found", req.kind, req.name))?;
+                Ok(serde_json::to_vec(object)?)
+            }
+            _ => Err(format!("unknown operation {}.{}", namespace, operation).into()),
+        }
+    }
+}
+
+fn type_matches(object: &Value, api_version: &str, kind: &str) -> bool {
+    object["apiVersion"] == api_version && object["kind"] == kind
+}
+
+fn same_object(a: &Value, b: &Value) -> bool {
+    a["apiVersion"] == b["apiVersion"]
+        && a["kind"] == b["kind"]
+        && a["metadata"]["name"] == b["metadata"]["name"]
+        && a["metadata"]["namespace"] == b["metadata"]["namespace"]
+}
+
+#[derive(Debug)]
+enum Requirement {
+    Equals(String, String),
+    NotEquals(String, String),
+    In(String, Vec<String>),
+    NotIn(String, Vec<String>),
+    Exists(String),
+    DoesNotExist(String),
+}
+
+/// A label selector, using the syntax of `kubectl get -l`
+#[derive(Debug)]
+struct LabelSelector(Vec<Requirement>);
+
+/// Split a selector on the commas that are not inside of a set of values
+fn split_requirements(selector: &str) -> Vec<&str> {
+    let mut requirements = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                requirements.push(&selector[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    requirements.push(&selector[start..]);
+    requirements
+        .into_iter()
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+fn parse_values(values: &str) -> Result<Vec<String>, String> {
+    let values = values
+        .trim()
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .ok_or_else(|| format!("invalid set of values `{}`", values))?;
+    Ok(values.split(',').map(|v| v.trim().to_string()).collect())
+}
+
+impl LabelSelector {
+    fn parse(selector: &str) -> Result<Self, String> {
+        split_requirements(selector)
+            .into_iter()
+            .map(|requirement| {
+                if let Some(key) = requirement.strip_prefix('!') {
+                    return Ok(Requirement::DoesNotExist(key.trim().to_string()));
+                }
+                if let Some((key, value)) = requirement.split_once("!=") {
+                    return Ok(Requirement::NotEquals(
+                        key.trim().to_string(),
+                        value.trim().to_string(),
+                    ));
+                }
+                if let Some((key, value)) = requirement
+                    .split_once("==")
+                    .or_else(|| requirement.split_once('='))
+                {
+                    return Ok(Requirement::Equals(
+                        key.trim().to_string(),
+                        value.trim().to_string(),
+                    ));
+                }
+                if let Some((key, values)) = requirement.split_once(" notin ") {
+                    return Ok(Requirement::NotIn(
+                        key.trim().to_string(),
+                        parse_values(values)?,
+                    ));
+                }
+                if let Some((key, values)) = requirement.split_once(" in ") {
+                    return Ok(Requirement::In(
+                        key.trim().to_string(),
+                        parse_values(values)?,
+                    ));
+                }
+                if requirement.contains(char::is_whitespace) {
+                    return Err(format!("invalid label selector `{}`", requirement));
+                }
+                Ok(Requirement::Exists(requirement.to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(LabelSelector)
+    }
+
+    fn matches(&self, object: &Value) -> bool {
+        let labels = &object["metadata"]["labels"];
+        let label = |key: &str| labels.get(key).and_then(Value::as_str);
+        self.0.iter().all(|requirement| match requirement {
+            Requirement::Equals(key, value) => label(key) == Some(value),
+            Requirement::NotEquals(key, value) => label(key) != Some(value),
+            Requirement::In(key, values) => {
+                label(key).is_some_and(|l| values.iter().any(|v| v == l))
+            }
+            Requirement::NotIn(key, values) => {
+                label(key).is_none_or(|l| values.iter().all(|v| v != l))
+            }
+            Requirement::Exists(key) => label(key).is_some(),
+            Requirement::DoesNotExist(key) => label(key).is_none(),
+        })
+    }
+}
+
+/// A field selector, like `metadata.name=nginx,spec.nodeName!=node-1`
+#[derive(Debug)]
+struct FieldSelector(Vec<(String, String, bool)>);
+
+impl FieldSelector {
+    fn parse(selector: &str) -> Result<Self, String> {
+        split_requirements(selector)
+            .into_iter()
+            .map(|requirement| {
+                if let Some((field, value)) = requirement.split_once("!=") {
+                    Ok((field.trim().to_string(), value.trim().to_string(), false))
+                } else if let Some((field, value)) = requirement
+                    .split_once("==")
+                    .or_else(|| requirement.split_once('='))
+                {
+                    Ok((field.trim().to_string(), value.trim().to_string(), true))
+                } else {
+                    Err(format!("invalid field selector `{}`", requirement))
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(FieldSelector)
+    }
+
+    fn matches(&self, object: &Value) -> bool {
+        self.0.iter().all(|(field, value, equal)| {
+            let current = field
+                .split('.')
+                .try_fold(object, |value, key| value.get(key))
+                .map(|v| match v {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .unwrap_or_default();
+            (current == *value) == *equal
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::with_host_client;
+    use crate::host_capabilities::kubernetes::{get_resource, list_resources_by_namespace};
+    use k8s_openapi::api::core::v1::Pod;
+    use std::rc::Rc;
+
+    fn pod(name: &str, labels: Value, node: &str) -> Pod {
+        serde_json::from_value(json!({
+            "metadata": {"name": name, "labels": labels},
+            "spec": {"nodeName": node, "containers": []}
+        }))
+        .unwrap()
+    }
+
+    fn list(namespace: &str, labels: Option<&str>, fields: Option<&str>) -> Vec<String> {
+        list_resources_by_namespace::<Pod>(&ListResourcesByNamespaceRequest {
+            api_version: "v1".to_string(),
+            kind: "Pod".to_string(),
+            namespace: namespace.to_string(),
+            label_selector: labels.map(str::to_string),
+            field_selector: fields.map(str::to_string),
+        })
+        .unwrap()
+        .items
+        .into_iter()
+        .map(|p| p.metadata.name.unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn list_and_get_resources() {
+        let cluster = Rc::new(FakeCluster::new());
+        cluster.insert(
+            Some("default"),
+            &pod("a", json!({"app": "web", "tier": "1"}), "node-1"),
+        );
+        cluster.insert(Some("default"), &pod("b", json!({"app": "db"}), "node-2"));
+        cluster.insert(Some("default"), &pod("c", json!({}), "node-1"));
+        cluster.insert(Some("other"), &pod("a", json!({"app": "web"}), "node-1"));
+
+        with_host_client(cluster.clone(), || {
+            assert_eq!(list("default", None, None), vec!["a", "b", "c"]);
+            assert_eq!(list("default", Some("app=web"), None), vec!["a"]);
+            assert_eq!(
+                list("default", Some("app in (web, db),!tier"), None),
+                vec!["b"]
+            );
+            assert_eq!(
+                list("default", Some("app notin (web)"), None),
+                vec!["b", "c"]
+            );
+            assert_eq!(list("default", Some("app"), None), vec!["a", "b"]);
+            assert_eq!(
+                list(
+                    "default",
+                    None,
+                    Some("spec.nodeName=node-1,metadata.name!=c")
+                ),
+                vec!["a"]
+            );
+
+            let pod: Pod = get_resource(&GetResourceRequest {
+                api_version: "v1".to_string(),
+                kind: "Pod".to_string(),
+                name: "a".to_string(),
+                namespace: Some("other".to_string()),
+                disable_cache: false,
+            })
+            .unwrap();
+            assert_eq!(pod.metadata.namespace.as_deref(), Some("other"));
+
+            assert!(get_resource::<Pod>(&GetResourceRequest {
+                api_version: "v1".to_string(),
+                kind: "Pod".to_string(),
+                name: "missing".to_string(),
+                namespace: Some("default".to_string()),
+                disable_cache: false,
+            })
+            .is_err());
+        });
+
+        assert_eq!(cluster.count("v1", "Pod", None, None).unwrap(), 4);
+        assert_eq!(
+            cluster
+                .count("v1", "Pod", Some("default"), Some("app"))
+                .unwrap(),
+            2
+        );
+        cluster.remove("v1", "Pod", Some("default"), "a");
+        cluster.insert(Some("default"), &pod("b", json!({}), "node-3"));
+        assert_eq!(
+            cluster.count("v1", "Pod", Some("default"), None).unwrap(),
+            2
+        );
+        assert!(cluster.count("v1", "Pod", None, Some("a in b")).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+use crate::host_capabilities::client::HostClient;
+use crate::host_capabilities::net::LookupResponse;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// The outcome of a DNS lookup performed against a [`FakeResolver`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Lookup {
+    /// The host resolves to the given addresses
+    Resolved(Vec<String>),
+    /// The host does not exist
+    NxDomain,
+    /// The DNS server did not answer in time
+    Timeout,
+}
+
+/// A fake DNS resolver, answering the `net.v1/dns_lookup_host` host
+/// capability with the outcome stubbed for each hostname.
+///
+/// Hostnames that have not been stubbed do not exist. Stubs for wildcard
+/// names, like `*.example.com`, match all the subdomains not stubbed
+/// explicitly.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::host_capabilities::client::with_host_client;
+/// use kubewarden_policy_sdk::host_capabilities::net::lookup_host;
+/// use kubewarden_policy_sdk::testing::FakeResolver;
+/// use std::rc::Rc;
+///
+/// let resolver = Rc::new(
+///     FakeResolver::new()
+///         .resolves("registry.local", &["10.0.0.1"])
+///         .timeout("slow.example.com"),
+/// );
+///
+/// with_host_client(resolver.clone(), || {
+///     assert_eq!(lookup_host("registry.local").unwrap().ips, vec!["10.0.0.1"]);
+///     assert!(lookup_host("slow.example.com").is_err());
+///     assert!(lookup_host("missing.example.com").is_err());
+/// });
+/// assert_eq!(resolver.lookups(), vec!["registry.local", "slow.example.com", "missing.example.com"]);
+/// ```
+#[derive(Debug, Default)]
+pub struct FakeResolver {
+    hosts: HashMap<String, Lookup>,
+    lookups: RefCell<Vec<String>>,
+}
+
+impl FakeResolver {
+    /// Create a resolver where no hostname exists
+    pub fn new() -> Self {
+        FakeResolver::default()
+    }
+
+    /// Stub the outcome of the lookups of `host`
+    pub fn stub(mut self, host: &str, lookup: Lookup) -> Self {
+        self.hosts.insert(host.to_lowercase(), lookup);
+        self
+    }
+
+    /// Make `host` resolve to the given addresses
+    pub fn resolves(self, host: &str, ips: &[&str]) -> Self {
+        let ips = ips.iter().map(|ip| ip.to_string()).collect();
+        self.stub(host, Lookup::Resolved(ips))
+    }
+
+    /// Make `host` not exist. Useful to override a wildcard stub
+    pub fn nxdomain(self, host: &str) -> Self {
+        self.stub(host, Lookup::NxDomain)
+    }
+
+    /// Make the lookups of `host` time out
+    pub fn timeout(self, host: &str) -> Self {
+        self.stub(host, Lookup::Timeout)
+    }
+
+    /// The hostnames looked up so far, in order
+    pub fn lookups(&self) -> Vec<String> {
+        self.lookups.borrow().clone()
+    }
+
+    fn lookup(&self, host: &str) -> &Lookup {
+        let host = host.trim_end_matches('.').to_lowercase();
+        self.hosts
+            .get(&host)
+            .or_else(|| {
+                // look for the closest wildcard, e.g. `*.b.c` then `*.c` for `a.b.c`
+                host.match_indices('.')
+                    .find_map(|(index, _)| self.hosts.get(&format!("*{}", &host[index..])))
+            })
+            .unwrap_or(&Lookup::NxDomain)
+    }
+}
+
+impl HostClient for FakeResolver {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        if (namespace, operation) != ("net", "v1/dns_lookup_host") {
+            return Err(format!("capability {}.{} not supported", namespace, operation).into());
+        }
+
+        let host: String = serde_json::from_slice(payload)?;
+        self.lookups.borrow_mut().push(host.clone());
+        match self.lookup(&host) {
+            Lookup::Resolved(ips) => Ok(serde_json::to_vec(&LookupResponse { ips: ips.clone() })?),
+            Lookup::NxDomain => Err(format!("no record found for {}: NXDOMAIN", host).into()),
+            Lookup::Timeout => Err(format!("lookup of {} timed out", host).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::with_host_client;
+    use crate::host_capabilities::net::lookup_host;
+    use std::rc::Rc;
+
+    #[test]
+    fn stubbed_lookups() {
+        let resolver = FakeResolver::new()
+            .resolves("*.example.com", &["10.0.0.1"])
+            .resolves("*.internal.example.com", &["192.168.0.1", "192.168.0.2"])
+            .nxdomain("gone.example.com")
+            .timeout("Slow.Example.com");
+
+        with_host_client(Rc::new(resolver), || {
+            assert_eq!(lookup_host("a.example.com").unwrap().ips, vec!["10.0.0.1"]);
+            assert_eq!(
+                lookup_host("db.internal.example.com.").unwrap().ips,
+                vec!["192.168.0.1", "192.168.0.2"]
+            );
+            assert!(lookup_host("example.com").is_err());
+
+            let error = lookup_host("gone.example.com").unwrap_err().to_string();
+            assert!(error.ends_with("no record found for gone.example.com: NXDOMAIN"));
+            let error = lookup_host("slow.example.com").unwrap_err().to_string();
+            assert!(error.ends_with("lookup of slow.example.com timed out"));
+        });
+    }
+}
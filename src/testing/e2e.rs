@@ -0,0 +1,239 @@
+//! End-to-end tests running the policy inside of a real Kubewarden host.
+//!
+//! The [`Kwctl`] runner builds the crate of the policy to WebAssembly,
+//! annotates the module with the policy metadata and evaluates requests
+//! with `kwctl run`. This gives the same coverage of the usual shell based
+//! end-to-end tests, from plain `cargo test`.
+//!
+//! The module is available when the `e2e` feature is enabled. The
+//! `wasm32-wasip1` target and `kwctl` must be installed; the path to the
+//! `kwctl` binary can be set with the `KWCTL` environment variable.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use kubewarden_policy_sdk::testing::e2e::Kwctl;
+//! use serde_json::json;
+//!
+//! let kwctl = Kwctl::new(env!("CARGO_MANIFEST_DIR"), env!("CARGO_PKG_NAME"))
+//!     .build()
+//!     .unwrap();
+//!
+//! let response = kwctl
+//!     .run("test_data/pod_creation.json", &json!({"allowedRegistries": ["ghcr.io"]}))
+//!     .unwrap();
+//! assert!(response.accepted);
+//! ```
+use super::load_request_fixture;
+use crate::response::ValidationResponse;
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The target used to build policies
+pub const WASM_TARGET: &str = "wasm32-wasip1";
+
+/// Builds a policy and runs it with `kwctl`
+#[derive(Debug, Clone)]
+pub struct Kwctl {
+    manifest_dir: PathBuf,
+    package: String,
+    metadata: PathBuf,
+    kwctl: PathBuf,
+    module: Option<PathBuf>,
+}
+
+impl Kwctl {
+    /// Create a runner for the given crate. The metadata of the policy is
+    /// read from the `metadata.yml` file stored inside of `manifest_dir`
+    pub fn new<P: AsRef<Path>>(manifest_dir: P, package: &str) -> Self {
+        let manifest_dir = manifest_dir.as_ref().to_path_buf();
+        Kwctl {
+            metadata: manifest_dir.join("metadata.yml"),
+            manifest_dir,
+            package: package.to_string(),
+            kwctl: std::env::var_os("KWCTL")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("kwctl")),
+            module: None,
+        }
+    }
+
+    /// Use a different metadata file
+    pub fn metadata<P: AsRef<Path>>(mut self, metadata: P) -> Self {
+        self.metadata = metadata.as_ref().to_path_buf();
+        self
+    }
+
+    /// Use the given annotated policy instead of building one
+    pub fn module<P: AsRef<Path>>(mut self, module: P) -> Self {
+        self.module = Some(module.as_ref().to_path_buf());
+        self
+    }
+
+    /// Build the policy in release mode and annotate it
+    pub fn build(mut self) -> anyhow::Result<Self> {
+        let target_dir = self.manifest_dir.join("target");
+        run_command(
+            Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+                .current_dir(&self.manifest_dir)
+                .args(["build", "--release", "--target", WASM_TARGET]),
+        )?;
+
+        let module = target_dir
+            .join(WASM_TARGET)
+            .join("release")
+            .join(format!("{}.wasm", self.package.replace('-', "_")));
+        let annotated = target_dir.join(format!("{}-annotated.wasm", self.package));
+        run_command(
+            Command::new(&self.kwctl)
+                .arg("annotate")
+                .arg("--metadata-path")
+                .arg(&self.metadata)
+                .arg("--output-path")
+                .arg(&annotated)
+                .arg(&module),
+        )?;
+
+        self.module = Some(annotated);
+        Ok(self)
+    }
+
+    /// Evaluate the admission request stored inside of the given fixture
+    /// file, which can hold either an `AdmissionRequest` or an
+    /// `AdmissionReview` object
+    pub fn run<P: AsRef<Path>>(
+        &self,
+        fixture: P,
+        settings: &Value,
+    ) -> anyhow::Result<ValidationResponse> {
+        let module = self
+            .module
+            .as_ref()
+            .ok_or_else(|| anyhow!("the policy has not been built"))?;
+        let request = load_request_fixture(fixture.as_ref())?;
+
+        let output = run_command(
+            Command::new(&self.kwctl)
+                .arg("run")
+                .arg("--request-path")
+                .arg(fixture.as_ref())
+                .arg("--settings-json")
+                .arg(settings.to_string())
+                .arg(module),
+        )?;
+        let response: AdmissionResponse =
+            serde_json::from_slice(&output).context("cannot decode the output of kwctl")?;
+        response.into_validation_response(&request["object"])
+    }
+}
+
+fn run_command(command: &mut Command) -> anyhow::Result<Vec<u8>> {
+    let output = command
+        .output()
+        .with_context(|| format!("cannot run {:?}", command))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{:?} failed with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// The `AdmissionResponse` printed by `kwctl run`
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct AdmissionResponse {
+    allowed: bool,
+    patch: Option<String>,
+    status: Option<Status>,
+    audit_annotations: Option<HashMap<String, String>>,
+    warnings: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Status {
+    message: Option<String>,
+    code: Option<u16>,
+}
+
+impl AdmissionResponse {
+    fn into_validation_response(self, object: &Value) -> anyhow::Result<ValidationResponse> {
+        let mutated_object = self
+            .patch
+            .map(|patch| -> anyhow::Result<Value> {
+                let patch = base64::engine::general_purpose::STANDARD
+                    .decode(patch)
+                    .context("the patch is not valid base64")?;
+                let patch: Value = serde_json::from_slice(&patch)?;
+                Ok(crate::diff::apply_patch(object, &patch)?)
+            })
+            .transpose()?;
+        let status = self.status.unwrap_or_default();
+
+        Ok(ValidationResponse {
+            accepted: self.allowed,
+            message: status.message,
+            code: status.code,
+            mutated_object,
+            audit_annotations: self.audit_annotations,
+            warnings: self.warnings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decode_kwctl_output() {
+        let patch = base64::engine::general_purpose::STANDARD
+            .encode(r#"[{"op":"add","path":"/metadata/labels","value":{"a":"b"}}]"#);
+        let response: AdmissionResponse = serde_json::from_value(json!({
+            "uid": "",
+            "allowed": true,
+            "patchType": "JSONPatch",
+            "patch": patch,
+            "warnings": ["deprecated"]
+        }))
+        .unwrap();
+        let response = response
+            .into_validation_response(&json!({"metadata": {"name": "nginx"}}))
+            .unwrap();
+        assert!(response.accepted);
+        assert_eq!(
+            response.mutated_object.unwrap(),
+            json!({"metadata": {"name": "nginx", "labels": {"a": "b"}}})
+        );
+        assert_eq!(response.warnings.unwrap(), vec!["deprecated"]);
+
+        let response: AdmissionResponse = serde_json::from_value(json!({
+            "uid": "",
+            "allowed": false,
+            "status": {"message": "denied", "code": 400}
+        }))
+        .unwrap();
+        let response = response.into_validation_response(&Value::Null).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.as_deref(), Some("denied"));
+        assert_eq!(response.code, Some(400));
+    }
+
+    #[test]
+    fn run_without_build() {
+        let error = Kwctl::new(env!("CARGO_MANIFEST_DIR"), "policy")
+            .run("test_data/pod_creation.json", &json!({}))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "the policy has not been built");
+    }
+}
@@ -0,0 +1,154 @@
+//! Entry points for fuzzing policies, e.g. with `cargo fuzz`.
+//!
+//! Each function feeds arbitrary bytes to one of the waPC functions of a
+//! policy and panics when the outcome would break the host: a panic
+//! escaping the guest, or a successful evaluation that does not return a
+//! well formed response. Malformed payloads are expected to produce errors,
+//! which the host turns into rejections.
+//!
+//! # Example
+//!
+//! A `fuzz/fuzz_targets/validate.rs` target of a policy:
+//!
+//! ```ignore
+//! #![no_main]
+//! use libfuzzer_sys::fuzz_target;
+//!
+//! fuzz_target!(|data: &[u8]| {
+//!     kubewarden_policy_sdk::testing::fuzz::fuzz_validate::<my_policy::MyPolicy>(data);
+//! });
+//! ```
+use crate::policy::Policy;
+use crate::request::{KubernetesAdmissionRequest, ValidationRequest};
+use crate::response::ValidationResponse;
+use crate::settings::SettingsValidationResponse;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Ensure `f` neither panics nor returns an `Ok` payload that cannot be
+/// decoded as `T`
+fn fuzz_guest_fn<T: serde::de::DeserializeOwned>(
+    what: &str,
+    data: &[u8],
+    f: impl FnOnce(&[u8]) -> wapc_guest::CallResult,
+) {
+    match catch_unwind(AssertUnwindSafe(|| f(data))) {
+        Ok(Ok(response)) => {
+            if let Err(e) = serde_json::from_slice::<T>(&response) {
+                panic!(
+                    "{} returned a malformed response {:?}: {}",
+                    what,
+                    String::from_utf8_lossy(&response),
+                    e
+                );
+            }
+        }
+        Ok(Err(_)) => {}
+        Err(_) => panic!(
+            "{} panicked with payload {:?}",
+            what,
+            String::from_utf8_lossy(data)
+        ),
+    }
+}
+
+/// Fuzz the `validate` function of the given policy
+pub fn fuzz_validate<P: Policy>(data: &[u8]) {
+    fuzz_guest_fn::<ValidationResponse>("validate", data, crate::policy::validate::<P>);
+}
+
+/// Fuzz the `validate_settings` function of the given policy
+pub fn fuzz_validate_settings<P: Policy>(data: &[u8]) {
+    fuzz_guest_fn::<SettingsValidationResponse>(
+        "validate_settings",
+        data,
+        crate::policy::validate_settings::<P>,
+    );
+}
+
+/// Fuzz the decoding of the validation requests, including the helpers that
+/// extract data from the object being evaluated
+pub fn fuzz_decode_request(data: &[u8]) {
+    let result = catch_unwind(|| {
+        if let Ok(request) = ValidationRequest::<serde_json::Value>::new(data) {
+            let _ = request.request.connect_options();
+            #[cfg(feature = "cluster-context")]
+            {
+                let _ = request.request.scale();
+                let _ = request.extract_pod_spec_from_object();
+            }
+        }
+        let _ = serde_json::from_slice::<KubernetesAdmissionRequest>(data);
+    });
+    if result.is_err() {
+        panic!(
+            "decoding panicked with payload {:?}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::generate::{check, pod, Mode, Rng};
+    use serde_json::json;
+
+    struct AcceptAll;
+
+    impl Policy for AcceptAll {
+        type Settings = ();
+
+        fn validate(_request: &ValidationRequest<Self::Settings>) -> wapc_guest::CallResult {
+            crate::accept_request()
+        }
+    }
+
+    struct Panicking;
+
+    impl Policy for Panicking {
+        type Settings = ();
+
+        fn validate(request: &ValidationRequest<Self::Settings>) -> wapc_guest::CallResult {
+            let _ = request.request.object["spec"]["containers"]
+                .as_array()
+                .unwrap()[0];
+            crate::accept_request()
+        }
+    }
+
+    fn payload(rng: &mut Rng) -> Vec<u8> {
+        let request = json!({
+            "settings": null,
+            "request": {"operation": "CREATE", "object": pod(rng, Mode::Adversarial)}
+        });
+        let mut payload = serde_json::to_vec(&request).unwrap();
+        // corrupt part of the payloads
+        if rng.chance(50) {
+            let index = rng.below(payload.len());
+            payload.truncate(index);
+        }
+        payload
+    }
+
+    #[test]
+    fn malformed_payloads() {
+        for data in [
+            &b""[..],
+            b"null",
+            b"[]",
+            b"{\"request\": 1}",
+            b"{\"settings\": {}, \"request\": {\"kind\": \"\"}}",
+            b"\xff\xfe",
+        ] {
+            fuzz_decode_request(data);
+            fuzz_validate::<AcceptAll>(data);
+            fuzz_validate_settings::<AcceptAll>(data);
+        }
+        check(200, payload, |data| {
+            fuzz_decode_request(data);
+            fuzz_validate::<AcceptAll>(data);
+            // panics are caught by the SDK and turned into rejections
+            fuzz_validate::<Panicking>(data);
+        });
+    }
+}
@@ -0,0 +1,750 @@
+//! Property-based testing of policies.
+//!
+//! This module provides generators of Kubernetes values, both valid and
+//! adversarial ones, together with [`check`], which evaluates a property
+//! against many generated inputs. Policy authors can use them to fuzz their
+//! validation logic, looking for panics and inconsistent decisions.
+//!
+//! Generation is driven by a seeded [`Rng`], which makes every failure
+//! reproducible: the seed of the failing case is reported, and can be set
+//! with the `KUBEWARDEN_PROPTEST_SEED` environment variable.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::quantity::Quantity;
+//! use kubewarden_policy_sdk::testing::generate::{check, quantity, Mode};
+//!
+//! // parsing a quantity never panics, and valid quantities are always parsed
+//! check(256, |rng| quantity(rng, Mode::Adversarial), |q| {
+//!     let _ = q.parse::<Quantity>();
+//! });
+//! check(256, |rng| quantity(rng, Mode::Valid), |q| {
+//!     assert!(q.parse::<Quantity>().is_ok(), "{q}");
+//! });
+//! ```
+use anyhow::anyhow;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+/// Environment variable used to set the seed of [`check`]
+pub const SEED_ENV: &str = "KUBEWARDEN_PROPTEST_SEED";
+
+const DEFAULT_SEED: u64 = 0x6b75_6265_7761_7264;
+const ALPHANUMERIC: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+const HEX: &[u8] = b"0123456789abcdef";
+
+/// A small, deterministic pseudo-random number generator (SplitMix64)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a generator from the given seed
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// Returns the next random number
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the `[0, n)` range. `n` must be greater than 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Returns a number in the `[min, max]` range
+    pub fn range(&mut self, min: usize, max: usize) -> usize {
+        min + self.below(max - min + 1)
+    }
+
+    /// Returns true with the given probability, expressed in percent
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    /// Pick one of the given items. `items` must not be empty
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    /// Returns a string of `len` characters picked from `alphabet`
+    pub fn string(&mut self, len: usize, alphabet: &[u8]) -> String {
+        (0..len).map(|_| *self.choose(alphabet) as char).collect()
+    }
+}
+
+/// The kind of values produced by the generators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Values accepted by the Kubernetes API server
+    Valid,
+    /// Malformed values, wrong types, empty and oversized strings,...
+    Adversarial,
+}
+
+/// Evaluate `property` against `cases` values produced by `generator`.
+///
+/// The property fails by panicking, e.g. through `assert!`. On failure the
+/// offending input is reported, together with the seed that reproduces it.
+///
+/// # Panics
+///
+/// Panics when the property fails for any of the generated values
+pub fn check<T: Debug>(cases: usize, generator: impl Fn(&mut Rng) -> T, property: impl Fn(&T)) {
+    let seed = std::env::var(SEED_ENV)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_SEED);
+    let mut seeds = Rng::new(seed);
+
+    for case in 0..cases {
+        let case_seed = seeds.next_u64();
+        let input = generator(&mut Rng::new(case_seed));
+        if let Err(e) = catch_unwind(AssertUnwindSafe(|| property(&input))) {
+            let reason = e
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| e.downcast_ref::<&str>().copied())
+                .unwrap_or("unknown panic");
+            panic!(
+                "property failed at case #{} (case seed {}, {}={}): {}\ninput: {:#?}",
+                case, case_seed, SEED_ENV, seed, reason, input
+            );
+        }
+    }
+}
+
+fn dns_label(rng: &mut Rng, max_len: usize) -> String {
+    let len = rng.range(1, max_len);
+    let mut label = rng.string(len, ALPHANUMERIC);
+    if len > 2 && rng.chance(30) {
+        let index = rng.range(1, len - 2);
+        label.replace_range(index..index + 1, "-");
+    }
+    label
+}
+
+fn label_name(rng: &mut Rng) -> String {
+    let len = rng.range(1, 63);
+    let mut name = rng.string(len, ALPHANUMERIC);
+    if len > 2 {
+        for _ in 0..rng.below(3) {
+            let index = rng.range(1, len - 2);
+            let separator = *rng.choose(&["-", "_", "."]);
+            name.replace_range(index..index + 1, separator);
+        }
+    }
+    name
+}
+
+fn adversarial_string(rng: &mut Rng) -> String {
+    match rng.below(8) {
+        0 => String::new(),
+        1 => "a".repeat(rng.range(64, 300)),
+        2 => "-leading-dash".to_string(),
+        3 => "trailing.".to_string(),
+        4 => "with space".to_string(),
+        5 => "ünïcødé/💥".to_string(),
+        6 => "a//b".to_string(),
+        _ => "UPPER/Case:colon@at".to_string(),
+    }
+}
+
+/// Generate a label key
+pub fn label_key(rng: &mut Rng, mode: Mode) -> String {
+    match mode {
+        Mode::Valid if rng.chance(40) => {
+            let prefix: Vec<String> = (0..rng.range(1, 3)).map(|_| dns_label(rng, 20)).collect();
+            format!("{}/{}", prefix.join("."), label_name(rng))
+        }
+        Mode::Valid => label_name(rng),
+        Mode::Adversarial => adversarial_string(rng),
+    }
+}
+
+/// Generate a label value
+pub fn label_value(rng: &mut Rng, mode: Mode) -> String {
+    match mode {
+        Mode::Valid if rng.chance(10) => String::new(),
+        Mode::Valid => label_name(rng),
+        Mode::Adversarial => adversarial_string(rng),
+    }
+}
+
+/// Generate a set of labels
+pub fn labels(rng: &mut Rng, mode: Mode) -> BTreeMap<String, String> {
+    (0..rng.below(6))
+        .map(|_| (label_key(rng, mode), label_value(rng, mode)))
+        .collect()
+}
+
+/// Generate a container image reference
+pub fn image(rng: &mut Rng, mode: Mode) -> String {
+    match mode {
+        Mode::Valid => {
+            let mut image = String::new();
+            if rng.chance(60) {
+                let registry = rng.choose(&["docker.io", "ghcr.io", "quay.io", "registry.local"]);
+                image.push_str(registry);
+                if rng.chance(20) {
+                    image.push_str(&format!(":{}", rng.range(1, 65535)));
+                }
+                image.push('/');
+            }
+            let path: Vec<String> = (0..rng.range(1, 3)).map(|_| dns_label(rng, 15)).collect();
+            image.push_str(&path.join("/"));
+            match rng.below(3) {
+                0 => image.push_str(&format!(":{}", label_name(rng))),
+                1 => image.push_str(&format!("@sha256:{}", rng.string(64, HEX))),
+                _ => {}
+            }
+            image
+        }
+        Mode::Adversarial => match rng.below(8) {
+            0 => String::new(),
+            1 => "nginx::latest".to_string(),
+            2 => "nginx@sha256:abc".to_string(),
+            3 => "UPPERCASE/Image".to_string(),
+            4 => ":latest".to_string(),
+            5 => "registry:port/nginx".to_string(),
+            6 => format!("{}/nginx", "a".repeat(rng.range(256, 1024))),
+            _ => "nginx latest".to_string(),
+        },
+    }
+}
+
+/// Generate a resource quantity, like `100m` or `1.5Gi`
+pub fn quantity(rng: &mut Rng, mode: Mode) -> String {
+    match mode {
+        Mode::Valid => {
+            let number = if rng.chance(30) {
+                format!("{}.{}", rng.below(1000), rng.range(1, 999))
+            } else {
+                rng.below(100_000).to_string()
+            };
+            let suffix = rng.choose(&[
+                "", "m", "k", "M", "G", "T", "Ki", "Mi", "Gi", "Ti", "e3", "E2",
+            ]);
+            format!("{}{}", number, suffix)
+        }
+        Mode::Adversarial => match rng.below(10) {
+            0 => String::new(),
+            1 => "1.2.3".to_string(),
+            2 => "Mi".to_string(),
+            3 => "-".to_string(),
+            4 => "1Qi".to_string(),
+            5 => " 1".to_string(),
+            6 => "1e".to_string(),
+            7 => "9".repeat(rng.range(20, 60)),
+            8 => format!("{}Ei", u64::MAX),
+            _ => "1e999999".to_string(),
+        },
+    }
+}
+
+fn resources(rng: &mut Rng, mode: Mode) -> Value {
+    let mut limits = Map::new();
+    if rng.chance(70) {
+        limits.insert("cpu".to_string(), json!(quantity(rng, mode)));
+    }
+    if rng.chance(70) {
+        limits.insert("memory".to_string(), json!(quantity(rng, mode)));
+    }
+    json!({"limits": limits})
+}
+
+fn container(rng: &mut Rng, mode: Mode) -> Value {
+    let mut container = json!({
+        "name": dns_label(rng, 20),
+        "image": image(rng, mode),
+    });
+    if rng.chance(70) {
+        container["resources"] = resources(rng, mode);
+    }
+    if rng.chance(50) {
+        container["securityContext"] = json!({
+            "privileged": rng.chance(20),
+            "runAsNonRoot": rng.chance(60),
+            "allowPrivilegeEscalation": rng.chance(30),
+        });
+    }
+    container
+}
+
+/// Generate a PodSpec. Adversarial specs contain fields with wrong types,
+/// `null` values and missing mandatory fields
+pub fn pod_spec(rng: &mut Rng, mode: Mode) -> Value {
+    let containers: Vec<Value> = (0..rng.range(1, 3)).map(|_| container(rng, mode)).collect();
+    let mut spec = json!({ "containers": containers });
+    if rng.chance(30) {
+        let init: Vec<Value> = (0..rng.range(1, 2)).map(|_| container(rng, mode)).collect();
+        spec["initContainers"] = json!(init);
+    }
+    if rng.chance(30) {
+        spec["hostNetwork"] = json!(rng.chance(50));
+    }
+
+    if mode == Mode::Adversarial {
+        match rng.below(6) {
+            0 => spec["containers"] = json!("nginx"),
+            1 => spec["containers"] = Value::Null,
+            2 => spec["containers"] = json!([]),
+            3 => spec["containers"][0]["image"] = json!(42),
+            4 => spec["containers"][0]["securityContext"] = json!({"privileged": "true"}),
+            _ => {
+                spec.as_object_mut().unwrap().remove("containers");
+            }
+        }
+    }
+    spec
+}
+
+/// Generate a Pod object, with labels and a PodSpec produced by the other
+/// generators
+pub fn pod(rng: &mut Rng, mode: Mode) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": dns_label(rng, 30),
+            "namespace": dns_label(rng, 20),
+            "labels": labels(rng, mode),
+        },
+        "spec": pod_spec(rng, mode),
+    })
+}
+
+/// Generate a value conforming to the given OpenAPI v3 schema, as found
+/// inside of a CustomResourceDefinition.
+///
+/// Valid values are biased towards the boundaries declared by the schema:
+/// minimum and maximum values, lengths and numbers of items. Adversarial
+/// values violate some of the constraints: wrong types, values out of range,
+/// missing required properties,...
+///
+/// `pattern` constraints are not honoured: the `default` value of the
+/// schema is used when present, an arbitrary string otherwise.
+pub fn from_schema(rng: &mut Rng, schema: &Value, mode: Mode) -> Value {
+    if mode == Mode::Adversarial && rng.chance(25) {
+        return schema_violation(rng, schema);
+    }
+
+    if schema["nullable"] == json!(true) && rng.chance(10) {
+        return Value::Null;
+    }
+    if let Some(values) = schema["enum"].as_array().filter(|v| !v.is_empty()) {
+        return rng.choose(values).clone();
+    }
+    if let Some(default) = schema.get("default") {
+        if schema.get("pattern").is_some() || rng.chance(20) {
+            return default.clone();
+        }
+    }
+    if schema["x-kubernetes-int-or-string"] == json!(true) {
+        return if rng.chance(50) {
+            json!(rng.below(100))
+        } else {
+            json!(format!("{}%", rng.below(101)))
+        };
+    }
+
+    match schema_type(schema) {
+        "object" => object_from_schema(rng, schema, mode),
+        "array" => {
+            let (min, max) = bounds(rng, schema, "minItems", "maxItems", 3);
+            let len = boundary_biased(rng, min, max);
+            let items = &schema["items"];
+            json!((0..len)
+                .map(|_| from_schema(rng, items, mode))
+                .collect::<Vec<_>>())
+        }
+        "integer" => json!(integer_from_schema(rng, schema)),
+        "number" => {
+            let n = integer_from_schema(rng, schema) as f64;
+            if schema.get("multipleOf").is_none() && rng.chance(50) {
+                let max = schema["maximum"].as_f64().unwrap_or(f64::MAX);
+                json!(if n + 0.5 < max { n + 0.5 } else { n })
+            } else {
+                json!(n)
+            }
+        }
+        "boolean" => json!(rng.chance(50)),
+        _ => json!(string_from_schema(rng, schema)),
+    }
+}
+
+fn schema_type(schema: &Value) -> &str {
+    match schema["type"].as_str() {
+        Some(t) => t,
+        None if schema.get("properties").is_some() => "object",
+        None if schema.get("items").is_some() => "array",
+        None => "string",
+    }
+}
+
+/// The `[min, max]` range of the given size constraints
+fn bounds(rng: &mut Rng, schema: &Value, min: &str, max: &str, spread: usize) -> (usize, usize) {
+    let min = schema[min].as_u64().unwrap_or(0) as usize;
+    let max = schema[max]
+        .as_u64()
+        .map(|max| max as usize)
+        .unwrap_or_else(|| min + rng.range(1, spread));
+    (min, max.max(min))
+}
+
+/// Pick a number in the `[min, max]` range, preferring the boundaries
+fn boundary_biased(rng: &mut Rng, min: usize, max: usize) -> usize {
+    match rng.below(10) {
+        0 | 1 => min,
+        2 | 3 => max,
+        _ => rng.range(min, max),
+    }
+}
+
+fn integer_from_schema(rng: &mut Rng, schema: &Value) -> i64 {
+    let exclusive = |key: &str| (schema[key] == json!(true)) as i64;
+    let min = schema["minimum"]
+        .as_f64()
+        .map(|m| m.ceil() as i64 + exclusive("exclusiveMinimum"));
+    let max = schema["maximum"]
+        .as_f64()
+        .map(|m| m.floor() as i64 - exclusive("exclusiveMaximum"));
+    let (min, max) = match (min, max) {
+        (Some(min), Some(max)) => (min, max.max(min)),
+        (Some(min), None) => (min, min.saturating_add(100)),
+        (None, Some(max)) => (max.saturating_sub(100), max),
+        (None, None) => (0, 100),
+    };
+
+    let value = match rng.below(10) {
+        0 | 1 => min,
+        2 | 3 => max,
+        _ => min.saturating_add((rng.next_u64() % (max.abs_diff(min) + 1)) as i64),
+    };
+    match schema["multipleOf"].as_u64().filter(|m| *m > 0) {
+        Some(step) => {
+            let step = step as i64;
+            let rounded = value.div_euclid(step) * step;
+            if rounded < min {
+                rounded.saturating_add(step)
+            } else {
+                rounded
+            }
+        }
+        None => value,
+    }
+}
+
+fn string_from_schema(rng: &mut Rng, schema: &Value) -> String {
+    match schema["format"].as_str() {
+        Some("date-time") => return "2024-02-29T23:59:59Z".to_string(),
+        Some("date") => return "2024-02-29".to_string(),
+        Some("byte") => return "a3ViZXdhcmRlbg==".to_string(),
+        Some("int-or-string") => return format!("{}", rng.below(100)),
+        _ => {}
+    }
+    let (min, max) = bounds(rng, schema, "minLength", "maxLength", 12);
+    let len = boundary_biased(rng, min, max);
+    rng.string(len, ALPHANUMERIC)
+}
+
+fn object_from_schema(rng: &mut Rng, schema: &Value, mode: Mode) -> Value {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let mut object = Map::new();
+    if let Some(properties) = schema["properties"].as_object() {
+        for (name, property) in properties {
+            if required.contains(&name.as_str()) || rng.chance(60) {
+                object.insert(name.clone(), from_schema(rng, property, mode));
+            }
+        }
+    }
+    if let Some(additional) = schema.get("additionalProperties").filter(|a| a.is_object()) {
+        for _ in 0..rng.below(4) {
+            object.insert(dns_label(rng, 20), from_schema(rng, additional, mode));
+        }
+    }
+    Value::Object(object)
+}
+
+fn schema_violation(rng: &mut Rng, schema: &Value) -> Value {
+    let mut violations = vec![Value::Null];
+    match schema_type(schema) {
+        "string" => {
+            violations.push(json!(rng.below(1000)));
+            if let Some(max) = schema["maxLength"].as_u64() {
+                violations.push(json!("a".repeat(max as usize + 1)));
+            }
+            if schema["minLength"].as_u64().is_some_and(|min| min > 0) {
+                violations.push(json!(""));
+            }
+        }
+        "integer" | "number" => {
+            violations.push(json!(rng.string(5, ALPHANUMERIC)));
+            if let Some(max) = schema["maximum"].as_f64() {
+                violations.push(json!(max as i64 + 1));
+            }
+            if let Some(min) = schema["minimum"].as_f64() {
+                violations.push(json!(min as i64 - 1));
+            }
+        }
+        "boolean" => violations.push(json!("true")),
+        "array" => {
+            violations.push(json!({}));
+            if let Some(max) = schema["maxItems"].as_u64() {
+                let items = &schema["items"];
+                violations.push(json!((0..=max)
+                    .map(|_| from_schema(rng, items, Mode::Valid))
+                    .collect::<Vec<_>>()));
+            }
+        }
+        _ => {
+            violations.push(json!([]));
+            let mut object = object_from_schema(rng, schema, Mode::Valid);
+            if let Some(required) = schema["required"].as_array().filter(|r| !r.is_empty()) {
+                let name = rng.choose(required).as_str().unwrap_or_default();
+                object.as_object_mut().unwrap().remove(name);
+                violations.push(object);
+            }
+        }
+    }
+    if schema.get("enum").is_some() {
+        violations.push(json!("not-a-declared-value"));
+    }
+    rng.choose(&violations).clone()
+}
+
+/// Generate a custom resource of the kind defined by the given
+/// CustomResourceDefinition, using the schema of its storage version.
+/// See [`from_schema`].
+///
+/// The `apiVersion`, `kind` and `metadata` of the resource are always valid.
+pub fn custom_resource(rng: &mut Rng, crd: &Value, mode: Mode) -> anyhow::Result<Value> {
+    let spec = &crd["spec"];
+    let versions = spec["versions"]
+        .as_array()
+        .ok_or_else(|| anyhow!("the CustomResourceDefinition has no versions"))?;
+    let version = versions
+        .iter()
+        .find(|v| v["storage"] == json!(true))
+        .or_else(|| versions.first())
+        .ok_or_else(|| anyhow!("the CustomResourceDefinition has no versions"))?;
+    let schema = &version["schema"]["openAPIV3Schema"];
+    let kind = spec["names"]["kind"]
+        .as_str()
+        .ok_or_else(|| anyhow!("the CustomResourceDefinition has no kind"))?;
+
+    let mut object = match object_from_schema(rng, schema, mode) {
+        Value::Object(object) => object,
+        _ => unreachable!(),
+    };
+    object.insert(
+        "apiVersion".to_string(),
+        json!(format!(
+            "{}/{}",
+            spec["group"].as_str().unwrap_or_default(),
+            version["name"].as_str().unwrap_or_default()
+        )),
+    );
+    object.insert("kind".to_string(), json!(kind));
+    let mut metadata = json!({"name": dns_label(rng, 30)});
+    if spec["scope"] != json!("Cluster") {
+        metadata["namespace"] = json!(dns_label(rng, 20));
+    }
+    object.insert("metadata".to_string(), metadata);
+    Ok(Value::Object(object))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quantity::Quantity;
+
+    #[test]
+    fn generation_is_deterministic() {
+        let a: Vec<Value> = (0..5).map(|_| pod(&mut Rng::new(7), Mode::Valid)).collect();
+        let b: Vec<Value> = (0..5).map(|_| pod(&mut Rng::new(7), Mode::Valid)).collect();
+        assert_eq!(a, b);
+        assert_ne!(
+            pod(&mut Rng::new(7), Mode::Valid),
+            pod(&mut Rng::new(8), Mode::Valid)
+        );
+    }
+
+    #[test]
+    fn valid_values() {
+        check(
+            500,
+            |rng| quantity(rng, Mode::Valid),
+            |q| assert!(q.parse::<Quantity>().is_ok(), "{}", q),
+        );
+        check(
+            500,
+            |rng| labels(rng, Mode::Valid),
+            |labels| {
+                for (key, value) in labels {
+                    let name = key.rsplit('/').next().unwrap();
+                    assert!(!name.is_empty() && name.len() <= 63);
+                    assert!(value.len() <= 63);
+                }
+            },
+        );
+        check(
+            500,
+            |rng| pod_spec(rng, Mode::Valid),
+            |spec| assert!(!spec["containers"].as_array().unwrap().is_empty()),
+        );
+    }
+
+    #[test]
+    fn adversarial_values_do_not_panic() {
+        check(
+            500,
+            |rng| quantity(rng, Mode::Adversarial),
+            |q| {
+                let _ = q.parse::<Quantity>();
+            },
+        );
+    }
+
+    fn crd() -> Value {
+        json!({
+            "apiVersion": "apiextensions.k8s.io/v1",
+            "kind": "CustomResourceDefinition",
+            "spec": {
+                "group": "example.com",
+                "names": {"kind": "Backup"},
+                "scope": "Namespaced",
+                "versions": [{
+                    "name": "v1",
+                    "storage": true,
+                    "schema": {"openAPIV3Schema": {
+                        "type": "object",
+                        "required": ["spec"],
+                        "properties": {"spec": {
+                            "type": "object",
+                            "required": ["schedule", "retention"],
+                            "properties": {
+                                "schedule": {"type": "string", "minLength": 1, "maxLength": 20},
+                                "retention": {"type": "integer", "minimum": 1, "maximum": 30},
+                                "ratio": {"type": "number", "minimum": 0, "maximum": 1},
+                                "mode": {"type": "string", "enum": ["full", "incremental"]},
+                                "suspend": {"type": "boolean"},
+                                "targets": {
+                                    "type": "array",
+                                    "maxItems": 3,
+                                    "items": {"type": "string", "maxLength": 10},
+                                },
+                                "labels": {
+                                    "type": "object",
+                                    "additionalProperties": {"type": "string"},
+                                },
+                                "storage": {"x-kubernetes-int-or-string": true},
+                            },
+                        }},
+                    }},
+                }],
+            },
+        })
+    }
+
+    /// Check the constraints used by the CRD of the tests
+    fn conforms(schema: &Value, value: &Value) -> bool {
+        if let Some(values) = schema["enum"].as_array() {
+            return values.contains(value);
+        }
+        if schema["x-kubernetes-int-or-string"] == json!(true) {
+            return value.is_i64() || value.is_string();
+        }
+        let within = |n: f64| {
+            schema["minimum"].as_f64().is_none_or(|min| n >= min)
+                && schema["maximum"].as_f64().is_none_or(|max| n <= max)
+        };
+        let len_within = |len: usize, min: &str, max: &str| {
+            schema[min].as_u64().is_none_or(|min| len as u64 >= min)
+                && schema[max].as_u64().is_none_or(|max| len as u64 <= max)
+        };
+        match (schema_type(schema), value) {
+            ("string", Value::String(s)) => len_within(s.len(), "minLength", "maxLength"),
+            ("integer", Value::Number(n)) => n.as_i64().is_some_and(|n| within(n as f64)),
+            ("number", Value::Number(n)) => within(n.as_f64().unwrap()),
+            ("boolean", Value::Bool(_)) => true,
+            ("array", Value::Array(items)) => {
+                len_within(items.len(), "minItems", "maxItems")
+                    && items.iter().all(|i| conforms(&schema["items"], i))
+            }
+            ("object", Value::Object(object)) => {
+                let required = schema["required"].as_array().cloned().unwrap_or_default();
+                required
+                    .iter()
+                    .all(|r| object.contains_key(r.as_str().unwrap()))
+                    && object
+                        .iter()
+                        .all(|(k, v)| match schema["properties"].get(k) {
+                            Some(property) => conforms(property, v),
+                            None => conforms(&schema["additionalProperties"], v),
+                        })
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn custom_resources_from_schema() {
+        let crd = crd();
+        let schema = &crd["spec"]["versions"][0]["schema"]["openAPIV3Schema"]["properties"]["spec"];
+
+        check(
+            500,
+            |rng| custom_resource(rng, &crd, Mode::Valid).unwrap(),
+            |resource| {
+                assert_eq!(resource["apiVersion"], "example.com/v1");
+                assert_eq!(resource["kind"], "Backup");
+                assert!(resource["metadata"]["namespace"].is_string());
+                assert!(conforms(schema, &resource["spec"]));
+            },
+        );
+
+        // boundaries are generated
+        let retentions: Vec<Value> = (0..100)
+            .map(|seed| {
+                from_schema(
+                    &mut Rng::new(seed),
+                    &schema["properties"]["retention"],
+                    Mode::Valid,
+                )
+            })
+            .collect();
+        assert!(retentions.contains(&json!(1)) && retentions.contains(&json!(30)));
+
+        // some of the adversarial resources violate the schema
+        let invalid = (0..100)
+            .map(|seed| custom_resource(&mut Rng::new(seed), &crd, Mode::Adversarial).unwrap())
+            .filter(|resource| !conforms(schema, &resource["spec"]))
+            .count();
+        assert!(invalid > 0);
+
+        assert!(custom_resource(&mut Rng::new(0), &json!({}), Mode::Valid).is_err());
+    }
+
+    #[test]
+    fn failures_are_reported_with_the_seed() {
+        let result = catch_unwind(|| check(100, |rng| rng.below(10), |n| assert!(*n < 9)));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("property failed at case #"));
+        assert!(message.contains("input: 9"));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::diff::json_patch;
+use crate::response::ValidationResponse;
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Environment variable that, when set to `1`, makes [`assert_golden`]
+/// (re)write the golden files instead of comparing against them
+pub const UPDATE_GOLDEN_ENV: &str = "KUBEWARDEN_UPDATE_GOLDEN";
+
+/// Serialize a [`ValidationResponse`] deterministically, to be stored
+/// inside of a golden file.
+///
+/// Object keys are sorted and, when the `original` object is provided, the
+/// mutated object is replaced by the JSON Patch turning the original object
+/// into the mutated one. That keeps golden files small and makes mutation
+/// changes obvious in review.
+pub fn response_snapshot(response: &ValidationResponse, original: Option<&Value>) -> String {
+    let mut snapshot = json!({
+        "accepted": response.accepted,
+        "message": response.message,
+        "code": response.code,
+        "warnings": response.warnings,
+        "auditAnnotations": response.audit_annotations,
+    });
+    match (&response.mutated_object, original) {
+        (Some(mutated), Some(original)) => {
+            snapshot["patch"] = json_patch(original, mutated);
+        }
+        (Some(mutated), None) => {
+            snapshot["mutatedObject"] = mutated.clone();
+        }
+        (None, _) => {}
+    }
+
+    let mut snapshot =
+        serde_json::to_string_pretty(&sort_keys(snapshot)).expect("cannot serialize snapshot");
+    snapshot.push('\n');
+    snapshot
+}
+
+/// Recursively sort the keys of all the objects, regardless of the
+/// `preserve_order` feature of `serde_json` being enabled
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+/// Compare `actual` against the contents of the golden file at `path`,
+/// panicking with a line by line diff when they differ.
+///
+/// When the `KUBEWARDEN_UPDATE_GOLDEN` environment variable is set to `1`
+/// the golden file is written instead, creating its parent directories.
+pub fn assert_golden<P: AsRef<Path>>(path: P, actual: &str) {
+    let update = std::env::var(UPDATE_GOLDEN_ENV).is_ok_and(|v| v == "1");
+    compare_golden(path.as_ref(), actual, update);
+}
+
+fn compare_golden(path: &Path, actual: &str, update: bool) {
+    if update {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("cannot create golden file directory");
+        }
+        std::fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("cannot write golden file {}: {}", path.display(), e));
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read golden file {}: {}. Run the tests with {}=1 to create it",
+            path.display(),
+            e,
+            UPDATE_GOLDEN_ENV
+        )
+    });
+    if expected != actual {
+        panic!(
+            "{} does not match (-expected +actual):\n{}\nRun the tests with {}=1 to update it",
+            path.display(),
+            line_diff(&expected, actual),
+            UPDATE_GOLDEN_ENV
+        );
+    }
+}
+
+/// Compare the snapshot of `response` against the golden file at `path`.
+/// See [`response_snapshot`] and [`assert_golden`]
+pub fn assert_response_golden<P: AsRef<Path>>(
+    path: P,
+    response: &ValidationResponse,
+    original: Option<&Value>,
+) {
+    assert_golden(path, &response_snapshot(response, original));
+}
+
+/// Line based diff, computed with the longest common subsequence of the two
+/// texts. Unchanged lines are prefixed by a space, removed ones by `-` and
+/// added ones by `+`
+fn line_diff(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+    diff.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_contains_patch() {
+        let original = json!({"metadata": {"name": "nginx"}});
+        let response = ValidationResponse::accept()
+            .mutate(json!({"metadata": {"name": "nginx", "labels": {"app": "nginx"}}}))
+            .audit_annotation("b", "2")
+            .audit_annotation("a", "1")
+            .response();
+
+        let snapshot: Value =
+            serde_json::from_str(&response_snapshot(&response, Some(&original))).unwrap();
+        assert_eq!(
+            snapshot,
+            json!({
+                "accepted": true,
+                "message": null,
+                "code": null,
+                "warnings": null,
+                "auditAnnotations": {"a": "1", "b": "2"},
+                "patch": [{"op": "add", "path": "/metadata/labels", "value": {"app": "nginx"}}]
+            })
+        );
+        assert_eq!(
+            response_snapshot(&response, Some(&original)),
+            response_snapshot(&response.clone(), Some(&original))
+        );
+    }
+
+    #[test]
+    fn golden_file_comparison() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test_data/golden/rejected.json"
+        );
+        let response = ValidationResponse::reject("privileged containers are not allowed")
+            .code(403)
+            .response();
+        assert_response_golden(path, &response, None);
+
+        let result = std::panic::catch_unwind(|| {
+            let accepted = ValidationResponse::accept().response();
+            compare_golden(path.as_ref(), &response_snapshot(&accepted, None), false)
+        });
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("-  \"accepted\": false,\n+  \"accepted\": true,\n"));
+    }
+
+    #[test]
+    fn diff_lines() {
+        assert_eq!(line_diff("a\nb\nc", "a\nc\nd"), " a\n-b\n c\n+d");
+    }
+}
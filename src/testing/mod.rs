@@ -0,0 +1,561 @@
+//! Utilities to test policies natively, without building them to WebAssembly
+//! and without running them with `kwctl`.
+//!
+//! The [`Harness`] loads an admission request fixture, combines it with the
+//! policy settings and invokes the `validate` function of the policy,
+//! returning the parsed [`ValidationResponse`].
+//!
+//! Fixtures can be written either in JSON or in YAML, and can contain either
+//! the `AdmissionRequest` object or a whole `AdmissionReview` object, like the
+//! ones produced by `kubectl` or consumed by `kwctl run`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use kubewarden_policy_sdk::testing::Harness;
+//! use serde_json::json;
+//!
+//! fn validate(payload: &[u8]) -> wapc_guest::CallResult {
+//!     // the policy implementation
+//!     # kubewarden_policy_sdk::accept_request()
+//! }
+//!
+//! let response = Harness::new(validate)
+//!     .settings(&json!({"allowedRegistries": ["ghcr.io"]}))
+//!     .run("test_data/pod_creation.json")
+//!     .unwrap();
+//! assert!(response.accepted);
+//! ```
+use crate::policy::Policy;
+use crate::response::ValidationResponse;
+use crate::settings::SettingsValidationResponse;
+use anyhow::{anyhow, Context};
+#[cfg(feature = "time")]
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+mod assertions;
+pub mod bench;
+mod builder;
+#[cfg(feature = "cluster-context")]
+mod cluster;
+#[cfg(feature = "net")]
+mod dns;
+#[cfg(feature = "e2e")]
+pub mod e2e;
+pub mod fuzz;
+pub mod generate;
+pub mod golden;
+#[cfg(feature = "verification")]
+mod sigstore;
+
+pub use assertions::{
+    assert_audit_annotation, assert_audit_annotations, assert_no_warnings, assert_warning_contains,
+    assert_warnings,
+};
+pub use builder::AdmissionRequestBuilder;
+#[cfg(feature = "cluster-context")]
+pub use cluster::FakeCluster;
+#[cfg(feature = "net")]
+pub use dns::{FakeResolver, Lookup};
+#[cfg(feature = "verification")]
+pub use sigstore::{FakeSigstore, Signer, GITHUB_ACTIONS_ISSUER};
+
+/// The signature of the waPC `validate` and `validate_settings` functions
+pub type GuestFn = fn(&[u8]) -> wapc_guest::CallResult;
+
+/// Load a fixture file. Files with the `.yaml` or `.yml` extension are
+/// parsed as YAML, all the other ones as JSON
+pub fn load_fixture<P: AsRef<Path>>(path: P) -> anyhow::Result<Value> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read fixture {}", path.display()))?;
+
+    let is_yaml = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    if is_yaml {
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("cannot parse YAML fixture {}", path.display()))
+    } else {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("cannot parse JSON fixture {}", path.display()))
+    }
+}
+
+/// Load a settings fixture, either JSON or YAML, and decode it
+pub fn load_settings_fixture<S: DeserializeOwned, P: AsRef<Path>>(path: P) -> anyhow::Result<S> {
+    let path = path.as_ref();
+    let settings = load_fixture(path)?;
+    serde_json::from_value(settings)
+        .with_context(|| format!("cannot decode settings fixture {}", path.display()))
+}
+
+/// Load all the Kubernetes objects stored inside of a manifest file, as
+/// produced by `kubectl get -o yaml`. Multi-document YAML files are
+/// supported, objects of kind `List` are replaced by their items
+pub fn load_manifests<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<Value>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("cannot read manifest {}", path.display()))?;
+
+    let documents = crate::yaml::documents(&contents)
+        .with_context(|| format!("cannot parse manifest {}", path.display()))?;
+    let mut objects = Vec::new();
+    for object in documents {
+        match object {
+            Value::Object(mut list) if list.get("kind") == Some(&json!("List")) => {
+                if let Some(Value::Array(items)) = list.remove("items") {
+                    objects.extend(items);
+                }
+            }
+            object => objects.push(object),
+        }
+    }
+    Ok(objects)
+}
+
+/// Load an admission request fixture. When the fixture holds an
+/// `AdmissionReview` object, its `request` is returned
+pub fn load_request_fixture<P: AsRef<Path>>(path: P) -> anyhow::Result<Value> {
+    let fixture = load_fixture(path)?;
+    Ok(unwrap_admission_review(fixture))
+}
+
+fn unwrap_admission_review(fixture: Value) -> Value {
+    match fixture {
+        Value::Object(mut review)
+            if review.get("kind") == Some(&json!("AdmissionReview"))
+                && review.contains_key("request") =>
+        {
+            review.remove("request").unwrap_or_default()
+        }
+        other => other,
+    }
+}
+
+/// The JSON Patch the Kubewarden host sends to the API server for the given
+/// response, computed between the `original` object and the mutated one.
+/// Returns `None` when the response does not mutate the object
+pub fn response_patch(original: &Value, response: &ValidationResponse) -> Option<Value> {
+    response
+        .mutated_object
+        .as_ref()
+        .map(|mutated| crate::diff::json_patch(original, mutated))
+}
+
+/// Apply the JSON Patch produced by a mutating response to the `original`
+/// object, returning the object that would be persisted by the API server.
+///
+/// Mutation tests can then assert on the final object instead of on the
+/// patch operations. The original object is returned when the response does
+/// not mutate it, an error is returned when the request has been rejected.
+pub fn apply_patch(original: &Value, response: &ValidationResponse) -> anyhow::Result<Value> {
+    if !response.accepted {
+        return Err(anyhow!(
+            "the request has been rejected: {}",
+            response.message.as_deref().unwrap_or_default()
+        ));
+    }
+
+    match response_patch(original, response) {
+        Some(patch) => Ok(crate::diff::apply_patch(original, &patch)?),
+        None => Ok(original.clone()),
+    }
+}
+
+/// Freeze the time returned by [`time::now`](crate::time::now) on the
+/// current thread, to test expiry and TTL logic deterministically
+#[cfg(feature = "time")]
+pub fn set_now(now: DateTime<Utc>) {
+    crate::time::set_now(Some(now));
+}
+
+/// Move the frozen time forward by the given duration. The clock is frozen
+/// at the current time when it was not frozen yet
+#[cfg(feature = "time")]
+pub fn advance_now(duration: crate::time::Duration) {
+    let now = crate::time::now() + duration.to_chrono();
+    crate::time::set_now(Some(now));
+}
+
+/// Make [`time::now`](crate::time::now) return the real time again
+#[cfg(feature = "time")]
+pub fn clear_now() {
+    crate::time::set_now(None);
+}
+
+/// Run `f` with [`time::now`](crate::time::now) frozen at the given time.
+/// The previous clock is restored afterwards, even when `f` panics
+#[cfg(feature = "time")]
+pub fn with_now<R>(now: DateTime<Utc>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<DateTime<Utc>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            crate::time::set_now(self.0);
+        }
+    }
+
+    let _restore = Restore(crate::time::set_now(Some(now)));
+    f()
+}
+
+/// Invoke the `validate_settings` function of a policy with the given
+/// settings and ensure they are accepted, when `expected_error` is `None`,
+/// or rejected with an error containing `expected_error`.
+///
+/// Settings that cannot be decoded are considered rejected too.
+///
+/// # Panics
+///
+/// Panics when the outcome of the validation is not the expected one
+pub fn assert_settings_validation(
+    validate_settings: GuestFn,
+    settings: &Value,
+    expected_error: Option<&str>,
+) {
+    let payload = serde_json::to_vec(settings).expect("cannot serialize settings");
+    let outcome = validate_settings(&payload)
+        .map_err(|e| e.to_string())
+        .and_then(|response| {
+            let response: SettingsValidationResponse = serde_json::from_slice(&response)
+                .expect("cannot decode the settings validation response");
+            if response.valid {
+                Ok(())
+            } else {
+                Err(response.message.unwrap_or_default())
+            }
+        });
+
+    match (outcome, expected_error) {
+        (Ok(()), None) => {}
+        (Err(error), Some(expected)) if error.contains(expected) => {}
+        (Ok(()), Some(expected)) => panic!(
+            "settings {} have been accepted, expected an error containing `{}`",
+            settings, expected
+        ),
+        (Err(error), expected) => panic!(
+            "settings {} have been rejected with `{}`, expected {}",
+            settings,
+            error,
+            expected
+                .map(|e| format!("an error containing `{}`", e))
+                .unwrap_or_else(|| "them to be valid".to_string())
+        ),
+    }
+}
+
+/// Generate a test for each of the given settings, checking whether they are
+/// accepted or rejected by the `validate_settings` function of the policy.
+///
+/// Rejected settings are declared with `Err("...")`, where the string must be
+/// contained inside of the error message. See [`testing::assert_settings_validation`](crate::testing::assert_settings_validation).
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::{settings::Validatable, settings_validation_tests};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Default)]
+/// struct Settings {
+///     registries: Vec<String>,
+/// }
+///
+/// impl Validatable for Settings {
+///     fn validate(&self) -> Result<(), String> {
+///         if self.registries.is_empty() {
+///             Err("registries cannot be empty".to_string())
+///         } else {
+///             Ok(())
+///         }
+///     }
+/// }
+///
+/// settings_validation_tests!(kubewarden_policy_sdk::validate_settings::<Settings>, {
+///     valid: serde_json::json!({"registries": ["ghcr.io"]}) => Ok,
+///     empty: serde_json::json!({"registries": []}) => Err("cannot be empty"),
+///     wrong_type: serde_json::json!({"registries": "ghcr.io"}) => Err("invalid type"),
+/// });
+/// ```
+#[macro_export]
+macro_rules! settings_validation_tests {
+    (@expected Ok) => {
+        None
+    };
+    (@expected Err($error:expr)) => {
+        Some($error)
+    };
+    ($validate_settings:expr, {
+        $($name:ident : $settings:expr => $outcome:ident $(($error:expr))?),* $(,)?
+    }) => {
+        $(
+            #[test]
+            fn $name() {
+                $crate::testing::assert_settings_validation(
+                    $validate_settings,
+                    &$settings,
+                    $crate::settings_validation_tests!(@expected $outcome $(($error))?),
+                );
+            }
+        )*
+    };
+}
+
+/// Invokes the policy functions the same way a Kubewarden host does
+#[derive(Debug, Clone)]
+pub struct Harness {
+    validate: GuestFn,
+    validate_settings: Option<GuestFn>,
+    settings: Value,
+}
+
+impl Harness {
+    /// Create a harness invoking the given `validate` function. The policy
+    /// settings default to an empty object
+    pub fn new(validate: GuestFn) -> Self {
+        Harness {
+            validate,
+            validate_settings: None,
+            settings: json!({}),
+        }
+    }
+
+    /// Create a harness for a policy implementing the [`Policy`] trait
+    pub fn for_policy<P: Policy>() -> Self {
+        Harness {
+            validate: crate::policy::validate::<P>,
+            validate_settings: Some(crate::policy::validate_settings::<P>),
+            settings: json!({}),
+        }
+    }
+
+    /// The `validate_settings` function of the policy, used by [`Harness::check_settings`]
+    pub fn validate_settings_fn(mut self, validate_settings: GuestFn) -> Self {
+        self.validate_settings = Some(validate_settings);
+        self
+    }
+
+    /// The settings given to the policy
+    ///
+    /// # Panics
+    ///
+    /// Panics when the settings cannot be serialized to JSON
+    pub fn settings<S: Serialize + ?Sized>(mut self, settings: &S) -> Self {
+        self.settings = serde_json::to_value(settings).expect("cannot serialize settings");
+        self
+    }
+
+    /// Load the settings given to the policy from a JSON or YAML file
+    pub fn settings_file<P: AsRef<Path>>(mut self, path: P) -> anyhow::Result<Self> {
+        self.settings = load_fixture(path)?;
+        Ok(self)
+    }
+
+    /// Evaluate the admission request stored inside of the given fixture file
+    pub fn run<P: AsRef<Path>>(&self, fixture: P) -> anyhow::Result<ValidationResponse> {
+        let request = load_request_fixture(fixture)?;
+        self.run_request(&request)
+    }
+
+    /// Evaluate the given admission request
+    pub fn run_request<R: Serialize + ?Sized>(
+        &self,
+        request: &R,
+    ) -> anyhow::Result<ValidationResponse> {
+        let payload = serde_json::to_vec(&json!({
+            "settings": self.settings,
+            "request": request,
+        }))?;
+        let response_raw =
+            (self.validate)(&payload).map_err(|e| anyhow!("validate failed: {}", e))?;
+
+        serde_json::from_slice(&response_raw).context("cannot decode the validation response")
+    }
+
+    /// Validate the settings of the harness with the `validate_settings`
+    /// function of the policy
+    pub fn check_settings(&self) -> anyhow::Result<SettingsValidationResponse> {
+        let validate_settings = self
+            .validate_settings
+            .ok_or_else(|| anyhow!("the validate_settings function has not been provided"))?;
+        let payload = serde_json::to_vec(&self.settings)?;
+        let response_raw =
+            validate_settings(&payload).map_err(|e| anyhow!("validate_settings failed: {}", e))?;
+
+        serde_json::from_slice(&response_raw)
+            .context("cannot decode the settings validation response")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::ValidationRequest;
+    use crate::settings::Validatable;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    struct Settings {
+        denied_namespaces: Vec<String>,
+    }
+
+    impl Validatable for Settings {
+        fn validate(&self) -> Result<(), String> {
+            if self.denied_namespaces.is_empty() {
+                Err("denied_namespaces cannot be empty".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    struct NamespacePolicy;
+
+    impl Policy for NamespacePolicy {
+        type Settings = Settings;
+
+        fn validate(request: &ValidationRequest<Settings>) -> wapc_guest::CallResult {
+            if request
+                .settings
+                .denied_namespaces
+                .contains(&request.request.namespace)
+            {
+                crate::reject_request(Some("namespace denied".to_string()), None, None, None)
+            } else {
+                crate::accept_request()
+            }
+        }
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/test_data/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn run_json_and_yaml_fixtures() {
+        let harness = Harness::for_policy::<NamespacePolicy>()
+            .settings(&json!({"denied_namespaces": ["kube-system"]}));
+
+        let response = harness.run(fixture("pod_creation.json")).unwrap();
+        assert!(response.accepted);
+
+        let response = harness.run(fixture("pod_creation_review.yaml")).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.unwrap(), "namespace denied");
+    }
+
+    #[test]
+    fn check_settings() {
+        let response = Harness::for_policy::<NamespacePolicy>()
+            .check_settings()
+            .unwrap();
+        assert!(!response.valid);
+
+        assert!(Harness::new(crate::policy::validate::<NamespacePolicy>)
+            .check_settings()
+            .is_err());
+    }
+
+    #[test]
+    fn yaml_settings_and_manifests() {
+        let settings: Settings = load_settings_fixture(fixture("yaml/settings.yaml")).unwrap();
+        assert_eq!(settings.denied_namespaces, vec!["kube-system"]);
+
+        let response = Harness::for_policy::<NamespacePolicy>()
+            .settings_file(fixture("yaml/settings.yaml"))
+            .unwrap()
+            .run(fixture("pod_creation_review.yaml"))
+            .unwrap();
+        assert!(!response.accepted);
+
+        let manifests = load_manifests(fixture("yaml/manifests.yaml")).unwrap();
+        let kinds: Vec<&str> = manifests
+            .iter()
+            .map(|m| m["kind"].as_str().unwrap())
+            .collect();
+        assert_eq!(kinds, vec!["Pod", "ConfigMap", "Secret"]);
+
+        let request = AdmissionRequestBuilder::create()
+            .object(&manifests[0])
+            .build();
+        assert_eq!(request.name, "nginx");
+        assert_eq!(request.kind.kind, "Pod");
+    }
+
+    #[test]
+    fn missing_fixture() {
+        let error = Harness::for_policy::<NamespacePolicy>()
+            .run(fixture("missing.json"))
+            .unwrap_err();
+        assert!(error.to_string().contains("cannot read fixture"));
+    }
+
+    #[test]
+    fn apply_response_patch() {
+        let pod = json!({"metadata": {"name": "nginx"}, "spec": {"containers": []}});
+        let mutated = json!({"metadata": {"name": "nginx", "labels": {"team": "a"}}, "spec": {"containers": []}});
+
+        let response = ValidationResponse::accept()
+            .mutate(mutated.clone())
+            .response();
+        assert_eq!(apply_patch(&pod, &response).unwrap(), mutated);
+        assert_eq!(
+            response_patch(&pod, &response).unwrap(),
+            json!([{"op": "add", "path": "/metadata/labels", "value": {"team": "a"}}])
+        );
+
+        let response = ValidationResponse::accept().response();
+        assert_eq!(apply_patch(&pod, &response).unwrap(), pod);
+        assert!(response_patch(&pod, &response).is_none());
+
+        let response = ValidationResponse::reject("denied").response();
+        assert_eq!(
+            apply_patch(&pod, &response).unwrap_err().to_string(),
+            "the request has been rejected: denied"
+        );
+    }
+
+    crate::settings_validation_tests!(crate::policy::validate_settings::<NamespacePolicy>, {
+        settings_accepted: json!({"denied_namespaces": ["kube-system"]}) => Ok,
+        settings_rejected: json!({"denied_namespaces": []}) => Err("cannot be empty"),
+        settings_not_decoded: json!({"denied_namespaces": 1}) => Err("invalid type"),
+    });
+
+    #[test]
+    #[should_panic(expected = "have been accepted, expected an error containing `empty`")]
+    fn settings_validation_mismatch() {
+        assert_settings_validation(
+            crate::policy::validate_settings::<NamespacePolicy>,
+            &json!({"denied_namespaces": ["default"]}),
+            Some("empty"),
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "time")]
+    fn frozen_clock() {
+        let frozen = crate::time::parse_timestamp("2024-01-01T10:00:00Z").unwrap();
+        let object = json!({"metadata": {"creationTimestamp": "2024-01-01T09:00:00Z"}});
+        let ttl: crate::time::Duration = "1h".parse().unwrap();
+
+        with_now(frozen, || {
+            assert_eq!(crate::time::now(), frozen);
+            assert!(!crate::time::is_older_than(&object, ttl, crate::time::now()).unwrap());
+
+            advance_now("1ns".parse().unwrap());
+            assert!(crate::time::is_older_than(&object, ttl, crate::time::now()).unwrap());
+        });
+        assert_ne!(crate::time::now(), frozen);
+
+        set_now(frozen);
+        assert_eq!(crate::time::now(), frozen);
+        clear_now();
+        assert!(crate::time::now() > frozen);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::host_capabilities::client::HostClient;
+use crate::host_capabilities::verification::VerificationResponse;
+use crate::host_capabilities::{SigstoreVerificationInputV1, SigstoreVerificationInputV2};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Issuer of the keyless signatures produced inside of GitHub Actions
+pub const GITHUB_ACTIONS_ISSUER: &str = "https://token.actions.githubusercontent.com";
+
+/// The identity that signed an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Signer {
+    /// Signature produced with the private key matching the given PEM
+    /// encoded public key
+    PubKey(String),
+    /// Keyless signature
+    Keyless {
+        /// The OIDC issuer
+        issuer: String,
+        /// The identity of the signer
+        subject: String,
+    },
+    /// Signature verifiable with the given PEM encoded certificate
+    Certificate(Vec<u8>),
+}
+
+/// Check whether a signer satisfies one of the verification requirements
+type SignerCheck<'a> = Box<dyn Fn(&Signer) -> bool + 'a>;
+
+#[derive(Debug, Clone)]
+struct Signature {
+    signer: Signer,
+    annotations: HashMap<String, String>,
+}
+
+/// A fake Sigstore verification backend, answering the `oci.v1/verify` and
+/// `oci.v2/verify` host capabilities.
+///
+/// Tests declare which identities signed which images, so that signature
+/// policies can exercise both the trusted and the untrusted code paths
+/// without network access and without real signatures. Like the real host,
+/// verifications that are not satisfied fail with an error.
+///
+/// # Example
+///
+/// ```
+/// use kubewarden_policy_sdk::host_capabilities::client::with_host_client;
+/// use kubewarden_policy_sdk::host_capabilities::verification::verify_keyless_github_actions;
+/// use kubewarden_policy_sdk::testing::FakeSigstore;
+/// use std::rc::Rc;
+///
+/// let sigstore = Rc::new(
+///     FakeSigstore::new().signed_by_github_actions("ghcr.io/kubewarden/policy:v1", "kubewarden", "policy"),
+/// );
+///
+/// with_host_client(sigstore, || {
+///     let trusted =
+///         verify_keyless_github_actions("ghcr.io/kubewarden/policy:v1", "kubewarden", None, None);
+///     assert!(trusted.unwrap().is_trusted);
+///
+///     let untrusted =
+///         verify_keyless_github_actions("ghcr.io/kubewarden/policy:v1", "someone-else", None, None);
+///     assert!(untrusted.is_err());
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeSigstore {
+    signatures: HashMap<String, Vec<Signature>>,
+    digests: HashMap<String, String>,
+}
+
+impl FakeSigstore {
+    /// Create a backend where no image is signed
+    pub fn new() -> Self {
+        FakeSigstore::default()
+    }
+
+    /// Declare `image` to be signed by `signer`, with the given annotations
+    pub fn signed(mut self, image: &str, signer: Signer, annotations: &[(&str, &str)]) -> Self {
+        self.signatures
+            .entry(image.to_string())
+            .or_default()
+            .push(Signature {
+                signer,
+                annotations: annotations
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            });
+        self
+    }
+
+    /// Declare `image` to be signed with the key matching the given public key
+    pub fn signed_with_key(self, image: &str, pub_key: &str) -> Self {
+        self.signed(image, Signer::PubKey(pub_key.to_string()), &[])
+    }
+
+    /// Declare `image` to be signed in keyless mode by the given identity
+    pub fn signed_keyless(self, image: &str, issuer: &str, subject: &str) -> Self {
+        self.signed(
+            image,
+            Signer::Keyless {
+                issuer: issuer.to_string(),
+                subject: subject.to_string(),
+            },
+            &[],
+        )
+    }
+
+    /// Declare `image` to be signed in keyless mode by a GitHub Actions
+    /// workflow of the given repository
+    pub fn signed_by_github_actions(self, image: &str, owner: &str, repo: &str) -> Self {
+        let subject = format!(
+            "https://github.com/{}/{}/.github/workflows/release.yml@refs/heads/main",
+            owner, repo
+        );
+        self.signed_keyless(image, GITHUB_ACTIONS_ISSUER, &subject)
+    }
+
+    /// Declare `image` to be signed with the given certificate
+    pub fn signed_with_certificate(self, image: &str, certificate: &[u8]) -> Self {
+        self.signed(image, Signer::Certificate(certificate.to_vec()), &[])
+    }
+
+    /// The digest returned for `image`. By default, images referenced by
+    /// digest return it and the other ones a digest derived from their name
+    pub fn digest(mut self, image: &str, digest: &str) -> Self {
+        self.digests.insert(image.to_string(), digest.to_string());
+        self
+    }
+
+    fn image_digest(&self, image: &str) -> String {
+        if let Some(digest) = self.digests.get(image) {
+            return digest.clone();
+        }
+        if let Some((_, digest)) = image.split_once('@') {
+            return digest.to_string();
+        }
+        let mut hasher = DefaultHasher::new();
+        image.hash(&mut hasher);
+        format!("sha256:{:064x}", hasher.finish())
+    }
+
+    /// Ensure every one of the `required` checks is satisfied by at least one
+    /// signature of `image` carrying the required annotations
+    fn verify(
+        &self,
+        image: &str,
+        required: Vec<SignerCheck<'_>>,
+        annotations: Option<HashMap<String, String>>,
+    ) -> wapc_guest::CallResult {
+        let annotations = annotations.unwrap_or_default();
+        let signatures = self.signatures.get(image).map(Vec::as_slice).unwrap_or(&[]);
+
+        let satisfied = !required.is_empty()
+            && required.iter().all(|check| {
+                signatures.iter().any(|signature| {
+                    check(&signature.signer)
+                        && annotations
+                            .iter()
+                            .all(|(k, v)| signature.annotations.get(k) == Some(v))
+                })
+            });
+        if !satisfied {
+            return Err(format!(
+                "Image verification failed: missing signatures for {}",
+                image
+            )
+            .into());
+        }
+
+        Ok(serde_json::to_vec(&VerificationResponse {
+            is_trusted: true,
+            digest: self.image_digest(image),
+        })?)
+    }
+
+    fn verify_v2(&self, input: SigstoreVerificationInputV2) -> wapc_guest::CallResult {
+        match input {
+            SigstoreVerificationInputV2::SigstorePubKeyVerify {
+                image,
+                pub_keys,
+                annotations,
+            } => self.verify(
+                &image,
+                pub_keys.into_iter().map(pub_key_check).collect(),
+                annotations,
+            ),
+            SigstoreVerificationInputV2::SigstoreKeylessVerify {
+                image,
+                keyless,
+                annotations,
+            } => self.verify(
+                &image,
+                keyless
+                    .into_iter()
+                    .map(|info| keyless_check(info.issuer, move |s| s == info.subject))
+                    .collect(),
+                annotations,
+            ),
+            SigstoreVerificationInputV2::SigstoreKeylessPrefixVerify {
+                image,
+                keyless_prefix,
+                annotations,
+            } => self.verify(
+                &image,
+                keyless_prefix
+                    .into_iter()
+                    .map(|info| {
+                        let prefix = if info.url_prefix.ends_with('/') {
+                            info.url_prefix
+                        } else {
+                            format!("{}/", info.url_prefix)
+                        };
+                        keyless_check(info.issuer, move |s| s.starts_with(&prefix))
+                    })
+                    .collect(),
+                annotations,
+            ),
+            SigstoreVerificationInputV2::SigstoreGithubActionsVerify {
+                image,
+                owner,
+                repo,
+                annotations,
+            } => {
+                let prefix = match repo {
+                    Some(repo) => format!("https://github.com/{}/{}/", owner, repo),
+                    None => format!("https://github.com/{}/", owner),
+                };
+                self.verify(
+                    &image,
+                    vec![keyless_check(GITHUB_ACTIONS_ISSUER.to_string(), move |s| {
+                        s.starts_with(&prefix)
+                    })],
+                    annotations,
+                )
+            }
+            SigstoreVerificationInputV2::SigstoreCertificateVerify {
+                image,
+                certificate,
+                annotations,
+                ..
+            } => self.verify(
+                &image,
+                vec![Box::new(
+                    move |signer| matches!(signer, Signer::Certificate(c) if *c == certificate),
+                )],
+                annotations,
+            ),
+        }
+    }
+}
+
+fn pub_key_check<'a>(key: String) -> SignerCheck<'a> {
+    Box::new(move |signer| matches!(signer, Signer::PubKey(k) if k.trim() == key.trim()))
+}
+
+fn keyless_check<'a>(
+    issuer: String,
+    subject_matches: impl Fn(&str) -> bool + 'a,
+) -> SignerCheck<'a> {
+    Box::new(
+        move |signer| matches!(signer, Signer::Keyless { issuer: i, subject } if *i == issuer && subject_matches(subject)),
+    )
+}
+
+impl HostClient for FakeSigstore {
+    fn call(&self, namespace: &str, operation: &str, payload: &[u8]) -> wapc_guest::CallResult {
+        match (namespace, operation) {
+            ("oci", "v2/verify") => self.verify_v2(serde_json::from_slice(payload)?),
+            ("oci", "v1/verify") => {
+                let input = match serde_json::from_slice(payload)? {
+                    SigstoreVerificationInputV1::SigstorePubKeyVerify {
+                        image,
+                        pub_keys,
+                        annotations,
+                    } => SigstoreVerificationInputV2::pub_key(&image, pub_keys, annotations),
+                    SigstoreVerificationInputV1::SigstoreKeylessVerify {
+                        image,
+                        keyless,
+                        annotations,
+                    } => SigstoreVerificationInputV2::keyless(&image, keyless, annotations),
+                };
+                self.verify_v2(input)
+            }
+            _ => Err(format!("capability {}.{} not supported", namespace, operation).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::with_host_client;
+    use crate::host_capabilities::verification::{
+        verify_certificate, verify_keyless_exact_match, verify_keyless_prefix_match,
+        verify_pub_keys_image, verify_v1, KeylessInfo, KeylessPrefixInfo,
+    };
+    use std::rc::Rc;
+
+    const IMAGE: &str = "ghcr.io/kubewarden/policy:v1";
+
+    #[test]
+    fn pub_keys_and_annotations() {
+        let sigstore = FakeSigstore::new()
+            .signed(
+                IMAGE,
+                Signer::PubKey("key-1".to_string()),
+                &[("env", "prod")],
+            )
+            .signed_with_key(IMAGE, "key-2")
+            .digest(IMAGE, "sha256:1234");
+
+        with_host_client(Rc::new(sigstore), || {
+            let response = verify_pub_keys_image(IMAGE, &["key-1".to_string()], None).unwrap();
+            assert_eq!(response.digest, "sha256:1234");
+
+            let keys = vec!["key-1".to_string(), "key-2".to_string()];
+            assert!(verify_pub_keys_image(IMAGE, &keys, None).is_ok());
+
+            let annotations = HashMap::from([("env".to_string(), "prod".to_string())]);
+            assert!(verify_pub_keys_image(IMAGE, &keys, Some(&annotations)).is_err());
+            assert!(
+                verify_pub_keys_image(IMAGE, &["key-1".to_string()], Some(&annotations)).is_ok()
+            );
+
+            assert!(verify_pub_keys_image(IMAGE, &["key-3".to_string()], None).is_err());
+            assert!(verify_pub_keys_image("busybox", &["key-1".to_string()], None).is_err());
+            assert!(verify_pub_keys_image(IMAGE, &[], None).is_err());
+
+            let v1 = SigstoreVerificationInputV1::pub_key(IMAGE, vec!["key-2".to_string()], None);
+            assert!(verify_v1(&v1).unwrap().is_trusted);
+        });
+    }
+
+    #[test]
+    fn keyless_signatures() {
+        let sigstore = FakeSigstore::new()
+            .signed_keyless(IMAGE, "https://accounts.google.com", "alice@example.com")
+            .signed_keyless(
+                IMAGE,
+                "https://github.com/login/oauth",
+                "https://example.com/team/bob",
+            )
+            .signed_with_certificate(IMAGE, b"cert");
+
+        with_host_client(Rc::new(sigstore), || {
+            let keyless = |issuer: &str, subject: &str| {
+                vec![KeylessInfo {
+                    issuer: issuer.to_string(),
+                    subject: subject.to_string(),
+                }]
+            };
+            assert!(verify_keyless_exact_match(
+                IMAGE,
+                &keyless("https://accounts.google.com", "alice@example.com"),
+                None
+            )
+            .is_ok());
+            assert!(verify_keyless_exact_match(
+                IMAGE,
+                &keyless("https://accounts.google.com", "eve@example.com"),
+                None
+            )
+            .is_err());
+
+            let prefix = |url_prefix: &str| {
+                vec![KeylessPrefixInfo {
+                    issuer: "https://github.com/login/oauth".to_string(),
+                    url_prefix: url_prefix.to_string(),
+                }]
+            };
+            assert!(
+                verify_keyless_prefix_match(IMAGE, &prefix("https://example.com/team"), None)
+                    .is_ok()
+            );
+            assert!(
+                verify_keyless_prefix_match(IMAGE, &prefix("https://example.com/te"), None)
+                    .is_err()
+            );
+
+            assert!(verify_certificate(IMAGE, "cert", None, true, None).is_ok());
+            assert!(verify_certificate(IMAGE, "other", None, true, None).is_err());
+        });
+    }
+}
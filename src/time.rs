@@ -0,0 +1,476 @@
+//! Parsing and formatting of Kubernetes durations and timestamps.
+//!
+//! Durations use the notation of Go's `time.ParseDuration` (e.g. `1h30m`,
+//! `500ms`), which is the one used by Kubernetes objects and by most of
+//! the tools of the ecosystem. Timestamps are RFC 3339 strings, like the
+//! `metadata.creationTimestamp` field of all the Kubernetes objects.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::time::{parse_timestamp, Duration};
+//! use serde_json::json;
+//!
+//! let ttl: Duration = "1h30m".parse().unwrap();
+//! assert_eq!(ttl.as_secs(), 5400);
+//!
+//! let object = json!({"metadata": {"creationTimestamp": "2024-01-01T10:00:00Z"}});
+//! let now = parse_timestamp("2024-01-01T12:00:00Z").unwrap();
+//! assert!(kubewarden_policy_sdk::time::is_older_than(&object, ttl, now).unwrap());
+//! ```
+use crate::error::SdkError;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::Cell;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+const NANOSECOND: i64 = 1;
+const MICROSECOND: i64 = 1_000 * NANOSECOND;
+const MILLISECOND: i64 = 1_000 * MICROSECOND;
+const SECOND: i64 = 1_000 * MILLISECOND;
+const MINUTE: i64 = 60 * SECOND;
+const HOUR: i64 = 60 * MINUTE;
+
+/// Units accepted by [`Duration`], with their length in nanoseconds
+const UNITS: &[(&str, i64)] = &[
+    ("ns", NANOSECOND),
+    ("us", MICROSECOND),
+    ("µs", MICROSECOND),
+    ("μs", MICROSECOND),
+    ("ms", MILLISECOND),
+    ("s", SECOND),
+    ("m", MINUTE),
+    ("h", HOUR),
+];
+
+/// A signed duration with nanosecond precision, like Go's `time.Duration`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    nanos: i64,
+}
+
+impl Duration {
+    /// A duration of zero length
+    pub const ZERO: Duration = Duration { nanos: 0 };
+
+    /// Create a duration from a number of nanoseconds
+    pub fn from_nanos(nanos: i64) -> Self {
+        Duration { nanos }
+    }
+
+    /// Create a duration from a number of seconds
+    pub fn from_secs(secs: i64) -> Self {
+        Duration {
+            nanos: secs.saturating_mul(SECOND),
+        }
+    }
+
+    /// The duration, in nanoseconds
+    pub fn as_nanos(&self) -> i64 {
+        self.nanos
+    }
+
+    /// The duration, in whole seconds
+    pub fn as_secs(&self) -> i64 {
+        self.nanos / SECOND
+    }
+
+    /// The duration, in seconds
+    pub fn as_secs_f64(&self) -> f64 {
+        self.nanos as f64 / SECOND as f64
+    }
+
+    /// Returns true when the duration is negative
+    pub fn is_negative(&self) -> bool {
+        self.nanos < 0
+    }
+
+    /// Convert into a [`std::time::Duration`], returns `None` when the duration is negative
+    pub fn to_std(&self) -> Option<std::time::Duration> {
+        u64::try_from(self.nanos)
+            .ok()
+            .map(std::time::Duration::from_nanos)
+    }
+
+    /// Convert into a [`chrono::TimeDelta`], which can be used with timestamps
+    pub fn to_chrono(&self) -> chrono::TimeDelta {
+        chrono::TimeDelta::nanoseconds(self.nanos)
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_add(other.nanos))
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_sub(other.nanos))
+    }
+}
+
+impl Neg for Duration {
+    type Output = Duration;
+
+    fn neg(self) -> Duration {
+        Duration::from_nanos(self.nanos.saturating_neg())
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    fn from(duration: std::time::Duration) -> Self {
+        Duration::from_nanos(i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX))
+    }
+}
+
+impl TryFrom<chrono::TimeDelta> for Duration {
+    type Error = SdkError;
+
+    fn try_from(delta: chrono::TimeDelta) -> Result<Self, Self::Error> {
+        delta
+            .num_nanoseconds()
+            .map(Duration::from_nanos)
+            .ok_or_else(|| SdkError::InvalidInput(format!("Duration out of range: {}", delta)))
+    }
+}
+
+/// Parse strings like `300ms`, `-1.5h` or `2h45m`, using the same rules of
+/// Go's `time.ParseDuration`
+impl FromStr for Duration {
+    type Err = SdkError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SdkError::InvalidInput(format!("Invalid duration: {}", s));
+
+        let (negative, mut rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if rest == "0" {
+            return Ok(Duration::ZERO);
+        }
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut total: i64 = 0;
+        while !rest.is_empty() {
+            let number_end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .ok_or_else(invalid)?;
+            let (number, tail) = rest.split_at(number_end);
+            let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+            if integer.is_empty() && fraction.is_empty() {
+                return Err(invalid());
+            }
+
+            let unit_end = tail
+                .find(|c: char| c.is_ascii_digit() || c == '.')
+                .unwrap_or(tail.len());
+            let (unit, tail) = tail.split_at(unit_end);
+            let scale = UNITS
+                .iter()
+                .find(|(name, _)| *name == unit)
+                .map(|(_, scale)| *scale)
+                .ok_or_else(invalid)?;
+
+            let mut value = if integer.is_empty() {
+                0
+            } else {
+                integer
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|v| v.checked_mul(scale))
+                    .ok_or_else(invalid)?
+            };
+            // the fractional part is truncated to the nanosecond
+            let mut fraction_scale = scale;
+            for digit in fraction.chars() {
+                fraction_scale /= 10;
+                if fraction_scale == 0 {
+                    break;
+                }
+                let digit = i64::from(digit.to_digit(10).ok_or_else(invalid)?);
+                value = value
+                    .checked_add(digit * fraction_scale)
+                    .ok_or_else(invalid)?;
+            }
+
+            total = total.checked_add(value).ok_or_else(invalid)?;
+            rest = tail;
+        }
+
+        Ok(Duration::from_nanos(if negative { -total } else { total }))
+    }
+}
+
+/// Format the duration like Go does, e.g. `1h30m0s`, `1.5s` or `300ms`
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.nanos < 0 { "-" } else { "" };
+        let nanos = self.nanos.unsigned_abs();
+
+        if nanos == 0 {
+            return write!(f, "0s");
+        }
+        if nanos < SECOND as u64 {
+            let (unit, scale) = if nanos < MICROSECOND as u64 {
+                ("ns", NANOSECOND)
+            } else if nanos < MILLISECOND as u64 {
+                ("µs", MICROSECOND)
+            } else {
+                ("ms", MILLISECOND)
+            };
+            return write!(
+                f,
+                "{}{}{}",
+                sign,
+                format_fraction(nanos, scale as u64),
+                unit
+            );
+        }
+
+        let hours = nanos / HOUR as u64;
+        let minutes = (nanos % HOUR as u64) / MINUTE as u64;
+        let seconds = format_fraction(nanos % MINUTE as u64, SECOND as u64);
+        write!(f, "{}", sign)?;
+        if hours > 0 {
+            write!(f, "{}h{}m", hours, minutes)?;
+        } else if minutes > 0 {
+            write!(f, "{}m", minutes)?;
+        }
+        write!(f, "{}s", seconds)
+    }
+}
+
+/// Format `value / scale` without trailing zeros
+fn format_fraction(value: u64, scale: u64) -> String {
+    let integer = value / scale;
+    let fraction = value % scale;
+    if fraction == 0 {
+        return integer.to_string();
+    }
+    let digits = scale.ilog10() as usize;
+    let fraction = format!("{:0width$}", fraction, width = digits);
+    format!("{}.{}", integer, fraction.trim_end_matches('0'))
+}
+
+impl Serialize for Duration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Duration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse a RFC 3339 timestamp, like `2024-01-01T10:00:00Z`
+pub fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>, SdkError> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| SdkError::InvalidInput(format!("Invalid timestamp {}: {}", timestamp, e)))
+}
+
+/// Format a timestamp the same way Kubernetes does, e.g. `2024-01-01T10:00:00Z`.
+/// Sub-second precision is preserved only when present
+pub fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+thread_local! {
+    static NOW: Cell<Option<DateTime<Utc>>> = const { Cell::new(None) };
+}
+
+/// The current time, as reported by the host running the policy.
+///
+/// Panics when the time cannot be read, use [`try_now`] to handle the
+/// error.
+pub fn now() -> DateTime<Utc> {
+    try_now().unwrap_or_else(|e| panic!("cannot read the current time: {}", e))
+}
+
+/// The current time, as reported by the host running the policy.
+///
+/// The time is read with the [`host_capabilities::time`](crate::host_capabilities::time)
+/// capability, hence it's the same for all the checks of an evaluation.
+/// Only the hosts not implementing the capability are served by the clock
+/// of the wasm runtime, when the target has one: all the other errors are
+/// returned, as well as the ones of `wasm32-unknown-unknown`, which has no
+/// clock.
+///
+/// Tests can freeze the value returned by this function with
+/// `testing::set_now`, available when the `testing` feature is enabled.
+pub fn try_now() -> Result<DateTime<Utc>, SdkError> {
+    if let Some(now) = NOW.with(|now| now.get()) {
+        return Ok(now);
+    }
+    match host_now() {
+        Err(e) if e.is_unsupported_capability() && crate::clock::AVAILABLE => {
+            Ok(DateTime::from(std::time::SystemTime::now()))
+        }
+        result => result,
+    }
+}
+
+/// The current time of the host, without falling back to the clock of the
+/// wasm runtime. The time frozen by the tests is ignored
+pub fn host_now() -> Result<DateTime<Utc>, SdkError> {
+    crate::host_capabilities::time::now().map(DateTime::from)
+}
+
+/// Make [`now`] return the given time, or the real time when `None`
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn set_now(now: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    NOW.with(|cell| cell.replace(now))
+}
+
+/// Returns the `metadata.creationTimestamp` of the given Kubernetes object,
+/// or `None` when it's not set (e.g. objects being created)
+pub fn creation_timestamp(object: &serde_json::Value) -> Result<Option<DateTime<Utc>>, SdkError> {
+    object["metadata"]["creationTimestamp"]
+        .as_str()
+        .map(parse_timestamp)
+        .transpose()
+}
+
+/// Returns the time elapsed between the creation of the object and `now`,
+/// or `None` when the creation timestamp is not set
+pub fn age(object: &serde_json::Value, now: DateTime<Utc>) -> Result<Option<Duration>, SdkError> {
+    creation_timestamp(object)?
+        .map(|created| Duration::try_from(now - created))
+        .transpose()
+}
+
+/// Returns true when the object has been created more than `duration` before `now`.
+/// Objects without a creation timestamp are considered new
+pub fn is_older_than(
+    object: &serde_json::Value,
+    duration: Duration,
+    now: DateTime<Utc>,
+) -> Result<bool, SdkError> {
+    Ok(age(object, now)?.is_some_and(|age| age > duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host_capabilities::client::{with_host_client, MockHostClient};
+    use serde_json::json;
+    use std::rc::Rc;
+
+    fn d(s: &str) -> Duration {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_duration() {
+        assert_eq!(d("1h30m"), Duration::from_secs(5400));
+        assert_eq!(d("1.5h"), Duration::from_secs(5400));
+        assert_eq!(d("300ms").as_nanos(), 300 * MILLISECOND);
+        assert_eq!(
+            d("2h45m30.5s").as_nanos(),
+            9930 * SECOND + 500 * MILLISECOND
+        );
+        assert_eq!(d("-1m"), -Duration::from_secs(60));
+        assert_eq!(d("1µs"), d("1us"));
+        assert_eq!(d(".5s").as_nanos(), 500 * MILLISECOND);
+        assert_eq!(d("0"), Duration::ZERO);
+
+        for invalid in ["", "1", "1d", "h", "1h-1m", "9999999999h", "."] {
+            assert!(invalid.parse::<Duration>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn format_duration() {
+        assert_eq!(d("1h30m").to_string(), "1h30m0s");
+        assert_eq!(d("90s").to_string(), "1m30s");
+        assert_eq!(d("1.5s").to_string(), "1.5s");
+        assert_eq!(d("300ms").to_string(), "300ms");
+        assert_eq!(d("1500us").to_string(), "1.5ms");
+        assert_eq!(d("-2h").to_string(), "-2h0m0s");
+        assert_eq!(Duration::ZERO.to_string(), "0s");
+
+        let roundtrip: Duration = serde_json::from_str(r#""10m""#).unwrap();
+        assert_eq!(serde_json::to_string(&roundtrip).unwrap(), r#""10m0s""#);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            d("1.5s").to_std(),
+            Some(std::time::Duration::from_millis(1500))
+        );
+        assert_eq!(d("-1s").to_std(), None);
+        assert_eq!(Duration::from(std::time::Duration::from_secs(3)), d("3s"));
+        assert_eq!(d("1m").to_chrono(), chrono::TimeDelta::seconds(60));
+    }
+
+    #[test]
+    fn timestamps() {
+        let ts = parse_timestamp("2024-01-01T10:00:00+02:00").unwrap();
+        assert_eq!(format_timestamp(&ts), "2024-01-01T08:00:00Z");
+        assert!(parse_timestamp("yesterday").is_err());
+
+        let object = json!({"metadata": {"creationTimestamp": "2024-01-01T08:00:00Z"}});
+        let now = parse_timestamp("2024-01-01T09:00:00Z").unwrap();
+        assert_eq!(age(&object, now).unwrap(), Some(d("1h")));
+        assert!(is_older_than(&object, d("30m"), now).unwrap());
+        assert!(!is_older_than(&object, d("2h"), now).unwrap());
+
+        let new_object = json!({"metadata": {"name": "new"}});
+        assert_eq!(creation_timestamp(&new_object).unwrap(), None);
+        assert!(!is_older_than(&new_object, Duration::ZERO, now).unwrap());
+    }
+
+    #[test]
+    fn now_is_read_from_the_host() {
+        let client = Rc::new(MockHostClient::new().respond(
+            "host",
+            "v1/now",
+            &json!({"unixNanos": 1_704_103_200_500_000_000i64}),
+        ));
+        with_host_client(client, || {
+            let now = now();
+            assert_eq!(format_timestamp(&now), "2024-01-01T10:00:00.500Z");
+            assert_eq!(host_now().unwrap(), now);
+        });
+
+        // hosts without the capability are served by the local clock
+        let client =
+            Rc::new(MockHostClient::new().fail("host", "v1/now", "unknown operation: v1/now"));
+        let before = DateTime::from(std::time::SystemTime::now());
+        let fallback = with_host_client(client.clone(), try_now).unwrap();
+        assert!(fallback >= before);
+        assert!(with_host_client(client, host_now).is_err());
+
+        // the other errors are not hidden
+        let client = Rc::new(MockHostClient::new().fail(
+            "host",
+            "v1/now",
+            r#"{"code": "deadline_exceeded", "message": "timeout", "retryable": true}"#,
+        ));
+        assert!(with_host_client(client.clone(), try_now)
+            .unwrap_err()
+            .is_retryable());
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            with_host_client(client, now)
+        }));
+        assert!(result.is_err());
+
+        let client = Rc::new(MockHostClient::new().respond("host", "v1/now", &"yesterday"));
+        assert!(with_host_client(client, try_now).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! W3C [Trace Context](https://www.w3.org/TR/trace-context/) propagation.
+//!
+//! The host can supply the `traceparent` of the admission request being
+//! evaluated, using the `traceparent` key of the validation payload. The SDK
+//! decodes it when the [`ValidationRequest`](crate::request::ValidationRequest)
+//! is created, and makes it available through [`current`] for the rest of
+//! the evaluation.
+//!
+//! All the log events emitted through the [`logging`](crate::logging) module,
+//! including the ones of the `log` facade bridge, carry the `trace_id` and
+//! the `span_id` of the current trace context. This allows to correlate the
+//! evaluations of a policy with the distributed traces of the host.
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fmt;
+use std::str::FromStr;
+
+thread_local! {
+    static CURRENT: RefCell<Option<TraceParent>> = const { RefCell::new(None) };
+}
+
+/// A W3C `traceparent` header, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TraceParent {
+    /// Version of the format, always `00`
+    pub version: u8,
+    /// Id of the whole trace, 32 lowercase hex characters
+    pub trace_id: String,
+    /// Id of the parent span, 16 lowercase hex characters
+    pub span_id: String,
+    /// Trace flags, e.g. `01` when the trace is sampled
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Returns true when the trace is sampled by the host
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 == 0x01
+    }
+}
+
+fn is_hex_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        && id.bytes().any(|b| b != b'0')
+}
+
+impl FromStr for TraceParent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid traceparent `{}`", s);
+        let parts: Vec<&str> = s.trim().split('-').collect();
+        let [version, trace_id, span_id, flags] = parts[..] else {
+            return Err(invalid());
+        };
+        if version.len() != 2 || flags.len() != 2 {
+            return Err(invalid());
+        }
+        let version = u8::from_str_radix(version, 16).map_err(|_| invalid())?;
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| invalid())?;
+        if version != 0 || !is_hex_id(trace_id, 32) || !is_hex_id(span_id, 16) {
+            return Err(invalid());
+        }
+
+        Ok(TraceParent {
+            version,
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            flags,
+        })
+    }
+}
+
+impl TryFrom<String> for TraceParent {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<TraceParent> for String {
+    fn from(value: TraceParent) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02x}-{}-{}-{:02x}",
+            self.version, self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+/// The trace context of the request being evaluated, if supplied by the host
+pub fn current() -> Option<TraceParent> {
+    CURRENT.with(|c| c.borrow().clone())
+}
+
+/// Set the trace context of the request being evaluated. This is done
+/// automatically by the SDK
+pub fn set_current(trace_parent: Option<TraceParent>) {
+    CURRENT.with(|c| *c.borrow_mut() = trace_parent);
+}
+
+/// Add the ids of the current trace context to a log event
+pub(crate) fn add_to_event(event: &mut serde_json::Map<String, serde_json::Value>) {
+    CURRENT.with(|c| {
+        if let Some(trace_parent) = c.borrow().as_ref() {
+            event.insert("trace_id".to_string(), trace_parent.trace_id.clone().into());
+            event.insert("span_id".to_string(), trace_parent.span_id.clone().into());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn parse_trace_parent() {
+        let trace_parent: TraceParent = TRACE_PARENT.parse().unwrap();
+        assert_eq!(trace_parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(trace_parent.span_id, "00f067aa0ba902b7");
+        assert!(trace_parent.sampled());
+        assert_eq!(trace_parent.to_string(), TRACE_PARENT);
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+        ] {
+            assert!(invalid.parse::<TraceParent>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn trace_parent_from_validation_payload() {
+        use crate::request::ValidationRequest;
+
+        let payload = serde_json::json!({
+            "settings": {"logLevel": "info"},
+            "request": {"uid": "1234"},
+            "traceparent": TRACE_PARENT,
+        });
+        ValidationRequest::<serde_json::Value>::new(&serde_json::to_vec(&payload).unwrap())
+            .unwrap();
+        assert_eq!(current().unwrap().to_string(), TRACE_PARENT);
+        assert_eq!(crate::logging::level(), Some(slog::Level::Info));
+
+        ValidationRequest::<serde_json::Value>::new(br#"{"settings": {}, "request": {}}"#).unwrap();
+        assert_eq!(current(), None);
+        assert_eq!(crate::logging::level(), None);
+    }
+
+    #[test]
+    fn trace_ids_in_events() {
+        let mut event = serde_json::Map::new();
+        set_current(None);
+        add_to_event(&mut event);
+        assert!(event.is_empty());
+
+        set_current(Some(TRACE_PARENT.parse().unwrap()));
+        add_to_event(&mut event);
+        assert_eq!(event["trace_id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(event["span_id"], "00f067aa0ba902b7");
+        set_current(None);
+    }
+}
@@ -0,0 +1,303 @@
+//! Small framework to build policies out of reusable checks.
+//!
+//! A [`Validator`] evaluates a value and returns a [`ValidationResult`],
+//! which holds the list of [`Violation`] found. Validators can be combined
+//! using [`all_of`], [`any_of`] and [`not`], and each one of them can be
+//! unit tested in isolation.
+//!
+//! # Example
+//!
+//! ```
+//! use kubewarden_policy_sdk::validator::{all_of, check, not, Validator};
+//! use serde_json::{json, Value};
+//!
+//! let has_name = check("/metadata/name", "name is required", |object: &Value| {
+//!     object.pointer("/metadata/name").is_some()
+//! });
+//! let privileged = check("/spec/privileged", "privileged", |object: &Value| {
+//!     object.pointer("/spec/privileged") == Some(&json!(true))
+//! });
+//! let validator = all_of(vec![
+//!     Box::new(has_name),
+//!     Box::new(not(privileged, "/spec/privileged", "privileged objects are not allowed")),
+//! ]);
+//!
+//! let result = validator.validate(&json!({"spec": {"privileged": true}}));
+//! assert!(!result.is_valid());
+//! assert_eq!(
+//!     result.message(),
+//!     "/metadata/name: name is required; /spec/privileged: privileged objects are not allowed"
+//! );
+//! ```
+use std::fmt;
+
+/// A single problem found by a [`Validator`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Path of the offending field, usually a JSON Pointer. Can be empty
+    /// when the violation is not related to a specific field
+    pub path: String,
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+impl Violation {
+    /// Create a new violation
+    pub fn new(path: &str, message: &str) -> Self {
+        Violation {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// The outcome of a [`Validator`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationResult {
+    violations: Vec<Violation>,
+}
+
+impl ValidationResult {
+    /// A result without violations
+    pub fn valid() -> Self {
+        ValidationResult::default()
+    }
+
+    /// A result with a single violation
+    pub fn invalid(path: &str, message: &str) -> Self {
+        ValidationResult {
+            violations: vec![Violation::new(path, message)],
+        }
+    }
+
+    /// Returns true when no violation has been found
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// The violations found
+    pub fn violations(&self) -> &[Violation] {
+        &self.violations
+    }
+
+    /// Merge the violations of `other` into this result
+    pub fn merge(mut self, other: ValidationResult) -> Self {
+        self.violations.extend(other.violations);
+        self
+    }
+
+    /// All the violations, joined into a single message
+    pub fn message(&self) -> String {
+        self.violations
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Build the response of the policy: the request is accepted when
+    /// no violation has been found, otherwise it's rejected using all the
+    /// violations as message
+    pub fn into_response(self) -> wapc_guest::CallResult {
+        if self.is_valid() {
+            crate::accept_request()
+        } else {
+            crate::reject_request(Some(self.message()), None, None, None)
+        }
+    }
+}
+
+impl FromIterator<Violation> for ValidationResult {
+    fn from_iter<I: IntoIterator<Item = Violation>>(iter: I) -> Self {
+        ValidationResult {
+            violations: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// A reusable check of a value of type `T`
+pub trait Validator<T: ?Sized> {
+    /// Evaluate the given value
+    fn validate(&self, value: &T) -> ValidationResult;
+}
+
+impl<T: ?Sized, F> Validator<T> for F
+where
+    F: Fn(&T) -> ValidationResult,
+{
+    fn validate(&self, value: &T) -> ValidationResult {
+        self(value)
+    }
+}
+
+impl<T: ?Sized> Validator<T> for Box<dyn Validator<T>> {
+    fn validate(&self, value: &T) -> ValidationResult {
+        self.as_ref().validate(value)
+    }
+}
+
+/// Create a validator from a predicate. A violation with the given path
+/// and message is reported when the predicate returns false
+pub fn check<T: ?Sized, F>(path: &str, message: &str, predicate: F) -> impl Validator<T>
+where
+    F: Fn(&T) -> bool,
+{
+    let violation = Violation::new(path, message);
+    move |value: &T| {
+        if predicate(value) {
+            ValidationResult::valid()
+        } else {
+            ValidationResult {
+                violations: vec![violation.clone()],
+            }
+        }
+    }
+}
+
+/// Validator that succeeds when all the validators succeed. The violations
+/// of all the failing validators are reported
+pub struct AllOf<T: ?Sized> {
+    validators: Vec<Box<dyn Validator<T>>>,
+}
+
+/// Create a validator that succeeds when all the given validators succeed
+pub fn all_of<T: ?Sized>(validators: Vec<Box<dyn Validator<T>>>) -> AllOf<T> {
+    AllOf { validators }
+}
+
+impl<T: ?Sized> Validator<T> for AllOf<T> {
+    fn validate(&self, value: &T) -> ValidationResult {
+        self.validators
+            .iter()
+            .fold(ValidationResult::valid(), |result, validator| {
+                result.merge(validator.validate(value))
+            })
+    }
+}
+
+/// Validator that succeeds when at least one of the validators succeeds.
+/// When all of them fail, the violations of all the validators are reported
+pub struct AnyOf<T: ?Sized> {
+    validators: Vec<Box<dyn Validator<T>>>,
+}
+
+/// Create a validator that succeeds when at least one of the given validators
+/// succeeds. An empty list of validators always fails
+pub fn any_of<T: ?Sized>(validators: Vec<Box<dyn Validator<T>>>) -> AnyOf<T> {
+    AnyOf { validators }
+}
+
+impl<T: ?Sized> Validator<T> for AnyOf<T> {
+    fn validate(&self, value: &T) -> ValidationResult {
+        if self.validators.is_empty() {
+            return ValidationResult::invalid("", "no validator to satisfy");
+        }
+
+        let mut result = ValidationResult::valid();
+        for validator in &self.validators {
+            let outcome = validator.validate(value);
+            if outcome.is_valid() {
+                return outcome;
+            }
+            result = result.merge(outcome);
+        }
+        result
+    }
+}
+
+/// Validator that succeeds when the wrapped validator fails
+pub struct Not<V> {
+    validator: V,
+    violation: Violation,
+}
+
+/// Create a validator that succeeds when `validator` fails. When `validator`
+/// succeeds, a violation with the given path and message is reported
+pub fn not<V>(validator: V, path: &str, message: &str) -> Not<V> {
+    Not {
+        validator,
+        violation: Violation::new(path, message),
+    }
+}
+
+impl<T: ?Sized, V: Validator<T>> Validator<T> for Not<V> {
+    fn validate(&self, value: &T) -> ValidationResult {
+        if self.validator.validate(value).is_valid() {
+            ValidationResult {
+                violations: vec![self.violation.clone()],
+            }
+        } else {
+            ValidationResult::valid()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::response::ValidationResponse;
+
+    fn positive() -> Box<dyn Validator<i64>> {
+        Box::new(check("", "must be positive", |v: &i64| *v > 0))
+    }
+
+    fn even() -> Box<dyn Validator<i64>> {
+        Box::new(check("", "must be even", |v: &i64| v % 2 == 0))
+    }
+
+    #[test]
+    fn all_of_reports_every_violation() {
+        let validator = all_of(vec![positive(), even()]);
+
+        assert!(validator.validate(&2).is_valid());
+        assert_eq!(
+            validator.validate(&-3).message(),
+            "must be positive; must be even"
+        );
+        assert!(all_of::<i64>(vec![]).validate(&1).is_valid());
+    }
+
+    #[test]
+    fn any_of_stops_at_first_success() {
+        let validator = any_of(vec![positive(), even()]);
+
+        assert!(validator.validate(&-2).is_valid());
+        assert!(validator.validate(&3).is_valid());
+        assert_eq!(validator.validate(&-3).violations().len(), 2);
+        assert!(!any_of::<i64>(vec![]).validate(&1).is_valid());
+    }
+
+    #[test]
+    fn not_inverts_the_result() {
+        let validator = not(even(), "/value", "must be odd");
+
+        assert!(validator.validate(&3).is_valid());
+        assert_eq!(
+            validator.validate(&2).violations(),
+            &[Violation::new("/value", "must be odd")]
+        );
+    }
+
+    #[test]
+    fn into_response() {
+        let response: ValidationResponse =
+            serde_json::from_slice(&ValidationResult::valid().into_response().unwrap()).unwrap();
+        assert!(response.accepted);
+
+        let result = ValidationResult::invalid("/spec", "boom");
+        let response: ValidationResponse =
+            serde_json::from_slice(&result.into_response().unwrap()).unwrap();
+        assert!(!response.accepted);
+        assert_eq!(response.message.unwrap(), "/spec: boom");
+    }
+}
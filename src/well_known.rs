@@ -0,0 +1,166 @@
+//! Well-known annotations and labels.
+//!
+//! Constants for the [annotations of the OCI image specification](https://github.com/opencontainers/image-spec/blob/main/annotations.md)
+//! and for the [recommended labels of Kubernetes](https://kubernetes.io/docs/concepts/overview/working-with-objects/common-labels/),
+//! plus helpers reading them from the metadata of the objects.
+//!
+//! ```
+//! use kubewarden_policy_sdk::well_known::{k8s, label, RecommendedLabels};
+//! use serde_json::json;
+//!
+//! let deployment = json!({
+//!     "metadata": {
+//!         "name": "wordpress",
+//!         "labels": {
+//!             "app.kubernetes.io/name": "wordpress",
+//!             "app.kubernetes.io/managed-by": "helm"
+//!         }
+//!     }
+//! });
+//!
+//! assert_eq!(label(&deployment, k8s::MANAGED_BY), Some("helm"));
+//!
+//! let labels = RecommendedLabels::from_object(&deployment);
+//! assert_eq!(labels.name, Some("wordpress"));
+//! assert!(labels.part_of.is_none());
+//! ```
+use serde_json::Value;
+
+/// Pre-defined annotation keys of the OCI image specification
+pub mod oci {
+    /// Date and time on which the image was built, RFC 3339
+    pub const CREATED: &str = "org.opencontainers.image.created";
+    /// Contact details of the people or organization responsible for the image
+    pub const AUTHORS: &str = "org.opencontainers.image.authors";
+    /// URL to find more information on the image
+    pub const URL: &str = "org.opencontainers.image.url";
+    /// URL to get documentation on the image
+    pub const DOCUMENTATION: &str = "org.opencontainers.image.documentation";
+    /// URL to get the source code for building the image
+    pub const SOURCE: &str = "org.opencontainers.image.source";
+    /// Version of the packaged software
+    pub const VERSION: &str = "org.opencontainers.image.version";
+    /// Source control revision identifier for the packaged software
+    pub const REVISION: &str = "org.opencontainers.image.revision";
+    /// Name of the distributing entity, organization or individual
+    pub const VENDOR: &str = "org.opencontainers.image.vendor";
+    /// License(s) under which contained software is distributed, SPDX expression
+    pub const LICENSES: &str = "org.opencontainers.image.licenses";
+    /// Name of the reference for a target
+    pub const REF_NAME: &str = "org.opencontainers.image.ref.name";
+    /// Human-readable title of the image
+    pub const TITLE: &str = "org.opencontainers.image.title";
+    /// Human-readable description of the software packaged in the image
+    pub const DESCRIPTION: &str = "org.opencontainers.image.description";
+    /// Digest of the image this image is based on
+    pub const BASE_DIGEST: &str = "org.opencontainers.image.base.digest";
+    /// Image reference of the image this image is based on
+    pub const BASE_NAME: &str = "org.opencontainers.image.base.name";
+}
+
+/// Recommended labels and well-known annotations of Kubernetes
+pub mod k8s {
+    /// The name of the application
+    pub const NAME: &str = "app.kubernetes.io/name";
+    /// A unique name identifying the instance of an application
+    pub const INSTANCE: &str = "app.kubernetes.io/instance";
+    /// The current version of the application
+    pub const VERSION: &str = "app.kubernetes.io/version";
+    /// The component within the architecture
+    pub const COMPONENT: &str = "app.kubernetes.io/component";
+    /// The name of a higher level application this one is part of
+    pub const PART_OF: &str = "app.kubernetes.io/part-of";
+    /// The tool being used to manage the operation of an application
+    pub const MANAGED_BY: &str = "app.kubernetes.io/managed-by";
+
+    /// The last configuration applied with `kubectl apply`
+    pub const LAST_APPLIED_CONFIGURATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+    /// The container targeted by default by `kubectl exec` and `kubectl logs`
+    pub const DEFAULT_CONTAINER: &str = "kubectl.kubernetes.io/default-container";
+    /// The Pod Security Standards level enforced on a namespace
+    pub const POD_SECURITY_ENFORCE: &str = "pod-security.kubernetes.io/enforce";
+    /// The name of the namespace, set on all the namespaces
+    pub const METADATA_NAME: &str = "kubernetes.io/metadata.name";
+}
+
+/// The value of the given label of a Kubernetes object
+pub fn label<'a>(object: &'a Value, key: &str) -> Option<&'a str> {
+    object["metadata"]["labels"][key].as_str()
+}
+
+/// The value of the given annotation of a Kubernetes object
+pub fn annotation<'a>(object: &'a Value, key: &str) -> Option<&'a str> {
+    object["metadata"]["annotations"][key].as_str()
+}
+
+/// The recommended labels of a Kubernetes object, see [`k8s`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecommendedLabels<'a> {
+    pub name: Option<&'a str>,
+    pub instance: Option<&'a str>,
+    pub version: Option<&'a str>,
+    pub component: Option<&'a str>,
+    pub part_of: Option<&'a str>,
+    pub managed_by: Option<&'a str>,
+}
+
+impl<'a> RecommendedLabels<'a> {
+    /// Read the recommended labels of the given Kubernetes object
+    pub fn from_object(object: &'a Value) -> Self {
+        RecommendedLabels {
+            name: label(object, k8s::NAME),
+            instance: label(object, k8s::INSTANCE),
+            version: label(object, k8s::VERSION),
+            component: label(object, k8s::COMPONENT),
+            part_of: label(object, k8s::PART_OF),
+            managed_by: label(object, k8s::MANAGED_BY),
+        }
+    }
+
+    /// The keys of the recommended labels the object doesn't have
+    pub fn missing(&self) -> Vec<&'static str> {
+        [
+            (k8s::NAME, self.name),
+            (k8s::INSTANCE, self.instance),
+            (k8s::VERSION, self.version),
+            (k8s::COMPONENT, self.component),
+            (k8s::PART_OF, self.part_of),
+            (k8s::MANAGED_BY, self.managed_by),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(key, _)| key)
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn read_labels_and_annotations() {
+        let pod = json!({
+            "metadata": {
+                "labels": {
+                    "app.kubernetes.io/name": "nginx",
+                    "app.kubernetes.io/instance": "nginx-prod",
+                    "app.kubernetes.io/version": "1.27"
+                },
+                "annotations": {"kubectl.kubernetes.io/default-container": "nginx"}
+            }
+        });
+
+        assert_eq!(annotation(&pod, k8s::DEFAULT_CONTAINER), Some("nginx"));
+        assert_eq!(annotation(&pod, k8s::LAST_APPLIED_CONFIGURATION), None);
+        assert_eq!(label(&json!({}), k8s::NAME), None);
+
+        let labels = RecommendedLabels::from_object(&pod);
+        assert_eq!(labels.instance, Some("nginx-prod"));
+        assert_eq!(
+            labels.missing(),
+            vec![k8s::COMPONENT, k8s::PART_OF, k8s::MANAGED_BY]
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Helpers for raw policies receiving YAML content, like a whole manifest file.
+//!
+//! A YAML stream can hold multiple documents separated by `---`. The helpers
+//! of this module split the stream and decode each document, either into a
+//! [`serde_json::Value`] or into a typed object. Empty documents are skipped.
+//!
+//! [`validate_documents`] checks all the documents and aggregates the
+//! outcome, so that a single response can report all the offending ones:
+//!
+//! ```
+//! use kubewarden_policy_sdk::yaml;
+//! use serde_json::Value;
+//!
+//! let manifest = r#"
+//! apiVersion: v1
+//! kind: Pod
+//! metadata:
+//!   name: nginx
+//! spec:
+//!   hostNetwork: true
+//! ---
+//! apiVersion: v1
+//! kind: Service
+//! metadata:
+//!   name: nginx
+//! "#;
+//!
+//! let validation = yaml::validate_documents(manifest, |object: &Value| {
+//!     if object.pointer("/spec/hostNetwork") == Some(&Value::Bool(true)) {
+//!         return Err("host network is not allowed".to_string());
+//!     }
+//!     Ok(())
+//! })
+//! .unwrap();
+//!
+//! assert!(!validation.is_valid());
+//! assert_eq!(
+//!     validation.response().message.as_deref(),
+//!     Some("document 0 (Pod/nginx): host network is not allowed")
+//! );
+//! ```
+use crate::error::{Result, SdkError};
+use crate::response::ValidationResponse;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Split the YAML stream into its documents. Empty documents are skipped
+pub fn documents(yaml: &str) -> Result<Vec<Value>> {
+    let mut documents = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_str(yaml).enumerate() {
+        let value = Value::deserialize(document).map_err(|e| {
+            SdkError::deserialization(
+                &format!("YAML document {}", index),
+                serde::de::Error::custom(e),
+            )
+        })?;
+        if !value.is_null() {
+            documents.push(value);
+        }
+    }
+    Ok(documents)
+}
+
+/// Split the YAML stream and decode all its documents into `T`. The first
+/// document that cannot be decoded causes an error
+pub fn decode<T: DeserializeOwned>(yaml: &str) -> Result<Vec<T>> {
+    documents(yaml)?
+        .into_iter()
+        .enumerate()
+        .map(|(index, document)| {
+            let description = describe(index, &document);
+            serde_json::from_value(document).map_err(|e| SdkError::deserialization(&description, e))
+        })
+        .collect()
+}
+
+/// Decode a single document, failing when the stream holds more than one
+pub fn decode_single<T: DeserializeOwned>(yaml: &str) -> Result<T> {
+    let mut documents = documents(yaml)?;
+    match documents.len() {
+        1 => serde_json::from_value(documents.remove(0))
+            .map_err(|e| SdkError::deserialization("YAML document", e)),
+        count => Err(SdkError::InvalidInput(format!(
+            "expected a single YAML document, found {}",
+            count
+        ))),
+    }
+}
+
+/// A document rejected by [`validate_documents`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentFailure {
+    /// Position of the document inside of the stream, starting from 0.
+    /// Empty documents are not counted
+    pub index: usize,
+    /// `kind/name` of the document, when it is a Kubernetes object
+    pub object: Option<String>,
+    /// Why the document has been rejected, decoding errors included
+    pub message: String,
+}
+
+impl std::fmt::Display for DocumentFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.object {
+            Some(object) => write!(f, "document {} ({}): {}", self.index, object, self.message),
+            None => write!(f, "document {}: {}", self.index, self.message),
+        }
+    }
+}
+
+/// The aggregated outcome of [`validate_documents`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentsValidation {
+    /// The number of documents checked
+    pub documents: usize,
+    /// The rejected documents, in stream order
+    pub failures: Vec<DocumentFailure>,
+}
+
+impl DocumentsValidation {
+    /// Whether all the documents are valid
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Accept when all the documents are valid, otherwise reject with a
+    /// message listing all the failures
+    pub fn response(&self) -> ValidationResponse {
+        if self.is_valid() {
+            return ValidationResponse::accept().response();
+        }
+        let message = self
+            .failures
+            .iter()
+            .map(DocumentFailure::to_string)
+            .collect::<Vec<_>>()
+            .join("; ");
+        ValidationResponse::reject(message).response()
+    }
+}
+
+/// Decode all the documents of the YAML stream into `T` and check them with
+/// `validate`. Documents that cannot be decoded are reported as failures,
+/// an error is returned only when the stream is not valid YAML
+pub fn validate_documents<T, F>(yaml: &str, mut validate: F) -> Result<DocumentsValidation>
+where
+    T: DeserializeOwned,
+    F: FnMut(&T) -> Result<(), String>,
+{
+    let documents = documents(yaml)?;
+    let mut validation = DocumentsValidation {
+        documents: documents.len(),
+        failures: Vec::new(),
+    };
+    for (index, document) in documents.into_iter().enumerate() {
+        let object = object_name(&document);
+        let outcome = serde_json::from_value(document)
+            .map_err(|e| format!("cannot decode: {}", e))
+            .and_then(|decoded: T| validate(&decoded));
+        if let Err(message) = outcome {
+            validation.failures.push(DocumentFailure {
+                index,
+                object,
+                message,
+            });
+        }
+    }
+    Ok(validation)
+}
+
+/// `kind/name` of a Kubernetes object
+fn object_name(document: &Value) -> Option<String> {
+    let kind = document.get("kind")?.as_str()?;
+    let name = document.pointer("/metadata/name")?.as_str()?;
+    Some(format!("{}/{}", kind, name))
+}
+
+fn describe(index: usize, document: &Value) -> String {
+    match object_name(document) {
+        Some(object) => format!("YAML document {} ({})", index, object),
+        None => format!("YAML document {}", index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: settings
+data:
+  replicas: "3"
+---
+---
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+spec:
+  replicas: 5
+"#;
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct Object {
+        kind: String,
+        metadata: Meta,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Meta {
+        name: String,
+    }
+
+    #[test]
+    fn split_documents() {
+        let values = documents(MANIFEST).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1]["spec"]["replicas"], 5);
+
+        let objects: Vec<Object> = decode(MANIFEST).unwrap();
+        assert_eq!(objects[0].kind, "ConfigMap");
+        assert_eq!(objects[1].metadata.name, "web");
+
+        assert!(documents("").unwrap().is_empty());
+        assert!(documents("a: [").is_err());
+
+        let err =
+            decode::<Object>("kind: Pod\n---\nkind: Pod\nmetadata: {name: nginx}\n---\nkind: Pod")
+                .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("error deserializing YAML document 0: "));
+    }
+
+    #[test]
+    fn decode_single_documents() {
+        let object: Object = decode_single("kind: Pod\nmetadata: {name: nginx}").unwrap();
+        assert_eq!(object.metadata.name, "nginx");
+        assert!(decode_single::<Object>(MANIFEST).is_err());
+        assert!(decode_single::<Object>("---").is_err());
+    }
+
+    #[test]
+    fn aggregate_validation_results() {
+        let yaml = format!("{}---\nkind: Secret\n", MANIFEST);
+        let validation = validate_documents(&yaml, |object: &Object| {
+            if object.kind == "Deployment" {
+                Err("deployments are not allowed".to_string())
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap();
+
+        assert_eq!(validation.documents, 3);
+        assert_eq!(
+            validation.failures,
+            vec![
+                DocumentFailure {
+                    index: 1,
+                    object: Some("Deployment/web".to_string()),
+                    message: "deployments are not allowed".to_string(),
+                },
+                DocumentFailure {
+                    index: 2,
+                    object: None,
+                    message: "cannot decode: missing field `metadata`".to_string(),
+                },
+            ]
+        );
+        let response = validation.response();
+        assert!(!response.accepted);
+        assert_eq!(
+            response.message.unwrap(),
+            "document 1 (Deployment/web): deployments are not allowed; \
+             document 2: cannot decode: missing field `metadata`"
+        );
+
+        let validation = validate_documents(MANIFEST, |_: &Value| Ok(())).unwrap();
+        assert!(validation.is_valid());
+        assert!(validation.response().accepted);
+    }
+}
@@ -0,0 +1,16 @@
+;; Adapter providing the functions of the `wapc` module, imported by the
+;; waPC bindings, to the policies built as components. The functions are never
+;; invoked: they report failures and ignore their arguments.
+;;
+;; Built with `wasm-tools parse wapc-adapter.wat -o wapc-adapter.wasm`
+(module
+  (func (export "__console_log") (param i32 i32))
+  (func (export "__host_call") (param i32 i32 i32 i32 i32 i32 i32 i32) (result i32) i32.const 0)
+  (func (export "__host_response") (param i32))
+  (func (export "__host_response_len") (result i32) i32.const 0)
+  (func (export "__host_error_len") (result i32) i32.const 0)
+  (func (export "__host_error") (param i32))
+  (func (export "__guest_response") (param i32 i32))
+  (func (export "__guest_error") (param i32 i32))
+  (func (export "__guest_request") (param i32 i32))
+)